@@ -0,0 +1,39 @@
+//! Compare `parse_json` and `parse_json_preallocated` on a large pack.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use sindit_senml::{parse_json, parse_json_preallocated};
+
+const RECORDS: usize = 10_000;
+
+fn large_pack() -> String {
+    let records: Vec<String> = (0..RECORDS)
+        .map(|index| {
+            format!(
+                r#"{{"n":"sensor{}","u":"Cel","v":{}.5,"t":{}}}"#,
+                index % 100,
+                index,
+                1320067464 + index
+            )
+        })
+        .collect();
+    format!(
+        r#"[{{"bn":"urn:dev:ow:10e2073a01080063:","bt":0}},{}]"#,
+        records.join(",")
+    )
+}
+
+fn bench_parse_json(c: &mut Criterion) {
+    let json_str = large_pack();
+    let mut group = c.benchmark_group("parse_json_10000_records");
+    group.throughput(Throughput::Elements(RECORDS as u64));
+    group.bench_function("parse_json", |b| {
+        b.iter(|| parse_json(black_box(&json_str), None).unwrap())
+    });
+    group.bench_function("parse_json_preallocated", |b| {
+        b.iter(|| parse_json_preallocated(black_box(&json_str), None).unwrap())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_parse_json);
+criterion_main!(benches);
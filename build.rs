@@ -0,0 +1,8 @@
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // The gRPC code is generated only when the grpc feature is enabled,
+    // as it requires protoc to be installed.
+    #[cfg(feature = "grpc")]
+    tonic_build::compile_protos("proto/senml_service.proto")?;
+    println!("cargo:rerun-if-changed=build.rs");
+    Ok(())
+}
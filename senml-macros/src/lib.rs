@@ -0,0 +1,248 @@
+//! # SenML macros
+//!
+//! Procedural macros for the [sindit-senml](https://docs.rs/sindit-senml) crate.
+//!
+//! Use them through the `macros` feature of `sindit-senml` rather than
+//! depending on this crate directly.
+
+use chrono::{DateTime, Utc};
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
+use syn::{bracketed, parse_macro_input, Expr, Ident, Lit, LitStr, Token};
+
+/// Same rule as `sindit_senml::validate_name::validate_name`.
+///
+/// Duplicated here as a procedural macro crate cannot depend on the crate
+/// it generates code for.
+fn validate_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(first) if first.is_ascii_alphanumeric() => {}
+        _ => return false,
+    }
+    chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | ':' | '.' | '/' | '_'))
+}
+
+fn set_once<T>(slot: &mut Option<T>, key: &Ident, value: T) -> syn::Result<()> {
+    if slot.is_some() {
+        return Err(syn::Error::new(
+            key.span(),
+            format!("duplicate field `{}`", key),
+        ));
+    }
+    *slot = Some(value);
+    Ok(())
+}
+
+/// Time literal of a record, an integer or float number of seconds since
+/// the Unix epoch, possibly negative.
+struct Time {
+    seconds: i64,
+    nanoseconds: u32,
+}
+
+impl Parse for Time {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let negative = input.parse::<Option<Token![-]>>()?.is_some();
+        let literal: Lit = input.parse()?;
+        let out_of_range = || syn::Error::new(literal.span(), "SenML time out of range");
+        let (seconds, nanoseconds) = match literal {
+            Lit::Int(ref time) => {
+                let time = time.base10_parse::<i128>()?;
+                let time = if negative { -time } else { time };
+                (i64::try_from(time).map_err(|_| out_of_range())?, 0)
+            }
+            Lit::Float(ref time) => {
+                let time = time.base10_parse::<f64>()?;
+                let time = if negative { -time } else { time };
+                let seconds = time.floor();
+                if !(i64::MIN as f64..i64::MAX as f64).contains(&seconds) {
+                    return Err(out_of_range());
+                }
+                let nanoseconds = ((time - seconds) * 1e9).round() as u32;
+                match nanoseconds {
+                    1_000_000_000 => (seconds as i64 + 1, 0),
+                    nanoseconds => (seconds as i64, nanoseconds),
+                }
+            }
+            _ => {
+                return Err(syn::Error::new(
+                    literal.span(),
+                    "expected an integer or float time",
+                ))
+            }
+        };
+        // Same range as the DateTime built by the expanded code.
+        if DateTime::<Utc>::from_timestamp(seconds, nanoseconds).is_none() {
+            return Err(out_of_range());
+        }
+        Ok(Time {
+            seconds,
+            nanoseconds,
+        })
+    }
+}
+
+/// One `[key: value, ...]` record of the macro input.
+struct Record {
+    name: LitStr,
+    unit: Option<LitStr>,
+    value: Option<TokenStream2>,
+    time: Option<Time>,
+}
+
+impl Parse for Record {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let content;
+        let brackets = bracketed!(content in input);
+
+        let mut name: Option<LitStr> = None;
+        let mut unit: Option<LitStr> = None;
+        let mut value: Option<TokenStream2> = None;
+        let mut time: Option<Time> = None;
+
+        while !content.is_empty() {
+            let key: Ident = content.parse()?;
+            content.parse::<Token![:]>()?;
+            match key.to_string().as_str() {
+                "name" => set_once(&mut name, &key, content.parse()?)?,
+                "unit" => set_once(&mut unit, &key, content.parse()?)?,
+                "time" => set_once(&mut time, &key, content.parse()?)?,
+                "value" => {
+                    let expr: Expr = content.parse()?;
+                    let field = quote!(FloatingPoint(::core::convert::Into::<f64>::into(#expr)));
+                    set_once(&mut value, &key, field)?
+                }
+                "value_bool" => {
+                    let expr: Expr = content.parse()?;
+                    set_once(&mut value, &key, quote!(BooleanValue(#expr)))?
+                }
+                "value_str" => {
+                    let expr: Expr = content.parse()?;
+                    let field = quote!(StringValue(::std::string::String::from(#expr)));
+                    set_once(&mut value, &key, field)?
+                }
+                "value_data" => {
+                    let expr: Expr = content.parse()?;
+                    let field = quote!(DataValue(::std::vec::Vec::<u8>::from(&(#expr)[..])));
+                    set_once(&mut value, &key, field)?
+                }
+                _ => {
+                    return Err(syn::Error::new(
+                        key.span(),
+                        format!("unknown field `{}`", key),
+                    ))
+                }
+            }
+            if content.is_empty() {
+                break;
+            }
+            content.parse::<Token![,]>()?;
+        }
+
+        let name = match name {
+            Some(name) => name,
+            None => {
+                return Err(syn::Error::new(
+                    brackets.span.join(),
+                    "missing field `name`",
+                ))
+            }
+        };
+        if !validate_name(&name.value()) {
+            return Err(syn::Error::new(
+                name.span(),
+                format!("invalid SenML name {:?}", name.value()),
+            ));
+        }
+
+        Ok(Record {
+            name,
+            unit,
+            value,
+            time,
+        })
+    }
+}
+
+impl Record {
+    fn expand(&self) -> TokenStream2 {
+        let name = &self.name;
+        let unit = match self.unit {
+            Some(ref unit) => quote!(::std::option::Option::Some(
+                ::std::string::String::from(#unit)
+            )),
+            None => quote!(::std::option::Option::None),
+        };
+        let value = match self.value {
+            Some(ref value) => quote!(::std::option::Option::Some(
+                ::sindit_senml::SenMLValueField::#value
+            )),
+            None => quote!(::std::option::Option::None),
+        };
+        let (seconds, nanoseconds) = match self.time {
+            Some(ref time) => (time.seconds, time.nanoseconds),
+            None => (0, 0),
+        };
+        quote!(
+            ::sindit_senml::SenMLResolvedRecord {
+                name: ::std::string::String::from(#name),
+                unit: #unit,
+                value: #value,
+                sum: ::std::option::Option::None,
+                time: ::sindit_senml::__private::DateTime::<::sindit_senml::__private::Utc>::from_timestamp(#seconds, #nanoseconds)
+                    .expect("SenML time checked by senml_pack!"),
+                update_time: ::std::option::Option::None,
+                base_version: ::std::option::Option::None,
+                extra_fields: ::std::option::Option::None,
+                active_base_name: ::std::option::Option::None,
+            }
+        )
+    }
+}
+
+struct Pack {
+    records: Punctuated<Record, Token![,]>,
+}
+
+impl Parse for Pack {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        Ok(Pack {
+            records: Punctuated::parse_terminated(input)?,
+        })
+    }
+}
+
+/// Build a `Vec<SenMLResolvedRecord>` from record literals.
+///
+/// Each record is a bracketed list of fields:
+/// * `name`: string literal, required. Invalid SenML names are compile errors.
+/// * `unit`: string literal, defaults to no unit.
+/// * `time`: Unix timestamp in seconds as an integer or float literal,
+///   possibly negative, defaults to `0`. Timestamps out of the range
+///   supported by chrono are compile errors.
+/// * At most one value, defaulting to no value:
+///   * `value`: a number converted to `f64`.
+///   * `value_bool`: a `bool`.
+///   * `value_str`: a `&str`.
+///   * `value_data`: bytes, such as `[1, 2, 3]` or `b"data"`.
+///
+/// # Examples
+/// ```ignore
+/// use sindit_senml::senml_pack;
+///
+/// let records = senml_pack! {
+///     [name: "temperature", unit: "Cel", value: 23.1, time: 1234567890],
+///     [name: "open", value_bool: true],
+/// };
+/// assert_eq!(records.len(), 2);
+/// ```
+#[proc_macro]
+pub fn senml_pack(input: TokenStream) -> TokenStream {
+    let pack = parse_macro_input!(input as Pack);
+    let records = pack.records.iter().map(Record::expand);
+    quote!(::std::vec![#(#records),*]).into()
+}
@@ -0,0 +1,272 @@
+//! # Build SenML records
+//!
+//! Builder of resolved records, to avoid filling every field of
+//! [`SenMLResolvedRecord`] by hand.
+
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+
+use crate::validate_name::validate_name;
+use crate::{SenMLResolvedRecord, SenMLValueField, SinditSenMLError};
+
+/// Builder of a [`SenMLResolvedRecord`].
+///
+/// Only the name is required. The time defaults to the current time
+/// when building and the other fields default to `None`.
+/// A record has a single value, setting values of different kinds
+/// is an error when building.
+///
+/// # Examples
+/// ```
+/// use sindit_senml::SenMLResolvedRecord;
+///
+/// let record = SenMLResolvedRecord::builder()
+///     .name("temperature")
+///     .unit("Cel")
+///     .float_value(23.1)
+///     .build()
+///     .unwrap();
+/// assert_eq!(record.name, "temperature");
+/// assert_eq!(record.get_float_value(), Some(23.1));
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SenMLResolvedRecordBuilder {
+    name: Option<String>,
+    unit: Option<String>,
+    value: Option<SenMLValueField>,
+    /// Values of different kinds were set.
+    value_conflict: bool,
+    sum: Option<f64>,
+    time: Option<DateTime<Utc>>,
+    update_time: Option<f64>,
+    base_version: Option<u64>,
+    extra_fields: Option<HashMap<String, serde_json::Value>>,
+}
+
+impl SenMLResolvedRecordBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    pub fn unit(mut self, unit: impl Into<String>) -> Self {
+        self.unit = Some(unit.into());
+        self
+    }
+
+    fn value(mut self, value: SenMLValueField) -> Self {
+        if let Some(ref previous) = self.value {
+            if std::mem::discriminant(previous) != std::mem::discriminant(&value) {
+                self.value_conflict = true;
+            }
+        }
+        self.value = Some(value);
+        self
+    }
+
+    pub fn float_value(self, value: f64) -> Self {
+        self.value(SenMLValueField::FloatingPoint(value))
+    }
+
+    pub fn bool_value(self, value: bool) -> Self {
+        self.value(SenMLValueField::BooleanValue(value))
+    }
+
+    pub fn string_value(self, value: impl Into<String>) -> Self {
+        self.value(SenMLValueField::StringValue(value.into()))
+    }
+
+    pub fn data_value(self, value: Vec<u8>) -> Self {
+        self.value(SenMLValueField::DataValue(value))
+    }
+
+    pub fn sum(mut self, sum: f64) -> Self {
+        self.sum = Some(sum);
+        self
+    }
+
+    pub fn time(mut self, time: DateTime<Utc>) -> Self {
+        self.time = Some(time);
+        self
+    }
+
+    /// Set the update time, in seconds.
+    pub fn update_time(mut self, update_time: f64) -> Self {
+        self.update_time = Some(update_time);
+        self
+    }
+
+    pub fn base_version(mut self, base_version: u64) -> Self {
+        self.base_version = Some(base_version);
+        self
+    }
+
+    /// Add an extra field, replacing the field with the same key.
+    pub fn extra_field(mut self, key: impl Into<String>, value: serde_json::Value) -> Self {
+        self.extra_fields
+            .get_or_insert_with(HashMap::new)
+            .insert(key.into(), value);
+        self
+    }
+
+    /// Build the record.
+    ///
+    /// # Returns
+    /// * `Result<SenMLResolvedRecord, SinditSenMLError>` - The record,
+    ///   `MissingName(0)` if no name was set, `InvalidName` if the name
+    ///   does not pass [`validate_name`], or `OnlyOneValuePerRecord(0)`
+    ///   if values of different kinds were set.
+    pub fn build(self) -> Result<SenMLResolvedRecord, SinditSenMLError> {
+        let name = self.name.ok_or(SinditSenMLError::MissingName(0))?;
+        if !validate_name(&name) {
+            return Err(SinditSenMLError::InvalidName);
+        }
+        if self.value_conflict {
+            return Err(SinditSenMLError::OnlyOneValuePerRecord(0));
+        }
+        Ok(SenMLResolvedRecord {
+            name,
+            unit: self.unit,
+            value: self.value,
+            sum: self.sum,
+            time: self.time.unwrap_or(Utc::now()),
+            update_time: self.update_time,
+            base_version: self.base_version,
+            extra_fields: self.extra_fields,
+            active_base_name: None,
+        })
+    }
+}
+
+impl SenMLResolvedRecord {
+    /// A builder of a record, see [`SenMLResolvedRecordBuilder`].
+    pub fn builder() -> SenMLResolvedRecordBuilder {
+        SenMLResolvedRecordBuilder::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_json;
+
+    #[test]
+    fn test_build_record() {
+        let time = DateTime::from_timestamp(1320067464, 0).unwrap();
+        let record = SenMLResolvedRecord::builder()
+            .name("urn:dev:ow:10e2073a01080063:temp")
+            .unit("Cel")
+            .float_value(23.1)
+            .time(time)
+            .update_time(60.0)
+            .base_version(5)
+            .extra_field("location", serde_json::json!("room 1"))
+            .build()
+            .unwrap();
+        let expected = parse_json(
+            r#"[{"bver": 5, "n": "urn:dev:ow:10e2073a01080063:temp", "u": "Cel", "v": 23.1,
+                "t": 1320067464, "ut": 60, "location": "room 1"}]"#,
+            None,
+        )
+        .unwrap();
+        assert_eq!(record, expected[0]);
+    }
+
+    #[test]
+    fn test_build_values() {
+        let time = DateTime::from_timestamp(1320067464, 0).unwrap();
+        let records: Vec<SenMLResolvedRecord> = [
+            SenMLResolvedRecord::builder()
+                .name("label")
+                .string_value("Machine Room"),
+            SenMLResolvedRecord::builder()
+                .name("open")
+                .bool_value(false),
+            SenMLResolvedRecord::builder()
+                .name("nfc-reader")
+                .data_value(vec![0x68, 0x69, 0x20, 0x0a]),
+            SenMLResolvedRecord::builder()
+                .name("energy")
+                .unit("J")
+                .sum(1200.0),
+        ]
+        .into_iter()
+        .map(|builder| builder.time(time).build().unwrap())
+        .collect();
+        let expected = parse_json(
+            r#"[{"bt": 1320067464, "n": "label", "vs": "Machine Room"},
+                {"n": "open", "vb": false},
+                {"n": "nfc-reader", "vd": "aGkgCg"},
+                {"n": "energy", "u": "J", "s": 1200}]"#,
+            None,
+        )
+        .unwrap();
+        assert_eq!(records, expected);
+
+        let record = SenMLResolvedRecord::builder()
+            .name("temp")
+            .float_value(1.0)
+            .float_value(2.0)
+            .sum(3.0)
+            .build()
+            .unwrap();
+        assert_eq!(record.get_float_value(), Some(2.0));
+        assert_eq!(record.sum, Some(3.0));
+    }
+
+    #[test]
+    fn test_build_defaults() {
+        let before = Utc::now();
+        let record = SenMLResolvedRecord::builder().name("a").build().unwrap();
+        assert!(record.time >= before && record.time <= Utc::now());
+        assert_eq!(record.unit, None);
+        assert_eq!(record.value, None);
+        assert_eq!(record.extra_fields, None);
+    }
+
+    #[test]
+    fn test_build_errors() {
+        assert!(matches!(
+            SenMLResolvedRecord::builder()
+                .unit("Cel")
+                .build()
+                .unwrap_err(),
+            SinditSenMLError::MissingName(0)
+        ));
+        assert!(matches!(
+            SenMLResolvedRecord::builder()
+                .name("-temp")
+                .build()
+                .unwrap_err(),
+            SinditSenMLError::InvalidName
+        ));
+        assert!(matches!(
+            SenMLResolvedRecord::builder().name("").build().unwrap_err(),
+            SinditSenMLError::InvalidName
+        ));
+        assert!(matches!(
+            SenMLResolvedRecord::builder()
+                .name("temp")
+                .float_value(1.0)
+                .string_value("one")
+                .build()
+                .unwrap_err(),
+            SinditSenMLError::OnlyOneValuePerRecord(0)
+        ));
+        assert!(matches!(
+            SenMLResolvedRecord::builder()
+                .name("temp")
+                .bool_value(true)
+                .data_value(vec![1])
+                .bool_value(true)
+                .build()
+                .unwrap_err(),
+            SinditSenMLError::OnlyOneValuePerRecord(0)
+        ));
+    }
+}
@@ -0,0 +1,523 @@
+//! # SenML CBOR
+//!
+//! CBOR representation of SenML, using the integer labels of the
+//! SenML registry instead of the JSON labels, and byte strings for the
+//! data values.
+//! <https://www.rfc-editor.org/rfc/rfc8428#section-6>
+//!
+//! [`parse_cbor`] and [`to_cbor_bytes`] read and write SenML CBOR packs.
+//!
+//! The CBOR diagnostic notation is a human readable representation
+//! of CBOR, useful to debug CBOR encoded SenML.
+//! <https://www.rfc-editor.org/rfc/rfc8949#section-8>
+
+use chrono::{DateTime, Utc};
+use ciborium::Value as CborValue;
+use serde_json::{Map, Number, Value};
+
+use crate::time::datetime_to_timestamp;
+use crate::{
+    encode_senml_data, resolve_records, SenMLRecord, SenMLResolvedRecord, SenMLValueField,
+    SinditSenMLError,
+};
+
+/// Integer labels of the SenML fields.
+const LABELS: [(&str, i64); 15] = [
+    ("bver", -1),
+    ("bn", -2),
+    ("bt", -3),
+    ("bu", -4),
+    ("bv", -5),
+    ("bs", -6),
+    ("n", 0),
+    ("u", 1),
+    ("v", 2),
+    ("vs", 3),
+    ("vb", 4),
+    ("s", 5),
+    ("t", 6),
+    ("ut", 7),
+    ("vd", 8),
+];
+
+/// Decode a single CBOR data item, without trailing bytes.
+fn decode(bytes: &[u8]) -> Result<CborValue, SinditSenMLError> {
+    let mut reader = bytes;
+    let value: CborValue =
+        ciborium::from_reader(&mut reader).map_err(|_| SinditSenMLError::InvalidCbor)?;
+    if !reader.is_empty() {
+        return Err(SinditSenMLError::InvalidCbor);
+    }
+    Ok(value)
+}
+
+fn encode(value: &CborValue) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    // Writing to a vector can't fail, and the values are always serialisable.
+    ciborium::into_writer(value, &mut bytes).unwrap_or_default();
+    bytes
+}
+
+fn write_diagnostic(value: &CborValue, out: &mut String) -> Result<(), SinditSenMLError> {
+    match value {
+        CborValue::Integer(value) => out.push_str(&i128::from(*value).to_string()),
+        CborValue::Bytes(bytes) => {
+            out.push_str("h'");
+            for byte in bytes.iter() {
+                out.push_str(&format!("{:02x}", byte));
+            }
+            out.push('\'');
+        }
+        // JSON string escaping is valid in the diagnostic notation.
+        CborValue::Text(text) => out.push_str(&Value::String(text.clone()).to_string()),
+        CborValue::Array(values) => {
+            out.push('[');
+            for (index, value) in values.iter().enumerate() {
+                if index > 0 {
+                    out.push_str(", ");
+                }
+                write_diagnostic(value, out)?;
+            }
+            out.push(']');
+        }
+        CborValue::Map(entries) => {
+            out.push('{');
+            for (index, (key, value)) in entries.iter().enumerate() {
+                if index > 0 {
+                    out.push_str(", ");
+                }
+                write_diagnostic(key, out)?;
+                out.push_str(": ");
+                write_diagnostic(value, out)?;
+            }
+            out.push('}');
+        }
+        CborValue::Tag(tag, value) => {
+            out.push_str(&format!("{}(", tag));
+            write_diagnostic(value, out)?;
+            out.push(')');
+        }
+        CborValue::Bool(value) => out.push_str(&value.to_string()),
+        CborValue::Null => out.push_str("null"),
+        CborValue::Float(value) => {
+            if value.is_nan() {
+                out.push_str("NaN");
+            } else if value.is_infinite() {
+                out.push_str(if *value > 0.0 {
+                    "Infinity"
+                } else {
+                    "-Infinity"
+                });
+            } else {
+                // The debug representation always has a fraction or an exponent.
+                out.push_str(&format!("{:?}", value));
+            }
+        }
+        _ => return Err(SinditSenMLError::InvalidCbor),
+    }
+    Ok(())
+}
+
+/// Convert a JSON value to CBOR.
+fn json_to_cbor(value: &Value) -> CborValue {
+    match value {
+        Value::Null => CborValue::Null,
+        Value::Bool(value) => CborValue::Bool(*value),
+        Value::Number(number) => match (number.as_i64(), number.as_u64()) {
+            (Some(value), _) => CborValue::Integer(value.into()),
+            (None, Some(value)) => CborValue::Integer(value.into()),
+            _ => CborValue::Float(number.as_f64().unwrap_or(f64::NAN)),
+        },
+        Value::String(value) => CborValue::Text(value.clone()),
+        Value::Array(values) => CborValue::Array(values.iter().map(json_to_cbor).collect()),
+        Value::Object(map) => CborValue::Map(
+            map.iter()
+                .map(|(key, value)| (CborValue::Text(key.clone()), json_to_cbor(value)))
+                .collect(),
+        ),
+    }
+}
+
+/// Convert a CBOR data item to JSON.
+///
+/// Only the data items having a JSON equivalent are supported.
+fn cbor_to_json(item: &CborValue) -> Result<Value, SinditSenMLError> {
+    Ok(match item {
+        CborValue::Integer(value) => match (i64::try_from(*value), u64::try_from(*value)) {
+            (Ok(value), _) => Value::from(value),
+            (_, Ok(value)) => Value::from(value),
+            _ => return Err(SinditSenMLError::InvalidCbor),
+        },
+        CborValue::Float(value) => {
+            Value::Number(Number::from_f64(*value).ok_or(SinditSenMLError::InvalidCbor)?)
+        }
+        CborValue::Text(text) => Value::String(text.clone()),
+        CborValue::Bool(value) => Value::Bool(*value),
+        CborValue::Null => Value::Null,
+        CborValue::Array(items) => Value::Array(
+            items
+                .iter()
+                .map(cbor_to_json)
+                .collect::<Result<Vec<Value>, SinditSenMLError>>()?,
+        ),
+        CborValue::Map(entries) => Value::Object(
+            entries
+                .iter()
+                .map(|(key, value)| match key {
+                    CborValue::Text(key) => Ok((key.clone(), cbor_to_json(value)?)),
+                    _ => Err(SinditSenMLError::InvalidCbor),
+                })
+                .collect::<Result<Map<String, Value>, SinditSenMLError>>()?,
+        ),
+        _ => return Err(SinditSenMLError::InvalidCbor),
+    })
+}
+
+/// Convert a SenML CBOR record to its JSON representation.
+///
+/// The integer labels are replaced by the SenML labels and the data
+/// values are encoded in base64.
+fn cbor_record_to_json(record: &CborValue) -> Result<Value, SinditSenMLError> {
+    let CborValue::Map(entries) = record else {
+        return Err(SinditSenMLError::InvalidCbor);
+    };
+    let mut map = Map::new();
+    for (key, value) in entries.iter() {
+        let (key, value) = match key {
+            CborValue::Integer(label) => {
+                let (json_label, _) = LABELS
+                    .iter()
+                    .find(|(_, known_label)| i128::from(*known_label) == i128::from(*label))
+                    .ok_or(SinditSenMLError::InvalidCbor)?;
+                let value = match (*json_label, value) {
+                    ("vd", CborValue::Bytes(data)) => Value::String(encode_senml_data(data)),
+                    _ => cbor_to_json(value)?,
+                };
+                (json_label.to_string(), value)
+            }
+            CborValue::Text(key) => (key.clone(), cbor_to_json(value)?),
+            _ => return Err(SinditSenMLError::InvalidCbor),
+        };
+        if map.insert(key, value).is_some() {
+            // Duplicate labels are not allowed.
+            return Err(SinditSenMLError::InvalidCbor);
+        }
+    }
+    Ok(Value::Object(map))
+}
+
+/// Number with an integer representation when it is integral.
+fn cbor_number(value: f64) -> CborValue {
+    if value.fract() == 0.0 && value.abs() < i64::MAX as f64 {
+        CborValue::Integer((value as i64).into())
+    } else {
+        CborValue::Float(value)
+    }
+}
+
+/// Convert a resolved record to SenML CBOR, with the same fields as its
+/// JSON serialisation.
+fn resolved_record_to_cbor(record: &SenMLResolvedRecord) -> CborValue {
+    let label = |label: i64| CborValue::Integer(label.into());
+    let mut entries = Vec::new();
+    if let Some(base_version) = record.base_version {
+        entries.push((label(-1), CborValue::Integer(base_version.into())));
+    }
+    entries.push((label(0), CborValue::Text(record.name.clone())));
+    if let Some(ref unit) = record.unit {
+        entries.push((label(1), CborValue::Text(unit.clone())));
+    }
+    match record.value {
+        Some(SenMLValueField::FloatingPoint(value)) => entries.push((label(2), cbor_number(value))),
+        Some(SenMLValueField::StringValue(ref value)) => {
+            entries.push((label(3), CborValue::Text(value.clone())))
+        }
+        Some(SenMLValueField::BooleanValue(value)) => {
+            entries.push((label(4), CborValue::Bool(value)))
+        }
+        Some(SenMLValueField::DataValue(ref value)) => {
+            entries.push((label(8), CborValue::Bytes(value.clone())))
+        }
+        None => {}
+    }
+    if let Some(sum) = record.sum {
+        entries.push((label(5), cbor_number(sum)));
+    }
+    let time = match datetime_to_timestamp(&record.time) {
+        (_, Some(precise_timestamp)) => CborValue::Float(precise_timestamp),
+        (timestamp, None) => CborValue::Integer(timestamp.into()),
+    };
+    entries.push((label(6), time));
+    if let Some(update_time) = record.update_time {
+        entries.push((label(7), cbor_number(update_time)));
+    }
+    if let Some(ref extra_fields) = record.extra_fields {
+        let mut extra_fields: Vec<(&String, &Value)> = extra_fields.iter().collect();
+        extra_fields.sort_by_key(|(key, _)| *key);
+        for (key, value) in extra_fields {
+            entries.push((CborValue::Text(key.clone()), json_to_cbor(value)));
+        }
+    }
+    CborValue::Map(entries)
+}
+
+/// Parse a SenML CBOR pack and return SenMLResolvedRecords.
+///
+/// The pack is resolved like a SenML JSON pack, see [`crate::parse_json`].
+/// Text labels are extra fields, and unknown integer labels are rejected.
+///
+/// # Arguments
+/// * `bytes` - The SenML CBOR pack, an array of maps.
+/// * `now` - The current time. Defaults to current UTC time.
+/// # Returns
+/// * `Result<Vec<SenMLResolvedRecord>, SinditSenMLError>` - The parsed SenML records,
+///   `InvalidCbor` if the bytes are not a SenML CBOR pack.
+/// # Examples
+/// ```
+/// use sindit_senml::cbor::parse_cbor;
+///
+/// // [{0: "temp", 2: 1.0}]
+/// let bytes = [0x81, 0xa2, 0x00, 0x64, 0x74, 0x65, 0x6d, 0x70, 0x02, 0xf9, 0x3c, 0x00];
+/// let records = parse_cbor(&bytes, None).unwrap();
+/// assert_eq!(records[0].name, "temp");
+/// assert_eq!(records[0].get_float_value(), Some(1.0));
+/// ```
+pub fn parse_cbor(
+    bytes: &[u8],
+    now: Option<DateTime<Utc>>,
+) -> Result<Vec<SenMLResolvedRecord>, SinditSenMLError> {
+    let CborValue::Array(items) = decode(bytes)? else {
+        return Err(SinditSenMLError::InvalidCbor);
+    };
+    let json = Value::Array(
+        items
+            .iter()
+            .map(cbor_record_to_json)
+            .collect::<Result<Vec<Value>, SinditSenMLError>>()?,
+    );
+    let records: Vec<SenMLRecord> = serde_json::from_value(json)?;
+    resolve_records(&records, now.unwrap_or(Utc::now()))
+}
+
+/// Encode resolved records to a SenML CBOR pack.
+///
+/// The records have the same fields as in their JSON serialisation,
+/// with the integer labels and the data values as byte strings.
+///
+/// # Examples
+/// ```
+/// use sindit_senml::{cbor::{parse_cbor, to_cbor_bytes}, parse_json};
+///
+/// let records = parse_json(r#"[{"n": "temp", "v": 23.1, "t": 1320067464}]"#, None).unwrap();
+/// assert_eq!(parse_cbor(&to_cbor_bytes(&records), None).unwrap(), records);
+/// ```
+pub fn to_cbor_bytes(records: &[SenMLResolvedRecord]) -> Vec<u8> {
+    encode(&CborValue::Array(
+        records.iter().map(resolved_record_to_cbor).collect(),
+    ))
+}
+
+/// Convert CBOR to the CBOR diagnostic notation.
+///
+/// # Arguments
+/// * `bytes` - A single CBOR data item.
+/// # Returns
+/// * `Result<String, SinditSenMLError>` - The diagnostic notation,
+///   `InvalidCbor` if the bytes are not a single well-formed data item.
+///   The simple values other than the booleans, null, and undefined are not
+///   supported, and undefined is written `null`.
+/// # Examples
+/// ```
+/// use sindit_senml::cbor::cbor_diagnostic;
+///
+/// let bytes = [0x81, 0xa2, 0x00, 0x64, 0x74, 0x65, 0x6d, 0x70, 0x02, 0xf9, 0x3c, 0x00];
+/// assert_eq!(cbor_diagnostic(&bytes).unwrap(), r#"[{0: "temp", 2: 1.0}]"#);
+/// ```
+pub fn cbor_diagnostic(bytes: &[u8]) -> Result<String, SinditSenMLError> {
+    let mut diagnostic = String::new();
+    write_diagnostic(&decode(bytes)?, &mut diagnostic)?;
+    Ok(diagnostic)
+}
+
+impl SenMLResolvedRecord {
+    /// Encode the record to SenML CBOR and return its diagnostic notation.
+    ///
+    /// See [`cbor_diagnostic`].
+    pub fn to_cbor_diagnostic(&self) -> Result<String, SinditSenMLError> {
+        cbor_diagnostic(&encode(&resolved_record_to_cbor(self)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{
+        COLLECTION_OF_RESOURCES, MULTIPLE_DATAPOINT_AND_TIME, MULTIPLE_DATATYPES, SINGLE_DATAPOINT,
+    };
+    use crate::parse_json;
+
+    #[test]
+    fn test_cbor_diagnostic_of_pack() {
+        let now = DateTime::from_timestamp(1700000000, 0).unwrap();
+        let records = parse_json(MULTIPLE_DATATYPES, Some(now)).unwrap();
+        assert_eq!(
+            cbor_diagnostic(&to_cbor_bytes(&records[..1])).unwrap(),
+            r#"[{0: "urn:dev:ow:10e2073a01080063:temp", 1: "Cel", 2: 23.1, 6: 1700000000}]"#
+        );
+        assert_eq!(
+            cbor_diagnostic(&to_cbor_bytes(&records[1..])).unwrap(),
+            concat!(
+                r#"[{0: "urn:dev:ow:10e2073a01080063:label", 3: "Machine Room", 6: 1700000000}, "#,
+                r#"{0: "urn:dev:ow:10e2073a01080063:open", 4: false, 6: 1700000000}, "#,
+                r#"{0: "urn:dev:ow:10e2073a01080063:nfc-reader", 8: h'6869200a', 6: 1700000000}]"#
+            )
+        );
+    }
+
+    #[test]
+    fn test_parse_cbor_base_fields() {
+        // [{-2: "dev/", -3: 1320067464, 0: "temp", 2: 1}, {0: "hum", 2: 2, 6: 10}]
+        let bytes = [
+            0x82, 0xa4, 0x21, 0x64, 0x64, 0x65, 0x76, 0x2f, 0x22, 0x1a, 0x4e, 0xae, 0xa1, 0x88,
+            0x00, 0x64, 0x74, 0x65, 0x6d, 0x70, 0x02, 0x01, 0xa3, 0x00, 0x63, 0x68, 0x75, 0x6d,
+            0x02, 0x02, 0x06, 0x0a,
+        ];
+        let records = parse_cbor(&bytes, None).unwrap();
+        let json = parse_json(
+            r#"[{"bn": "dev/", "bt": 1320067464, "n": "temp", "v": 1}, {"n": "hum", "v": 2, "t": 10}]"#,
+            None,
+        )
+        .unwrap();
+        assert_eq!(records, json);
+    }
+
+    #[test]
+    fn test_record_to_cbor_diagnostic() {
+        let records = parse_json(
+            r#"[{"n": "temperature", "u": "Cel", "v": 23.5, "t": 1320067464, "ut": 60, "location": {"room": 1}}]"#,
+            None,
+        )
+        .unwrap();
+        assert_eq!(
+            records[0].to_cbor_diagnostic().unwrap(),
+            r#"{0: "temperature", 1: "Cel", 2: 23.5, 6: 1320067464, 7: 60, "location": {"room": 1}}"#
+        );
+    }
+
+    const SUM_AND_UPDATE_TIME: &str = r#"[
+        {"bn":"2001:db8::2/","bt":1.320078429e+09,
+        "n":"temperature","u":"K","v":272.15},
+        {"n":"humidity","u":"%RH","v":80},
+        {"n":"battery","u":"%EL","v":100, "s":2.5, "ut":60}
+    ]"#;
+
+    #[test]
+    fn test_cbor_roundtrip_rfc_examples() {
+        // Whole seconds, as float times lose the nanoseconds of now.
+        let now = DateTime::from_timestamp(1700000000, 0).unwrap();
+        for json in [
+            SINGLE_DATAPOINT,
+            MULTIPLE_DATAPOINT_AND_TIME,
+            SUM_AND_UPDATE_TIME,
+            COLLECTION_OF_RESOURCES,
+            MULTIPLE_DATATYPES,
+        ] {
+            let records = parse_json(json, Some(now)).unwrap();
+            assert_eq!(
+                parse_cbor(&to_cbor_bytes(&records), Some(now)).unwrap(),
+                records
+            );
+        }
+    }
+
+    #[test]
+    fn test_to_cbor_bytes() {
+        let records = parse_json(
+            r#"[{"n": "temperature", "u": "Cel", "v": 23.5, "t": 1320067464, "ut": 60, "location": {"room": 1}}]"#,
+            None,
+        )
+        .unwrap();
+        let bytes = to_cbor_bytes(&records);
+        assert_eq!(
+            cbor_diagnostic(&bytes).unwrap(),
+            r#"[{0: "temperature", 1: "Cel", 2: 23.5, 6: 1320067464, 7: 60, "location": {"room": 1}}]"#
+        );
+        assert_eq!(parse_cbor(&bytes, None).unwrap(), records);
+        assert_eq!(to_cbor_bytes(&[]), vec![0x80]);
+    }
+
+    #[test]
+    fn test_invalid_senml_cbor() {
+        for bytes in [
+            // {0: "temp"}, not an array
+            &[0xa1, 0x00, 0x64, 0x74, 0x65, 0x6d, 0x70][..],
+            // [{9: "temp"}], unknown label
+            &[0x81, 0xa1, 0x09, 0x64, 0x74, 0x65, 0x6d, 0x70],
+            // [{0: h'00'}], byte string name
+            &[0x81, 0xa1, 0x00, 0x41, 0x00],
+            // [{0: "a", 0: "b"}], duplicate label
+            &[0x81, 0xa2, 0x00, 0x61, 0x61, 0x00, 0x61, 0x62],
+            // [1]
+            &[0x81, 0x01],
+        ] {
+            assert!(matches!(
+                parse_cbor(bytes, None).unwrap_err(),
+                SinditSenMLError::InvalidCbor
+            ));
+        }
+        // [{2: 1.0}], no name
+        assert!(matches!(
+            parse_cbor(&[0x81, 0xa1, 0x02, 0xf9, 0x3c, 0x00], None).unwrap_err(),
+            SinditSenMLError::MissingName(0)
+        ));
+    }
+
+    #[test]
+    fn test_cbor_diagnostic_items() {
+        // RFC8949 appendix A
+        let examples: [(&[u8], &str); 10] = [
+            (
+                &[0x3b, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff],
+                "-18446744073709551616",
+            ),
+            (&[0xf9, 0x7c, 0x00], "Infinity"),
+            (&[0xf9, 0xc4, 0x00], "-4.0"),
+            (
+                &[0xfb, 0x7e, 0x37, 0xe4, 0x3c, 0x88, 0x00, 0x75, 0x9c],
+                "1e300",
+            ),
+            (&[0xc1, 0x1a, 0x51, 0x4b, 0x67, 0xb0], "1(1363896240)"),
+            (&[0x62, 0x22, 0x5c], r#""\"\\""#),
+            (
+                &[0x5f, 0x42, 0x01, 0x02, 0x43, 0x03, 0x04, 0x05, 0xff],
+                "h'0102030405'",
+            ),
+            (&[0x9f, 0x01, 0x82, 0x02, 0x03, 0xff], "[1, [2, 3]]"),
+            (&[0xbf, 0x61, 0x61, 0x01, 0xff], r#"{"a": 1}"#),
+            (&[0xa0], "{}"),
+        ];
+        for (bytes, expected) in examples {
+            assert_eq!(cbor_diagnostic(bytes).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn test_invalid_cbor() {
+        for bytes in [
+            &[][..],
+            &[0x82, 0x01],
+            &[0x01, 0x02],
+            &[0x62, 0xff, 0xfe],
+            &[0x1c],
+            &[0x9f, 0x01],
+            &[0xf8, 0xff],
+        ] {
+            assert!(matches!(
+                cbor_diagnostic(bytes).unwrap_err(),
+                SinditSenMLError::InvalidCbor
+            ));
+        }
+        let nested = [0x81; 100];
+        assert!(cbor_diagnostic(&nested).is_err());
+    }
+}
@@ -0,0 +1,532 @@
+//! # Compact SenML Packs
+//!
+//! Convert resolved records back to SenML records using base fields,
+//! and split packs into chunks for networks with a small MTU,
+//! such as LoRaWAN or Zigbee.
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::time::{datetime_to_timestamp, duration_seconds};
+use crate::{Base64Variant, SenMLRecord, SenMLResolvedRecord, SenMLTime, SenMLValueField};
+
+/// Size of the JSON serialisation.
+fn json_len<T: Serialize + ?Sized>(value: &T) -> usize {
+    // Serialising records to JSON does not fail as all the keys are strings.
+    serde_json::to_vec(value)
+        .map(|json| json.len())
+        .unwrap_or(usize::MAX)
+}
+
+fn timestamp_seconds(datetime: &DateTime<Utc>) -> f64 {
+    let (timestamp, precise_timestamp) = datetime_to_timestamp(datetime);
+    precise_timestamp.unwrap_or(timestamp as f64)
+}
+
+/// Longest common prefix of the names, ending with a `:` or `/` separator.
+fn common_base_name(records: &[SenMLResolvedRecord]) -> &str {
+    let first = match records.first() {
+        Some(first) => first.name.as_str(),
+        None => return "",
+    };
+    let prefix_len = records
+        .iter()
+        .skip(1)
+        .fold(first.len(), |prefix_len, record| {
+            first[..prefix_len]
+                .char_indices()
+                .zip(record.name.chars())
+                .find(|((_, a), b)| a != b)
+                .map(|((index, _), _)| index)
+                .unwrap_or(prefix_len.min(record.name.len()))
+        });
+    name_prefix(&first[..prefix_len])
+}
+
+/// Record with the value, sum, update time, and extra fields of `record`,
+/// and its time relative to `base_time`.
+///
+/// The name and the unit are not set.
+fn record_relative_to(record: &SenMLResolvedRecord, base_time: DateTime<Utc>) -> SenMLRecord {
+    let offset = duration_seconds(record.time - base_time);
+    let mut compact = SenMLRecord {
+        sum: record.sum,
+        time: (offset != 0.0).then_some(SenMLTime::Seconds(offset)),
+        update_time: record.update_time,
+        extra_fields: record.extra_fields.clone(),
+        ..Default::default()
+    };
+    match record.value {
+        Some(SenMLValueField::FloatingPoint(value)) => compact.value = Some(value),
+        Some(SenMLValueField::StringValue(ref value)) => compact.string_value = Some(value.clone()),
+        Some(SenMLValueField::BooleanValue(value)) => compact.bool_value = Some(value),
+        Some(SenMLValueField::DataValue(ref value)) => {
+            compact.data_value = Some(Base64Variant::UrlSafeNoPad.encode(value))
+        }
+        None => {}
+    }
+    compact
+}
+
+/// Prefix of the name up to its last `:` or `/` separator.
+fn name_prefix(name: &str) -> &str {
+    match name.rfind([':', '/']) {
+        Some(index) => &name[..index + 1],
+        None => "",
+    }
+}
+
+/// Convert resolved records to a compact SenML Pack.
+///
+/// The first record holds the base fields:
+/// * `bn`: the common prefix of the names, up to a `:` or `/` separator.
+/// * `bt`: the time of the first record. The other records have a time
+///   relative to it.
+/// * `bu`: the unit, if all the records have the same unit.
+/// * `bver`: the version of the first record, if it is not the default one.
+///
+/// Parsing the compact pack gives the same records, apart from the
+/// floating point imprecision of subsecond times.
+///
+/// # Examples
+/// ```
+/// use sindit_senml::{parse_json, compact::to_compact_pack};
+///
+/// let records = parse_json(
+///     r#"[{"n": "urn:dev:ow:10e2073a01080063:temp", "u": "Cel", "v": 23.1, "t": 1320067464},
+///         {"n": "urn:dev:ow:10e2073a01080063:temp", "u": "Cel", "v": 23.2, "t": 1320067524}]"#,
+///     None,
+/// ).unwrap();
+/// let compact = to_compact_pack(&records);
+/// assert_eq!(
+///     serde_json::to_string(&compact).unwrap(),
+///     r#"[{"bn":"urn:dev:ow:10e2073a01080063:","bt":1320067464.0,"bu":"Cel","n":"temp","v":23.1},{"n":"temp","v":23.2,"t":60.0}]"#
+/// );
+/// ```
+pub fn to_compact_pack(records: &[SenMLResolvedRecord]) -> Vec<SenMLRecord> {
+    let first = match records.first() {
+        Some(first) => first,
+        None => return Vec::new(),
+    };
+    let base_name = common_base_name(records);
+    let base_unit = match first.unit {
+        Some(ref unit) if records.iter().all(|record| record.unit == first.unit) => {
+            Some(unit.clone())
+        }
+        _ => None,
+    };
+
+    records
+        .iter()
+        .enumerate()
+        .map(|(index, record)| {
+            let mut compact = record_relative_to(record, first.time);
+            let name = &record.name[base_name.len()..];
+            compact.name = (!name.is_empty()).then(|| name.to_string());
+            if base_unit.is_none() {
+                compact.unit = record.unit.clone();
+            }
+            if index == 0 {
+                compact.base_name = (!base_name.is_empty()).then(|| base_name.to_string());
+                compact.base_time = Some(timestamp_seconds(&first.time));
+                compact.base_unit = base_unit.clone();
+                compact.base_version = first.base_version;
+            }
+            compact
+        })
+        .collect()
+}
+
+/// Convert resolved records back to a SenML Pack, record by record.
+///
+/// Unlike [`to_compact_pack`], the base name can change within the pack:
+/// * `bn`: the prefix of the first record name, up to its last `:` or `/`
+///   separator. A record whose name has another prefix sets a new base
+///   name, the prefix of its own name.
+/// * `bt`: the time of the first record. The other records have a time
+///   relative to it.
+/// * `bu`: the most common unit, if all the records have a unit. The records
+///   with another unit keep it.
+/// * `bver`: the version of the first record, if it is not the default one.
+///
+/// The extra fields of each record are carried over to the same record.
+///
+/// # Examples
+/// ```
+/// use sindit_senml::{parse_json, compact::deresolved_pack};
+///
+/// let records = parse_json(
+///     r#"[{"n": "2001:db8::2/temperature", "u": "Cel", "v": 25.2, "t": 1320078429},
+///         {"n": "2001:db8::1/temperature", "u": "Cel", "v": 12.3, "t": 1320078429, "location": "room1"}]"#,
+///     None,
+/// ).unwrap();
+/// let pack = deresolved_pack(&records);
+/// assert_eq!(
+///     serde_json::to_string(&pack).unwrap(),
+///     r#"[{"bn":"2001:db8::2/","bt":1320078429.0,"bu":"Cel","n":"temperature","v":25.2},{"bn":"2001:db8::1/","n":"temperature","v":12.3,"location":"room1"}]"#
+/// );
+/// ```
+pub fn deresolved_pack(records: &[SenMLResolvedRecord]) -> Vec<SenMLRecord> {
+    let first = match records.first() {
+        Some(first) => first,
+        None => return Vec::new(),
+    };
+
+    // Most common unit, the first one in case of a tie.
+    let mut unit_counts: Vec<(&str, usize)> = Vec::new();
+    for record in records.iter() {
+        match record.unit {
+            Some(ref unit) => match unit_counts.iter_mut().find(|(other, _)| other == unit) {
+                Some((_, count)) => *count += 1,
+                None => unit_counts.push((unit, 1)),
+            },
+            None => {
+                unit_counts.clear();
+                break;
+            }
+        }
+    }
+    let base_unit = unit_counts
+        .iter()
+        .rev()
+        .max_by_key(|(_, count)| *count)
+        .map(|(unit, _)| *unit);
+
+    let mut base_name = name_prefix(&first.name);
+    records
+        .iter()
+        .enumerate()
+        .map(|(index, record)| {
+            let mut deresolved = record_relative_to(record, first.time);
+            if index == 0 {
+                deresolved.base_name = (!base_name.is_empty()).then(|| base_name.to_string());
+            } else if name_prefix(&record.name) != base_name {
+                // An empty base name resets the base name.
+                base_name = name_prefix(&record.name);
+                deresolved.base_name = Some(base_name.to_string());
+            }
+            let name = &record.name[base_name.len()..];
+            deresolved.name = (!name.is_empty()).then(|| name.to_string());
+            if record.unit.as_deref() != base_unit {
+                deresolved.unit = record.unit.clone();
+            }
+            if index == 0 {
+                deresolved.base_time = Some(timestamp_seconds(&first.time));
+                deresolved.base_unit = base_unit.map(|unit| unit.to_string());
+                deresolved.base_version = first.base_version;
+            }
+            deresolved
+        })
+        .collect()
+}
+
+/// Convert resolved records to a compact SenML Pack in JSON.
+///
+/// This is the JSON serialisation of [`deresolved_pack`]: the runs of
+/// consecutive records with the same name prefix share a base name, the
+/// records have a time relative to the base time, and the most common unit
+/// is the base unit.
+///
+/// Parsing the pack gives the same records, apart from the floating point
+/// imprecision of subsecond times.
+///
+/// # Examples
+/// ```
+/// use sindit_senml::{parse_json, compact::optimize_pack};
+///
+/// let records = parse_json(
+///     r#"[{"n": "urn:dev:ow:10e2073a01080063:temp", "u": "Cel", "v": 23.1, "t": 1320067464},
+///         {"n": "urn:dev:ow:10e2073a01080063:temp", "u": "Cel", "v": 23.2, "t": 1320067524},
+///         {"n": "urn:dev:ow:10e2073a01080063:humidity", "u": "%RH", "v": 40, "t": 1320067524}]"#,
+///     None,
+/// ).unwrap();
+/// assert_eq!(
+///     optimize_pack(&records),
+///     r#"[{"bn":"urn:dev:ow:10e2073a01080063:","bt":1320067464.0,"bu":"Cel","n":"temp","v":23.1},{"n":"temp","v":23.2,"t":60.0},{"n":"humidity","u":"%RH","v":40.0,"t":60.0}]"#
+/// );
+/// assert_eq!(parse_json(&optimize_pack(&records), None).unwrap(), records);
+/// ```
+pub fn optimize_pack(records: &[SenMLResolvedRecord]) -> String {
+    // Serialising records to JSON does not fail as all the keys are strings.
+    serde_json::to_string(&deresolved_pack(records)).unwrap_or_default()
+}
+
+/// Split the records in chunks serialising to at most `max_bytes` of JSON.
+///
+/// The records are serialised fully resolved, without base fields.
+/// The records are accumulated greedily in the chunks, in order.
+/// A record serialising to more than `max_bytes` is alone in its chunk.
+///
+/// # Examples
+/// ```
+/// use sindit_senml::{parse_json, compact::chunk_pack};
+///
+/// let records = parse_json(r#"[{"n": "a", "v": 1, "t": 1320067464},{"n": "b", "v": 2, "t": 1320067464}]"#, None).unwrap();
+/// let chunks = chunk_pack(&records, 40);
+/// assert_eq!(chunks.len(), 2);
+/// ```
+pub fn chunk_pack(
+    records: &[SenMLResolvedRecord],
+    max_bytes: usize,
+) -> Vec<Vec<SenMLResolvedRecord>> {
+    // Length of "[]"
+    const EMPTY_LEN: usize = 2;
+
+    let mut chunks = Vec::new();
+    let mut chunk: Vec<SenMLResolvedRecord> = Vec::new();
+    let mut chunk_len = EMPTY_LEN;
+    for record in records.iter() {
+        let record_len = json_len(record);
+        // Records are separated by a comma
+        if !chunk.is_empty() && chunk_len.saturating_add(record_len) >= max_bytes {
+            chunks.push(std::mem::take(&mut chunk));
+            chunk_len = EMPTY_LEN;
+        }
+        if !chunk.is_empty() {
+            chunk_len += 1;
+        }
+        chunk_len = chunk_len.saturating_add(record_len);
+        chunk.push(record.clone());
+    }
+    if !chunk.is_empty() {
+        chunks.push(chunk);
+    }
+    chunks
+}
+
+/// Same as [`chunk_pack`] but each chunk is compacted with [`to_compact_pack`].
+///
+/// Each chunk is a standalone SenML Pack with its own base fields.
+pub fn chunk_pack_compact(
+    records: &[SenMLResolvedRecord],
+    max_bytes: usize,
+) -> Vec<Vec<SenMLRecord>> {
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    while start < records.len() {
+        let mut end = start + 1;
+        while end < records.len()
+            && json_len(&to_compact_pack(&records[start..end + 1])) <= max_bytes
+        {
+            end += 1;
+        }
+        chunks.push(to_compact_pack(&records[start..end]));
+        start = end;
+    }
+    chunks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_json;
+
+    fn pack_json(count: usize) -> String {
+        format!(
+            "[{}]",
+            (0..count)
+                .map(|index| format!(
+                    r#"{{"n":"urn:dev:ow:10e2073a01080063:temp","u":"Cel","v":{}.5,"t":{}}}"#,
+                    20 + index,
+                    1320067464 + index * 60
+                ))
+                .collect::<Vec<_>>()
+                .join(",")
+        )
+    }
+
+    #[test]
+    fn test_compact_roundtrip() {
+        let records = parse_json(
+            r#"[
+                {"bn":"2001:db8::2/","bt":1.320078429e+09,"n":"temperature","u":"Cel","v":25.2},
+                {"n":"humidity","u":"%RH","v":30,"ut":60},
+                {"bn":"2001:db8::1/","n":"temperature","u":"Cel","v":12.3,"location":"room1"},
+                {"n":"label","vs":"Machine Room","t":-1.5},
+                {"n":"open","vb":false,"t":2},
+                {"n":"nfc-reader","vd":"aGkgCg"},
+                {"n":"energy","u":"kWh","s":42.0}
+            ]"#,
+            None,
+        )
+        .unwrap();
+        let compact = to_compact_pack(&records);
+        assert_eq!(compact.len(), records.len());
+        assert_eq!(compact[0].base_name.as_deref(), Some("2001:db8::"));
+        assert_eq!(compact[0].base_unit, None);
+        let json = serde_json::to_string(&compact).unwrap();
+        assert!(json.len() < serde_json::to_string(&records).unwrap().len());
+        assert_eq!(parse_json(&json, None).unwrap(), records);
+    }
+
+    #[test]
+    fn test_compact_base_unit_and_version() {
+        let records = parse_json(&pack_json(3), None).unwrap();
+        let compact = to_compact_pack(&records);
+        assert_eq!(compact[0].base_unit.as_deref(), Some("Cel"));
+        assert!(compact.iter().all(|record| record.unit.is_none()));
+        assert_eq!(compact[0].base_version, None);
+        assert_eq!(compact[2].time, Some(SenMLTime::Seconds(120.0)));
+
+        let records = parse_json(
+            r#"[{"bver":11,"n":"a","t":1320067464},{"n":"b","t":1320067464}]"#,
+            None,
+        )
+        .unwrap();
+        let compact = to_compact_pack(&records);
+        assert_eq!(compact[0].base_name, None);
+        assert_eq!(compact[0].base_version, Some(11));
+        let json = serde_json::to_string(&compact).unwrap();
+        assert_eq!(parse_json(&json, None).unwrap(), records);
+
+        assert!(to_compact_pack(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_deresolved_pack_roundtrip() {
+        // RFC8428 section 5.5, with extra fields
+        let records = parse_json(
+            r#"[
+                {"bn":"2001:db8::2/","bt":1.320078429e+09,"n":"temperature","u":"Cel","v":25.2},
+                {"n":"humidity","u":"%RH","v":30,"location":"room2"},
+                {"bn":"2001:db8::1/","n":"temperature","u":"Cel","v":12.3,"location":"room1"},
+                {"n":"humidity","u":"%RH","v":67}
+            ]"#,
+            None,
+        )
+        .unwrap();
+        let pack = deresolved_pack(&records);
+        assert_eq!(pack.len(), 4);
+        assert_eq!(pack[0].base_name.as_deref(), Some("2001:db8::2/"));
+        assert_eq!(pack[1].base_name, None);
+        assert_eq!(pack[2].base_name.as_deref(), Some("2001:db8::1/"));
+        assert_eq!(pack[3].base_name, None);
+        assert_eq!(pack[0].base_unit.as_deref(), Some("Cel"));
+        assert_eq!(pack[1].unit.as_deref(), Some("%RH"));
+        assert!(pack[1]
+            .extra_fields
+            .as_ref()
+            .unwrap()
+            .contains_key("location"));
+
+        let json = serde_json::to_string(&pack).unwrap();
+        assert_eq!(parse_json(&json, None).unwrap(), records);
+    }
+
+    #[test]
+    fn test_deresolved_pack_units_and_names() {
+        let records = parse_json(
+            r#"[
+                {"n":"temperature","u":"Cel","v":25.2,"t":1320078429},
+                {"n":"urn:dev:ow:10e2073a01080063:temp","u":"K","v":300,"t":1320078430},
+                {"n":"urn:dev:ow:10e2073a01080063:temp","u":"K","v":301,"t":1320078431},
+                {"n":"light","v":300,"t":1320078432}
+            ]"#,
+            None,
+        )
+        .unwrap();
+        let pack = deresolved_pack(&records);
+        // A record without unit, no base unit
+        assert_eq!(pack[0].base_unit, None);
+        assert_eq!(pack[0].base_name, None);
+        assert_eq!(
+            pack[1].base_name.as_deref(),
+            Some("urn:dev:ow:10e2073a01080063:")
+        );
+        assert_eq!(pack[2].base_name, None);
+        assert_eq!(pack[3].base_name.as_deref(), Some(""));
+        let json = serde_json::to_string(&pack).unwrap();
+        assert_eq!(parse_json(&json, None).unwrap(), records);
+
+        let pack = deresolved_pack(&records[..3]);
+        assert_eq!(pack[0].base_unit.as_deref(), Some("K"));
+        assert_eq!(pack[0].unit.as_deref(), Some("Cel"));
+        assert_eq!(pack[1].unit, None);
+        let json = serde_json::to_string(&pack).unwrap();
+        assert_eq!(parse_json(&json, None).unwrap(), &records[..3]);
+
+        assert!(deresolved_pack(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_optimize_pack() {
+        let records = parse_json(&pack_json(100), None).unwrap();
+        let json = optimize_pack(&records);
+        assert_eq!(json.matches(r#""u""#).count(), 0);
+        assert_eq!(json.matches(r#""bu""#).count(), 1);
+        assert_eq!(json.matches(r#""bn""#).count(), 1);
+        assert!(json.len() < serde_json::to_string(&records).unwrap().len() / 2);
+        assert_eq!(parse_json(&json, None).unwrap(), records);
+
+        let records = parse_json(
+            r#"[
+                {"bn":"2001:db8::2/","bt":1.320078429e+09,"bver":5,"n":"temperature","u":"Cel","v":25.2},
+                {"n":"humidity","u":"%RH","v":30,"ut":60},
+                {"bn":"2001:db8::1/","n":"temperature","u":"Cel","v":12.3,"location":"room1"},
+                {"n":"label","vs":"Machine Room","t":-1.5},
+                {"bn":"","n":"open","vb":false,"t":2},
+                {"n":"nfc-reader","vd":"aGkgCg"},
+                {"bn":"2001:db8::1/","n":"energy","u":"kWh","s":42.0}
+            ]"#,
+            None,
+        )
+        .unwrap();
+        let json = optimize_pack(&records);
+        assert_eq!(parse_json(&json, None).unwrap(), records);
+        assert_eq!(json.matches(r#""bu""#).count(), 0);
+
+        assert_eq!(optimize_pack(&[]), "[]");
+    }
+
+    #[test]
+    fn test_common_base_name() {
+        let records = parse_json(
+            r#"[{"n":"urn:dev:ow:10e2073a01080063:temp"},{"n":"urn:dev:ow:10e2073a01080063:temperature"}]"#,
+            None,
+        )
+        .unwrap();
+        assert_eq!(common_base_name(&records), "urn:dev:ow:10e2073a01080063:");
+        assert_eq!(
+            common_base_name(&records[..1]),
+            "urn:dev:ow:10e2073a01080063:"
+        );
+        let records = parse_json(r#"[{"n":"temp"},{"n":"temperature"}]"#, None).unwrap();
+        assert_eq!(common_base_name(&records), "");
+    }
+
+    #[test]
+    fn test_chunk_pack() {
+        let records = parse_json(&pack_json(14), None).unwrap();
+        assert!(json_len(&records) >= 1000);
+
+        let chunks = chunk_pack(&records, 200);
+        assert!(chunks.len() > 1);
+        for chunk in chunks.iter() {
+            assert!(json_len(chunk) <= 200);
+        }
+        assert_eq!(chunks.concat(), records);
+    }
+
+    #[test]
+    fn test_chunk_pack_compact() {
+        let records = parse_json(&pack_json(14), None).unwrap();
+        let chunks = chunk_pack_compact(&records, 200);
+        assert!(chunks.len() < chunk_pack(&records, 200).len());
+        let mut reassembled = Vec::new();
+        for chunk in chunks.iter() {
+            let json = serde_json::to_string(chunk).unwrap();
+            assert!(json.len() <= 200);
+            reassembled.extend(parse_json(&json, None).unwrap());
+        }
+        assert_eq!(reassembled, records);
+    }
+
+    #[test]
+    fn test_chunk_larger_than_max_bytes() {
+        let records = parse_json(&pack_json(2), None).unwrap();
+        let chunks = chunk_pack(&records, 10);
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunk_pack_compact(&records, 10).len(), 2);
+        assert!(chunk_pack(&[], 10).is_empty());
+    }
+}
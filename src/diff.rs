@@ -0,0 +1,288 @@
+//! # Diff SenML records
+//!
+//! Describe field by field how a record changed, for example when the
+//! configuration of a sensor is updated, and compare records with
+//! tolerances.
+
+use std::collections::BTreeSet;
+
+use chrono::{DateTime, Utc};
+
+use crate::{SenMLResolvedRecord, SenMLValueField};
+
+/// A field that differs between two records.
+#[derive(Debug, PartialEq, Clone)]
+pub enum FieldChange {
+    Name {
+        old: String,
+        new: String,
+    },
+    Unit {
+        old: Option<String>,
+        new: Option<String>,
+    },
+    Value {
+        old: Option<SenMLValueField>,
+        new: Option<SenMLValueField>,
+    },
+    Sum {
+        old: Option<f64>,
+        new: Option<f64>,
+    },
+    Time {
+        old: DateTime<Utc>,
+        new: DateTime<Utc>,
+    },
+    UpdateTime {
+        old: Option<f64>,
+        new: Option<f64>,
+    },
+    /// An extra field that was added, removed, or modified.
+    ExtraField {
+        key: String,
+        old: Option<serde_json::Value>,
+        new: Option<serde_json::Value>,
+    },
+}
+
+/// List the fields that differ between two records.
+///
+/// The changes are in the order of the record fields,
+/// the extra fields changes are sorted by key.
+/// The base version is not compared.
+///
+/// # Arguments
+/// * `a` - The old record.
+/// * `b` - The new record.
+/// # Returns
+/// * `Vec<FieldChange>` - The changes, empty if the records are equal.
+/// # Examples
+/// ```
+/// use sindit_senml::{parse_json, diff::{diff_records, FieldChange}};
+///
+/// let records = parse_json(r#"[{"n": "temperature", "u": "Cel"},{"n": "temperature", "u": "K"}]"#, None).unwrap();
+/// assert_eq!(
+///     diff_records(&records[0], &records[1]),
+///     vec![FieldChange::Unit { old: Some("Cel".to_string()), new: Some("K".to_string()) }]
+/// );
+/// ```
+pub fn diff_records(a: &SenMLResolvedRecord, b: &SenMLResolvedRecord) -> Vec<FieldChange> {
+    let mut changes = Vec::new();
+
+    if a.name != b.name {
+        changes.push(FieldChange::Name {
+            old: a.name.clone(),
+            new: b.name.clone(),
+        });
+    }
+    if a.unit != b.unit {
+        changes.push(FieldChange::Unit {
+            old: a.unit.clone(),
+            new: b.unit.clone(),
+        });
+    }
+    if a.value != b.value {
+        changes.push(FieldChange::Value {
+            old: a.value.clone(),
+            new: b.value.clone(),
+        });
+    }
+    if a.sum != b.sum {
+        changes.push(FieldChange::Sum {
+            old: a.sum,
+            new: b.sum,
+        });
+    }
+    if a.time != b.time {
+        changes.push(FieldChange::Time {
+            old: a.time,
+            new: b.time,
+        });
+    }
+    if a.update_time != b.update_time {
+        changes.push(FieldChange::UpdateTime {
+            old: a.update_time,
+            new: b.update_time,
+        });
+    }
+
+    let keys: BTreeSet<&String> = a
+        .extra_fields
+        .iter()
+        .chain(b.extra_fields.iter())
+        .flat_map(|extra_fields| extra_fields.keys())
+        .collect();
+    for key in keys {
+        let old = a.extra_fields.as_ref().and_then(|fields| fields.get(key));
+        let new = b.extra_fields.as_ref().and_then(|fields| fields.get(key));
+        if old != new {
+            changes.push(FieldChange::ExtraField {
+                key: key.clone(),
+                old: old.cloned(),
+                new: new.cloned(),
+            });
+        }
+    }
+
+    changes
+}
+
+fn approx_eq_option(a: Option<f64>, b: Option<f64>, epsilon: f64) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => (a - b).abs() <= epsilon,
+        (None, None) => true,
+        _ => false,
+    }
+}
+
+impl SenMLResolvedRecord {
+    /// Compare the records, with tolerances for the floating point values
+    /// and the time.
+    ///
+    /// # Arguments
+    /// * `other` - The record to compare with.
+    /// * `value_epsilon` - The tolerance of the value, the sum, and the update time.
+    /// * `time_tolerance_nanos` - The tolerance of the time, in nanoseconds.
+    pub fn approx_eq(
+        &self,
+        other: &SenMLResolvedRecord,
+        value_epsilon: f64,
+        time_tolerance_nanos: i64,
+    ) -> bool {
+        let value_eq = match (&self.value, &other.value) {
+            (Some(a), Some(b)) => a.approx_eq(b, value_epsilon),
+            (None, None) => true,
+            _ => false,
+        };
+        let time_eq = (self.time - other.time)
+            .num_nanoseconds()
+            .map(|nanoseconds| nanoseconds.abs() <= time_tolerance_nanos)
+            .unwrap_or(false);
+
+        self.name == other.name
+            && self.unit == other.unit
+            && value_eq
+            && approx_eq_option(self.sum, other.sum, value_epsilon)
+            && time_eq
+            && approx_eq_option(self.update_time, other.update_time, value_epsilon)
+            && self.base_version == other.base_version
+            && self.extra_fields == other.extra_fields
+    }
+}
+
+/// Compare the records of two packs, in order, with tolerances.
+///
+/// See [`SenMLResolvedRecord::approx_eq`].
+pub fn packs_approx_equal(
+    a: &[SenMLResolvedRecord],
+    b: &[SenMLResolvedRecord],
+    value_epsilon: f64,
+    time_tolerance_nanos: i64,
+) -> bool {
+    a.len() == b.len()
+        && a.iter()
+            .zip(b.iter())
+            .all(|(a, b)| a.approx_eq(b, value_epsilon, time_tolerance_nanos))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_json;
+
+    #[test]
+    fn test_diff_records() {
+        let records = parse_json(
+            r#"[
+                {"n": "temperature", "u": "Cel", "v": 23.1, "t": 1320067464, "location": "room1", "owner": "alice"},
+                {"n": "temperature", "u": "K", "v": 296.25, "t": 1320067464, "location": "room2", "owner": "alice"}
+            ]"#,
+            None,
+        )
+        .unwrap();
+        let changes = diff_records(&records[0], &records[1]);
+        assert_eq!(changes.len(), 3);
+        assert_eq!(
+            changes[0],
+            FieldChange::Unit {
+                old: Some("Cel".to_string()),
+                new: Some("K".to_string()),
+            }
+        );
+        assert_eq!(
+            changes[1],
+            FieldChange::Value {
+                old: Some(SenMLValueField::FloatingPoint(23.1)),
+                new: Some(SenMLValueField::FloatingPoint(296.25)),
+            }
+        );
+        assert_eq!(
+            changes[2],
+            FieldChange::ExtraField {
+                key: "location".to_string(),
+                old: Some(serde_json::json!("room1")),
+                new: Some(serde_json::json!("room2")),
+            }
+        );
+    }
+
+    #[test]
+    fn test_diff_extra_field_added_and_removed() {
+        let records = parse_json(
+            r#"[{"n": "temperature", "t": 1, "location": "room1"},{"n": "temperature", "t": 1, "owner": "alice"}]"#,
+            None,
+        )
+        .unwrap();
+        assert_eq!(
+            diff_records(&records[0], &records[1]),
+            vec![
+                FieldChange::ExtraField {
+                    key: "location".to_string(),
+                    old: Some(serde_json::json!("room1")),
+                    new: None,
+                },
+                FieldChange::ExtraField {
+                    key: "owner".to_string(),
+                    old: None,
+                    new: Some(serde_json::json!("alice")),
+                },
+            ]
+        );
+        assert!(diff_records(&records[0], &records[0]).is_empty());
+    }
+
+    #[test]
+    fn test_approx_eq() {
+        let records = parse_json(
+            r#"[
+                {"n": "temperature", "u": "Cel", "v": 23.1, "t": 1320067464},
+                {"n": "temperature", "u": "Cel", "v": 23.1000000001, "t": 1320067464.000001}
+            ]"#,
+            None,
+        )
+        .unwrap();
+        assert_ne!(records[0], records[1]);
+        assert!(records[0].approx_eq(&records[1], 1e-9, 1_000_000));
+        assert!(!records[0].approx_eq(&records[1], 1e-11, 1_000_000));
+        assert!(!records[0].approx_eq(&records[1], 1e-9, 0));
+        assert!(packs_approx_equal(
+            &records[..1],
+            &records[1..],
+            1e-9,
+            1_000_000
+        ));
+        assert!(!packs_approx_equal(
+            &records,
+            &records[1..],
+            1e-9,
+            1_000_000
+        ));
+
+        let a = SenMLValueField::FloatingPoint(1.0);
+        assert!(a.approx_eq(&SenMLValueField::FloatingPoint(1.0 + 1e-10), 1e-9));
+        assert!(!a.approx_eq(&SenMLValueField::FloatingPoint(1.0 + 1e-10), 1e-11));
+        assert!(!a.approx_eq(&SenMLValueField::BooleanValue(true), 1.0));
+        assert!(SenMLValueField::BooleanValue(true)
+            .approx_eq(&SenMLValueField::BooleanValue(true), 0.0));
+    }
+}
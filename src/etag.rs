@@ -0,0 +1,99 @@
+//! # Conditional parsing of SenML Packs
+//!
+//! Clients implementing conditional fetches (HTTP ETag style) can keep the
+//! hash of the last pack they parsed and skip parsing when the pack
+//! did not change.
+//!
+//! The hash is the SHA-256 of the raw JSON bytes, so two packs that are
+//! semantically equal but formatted differently have different hashes.
+
+use chrono::{DateTime, Utc};
+use sha2::{Digest, Sha256};
+
+use crate::{parse_json, SenMLResolvedRecord, SinditSenMLError};
+
+/// Compute the hash of a raw SenML JSON pack.
+///
+/// # Arguments
+/// * `json_str` - The SenML JSON string.
+/// # Returns
+/// * `[u8; 32]` - The SHA-256 of the JSON string bytes.
+pub fn current_pack_hash(json_str: &str) -> [u8; 32] {
+    Sha256::digest(json_str.as_bytes()).into()
+}
+
+/// Parse SenML JSON only if it changed since the previous parse.
+///
+/// # Arguments
+/// * `json_str` - The SenML JSON string to parse.
+/// * `previous_hash` - The hash of the previously parsed pack,
+///   as returned by [`current_pack_hash`].
+/// * `now` - The current time. Defaults to current UTC time.
+/// # Returns
+/// * `Ok(None)` - The pack did not change.
+/// * `Ok(Some(records))` - The pack changed and was parsed.
+/// * `Err(SinditSenMLError)` - The pack changed but could not be parsed.
+/// # Examples
+/// ```
+/// use sindit_senml::etag::{current_pack_hash, parse_json_if_changed};
+///
+/// let json_str = r#"[{"n": "temperature", "v": 42.0}]"#;
+/// let hash = current_pack_hash(json_str);
+/// assert!(parse_json_if_changed(json_str, &hash, None).unwrap().is_none());
+/// ```
+pub fn parse_json_if_changed(
+    json_str: &str,
+    previous_hash: &[u8; 32],
+    now: Option<DateTime<Utc>>,
+) -> Result<Option<Vec<SenMLResolvedRecord>>, SinditSenMLError> {
+    if current_pack_hash(json_str) == *previous_hash {
+        return Ok(None);
+    }
+    parse_json(json_str, now).map(Some)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PACK: &str = r#"[{"n": "temperature", "u": "Cel", "v": 23.1}]"#;
+
+    #[test]
+    fn test_unchanged_pack() {
+        let hash = current_pack_hash(PACK);
+        assert!(parse_json_if_changed(PACK, &hash, None).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_changed_pack() {
+        let hash = current_pack_hash(PACK);
+        let changed_pack = PACK.replace("23.1", "23.2");
+        let records = parse_json_if_changed(&changed_pack, &hash, None)
+            .unwrap()
+            .unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].get_float_value(), Some(23.2));
+        assert_ne!(current_pack_hash(&changed_pack), hash);
+    }
+
+    #[test]
+    fn test_changed_invalid_pack() {
+        let hash = current_pack_hash(PACK);
+        assert!(matches!(
+            parse_json_if_changed("[{", &hash, None).unwrap_err(),
+            SinditSenMLError::InvalidJSON(_)
+        ));
+    }
+
+    #[test]
+    fn test_known_hash() {
+        assert_eq!(
+            current_pack_hash(""),
+            [
+                0xe3, 0xb0, 0xc4, 0x42, 0x98, 0xfc, 0x1c, 0x14, 0x9a, 0xfb, 0xf4, 0xc8, 0x99, 0x6f,
+                0xb9, 0x24, 0x27, 0xae, 0x41, 0xe4, 0x64, 0x9b, 0x93, 0x4c, 0xa4, 0x95, 0x99, 0x1b,
+                0x78, 0x52, 0xb8, 0x55
+            ]
+        );
+    }
+}
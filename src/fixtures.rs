@@ -0,0 +1,40 @@
+//! # SenML specification examples
+//!
+//! Examples of RFC 8428 shared by the unit tests.
+//! <https://www.rfc-editor.org/rfc/rfc8428#section-5>
+
+/// A temperature reading taken approximately "now" by a 1-wire sensor.
+pub(crate) const SINGLE_DATAPOINT: &str = r#"[
+    {"n":"urn:dev:ow:10e2073a01080063","u":"Cel","v":23.1}
+]"#;
+
+/// Voltage and current at a base time, with the current at each second
+/// for the previous 5 seconds.
+pub(crate) const MULTIPLE_DATAPOINT_AND_TIME: &str = r#"[
+    {"bn":"urn:dev:ow:10e2073a0108006:","bt":1.276020076001e+09,
+    "bu":"A","bver":5,
+    "n":"voltage","u":"V","v":120.1},
+    {"n":"current","t":-5,"v":1.2},
+    {"n":"current","t":-4,"v":1.3},
+    {"n":"current","t":-3,"v":1.4},
+    {"n":"current","t":-2,"v":1.5},
+    {"n":"current","t":-1,"v":1.6},
+    {"n":"current","v":1.7}
+]"#;
+
+/// A sensor returning different data types.
+pub(crate) const MULTIPLE_DATATYPES: &str = r#"[
+    {"bn":"urn:dev:ow:10e2073a01080063:","n":"temp","u":"Cel","v":23.1},
+    {"n":"label","vs":"Machine Room"},
+    {"n":"open","vb":false},
+    {"n":"nfc-reader","vd":"aGkgCg"}
+]"#;
+
+/// Measurements of two devices, the last record uses the base name of the
+/// third record and the base time of the first record.
+pub(crate) const COLLECTION_OF_RESOURCES: &str = r#"[
+    {"bn":"2001:db8::2/","bt":1.320078429e+09,"n":"temperature","u":"Cel","v":25.2},
+    {"n":"humidity","u":"%RH","v":30},
+    {"bn":"2001:db8::1/","n":"temperature","u":"Cel","v":12.3},
+    {"n":"humidity","u":"%RH","v":67}
+]"#;
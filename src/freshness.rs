@@ -0,0 +1,249 @@
+//! # Freshness of SenML Records
+//!
+//! The update time of a record is the maximum time before the sensor
+//! provides an updated reading. A record older than its update time is
+//! stale, which can indicate a failure of the sensor or of the
+//! communications path from the sensor.
+
+use std::collections::{BTreeMap, HashMap};
+
+use chrono::{DateTime, Duration, Utc};
+
+use crate::SenMLResolvedRecord;
+
+impl SenMLResolvedRecord {
+    /// Time left before the record is stale.
+    ///
+    /// # Returns
+    /// * `Option<Duration>` - `None` if the record has no update time,
+    ///   a negative duration if the record is already stale.
+    /// # Examples
+    /// ```
+    /// use chrono::{Duration, Utc};
+    /// use sindit_senml::parse_json;
+    ///
+    /// let now = Utc::now();
+    /// let records = parse_json(r#"[{"n": "temperature", "v": 21.5, "t": -5, "ut": 10}]"#, Some(now)).unwrap();
+    /// assert_eq!(records[0].time_until_stale(now), Some(Duration::seconds(5)));
+    /// assert!(records[0].is_fresh(now));
+    /// ```
+    pub fn time_until_stale(&self, now: DateTime<Utc>) -> Option<Duration> {
+        // Update times longer than about 292 years are truncated.
+        let update_time = Duration::nanoseconds((self.update_time? * 1_000_000_000f64) as i64);
+        Some(self.time - now + update_time)
+    }
+
+    /// The update time as a duration, with a millisecond precision.
+    pub fn update_duration(&self) -> Option<Duration> {
+        Some(Duration::milliseconds((self.update_time? * 1000.0) as i64))
+    }
+
+    /// Set the update time from a duration, in seconds.
+    pub fn set_update_duration(&mut self, duration: Duration) {
+        self.update_time = Some(match duration.num_nanoseconds() {
+            Some(nanoseconds) => nanoseconds as f64 / 1_000_000_000f64,
+            None => duration.num_milliseconds() as f64 / 1000.0,
+        });
+    }
+
+    /// Time before which the sensor should provide an updated reading.
+    ///
+    /// # Returns
+    /// * `Option<DateTime<Utc>>` - `None` if the record has no update time
+    ///   or the time is out of range.
+    pub fn next_expected_update(&self) -> Option<DateTime<Utc>> {
+        self.time.checked_add_signed(self.update_duration()?)
+    }
+
+    /// Whether the record is not older than its update time.
+    ///
+    /// Records without an update time are always fresh.
+    pub fn is_fresh(&self, now: DateTime<Utc>) -> bool {
+        match self.time_until_stale(now) {
+            Some(time_until_stale) => time_until_stale >= Duration::zero(),
+            None => true,
+        }
+    }
+}
+
+/// Records that are fresh at `now`, see [`SenMLResolvedRecord::is_fresh`].
+pub fn filter_fresh_records(
+    records: &[SenMLResolvedRecord],
+    now: DateTime<Utc>,
+) -> Vec<&SenMLResolvedRecord> {
+    records
+        .iter()
+        .filter(|record| record.is_fresh(now))
+        .collect()
+}
+
+/// Records that are stale at `now`, the complement of [`filter_fresh_records`].
+pub fn filter_stale_records(
+    records: &[SenMLResolvedRecord],
+    now: DateTime<Utc>,
+) -> Vec<&SenMLResolvedRecord> {
+    records
+        .iter()
+        .filter(|record| !record.is_fresh(now))
+        .collect()
+}
+
+/// Group the records by update time, rounded to the nearest second.
+///
+/// Records without an update time are grouped under `None`.
+pub fn group_by_update_time(
+    records: &[SenMLResolvedRecord],
+) -> HashMap<Option<u64>, Vec<&SenMLResolvedRecord>> {
+    let mut groups: HashMap<Option<u64>, Vec<&SenMLResolvedRecord>> = HashMap::new();
+    for record in records.iter() {
+        // Negative update times are grouped with 0.
+        let update_time = record
+            .update_time
+            .map(|update_time| update_time.round() as u64);
+        groups.entry(update_time).or_default().push(record);
+    }
+    groups
+}
+
+/// Names of the sensors whose most recent record is stale at `now`.
+///
+/// # Returns
+/// * `Vec<String>` - The names, sorted.
+/// # Examples
+/// ```
+/// use chrono::Utc;
+/// use sindit_senml::{parse_json, freshness::sensors_missing_updates};
+///
+/// let now = Utc::now();
+/// let records = parse_json(r#"[{"n": "door", "vb": true, "t": -120, "ut": 60}]"#, Some(now)).unwrap();
+/// assert_eq!(sensors_missing_updates(&records, now), vec!["door".to_string()]);
+/// ```
+pub fn sensors_missing_updates(records: &[SenMLResolvedRecord], now: DateTime<Utc>) -> Vec<String> {
+    let mut latest: BTreeMap<&str, &SenMLResolvedRecord> = BTreeMap::new();
+    for record in records.iter() {
+        let entry = latest.entry(record.name.as_str()).or_insert(record);
+        if record.time >= entry.time {
+            *entry = record;
+        }
+    }
+    latest
+        .into_iter()
+        .filter(|(_, record)| !record.is_fresh(now))
+        .map(|(name, _)| name.to_string())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_json;
+
+    #[test]
+    fn test_fresh_and_stale() {
+        let now = Utc::now();
+        let records = parse_json(
+            r#"[
+                {"n": "temperature", "v": 21.5, "t": -5, "ut": 10.0},
+                {"n": "humidity", "v": 40.0, "t": -5, "ut": 3.0},
+                {"n": "pressure", "v": 1013.0, "t": -5}
+            ]"#,
+            Some(now),
+        )
+        .unwrap();
+        assert!(records[0].is_fresh(now));
+        assert!(!records[1].is_fresh(now));
+        assert!(records[2].is_fresh(now));
+
+        assert_eq!(records[0].time_until_stale(now), Some(Duration::seconds(5)));
+        assert_eq!(
+            records[1].time_until_stale(now),
+            Some(Duration::seconds(-2))
+        );
+        assert_eq!(records[2].time_until_stale(now), None);
+
+        assert_eq!(
+            filter_fresh_records(&records, now),
+            vec![&records[0], &records[2]]
+        );
+        assert_eq!(filter_stale_records(&records, now), vec![&records[1]]);
+    }
+
+    #[test]
+    fn test_boundary_and_large_update_time() {
+        let now = Utc::now();
+        let records = parse_json(
+            r#"[{"n": "a", "v": 1, "t": -10, "ut": 10},{"n": "b", "v": 1, "t": -10, "ut": 1e300}]"#,
+            Some(now),
+        )
+        .unwrap();
+        assert!(records[0].is_fresh(now));
+        assert!(!records[0].is_fresh(now + Duration::nanoseconds(1)));
+        assert!(records[1].is_fresh(now + Duration::days(36500)));
+    }
+
+    #[test]
+    fn test_update_duration() {
+        let mut records = parse_json(
+            r#"[{"n": "temperature", "v": 21.5, "t": 1320067464}]"#,
+            None,
+        )
+        .unwrap();
+        assert_eq!(records[0].update_duration(), None);
+        assert_eq!(records[0].next_expected_update(), None);
+
+        records[0].set_update_duration(Duration::seconds(30));
+        assert_eq!(records[0].update_time, Some(30.0));
+        assert_eq!(records[0].update_duration(), Some(Duration::seconds(30)));
+        assert_eq!(
+            records[0].next_expected_update(),
+            Some(records[0].time + Duration::seconds(30))
+        );
+
+        records[0].set_update_duration(Duration::milliseconds(500));
+        assert!((records[0].update_time.unwrap() - 0.5).abs() < 1e-12);
+        assert_eq!(
+            records[0].update_duration(),
+            Some(Duration::milliseconds(500))
+        );
+    }
+
+    #[test]
+    fn test_group_by_update_time() {
+        let records = parse_json(
+            r#"[
+                {"n": "a", "v": 1, "t": 1320067464, "ut": 10.0},
+                {"n": "b", "v": 1, "t": 1320067464, "ut": 9.6},
+                {"n": "c", "v": 1, "t": 1320067464, "ut": 60},
+                {"n": "d", "v": 1, "t": 1320067464}
+            ]"#,
+            None,
+        )
+        .unwrap();
+        let groups = group_by_update_time(&records);
+        assert_eq!(groups.len(), 3);
+        assert_eq!(groups[&Some(10)], vec![&records[0], &records[1]]);
+        assert_eq!(groups[&Some(60)], vec![&records[2]]);
+        assert_eq!(groups[&None], vec![&records[3]]);
+    }
+
+    #[test]
+    fn test_sensors_missing_updates() {
+        let now = Utc::now();
+        let records = parse_json(
+            r#"[
+                {"n": "temperature", "v": 21.5, "t": -15, "ut": 10.0},
+                {"n": "humidity", "v": 40.0, "t": -5, "ut": 10.0},
+                {"n": "pressure", "v": 1013.0, "t": -30, "ut": 10.0},
+                {"n": "pressure", "v": 1012.0, "t": -2, "ut": 10.0},
+                {"n": "label", "vs": "Machine Room", "t": -3600}
+            ]"#,
+            Some(now),
+        )
+        .unwrap();
+        assert_eq!(
+            sensors_missing_updates(&records, now),
+            vec!["temperature".to_string()]
+        );
+        assert!(sensors_missing_updates(&records[1..], now).is_empty());
+    }
+}
@@ -0,0 +1,255 @@
+//! # SenML over gRPC
+//!
+//! Protocol Buffers encoding of SenML Packs and a bidirectional streaming
+//! gRPC service, for pipelines with a high throughput of sensor data.
+//!
+//! The service is defined in `proto/senml_service.proto`. The Rust code is
+//! generated at build time with `tonic-build`, which requires `protoc`.
+//!
+//! Extra fields are not part of the Protocol Buffers schema and are dropped.
+//!
+//! This module is available with the `grpc` feature.
+
+use chrono::{DateTime, Utc};
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::transport::Channel;
+use tonic::{Request, Response, Status, Streaming};
+
+use crate::time::datetime_to_timestamp;
+use crate::{
+    resolve_records, Base64Variant, SenMLRecord, SenMLResolvedRecord, SenMLTime, SenMLValueField,
+    SinditSenMLError,
+};
+
+/// Types generated from `proto/senml_service.proto`.
+pub mod proto {
+    tonic::include_proto!("senml");
+}
+
+use proto::sen_ml_service_client::SenMlServiceClient;
+use proto::sen_ml_service_server::{SenMlService, SenMlServiceServer};
+
+/// Parse a Protocol Buffers SenML Pack and return SenMLResolvedRecords.
+///
+/// # Arguments
+/// * `pack` - The Protocol Buffers SenML Pack.
+/// * `now` - The current time. Defaults to current UTC time.
+pub fn parse_protobuf(
+    pack: &proto::SenMlPack,
+    now: Option<DateTime<Utc>>,
+) -> Result<Vec<SenMLResolvedRecord>, SinditSenMLError> {
+    let records: Vec<SenMLRecord> = pack
+        .records
+        .iter()
+        .map(|record| SenMLRecord {
+            base_name: record.bn.clone(),
+            base_time: record.bt,
+            base_unit: record.bu.clone(),
+            base_value: record.bv,
+            base_sum: record.bs,
+            base_version: record.bver,
+            name: record.n.clone(),
+            unit: record.u.clone(),
+            value: record.v,
+            string_value: record.vs.clone(),
+            bool_value: record.vb,
+            // The resolution expects base64 data, as in the JSON representation.
+            data_value: record
+                .vd
+                .as_ref()
+                .map(|data| Base64Variant::UrlSafeNoPad.encode(data)),
+            sum: record.s,
+            time: record.t.map(SenMLTime::Seconds),
+            update_time: record.ut,
+            extra_fields: None,
+        })
+        .collect();
+
+    resolve_records(&records, now.unwrap_or(Utc::now()))
+}
+
+/// Convert SenMLResolvedRecords to a Protocol Buffers SenML Pack.
+///
+/// The records are not compacted using base fields.
+pub fn to_protobuf(records: &[SenMLResolvedRecord]) -> proto::SenMlPack {
+    proto::SenMlPack {
+        records: records
+            .iter()
+            .map(|record| {
+                let (timestamp, precise_timestamp) = datetime_to_timestamp(&record.time);
+                let mut proto_record = proto::SenMlRecord {
+                    n: Some(record.name.clone()),
+                    u: record.unit.clone(),
+                    s: record.sum,
+                    t: Some(precise_timestamp.unwrap_or(timestamp as f64)),
+                    ut: record.update_time,
+                    bver: record.base_version,
+                    ..Default::default()
+                };
+                match record.value {
+                    Some(SenMLValueField::FloatingPoint(value)) => proto_record.v = Some(value),
+                    Some(SenMLValueField::StringValue(ref value)) => {
+                        proto_record.vs = Some(value.clone())
+                    }
+                    Some(SenMLValueField::BooleanValue(value)) => proto_record.vb = Some(value),
+                    Some(SenMLValueField::DataValue(ref value)) => {
+                        proto_record.vd = Some(value.clone())
+                    }
+                    None => {}
+                }
+                proto_record
+            })
+            .collect(),
+    }
+}
+
+/// gRPC service receiving streams of SenML Packs.
+///
+/// Each received pack is resolved and forwarded to a channel,
+/// then acknowledged with the number of records it contained.
+pub struct SenMLServiceImpl {
+    sender: mpsc::Sender<Vec<SenMLResolvedRecord>>,
+}
+
+impl SenMLServiceImpl {
+    /// Create a new service forwarding the resolved records to `sender`.
+    pub fn new(sender: mpsc::Sender<Vec<SenMLResolvedRecord>>) -> Self {
+        SenMLServiceImpl { sender }
+    }
+
+    /// Wrap the service in a server that can be added to a
+    /// `tonic::transport::Server`.
+    pub fn into_server(self) -> SenMlServiceServer<Self> {
+        SenMlServiceServer::new(self)
+    }
+}
+
+#[tonic::async_trait]
+impl SenMlService for SenMLServiceImpl {
+    type StreamRecordsStream = ReceiverStream<Result<proto::SenMlAck, Status>>;
+
+    async fn stream_records(
+        &self,
+        request: Request<Streaming<proto::SenMlPack>>,
+    ) -> Result<Response<Self::StreamRecordsStream>, Status> {
+        let mut packs = request.into_inner();
+        let sender = self.sender.clone();
+        let (ack_sender, ack_receiver) = mpsc::channel(16);
+
+        tokio::spawn(async move {
+            loop {
+                let ack = match packs.message().await {
+                    Ok(Some(pack)) => match parse_protobuf(&pack, None) {
+                        Ok(records) => {
+                            let received = records.len() as u64;
+                            match sender.send(records).await {
+                                Ok(()) => Ok(proto::SenMlAck { received }),
+                                Err(_) => Err(Status::unavailable("Records receiver closed")),
+                            }
+                        }
+                        Err(error) => Err(Status::invalid_argument(error.to_string())),
+                    },
+                    Ok(None) => break,
+                    Err(status) => Err(status),
+                };
+                let failed = ack.is_err();
+                if ack_sender.send(ack).await.is_err() || failed {
+                    break;
+                }
+            }
+        });
+
+        Ok(Response::new(ReceiverStream::new(ack_receiver)))
+    }
+}
+
+/// Client of the SenML gRPC service.
+pub struct SenMLServiceClient {
+    client: SenMlServiceClient<Channel>,
+}
+
+impl SenMLServiceClient {
+    /// Connect to a SenML gRPC service.
+    ///
+    /// # Arguments
+    /// * `endpoint` - The URI of the service, such as `http://[::1]:50051`.
+    pub async fn connect(endpoint: String) -> Result<Self, SinditSenMLError> {
+        Ok(SenMLServiceClient {
+            client: SenMlServiceClient::connect(endpoint).await?,
+        })
+    }
+
+    /// Send records to the service and wait for their acknowledgement.
+    pub async fn send_records(
+        &mut self,
+        records: &[SenMLResolvedRecord],
+    ) -> Result<(), SinditSenMLError> {
+        let response = self
+            .client
+            .stream_records(tokio_stream::once(to_protobuf(records)))
+            .await?;
+        let mut acks = response.into_inner();
+        let mut received = 0;
+        while let Some(ack) = acks.message().await? {
+            received += ack.received;
+        }
+        if received != records.len() as u64 {
+            return Err(Status::data_loss(format!(
+                "Sent {} records but {} were acknowledged",
+                records.len(),
+                received
+            ))
+            .into());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_json;
+
+    #[test]
+    fn test_protobuf_roundtrip() {
+        let records = parse_json(
+            r#"[
+                {"bn": "urn:dev:ow:10e2073a01080063:", "bt": 1320067464, "n": "temp", "u": "Cel", "v": 23.1},
+                {"n": "label", "vs": "Machine Room"},
+                {"n": "open", "vb": false},
+                {"n": "nfc-reader", "vd": "aGkgCg", "ut": 60}
+            ]"#,
+            None,
+        )
+        .unwrap();
+        let pack = to_protobuf(&records);
+        assert_eq!(pack.records.len(), 4);
+        assert_eq!(pack.records[3].vd, Some(vec![0x68, 0x69, 0x20, 0x0a]));
+        assert_eq!(parse_protobuf(&pack, None).unwrap(), records);
+    }
+
+    #[test]
+    fn test_parse_protobuf_base_fields() {
+        let pack = proto::SenMlPack {
+            records: vec![
+                proto::SenMlRecord {
+                    bn: Some("sensor-".to_string()),
+                    bt: Some(1320067464.0),
+                    n: Some("temp".to_string()),
+                    v: Some(20.0),
+                    ..Default::default()
+                },
+                proto::SenMlRecord {
+                    n: Some("temp".to_string()),
+                    t: Some(60.0),
+                    v: Some(21.0),
+                    ..Default::default()
+                },
+            ],
+        };
+        let records = parse_protobuf(&pack, None).unwrap();
+        assert_eq!(records[1].name, "sensor-temp");
+        assert_eq!(records[1].time.timestamp(), 1320067524);
+    }
+}
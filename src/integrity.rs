@@ -0,0 +1,127 @@
+//! # Integrity of SenML Packs
+//!
+//! Detect replayed packs using an issue time stamped in the pack.
+//!
+//! The issue time is stored as the `_issued_at` extra field of the first
+//! record, in seconds since the Unix epoch.
+//! Please note that the stamp itself is not protected against tampering,
+//! unless the pack is signed with the `signing` feature.
+
+use chrono::{DateTime, Duration, Utc};
+
+use crate::{SenMLResolvedRecord, SinditSenMLError};
+
+/// Name of the extra field holding the issue time.
+pub const ISSUED_AT_FIELD: &str = "_issued_at";
+
+/// Stamp the pack with its issue time.
+///
+/// The issue time is truncated to the second.
+/// An empty pack is left unchanged.
+///
+/// # Arguments
+/// * `records` - The records of the pack.
+/// * `issue_time` - The time the pack is issued.
+#[allow(clippy::ptr_arg)]
+pub fn stamp_pack(records: &mut Vec<SenMLResolvedRecord>, issue_time: DateTime<Utc>) {
+    if let Some(record) = records.first_mut() {
+        record
+            .extra_fields
+            .get_or_insert_with(Default::default)
+            .insert(
+                ISSUED_AT_FIELD.to_string(),
+                serde_json::Value::from(issue_time.timestamp()),
+            );
+    }
+}
+
+/// Check that the pack was issued recently.
+///
+/// # Arguments
+/// * `records` - The records of the pack.
+/// * `now` - The current time.
+/// * `max_age` - The maximum time since the pack was issued.
+/// # Returns
+/// * `Result<(), SinditSenMLError>` - `PackExpired` if the pack is too old,
+///   `InvalidIssueTime` if the pack is not stamped.
+/// # Examples
+/// ```
+/// use chrono::{Duration, Utc};
+/// use sindit_senml::{parse_json, integrity::{stamp_pack, verify_pack_freshness}};
+///
+/// let mut records = parse_json(r#"[{"n": "temperature", "v": 42.0}]"#, None).unwrap();
+/// stamp_pack(&mut records, Utc::now());
+/// assert!(verify_pack_freshness(&records, Utc::now(), Duration::minutes(5)).is_ok());
+/// ```
+pub fn verify_pack_freshness(
+    records: &[SenMLResolvedRecord],
+    now: DateTime<Utc>,
+    max_age: Duration,
+) -> Result<(), SinditSenMLError> {
+    let issued_at = records
+        .first()
+        .and_then(|record| record.extra_fields.as_ref())
+        .and_then(|extra_fields| extra_fields.get(ISSUED_AT_FIELD))
+        .and_then(|issued_at| issued_at.as_i64())
+        .and_then(|issued_at| DateTime::<Utc>::from_timestamp(issued_at, 0))
+        .ok_or(SinditSenMLError::InvalidIssueTime)?;
+
+    if now - issued_at > max_age {
+        return Err(SinditSenMLError::PackExpired { issued_at, now });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_json;
+
+    const PACK: &str = r#"[
+        {"bn": "urn:dev:ow:10e2073a01080063:", "n": "temp", "u": "Cel", "v": 23.1},
+        {"n": "humidity", "u": "%RH", "v": 40.0}
+    ]"#;
+
+    #[test]
+    fn test_stamp_and_verify() {
+        let mut records = parse_json(PACK, None).unwrap();
+        let issue_time = Utc::now();
+        stamp_pack(&mut records, issue_time);
+        assert_eq!(
+            records[0].extra_fields.as_ref().unwrap()[ISSUED_AT_FIELD],
+            serde_json::json!(issue_time.timestamp())
+        );
+        assert_eq!(records[1].extra_fields, None);
+
+        assert!(verify_pack_freshness(&records, issue_time, Duration::seconds(1)).is_ok());
+
+        // Replayed later
+        let now = issue_time + Duration::seconds(2);
+        match verify_pack_freshness(&records, now, Duration::seconds(1)).unwrap_err() {
+            SinditSenMLError::PackExpired {
+                issued_at,
+                now: error_now,
+            } => {
+                assert_eq!(issued_at.timestamp(), issue_time.timestamp());
+                assert_eq!(error_now, now);
+            }
+            error => panic!("Unexpected error: {:?}", error),
+        }
+    }
+
+    #[test]
+    fn test_unstamped_pack() {
+        let records = parse_json(PACK, None).unwrap();
+        assert!(matches!(
+            verify_pack_freshness(&records, Utc::now(), Duration::seconds(1)).unwrap_err(),
+            SinditSenMLError::InvalidIssueTime
+        ));
+
+        let mut records: Vec<SenMLResolvedRecord> = Vec::new();
+        stamp_pack(&mut records, Utc::now());
+        assert!(matches!(
+            verify_pack_freshness(&records, Utc::now(), Duration::seconds(1)).unwrap_err(),
+            SinditSenMLError::InvalidIssueTime
+        ));
+    }
+}
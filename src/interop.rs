@@ -0,0 +1,802 @@
+//! # Interoperability
+//!
+//! Map SenML records to the data models of other systems, such as OPC-UA
+//! servers, numerical processing tools, Grafana, or RDF stores.
+
+/// Time-aligned matrix of the floating point values.
+pub mod matrix {
+    use std::collections::{BTreeMap, BTreeSet};
+
+    use chrono::{DateTime, Utc};
+
+    use crate::time::datetime_to_timestamp;
+    use crate::SenMLResolvedRecord;
+
+    /// Floating point values indexed by sensor and time.
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct PackMatrix {
+        /// Names of the sensors, sorted.
+        pub sensor_names: Vec<String>,
+        /// Times of all the records, sorted and deduplicated.
+        pub timestamps: Vec<DateTime<Utc>>,
+        /// `values[i][j]` is the value of `sensor_names[i]` at `timestamps[j]`,
+        /// `None` if there is no record or the record has no floating point value.
+        pub values: Vec<Vec<Option<f64>>>,
+    }
+
+    /// Convert the records to a time-aligned matrix.
+    ///
+    /// The last record wins when a sensor has multiple records at the same time.
+    ///
+    /// # Examples
+    /// ```
+    /// use sindit_senml::{parse_json, interop::matrix::pack_to_matrix};
+    ///
+    /// let records = parse_json(
+    ///     r#"[{"bt": 1320067464, "n": "a", "v": 1},{"n": "b", "v": 2},{"n": "a", "t": 60, "v": 3}]"#,
+    ///     None,
+    /// ).unwrap();
+    /// let matrix = pack_to_matrix(&records);
+    /// assert_eq!(matrix.values, vec![vec![Some(1.0), Some(3.0)], vec![Some(2.0), None]]);
+    /// assert_eq!(matrix.to_csv(), "time,a,b\n1320067464,1,2\n1320067524,3,\n");
+    /// ```
+    pub fn pack_to_matrix(records: &[SenMLResolvedRecord]) -> PackMatrix {
+        let timestamps: Vec<DateTime<Utc>> = records
+            .iter()
+            .map(|record| record.time)
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect();
+        let mut rows: BTreeMap<&str, Vec<Option<f64>>> = BTreeMap::new();
+        for record in records.iter() {
+            // The timestamps contain the time of every record.
+            let column = timestamps.binary_search(&record.time).unwrap_or_default();
+            rows.entry(record.name.as_str())
+                .or_insert_with(|| vec![None; timestamps.len()])[column] = record.get_float_value();
+        }
+
+        let (sensor_names, values) = rows
+            .into_iter()
+            .map(|(name, row)| (name.to_string(), row))
+            .unzip();
+        PackMatrix {
+            sensor_names,
+            timestamps,
+            values,
+        }
+    }
+
+    impl PackMatrix {
+        /// CSV with a row per timestamp and a column per sensor.
+        ///
+        /// The header row is `time,<sensor1>,<sensor2>,...`. The times are
+        /// SenML times, in seconds since the Unix epoch. Missing values are
+        /// empty cells.
+        pub fn to_csv(&self) -> String {
+            let mut csv = String::from("time");
+            for name in self.sensor_names.iter() {
+                // The SenML names do not contain commas nor quotes.
+                csv.push(',');
+                csv.push_str(name);
+            }
+            csv.push('\n');
+            for (column, time) in self.timestamps.iter().enumerate() {
+                let (timestamp, precise_timestamp) = datetime_to_timestamp(time);
+                match precise_timestamp {
+                    Some(precise_timestamp) => csv.push_str(&precise_timestamp.to_string()),
+                    None => csv.push_str(&timestamp.to_string()),
+                }
+                for row in self.values.iter() {
+                    csv.push(',');
+                    if let Some(value) = row[column] {
+                        csv.push_str(&value.to_string());
+                    }
+                }
+                csv.push('\n');
+            }
+            csv
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::fixtures::MULTIPLE_DATAPOINT_AND_TIME;
+        use crate::parse_json;
+
+        #[test]
+        fn test_multiple_datapoints_and_time() {
+            let records = parse_json(MULTIPLE_DATAPOINT_AND_TIME, None).unwrap();
+            let matrix = pack_to_matrix(&records);
+            assert_eq!(
+                matrix.sensor_names,
+                vec![
+                    "urn:dev:ow:10e2073a0108006:current",
+                    "urn:dev:ow:10e2073a0108006:voltage"
+                ]
+            );
+            // The voltage is measured at the same time as the last current.
+            assert_eq!(matrix.timestamps.len(), 6);
+            assert_eq!(matrix.timestamps[5], records[0].time);
+            assert_eq!(matrix.values.len(), 2);
+            assert!(matrix.values.iter().all(|row| row.len() == 6));
+            assert_eq!(
+                matrix.values[0],
+                vec![
+                    Some(1.2),
+                    Some(1.3),
+                    Some(1.4),
+                    Some(1.5),
+                    Some(1.6),
+                    Some(1.7)
+                ]
+            );
+            assert_eq!(
+                matrix.values[1],
+                vec![None, None, None, None, None, Some(120.1)]
+            );
+
+            let csv = matrix.to_csv();
+            let lines: Vec<&str> = csv.lines().collect();
+            assert_eq!(lines.len(), 7);
+            assert_eq!(
+                lines[0],
+                "time,urn:dev:ow:10e2073a0108006:current,urn:dev:ow:10e2073a0108006:voltage"
+            );
+            assert!(lines[1].ends_with(",1.2,"));
+            assert!(lines[6].ends_with(",1.7,120.1"));
+        }
+
+        #[test]
+        fn test_non_float_values() {
+            let records =
+                parse_json(r#"[{"n": "open", "vb": true, "t": 1320067464}]"#, None).unwrap();
+            let matrix = pack_to_matrix(&records);
+            assert_eq!(matrix.values, vec![vec![None]]);
+            assert_eq!(pack_to_matrix(&[]).to_csv(), "time\n");
+        }
+    }
+}
+
+/// OPC-UA data changes.
+///
+/// The SenML names are used as string identifiers of the OPC-UA nodes,
+/// for example `ns=2;s=urn:dev:ow:10e2073a01080063:temp`.
+pub mod opcua {
+    use std::fmt;
+
+    use chrono::{DateTime, Utc};
+
+    use crate::{SenMLResolvedRecord, SenMLValueField};
+
+    /// Namespace of the nodes created by [`records_to_opcua_data_changes`].
+    ///
+    /// The namespace 0 is reserved for the OPC-UA standard nodes,
+    /// the namespace 1 is the local namespace of the server.
+    pub const DEFAULT_NAMESPACE_INDEX: u16 = 1;
+
+    /// OPC-UA node identifier with a string identifier.
+    #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+    pub struct OpcUaNodeId {
+        pub namespace_index: u16,
+        pub identifier: String,
+    }
+
+    impl fmt::Display for OpcUaNodeId {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "ns={};s={}", self.namespace_index, self.identifier)
+        }
+    }
+
+    /// OPC-UA variant of a SenML value.
+    #[derive(Debug, Clone, PartialEq)]
+    pub enum OpcUaVariant {
+        Double(f64),
+        Boolean(bool),
+        String(String),
+        ByteString(Vec<u8>),
+    }
+
+    impl From<&SenMLValueField> for OpcUaVariant {
+        fn from(value: &SenMLValueField) -> Self {
+            match value {
+                SenMLValueField::FloatingPoint(value) => OpcUaVariant::Double(*value),
+                SenMLValueField::BooleanValue(value) => OpcUaVariant::Boolean(*value),
+                SenMLValueField::StringValue(value) => OpcUaVariant::String(value.clone()),
+                SenMLValueField::DataValue(value) => OpcUaVariant::ByteString(value.clone()),
+            }
+        }
+    }
+
+    /// Change of the value of an OPC-UA node.
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct OpcUaDataChange {
+        pub node_id: OpcUaNodeId,
+        pub value: OpcUaVariant,
+        pub source_timestamp: DateTime<Utc>,
+    }
+
+    /// OPC-UA node identifier using the SenML name as string identifier.
+    ///
+    /// # Examples
+    /// ```
+    /// use sindit_senml::interop::opcua::name_to_opcua_node_id;
+    ///
+    /// let node_id = name_to_opcua_node_id("Sensor.Temperature", 2);
+    /// assert_eq!(node_id.to_string(), "ns=2;s=Sensor.Temperature");
+    /// ```
+    pub fn name_to_opcua_node_id(name: &str, namespace_index: u16) -> OpcUaNodeId {
+        OpcUaNodeId {
+            namespace_index,
+            identifier: name.to_string(),
+        }
+    }
+
+    /// Convert the records to OPC-UA data changes.
+    ///
+    /// The nodes are in the [`DEFAULT_NAMESPACE_INDEX`] namespace.
+    /// Records without a value, such as the records with only a sum,
+    /// are skipped.
+    pub fn records_to_opcua_data_changes(records: &[SenMLResolvedRecord]) -> Vec<OpcUaDataChange> {
+        records
+            .iter()
+            .filter_map(|record| {
+                record.value.as_ref().map(|value| OpcUaDataChange {
+                    node_id: name_to_opcua_node_id(&record.name, DEFAULT_NAMESPACE_INDEX),
+                    value: value.into(),
+                    source_timestamp: record.time,
+                })
+            })
+            .collect()
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::fixtures::MULTIPLE_DATATYPES;
+        use crate::parse_json;
+
+        #[test]
+        fn test_multiple_datatypes() {
+            let records = parse_json(MULTIPLE_DATATYPES, None).unwrap();
+            let changes = records_to_opcua_data_changes(&records);
+            assert_eq!(changes.len(), 4);
+            assert_eq!(changes[0].value, OpcUaVariant::Double(23.1));
+            assert_eq!(
+                changes[1].value,
+                OpcUaVariant::String("Machine Room".to_string())
+            );
+            assert_eq!(changes[2].value, OpcUaVariant::Boolean(false));
+            assert_eq!(
+                changes[3].value,
+                OpcUaVariant::ByteString(b"hi \n".to_vec())
+            );
+            assert_eq!(
+                changes[0].node_id.to_string(),
+                "ns=1;s=urn:dev:ow:10e2073a01080063:temp"
+            );
+            assert_eq!(changes[0].source_timestamp, records[0].time);
+        }
+
+        #[test]
+        fn test_sum_only_record_skipped() {
+            let records = parse_json(r#"[{"n": "energy", "s": 42.0}]"#, None).unwrap();
+            assert!(records_to_opcua_data_changes(&records).is_empty());
+        }
+    }
+}
+
+/// Grafana JSON data source responses.
+///
+/// The times are in milliseconds since the Unix epoch, rounded to the
+/// nearest millisecond.
+pub mod grafana {
+    use chrono::{DateTime, Utc};
+    use serde_json::json;
+
+    use crate::{Base64Variant, SenMLResolvedRecord, SenMLValueField, SinditSenMLError};
+
+    fn timestamp_millis(time: &DateTime<Utc>) -> i64 {
+        let millis = time.timestamp_millis();
+        if time.timestamp_subsec_nanos() % 1_000_000 >= 500_000 {
+            millis + 1
+        } else {
+            millis
+        }
+    }
+
+    fn finite(value: f64, index: usize) -> Result<f64, SinditSenMLError> {
+        match value.is_finite() {
+            true => Ok(value),
+            false => Err(SinditSenMLError::NonFiniteValue(index)),
+        }
+    }
+
+    /// Time series response, one target per sensor name.
+    ///
+    /// The targets are in the order of the first record of each sensor.
+    /// Records without a floating point value are skipped.
+    ///
+    /// Returns `NonFiniteValue` if a value is not finite, as JSON does not
+    /// support it.
+    ///
+    /// # Examples
+    /// ```
+    /// use sindit_senml::{parse_json, interop::grafana::serialize_grafana_timeseries};
+    ///
+    /// let records = parse_json(r#"[{"n": "temperature", "v": 21.5, "t": 1320067464}]"#, None).unwrap();
+    /// assert_eq!(
+    ///     serialize_grafana_timeseries(&records).unwrap().to_string(),
+    ///     r#"[{"datapoints":[[21.5,1320067464000]],"target":"temperature"}]"#
+    /// );
+    /// ```
+    pub fn serialize_grafana_timeseries(
+        records: &[SenMLResolvedRecord],
+    ) -> Result<serde_json::Value, SinditSenMLError> {
+        let mut targets: Vec<(&str, Vec<serde_json::Value>)> = Vec::new();
+        for (index, record) in records.iter().enumerate() {
+            let value = match record.get_float_value() {
+                Some(value) => finite(value, index)?,
+                None => continue,
+            };
+            let datapoint = json!([value, timestamp_millis(&record.time)]);
+            match targets
+                .iter_mut()
+                .find(|(name, _)| *name == record.name.as_str())
+            {
+                Some((_, datapoints)) => datapoints.push(datapoint),
+                None => targets.push((record.name.as_str(), vec![datapoint])),
+            }
+        }
+
+        Ok(serde_json::Value::Array(
+            targets
+                .into_iter()
+                .map(|(name, datapoints)| json!({"target": name, "datapoints": datapoints}))
+                .collect(),
+        ))
+    }
+
+    /// Table response, one row per record.
+    ///
+    /// The columns are the time, the name, the value, the unit, and the sum.
+    /// The values are numbers, booleans, strings, or base64 strings for the
+    /// data values.
+    ///
+    /// Returns `NonFiniteValue` if a value or a sum is not finite.
+    pub fn serialize_grafana_table(
+        records: &[SenMLResolvedRecord],
+    ) -> Result<serde_json::Value, SinditSenMLError> {
+        let rows = records
+            .iter()
+            .enumerate()
+            .map(|(index, record)| {
+                let value = match record.value {
+                    Some(SenMLValueField::FloatingPoint(value)) => json!(finite(value, index)?),
+                    Some(SenMLValueField::BooleanValue(value)) => json!(value),
+                    Some(SenMLValueField::StringValue(ref value)) => json!(value),
+                    Some(SenMLValueField::DataValue(ref value)) => {
+                        json!(Base64Variant::UrlSafeNoPad.encode(value))
+                    }
+                    None => serde_json::Value::Null,
+                };
+                let sum = record.sum.map(|sum| finite(sum, index)).transpose()?;
+                Ok(json!([
+                    timestamp_millis(&record.time),
+                    record.name,
+                    value,
+                    record.unit,
+                    sum
+                ]))
+            })
+            .collect::<Result<Vec<_>, SinditSenMLError>>()?;
+
+        Ok(json!({
+            "columns": [
+                {"text": "Time", "type": "time"},
+                {"text": "Name", "type": "string"},
+                {"text": "Value"},
+                {"text": "Unit", "type": "string"},
+                {"text": "Sum", "type": "number"}
+            ],
+            "rows": rows,
+            "type": "table"
+        }))
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::fixtures::MULTIPLE_DATAPOINT_AND_TIME;
+        use crate::parse_json;
+
+        #[test]
+        fn test_timeseries() {
+            let records = parse_json(MULTIPLE_DATAPOINT_AND_TIME, None).unwrap();
+            let timeseries = serialize_grafana_timeseries(&records).unwrap();
+            let targets = timeseries.as_array().unwrap();
+            assert_eq!(targets.len(), 2);
+            assert_eq!(targets[0]["target"], "urn:dev:ow:10e2073a0108006:voltage");
+            assert_eq!(targets[0]["datapoints"], json!([[120.1, 1276020076001i64]]));
+            assert_eq!(targets[1]["target"], "urn:dev:ow:10e2073a0108006:current");
+            assert_eq!(
+                targets[1]["datapoints"],
+                json!([
+                    [1.2, 1276020071001i64],
+                    [1.3, 1276020072001i64],
+                    [1.4, 1276020073001i64],
+                    [1.5, 1276020074001i64],
+                    [1.6, 1276020075001i64],
+                    [1.7, 1276020076001i64]
+                ])
+            );
+        }
+
+        #[test]
+        fn test_timeseries_skips_non_float_records() {
+            let records = parse_json(
+                r#"[{"n": "open", "vb": true},{"n": "label", "vs": "Machine Room"}]"#,
+                None,
+            )
+            .unwrap();
+            assert_eq!(serialize_grafana_timeseries(&records).unwrap(), json!([]));
+        }
+
+        #[test]
+        fn test_table() {
+            let records = parse_json(
+                r#"[
+                    {"bt": 1320067464, "n": "temperature", "u": "Cel", "v": 21.5},
+                    {"n": "open", "vb": true, "t": 0.5},
+                    {"n": "nfc-reader", "vd": "aGkgCg"},
+                    {"n": "energy", "u": "kWh", "s": 42.0}
+                ]"#,
+                None,
+            )
+            .unwrap();
+            let table = serialize_grafana_table(&records).unwrap();
+            assert_eq!(table["type"], "table");
+            assert_eq!(table["columns"].as_array().unwrap().len(), 5);
+            assert_eq!(
+                table["rows"],
+                json!([
+                    [1320067464000i64, "temperature", 21.5, "Cel", null],
+                    [1320067464500i64, "open", true, null, null],
+                    [1320067464000i64, "nfc-reader", "aGkgCg", null, null],
+                    [1320067464000i64, "energy", null, "kWh", 42.0]
+                ])
+            );
+        }
+    }
+}
+
+/// RDF Turtle serialisation.
+///
+/// Each record is a `sosa:Observation` blank node. The predicates use the
+/// SenML namespace registered by the RFC, `urn:ietf:params:xml:ns:senml`.
+#[cfg(feature = "rdf")]
+pub mod rdf {
+    use chrono::SecondsFormat;
+
+    use crate::{Base64Variant, SenMLResolvedRecord, SenMLValueField, SinditSenMLError};
+
+    const PREFIXES: &str = "@prefix senml: <urn:ietf:params:xml:ns:senml#> .
+@prefix sosa: <https://www.w3.org/ns/sosa/> .
+@prefix xsd: <http://www.w3.org/2001/XMLSchema#> .
+@prefix qudt: <http://qudt.org/schema/qudt/> .
+@prefix unit: <http://qudt.org/vocab/unit/> .
+";
+
+    /// QUDT unit of the SenML units having one.
+    fn qudt_unit(unit: &str) -> Option<&'static str> {
+        Some(match unit {
+            "m" => "M",
+            "kg" => "KiloGM",
+            "g" => "GM",
+            "s" => "SEC",
+            "A" => "A",
+            "K" => "K",
+            "Cel" => "DEG_C",
+            "Hz" => "HZ",
+            "N" => "N",
+            "Pa" => "PA",
+            "J" => "J",
+            "W" => "W",
+            "V" => "V",
+            "Ohm" => "OHM",
+            "lx" => "LUX",
+            "m/s" => "M-PER-SEC",
+            "m/s2" => "M-PER-SEC2",
+            "kWh" => "KiloW-HR",
+            "%" => "PERCENT",
+            "%RH" => "PERCENT_RH",
+            "dB" => "DeciB",
+            _ => return None,
+        })
+    }
+
+    fn escape_string(value: &str) -> String {
+        let mut escaped = String::with_capacity(value.len() + 2);
+        escaped.push('"');
+        for character in value.chars() {
+            match character {
+                '"' => escaped.push_str("\\\""),
+                '\\' => escaped.push_str("\\\\"),
+                '\n' => escaped.push_str("\\n"),
+                '\r' => escaped.push_str("\\r"),
+                '\t' => escaped.push_str("\\t"),
+                _ => escaped.push(character),
+            }
+        }
+        escaped.push('"');
+        escaped
+    }
+
+    fn double_literal(value: f64) -> String {
+        let lexical = if value.is_nan() {
+            "NaN".to_string()
+        } else if value.is_infinite() {
+            (if value > 0.0 { "INF" } else { "-INF" }).to_string()
+        } else {
+            value.to_string()
+        };
+        format!("\"{}\"^^xsd:double", lexical)
+    }
+
+    /// Whether the name is an absolute IRI, starting with a scheme.
+    fn is_absolute(name: &str) -> bool {
+        match name.find(':') {
+            Some(index) => {
+                let scheme = &name[..index];
+                scheme.starts_with(|character: char| character.is_ascii_alphabetic())
+                    && scheme.chars().all(|character| {
+                        character.is_ascii_alphanumeric() || matches!(character, '+' | '-' | '.')
+                    })
+            }
+            None => false,
+        }
+    }
+
+    /// Whether the IRI is absolute and can be written between angle
+    /// brackets, without the characters excluded by the Turtle grammar.
+    /// <https://www.w3.org/TR/turtle/#grammar-production-IRIREF>
+    fn is_valid_iri(iri: &str) -> bool {
+        is_absolute(iri)
+            && !iri.chars().any(|character| {
+                character <= ' '
+                    || matches!(
+                        character,
+                        '<' | '>' | '"' | '{' | '}' | '|' | '^' | '`' | '\\'
+                    )
+            })
+    }
+
+    /// Serialise the records to an RDF Turtle document.
+    ///
+    /// The sensor of each observation is the IRI of the record name,
+    /// prefixed by `base_uri` if the name is not an absolute IRI.
+    /// The unit is the SenML unit in `senml:unit`, and the QUDT unit in
+    /// `qudt:hasUnit` when there is one.
+    ///
+    /// # Arguments
+    /// * `records` - The records to serialise.
+    /// * `base_uri` - An absolute IRI, prefixing the relative names.
+    /// # Returns
+    /// * `Result<String, SinditSenMLError>` - The Turtle document,
+    ///   `InvalidIri` if `base_uri` or the IRI of a sensor is not a valid
+    ///   absolute IRI.
+    /// # Examples
+    /// ```
+    /// use sindit_senml::{parse_json, interop::rdf::serialize_turtle};
+    ///
+    /// let records = parse_json(r#"[{"n": "temperature", "u": "Cel", "v": 23.1, "t": 1320067464}]"#, None).unwrap();
+    /// let turtle = serialize_turtle(&records, "http://example.com/sensors/").unwrap();
+    /// assert!(turtle.contains("sosa:madeBySensor <http://example.com/sensors/temperature>"));
+    /// assert!(turtle.contains("qudt:hasUnit unit:DEG_C"));
+    /// assert!(serialize_turtle(&records, "sensors/").is_err());
+    /// ```
+    pub fn serialize_turtle(
+        records: &[SenMLResolvedRecord],
+        base_uri: &str,
+    ) -> Result<String, SinditSenMLError> {
+        if !is_valid_iri(base_uri) {
+            return Err(SinditSenMLError::InvalidIri(base_uri.to_string()));
+        }
+
+        let mut turtle = String::from(PREFIXES);
+        for record in records.iter() {
+            let sensor = match is_absolute(&record.name) {
+                true => record.name.clone(),
+                false => format!("{}{}", base_uri, record.name),
+            };
+            if !is_valid_iri(&sensor) {
+                return Err(SinditSenMLError::InvalidIri(sensor));
+            }
+            turtle.push_str(&format!(
+                "\n[] a sosa:Observation ;\n    sosa:madeBySensor <{}> ;\n    senml:name {}",
+                sensor,
+                escape_string(&record.name)
+            ));
+            if let Some(ref unit) = record.unit {
+                turtle.push_str(&format!(" ;\n    senml:unit {}", escape_string(unit)));
+                if let Some(qudt_unit) = qudt_unit(unit) {
+                    turtle.push_str(&format!(" ;\n    qudt:hasUnit unit:{}", qudt_unit));
+                }
+            }
+            let value = match record.value {
+                Some(SenMLValueField::FloatingPoint(value)) => Some(double_literal(value)),
+                Some(SenMLValueField::BooleanValue(value)) => {
+                    Some(format!("\"{}\"^^xsd:boolean", value))
+                }
+                Some(SenMLValueField::StringValue(ref value)) => Some(escape_string(value)),
+                Some(SenMLValueField::DataValue(ref value)) => Some(format!(
+                    "\"{}\"^^xsd:base64Binary",
+                    Base64Variant::Standard.encode(value)
+                )),
+                None => None,
+            };
+            if let Some(value) = value {
+                turtle.push_str(&format!(" ;\n    senml:value {}", value));
+            }
+            if let Some(sum) = record.sum {
+                turtle.push_str(&format!(" ;\n    senml:sum {}", double_literal(sum)));
+            }
+            turtle.push_str(&format!(
+                " ;\n    senml:time \"{}\"^^xsd:dateTime .\n",
+                record.time.to_rfc3339_opts(SecondsFormat::AutoSi, true)
+            ));
+        }
+        Ok(turtle)
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::fixtures::SINGLE_DATAPOINT;
+        use crate::{parse_json, parse_json_with_options, ParseOptions};
+        use chrono::DateTime;
+        use rio_api::parser::TriplesParser;
+        use rio_turtle::{TurtleError, TurtleParser};
+
+        /// Parse the Turtle document, and return its triples in the
+        /// N-Triples notation.
+        fn parse_turtle(turtle: &str) -> Vec<(String, String, String)> {
+            let mut triples = Vec::new();
+            TurtleParser::new(turtle.as_bytes(), None)
+                .parse_all(&mut |triple| -> Result<(), TurtleError> {
+                    triples.push((
+                        triple.subject.to_string(),
+                        triple.predicate.to_string(),
+                        triple.object.to_string(),
+                    ));
+                    Ok(())
+                })
+                .unwrap();
+            triples
+        }
+
+        /// Predicates and objects of the triples, without the subjects.
+        fn predicates_and_objects(triples: &[(String, String, String)]) -> Vec<(&str, &str)> {
+            triples
+                .iter()
+                .map(|(_, predicate, object)| (predicate.as_str(), object.as_str()))
+                .collect()
+        }
+
+        #[test]
+        fn test_single_datapoint() {
+            let now = DateTime::from_timestamp(1320067464, 0).unwrap();
+            let records = parse_json(SINGLE_DATAPOINT, Some(now)).unwrap();
+            let turtle = serialize_turtle(&records, "http://example.com/").unwrap();
+            let triples = parse_turtle(&turtle);
+            assert!(triples
+                .iter()
+                .all(|(subject, _, _)| subject.starts_with("_:") && *subject == triples[0].0));
+            assert_eq!(
+                predicates_and_objects(&triples),
+                vec![
+                    (
+                        "<http://www.w3.org/1999/02/22-rdf-syntax-ns#type>",
+                        "<https://www.w3.org/ns/sosa/Observation>"
+                    ),
+                    (
+                        "<https://www.w3.org/ns/sosa/madeBySensor>",
+                        "<urn:dev:ow:10e2073a01080063>"
+                    ),
+                    (
+                        "<urn:ietf:params:xml:ns:senml#name>",
+                        r#""urn:dev:ow:10e2073a01080063""#
+                    ),
+                    ("<urn:ietf:params:xml:ns:senml#unit>", r#""Cel""#),
+                    (
+                        "<http://qudt.org/schema/qudt/hasUnit>",
+                        "<http://qudt.org/vocab/unit/DEG_C>"
+                    ),
+                    (
+                        "<urn:ietf:params:xml:ns:senml#value>",
+                        r#""23.1"^^<http://www.w3.org/2001/XMLSchema#double>"#
+                    ),
+                    (
+                        "<urn:ietf:params:xml:ns:senml#time>",
+                        r#""2011-10-31T13:24:24Z"^^<http://www.w3.org/2001/XMLSchema#dateTime>"#
+                    ),
+                ]
+            );
+        }
+
+        #[test]
+        fn test_literals() {
+            let records = parse_json(
+                r#"[
+                    {"bt": 1320067464, "n": "2001:db8::2/label", "vs": "Machine \"Room\"\n1"},
+                    {"n": "open", "vb": false, "u": "custom", "t": 0.5},
+                    {"n": "nfc-reader", "vd": "aGkgCg"},
+                    {"n": "energy", "s": 42.0}
+                ]"#,
+                None,
+            )
+            .unwrap();
+            let turtle = serialize_turtle(&records, "http://example.com/").unwrap();
+            let triples = parse_turtle(&turtle);
+            let predicates_and_objects = predicates_and_objects(&triples);
+            for expected in [
+                (
+                    "<https://www.w3.org/ns/sosa/madeBySensor>",
+                    "<http://example.com/2001:db8::2/label>",
+                ),
+                (
+                    "<urn:ietf:params:xml:ns:senml#value>",
+                    r#""Machine \"Room\"\n1""#,
+                ),
+                (
+                    "<urn:ietf:params:xml:ns:senml#value>",
+                    r#""false"^^<http://www.w3.org/2001/XMLSchema#boolean>"#,
+                ),
+                ("<urn:ietf:params:xml:ns:senml#unit>", r#""custom""#),
+                (
+                    "<urn:ietf:params:xml:ns:senml#value>",
+                    r#""aGkgCg=="^^<http://www.w3.org/2001/XMLSchema#base64Binary>"#,
+                ),
+                (
+                    "<urn:ietf:params:xml:ns:senml#sum>",
+                    r#""42"^^<http://www.w3.org/2001/XMLSchema#double>"#,
+                ),
+                (
+                    "<urn:ietf:params:xml:ns:senml#time>",
+                    r#""2011-10-31T13:24:24.500Z"^^<http://www.w3.org/2001/XMLSchema#dateTime>"#,
+                ),
+            ] {
+                assert!(predicates_and_objects.contains(&expected), "{:?}", expected);
+            }
+            // Only the SenML unit of the custom unit.
+            assert!(!turtle.contains("qudt:hasUnit"));
+        }
+
+        #[test]
+        fn test_invalid_iris() {
+            let records = parse_json(r#"[{"n": "temperature", "v": 23.1}]"#, None).unwrap();
+            for base_uri in [
+                "",
+                "sensors/",
+                "http://example.com/a b/",
+                "http://example.com/<",
+            ] {
+                assert!(matches!(
+                    serialize_turtle(&records, base_uri).unwrap_err(),
+                    SinditSenMLError::InvalidIri(iri) if iri == base_uri
+                ));
+            }
+
+            let options = ParseOptions {
+                strict_names: false,
+                ..Default::default()
+            };
+            let records =
+                parse_json_with_options(r#"[{"n": "room 1>", "v": 1}]"#, None, &options).unwrap();
+            assert!(matches!(
+                serialize_turtle(&records, "http://example.com/").unwrap_err(),
+                SinditSenMLError::InvalidIri(iri) if iri == "http://example.com/room 1>"
+            ));
+        }
+    }
+}
@@ -0,0 +1,260 @@
+//! # Join SenML Packs
+//!
+//! Correlate the records of two packs by name and time,
+//! or of multiple packs by time.
+
+use std::collections::HashMap;
+
+use chrono::Duration;
+
+use crate::SenMLResolvedRecord;
+
+/// For each left record, index of the matching right record.
+///
+/// Greedy matching in the left records order: each left record takes the
+/// closest right record with the same name that is within the tolerance
+/// and not already taken.
+fn match_by_name_and_time(
+    left: &[SenMLResolvedRecord],
+    right: &[SenMLResolvedRecord],
+    time_tolerance: Duration,
+) -> Vec<Option<usize>> {
+    let mut right_by_name: HashMap<&str, Vec<usize>> = HashMap::new();
+    for (index, record) in right.iter().enumerate() {
+        right_by_name
+            .entry(record.name.as_str())
+            .or_default()
+            .push(index);
+    }
+
+    let mut taken = vec![false; right.len()];
+    left.iter()
+        .map(|record| {
+            let closest = right_by_name
+                .get(record.name.as_str())?
+                .iter()
+                .filter(|&&index| !taken[index])
+                .map(|&index| (index, (right[index].time - record.time).abs()))
+                .filter(|(_, distance)| *distance <= time_tolerance)
+                .min_by_key(|(_, distance)| *distance)
+                .map(|(index, _)| index);
+            if let Some(index) = closest {
+                taken[index] = true;
+            }
+            closest
+        })
+        .collect()
+}
+
+/// Pair records of two packs having the same name and a close time.
+///
+/// Each left record is paired with the closest right record having the
+/// same name and a time within `time_tolerance`. A right record is paired
+/// at most once, the left records are matched in order.
+/// Left records without a match are skipped.
+///
+/// # Arguments
+/// * `left` - The left records.
+/// * `right` - The right records.
+/// * `time_tolerance` - The maximum time difference between paired records.
+/// # Returns
+/// * `Vec<(SenMLResolvedRecord, SenMLResolvedRecord)>` - The pairs, in the left records order.
+pub fn natural_join(
+    left: &[SenMLResolvedRecord],
+    right: &[SenMLResolvedRecord],
+    time_tolerance: Duration,
+) -> Vec<(SenMLResolvedRecord, SenMLResolvedRecord)> {
+    match_by_name_and_time(left, right, time_tolerance)
+        .into_iter()
+        .zip(left.iter())
+        .filter_map(|(matched, record)| matched.map(|index| (record.clone(), right[index].clone())))
+        .collect()
+}
+
+/// Same as [`natural_join`] but left records without a match are kept
+/// and paired with `None`.
+pub fn left_outer_join(
+    left: &[SenMLResolvedRecord],
+    right: &[SenMLResolvedRecord],
+    time_tolerance: Duration,
+) -> Vec<(SenMLResolvedRecord, Option<SenMLResolvedRecord>)> {
+    match_by_name_and_time(left, right, time_tolerance)
+        .into_iter()
+        .zip(left.iter())
+        .map(|(matched, record)| (record.clone(), matched.map(|index| right[index].clone())))
+        .collect()
+}
+
+/// Align the records of multiple packs by time.
+///
+/// Each row contains at most one record of each pack, `None` when no
+/// record of the pack falls in the row time window. A row starts at its
+/// earliest record and its window spans `tolerance`. The records are taken
+/// in time order: a record starts a new row when it is out of the window
+/// of the current row, or when the current row already has a record of
+/// its pack. Every record is in exactly one row, so the rows do not overlap.
+///
+/// # Arguments
+/// * `packs` - The packs to align, for example one per sensor.
+/// * `tolerance` - The maximum time difference between the records of a row.
+/// # Returns
+/// * `Vec<Vec<Option<&SenMLResolvedRecord>>>` - The rows in time order,
+///   with one column per pack.
+pub fn temporal_join<'a>(
+    packs: &[&'a [SenMLResolvedRecord]],
+    tolerance: Duration,
+) -> Vec<Vec<Option<&'a SenMLResolvedRecord>>> {
+    let mut records: Vec<(usize, &SenMLResolvedRecord)> = packs
+        .iter()
+        .enumerate()
+        .flat_map(|(pack_index, pack)| pack.iter().map(move |record| (pack_index, record)))
+        .collect();
+    // Stable sort, the records of a pack having the same time keep their order.
+    records.sort_by_key(|(pack_index, record)| (record.time, *pack_index));
+
+    let mut rows = Vec::new();
+    let mut row: Vec<Option<&SenMLResolvedRecord>> = vec![None; packs.len()];
+    let mut row_start = None;
+    for (pack_index, record) in records {
+        let in_window = match row_start {
+            Some(start) => record.time - start <= tolerance && row[pack_index].is_none(),
+            None => true,
+        };
+        if !in_window {
+            rows.push(std::mem::replace(&mut row, vec![None; packs.len()]));
+            row_start = None;
+        }
+        if row_start.is_none() {
+            row_start = Some(record.time);
+        }
+        row[pack_index] = Some(record);
+    }
+    if row_start.is_some() {
+        rows.push(row);
+    }
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_json;
+
+    const TEMPERATURES: &str = r#"[
+        {"bn": "urn:dev:ow:10e2073a01080063:", "bt": 1320067464, "bu": "Cel"},
+        {"n": "room1", "v": 20.1},
+        {"n": "room2", "v": 21.2},
+        {"n": "room1", "t": 60, "v": 20.3},
+        {"n": "room2", "t": 60, "v": 21.4}
+    ]"#;
+
+    const HUMIDITIES: &str = r#"[
+        {"bn": "urn:dev:ow:10e2073a01080063:", "bt": 1320067464, "bu": "%RH"},
+        {"n": "room2", "v": 45.0},
+        {"n": "room1", "v": 40.0},
+        {"n": "room2", "t": 60, "v": 46.0},
+        {"n": "room1", "t": 60, "v": 41.0}
+    ]"#;
+
+    #[test]
+    fn test_natural_join() {
+        let temperatures = &parse_json(TEMPERATURES, None).unwrap()[1..];
+        let humidities = &parse_json(HUMIDITIES, None).unwrap()[1..];
+        let pairs = natural_join(temperatures, humidities, Duration::zero());
+        assert_eq!(pairs.len(), 4);
+        for (temperature, humidity) in pairs.iter() {
+            assert_eq!(temperature.name, humidity.name);
+            assert_eq!(temperature.time, humidity.time);
+            assert_eq!(temperature.unit.as_deref(), Some("Cel"));
+            assert_eq!(humidity.unit.as_deref(), Some("%RH"));
+        }
+        assert_eq!(pairs[0].1.get_float_value(), Some(40.0));
+        assert_eq!(pairs[3].1.get_float_value(), Some(46.0));
+    }
+
+    #[test]
+    fn test_natural_join_tolerance() {
+        let left = parse_json(r#"[{"n": "sensor", "t": 1320067464, "v": 1.0}]"#, None).unwrap();
+        let right = parse_json(
+            r#"[{"n": "sensor", "t": 1320067464.1, "v": 2.0},{"n": "sensor", "t": 1320067462, "v": 3.0}]"#,
+            None,
+        )
+        .unwrap();
+        let pairs = natural_join(&left, &right, Duration::seconds(1));
+        assert_eq!(pairs.len(), 1);
+        assert_eq!(pairs[0].1.get_float_value(), Some(2.0));
+
+        assert!(natural_join(&left, &right, Duration::milliseconds(10)).is_empty());
+    }
+
+    #[test]
+    fn test_right_record_matched_once() {
+        let left = parse_json(
+            r#"[{"n": "sensor", "t": 1320067464, "v": 1.0},{"n": "sensor", "t": 1320067464, "v": 2.0}]"#,
+            None,
+        )
+        .unwrap();
+        let right = parse_json(r#"[{"n": "sensor", "t": 1320067464, "v": 3.0}]"#, None).unwrap();
+        assert_eq!(natural_join(&left, &right, Duration::seconds(1)).len(), 1);
+
+        let pairs = left_outer_join(&left, &right, Duration::seconds(1));
+        assert_eq!(pairs.len(), 2);
+        assert_eq!(pairs[0].1, Some(right[0].clone()));
+        assert_eq!(pairs[1].0, left[1]);
+        assert_eq!(pairs[1].1, None);
+    }
+
+    fn sensor_pack(name: &str, offsets_ms: &[i64]) -> Vec<SenMLResolvedRecord> {
+        let json = format!(
+            "[{}]",
+            offsets_ms
+                .iter()
+                .enumerate()
+                .map(|(index, offset)| format!(
+                    r#"{{"n":"{}","v":{},"t":{}}}"#,
+                    name,
+                    index,
+                    1320067464.0 + index as f64 + *offset as f64 / 1000.0
+                ))
+                .collect::<Vec<_>>()
+                .join(",")
+        );
+        parse_json(&json, None).unwrap()
+    }
+
+    #[test]
+    fn test_temporal_join() {
+        let temperatures = sensor_pack("temperature", &[0, 0, 0, 0]);
+        let humidities = sensor_pack("humidity", &[50, 5, 50, 5]);
+        let packs = [temperatures.as_slice(), humidities.as_slice()];
+
+        let rows = temporal_join(&packs, Duration::milliseconds(100));
+        assert_eq!(rows.len(), 4);
+        for (index, row) in rows.iter().enumerate() {
+            assert_eq!(
+                row,
+                &vec![Some(&temperatures[index]), Some(&humidities[index])]
+            );
+        }
+
+        let rows = temporal_join(&packs, Duration::milliseconds(10));
+        assert_eq!(rows.len(), 6);
+        assert_eq!(rows[0], vec![Some(&temperatures[0]), None]);
+        assert_eq!(rows[1], vec![None, Some(&humidities[0])]);
+        assert_eq!(rows[2], vec![Some(&temperatures[1]), Some(&humidities[1])]);
+        let matched = rows
+            .iter()
+            .filter(|row| row.iter().all(|record| record.is_some()))
+            .count();
+        assert_eq!(matched, 2);
+    }
+
+    #[test]
+    fn test_temporal_join_same_pack_in_window() {
+        let temperatures = sensor_pack("temperature", &[0, 0]);
+        let packs = [temperatures.as_slice()];
+        let rows = temporal_join(&packs, Duration::seconds(10));
+        assert_eq!(rows.len(), 2);
+        assert!(temporal_join(&[], Duration::seconds(1)).is_empty());
+    }
+}
@@ -29,6 +29,7 @@
 //!     update_time: None,
 //!     base_version: None,
 //!     extra_fields: None,
+//!     active_base_name: None,
 //! };
 //! let json = serde_json::to_string(&vec![record]).unwrap();
 //! assert_eq!(
@@ -37,28 +38,72 @@
 //! );
 //! ```
 //!
+use std::cmp::Ordering;
 use std::collections::HashMap;
 
 use base64::Engine;
 use chrono::{DateTime, Utc};
-use serde::ser::{SerializeStruct, Serializer};
-use serde::Deserialize;
-use serde::Serialize;
+use serde::ser::{SerializeMap, Serializer};
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
 use time::datetime_to_timestamp;
 use validate_name::validate_name;
 
-#[cfg(feature = "stream")]
-mod stream;
+#[cfg(feature = "macros")]
+pub use senml_macros::senml_pack;
+
+/// Items used by the code generated by the macros.
+#[cfg(feature = "macros")]
+#[doc(hidden)]
+pub mod __private {
+    pub use chrono::{DateTime, Utc};
+}
 
+pub mod builder;
+#[cfg(feature = "cbor")]
+pub mod cbor;
+pub mod compact;
+pub mod diff;
+pub mod etag;
+#[cfg(test)]
+pub(crate) mod fixtures;
+pub mod freshness;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+pub mod integrity;
+pub mod interop;
+pub mod join;
+pub mod merge_patch;
+#[cfg(feature = "msgpack")]
+pub mod msgpack;
+pub mod pack;
+pub mod pack_ops;
+#[cfg(feature = "parquet")]
+pub mod parquet;
+pub mod replay;
+pub mod schema;
+#[cfg(feature = "signing")]
+pub mod signing;
+pub mod stats;
+#[cfg(feature = "stream")]
+pub mod stream;
+#[cfg(feature = "test_utils")]
+pub mod test_utils;
 pub mod time;
+pub mod transform;
 pub mod validate_name;
+pub mod validate_unit;
 
 /// SINDIT SenML Error.
 ///
 /// This represent the common errors that can happen when using this library.
 /// The library is not supposed to panic, but instead return an error.
+///
+/// Some variants only exist with the features of the library that
+/// return them, so the enum is non exhaustive: a `match` must have
+/// a wildcard arm whatever the enabled features.
 #[derive(Error, Debug)]
+#[non_exhaustive]
 pub enum SinditSenMLError {
     #[error("Invalid JSON")]
     InvalidJSON(#[from] serde_json::Error),
@@ -76,60 +121,245 @@ pub enum SinditSenMLError {
     DifferentBaseVersion,
     #[error("Only one kind of value per record at index {0}")]
     OnlyOneValuePerRecord(usize),
-    #[error("Invalid base64 value in record at index {0}")]
+    #[error("Invalid base64 value")]
     InvalidBase64Value(#[from] base64::DecodeError),
     #[error("Positive version number required")]
     InvalidVersionNumber,
+    #[error("Empty string value in record at index {0}")]
+    EmptyStringValue(usize),
+    #[error("Non finite value or sum in record at index {0}")]
+    NonFiniteValue(usize),
+    #[error("Wrong value type")]
+    WrongValueType,
+    #[error("Time before the Unix epoch in record at index {0}")]
+    PreEpochTimestamp(usize),
+    #[error("Time before 2**28 seconds after the Unix epoch in resolved record at index {0}")]
+    RelativeTimestampInResolvedPack(usize),
+    #[error("Field {0} cannot be removed")]
+    NonNullableField(String),
+    #[error("Unknown unit in record at index {0}")]
+    UnknownUnit(usize),
+    #[error("Unexpected field {field} in record at index {index}")]
+    UnexpectedField { index: usize, field: String },
+    #[error("More than {0} records in the pack")]
+    TooManyRecords(usize),
+    #[error("Unsupported version number {0}")]
+    UnsupportedVersion(u64),
+    #[error("Update time not positive in record at index {0}")]
+    InvalidUpdateTime(usize),
+    #[error("Record at index {0} has the same name and time as a previous record")]
+    DuplicateRecord(usize),
+    #[error("Invalid regular expression")]
+    InvalidRegex(#[from] regex::Error),
+    #[error("Invalid glob pattern")]
+    InvalidGlobPattern(#[from] globset::Error),
+    #[error("{times} times but {values} values in the series")]
+    SeriesLengthMismatch { times: usize, values: usize },
+    #[error("Speed factor must be a positive number")]
+    InvalidSpeedFactor,
+    #[error("Missing or invalid issue time in the pack")]
+    InvalidIssueTime,
+    #[error("Pack issued at {issued_at} has expired at {now}")]
+    PackExpired {
+        issued_at: DateTime<Utc>,
+        now: DateTime<Utc>,
+    },
+    #[error("I/O error")]
+    Io(#[from] std::io::Error),
+    #[cfg(feature = "cbor")]
+    #[error("Invalid CBOR")]
+    InvalidCbor,
+    #[cfg(feature = "msgpack")]
+    #[error("Invalid MessagePack")]
+    InvalidMsgPack,
+    #[cfg(feature = "parquet")]
+    #[error("Invalid or unsupported Parquet file")]
+    InvalidParquet,
+    #[cfg(feature = "rdf")]
+    #[error("Invalid IRI {0}")]
+    InvalidIri(String),
+    #[cfg(feature = "signing")]
+    #[error("Invalid signature")]
+    InvalidSignature,
+    #[cfg(feature = "signing")]
+    #[error("Cannot sign an empty pack")]
+    EmptyPack,
+    #[cfg(feature = "grpc")]
+    #[error("gRPC transport error")]
+    GrpcTransport(#[from] tonic::transport::Error),
+    #[cfg(feature = "grpc")]
+    #[error("gRPC error: {0}")]
+    GrpcStatus(#[source] Box<tonic::Status>),
+}
+
+#[cfg(feature = "grpc")]
+impl From<tonic::Status> for SinditSenMLError {
+    fn from(status: tonic::Status) -> Self {
+        // tonic::Status is large, it is boxed to keep the error small.
+        SinditSenMLError::GrpcStatus(Box::new(status))
+    }
+}
+
+/// Deserialise an optional field that is not null when present.
+///
+/// A null value is not a valid SenML value, it is rejected instead of
+/// being read as a missing value.
+fn deserialize_non_null<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    T::deserialize(deserializer).map(Some)
+}
+
+/// Time of a SenML Record, as found in a SenML Pack.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum SenMLTime {
+    /// A number of seconds, relative to the base time, or to now when
+    /// it is below 2^28.
+    Seconds(f64),
+    /// An RFC 3339 time, which is neither relative to the base time
+    /// nor to now.
+    Absolute(DateTime<Utc>),
+}
+
+impl Serialize for SenMLTime {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            SenMLTime::Seconds(seconds) => serializer.serialize_f64(*seconds),
+            SenMLTime::Absolute(datetime) => serializer
+                .serialize_str(&datetime.to_rfc3339_opts(chrono::SecondsFormat::AutoSi, true)),
+        }
+    }
+}
+
+/// Deserialise an optional time, either as a number of seconds or as an
+/// RFC 3339 string such as `"2023-01-01T12:00:00Z"`.
+fn deserialize_time<'de, D>(deserializer: D) -> Result<Option<SenMLTime>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    struct TimeVisitor;
+
+    impl serde::de::Visitor<'_> for TimeVisitor {
+        type Value = Option<SenMLTime>;
+
+        fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+            formatter.write_str("a number of seconds or an RFC 3339 time")
+        }
+
+        fn visit_f64<E>(self, value: f64) -> Result<Self::Value, E> {
+            Ok(Some(SenMLTime::Seconds(value)))
+        }
+
+        fn visit_i64<E>(self, value: i64) -> Result<Self::Value, E> {
+            Ok(Some(SenMLTime::Seconds(value as f64)))
+        }
+
+        fn visit_u64<E>(self, value: u64) -> Result<Self::Value, E> {
+            Ok(Some(SenMLTime::Seconds(value as f64)))
+        }
+
+        fn visit_str<E>(self, value: &str) -> Result<Self::Value, E>
+        where
+            E: serde::de::Error,
+        {
+            let datetime = DateTime::parse_from_rfc3339(value)
+                .map_err(|_| E::invalid_value(serde::de::Unexpected::Str(value), &self))?
+                .with_timezone(&Utc);
+            Ok(Some(SenMLTime::Absolute(datetime)))
+        }
+    }
+
+    deserializer.deserialize_any(TimeVisitor)
 }
 
-#[derive(Deserialize, Debug, Clone)]
-struct SenMLRecord {
-    #[serde(rename = "bn")]
+/// SenML Record, as found in a SenML Pack.
+///
+/// The record is not resolved: its fields may depend on the base fields
+/// of the previous records of the pack.
+/// This can be serialised and deserialised using serde.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct SenMLRecord {
+    #[serde(rename = "bn", skip_serializing_if = "Option::is_none")]
     base_name: Option<String>,
 
-    #[serde(rename = "bt")]
+    #[serde(rename = "bt", skip_serializing_if = "Option::is_none")]
     base_time: Option<f64>,
 
-    #[serde(rename = "bu")]
+    #[serde(rename = "bu", skip_serializing_if = "Option::is_none")]
     base_unit: Option<String>,
 
-    #[serde(rename = "bv")]
+    #[serde(rename = "bv", skip_serializing_if = "Option::is_none")]
     base_value: Option<f64>,
 
-    #[serde(rename = "bs")]
+    #[serde(rename = "bs", skip_serializing_if = "Option::is_none")]
     base_sum: Option<f64>,
 
-    #[serde(rename = "bver")]
+    #[serde(rename = "bver", skip_serializing_if = "Option::is_none")]
     base_version: Option<u64>,
 
-    #[serde(rename = "n")]
+    #[serde(rename = "n", skip_serializing_if = "Option::is_none")]
     name: Option<String>,
 
-    #[serde(rename = "u")]
+    #[serde(rename = "u", skip_serializing_if = "Option::is_none")]
     unit: Option<String>,
 
-    #[serde(rename = "v")]
+    #[serde(
+        rename = "v",
+        default,
+        deserialize_with = "deserialize_non_null",
+        skip_serializing_if = "Option::is_none"
+    )]
     value: Option<f64>,
 
-    #[serde(rename = "vs")]
+    #[serde(
+        rename = "vs",
+        default,
+        deserialize_with = "deserialize_non_null",
+        skip_serializing_if = "Option::is_none"
+    )]
     string_value: Option<String>,
 
-    #[serde(rename = "vb")]
+    #[serde(
+        rename = "vb",
+        default,
+        deserialize_with = "deserialize_non_null",
+        skip_serializing_if = "Option::is_none"
+    )]
     bool_value: Option<bool>,
 
-    #[serde(rename = "vd")]
+    #[serde(
+        rename = "vd",
+        default,
+        deserialize_with = "deserialize_non_null",
+        skip_serializing_if = "Option::is_none"
+    )]
     data_value: Option<String>,
 
-    #[serde(rename = "s")]
+    #[serde(
+        rename = "s",
+        default,
+        deserialize_with = "deserialize_non_null",
+        skip_serializing_if = "Option::is_none"
+    )]
     sum: Option<f64>,
 
-    #[serde(rename = "t")]
-    time: Option<f64>,
+    #[serde(
+        rename = "t",
+        default,
+        deserialize_with = "deserialize_time",
+        skip_serializing_if = "Option::is_none"
+    )]
+    time: Option<SenMLTime>,
 
-    #[serde(rename = "ut")]
+    #[serde(rename = "ut", skip_serializing_if = "Option::is_none")]
     update_time: Option<f64>,
 
-    #[serde(flatten, default)]
+    #[serde(flatten, default, skip_serializing_if = "Option::is_none")]
     extra_fields: Option<HashMap<String, serde_json::Value>>,
 }
 
@@ -182,6 +412,409 @@ impl SenMLValueField {
             None
         }
     }
+
+    /// Whether the value is a floating point number.
+    pub fn is_numeric(&self) -> bool {
+        matches!(self, SenMLValueField::FloatingPoint(_))
+    }
+
+    pub fn is_string(&self) -> bool {
+        matches!(self, SenMLValueField::StringValue(_))
+    }
+
+    pub fn is_bool(&self) -> bool {
+        matches!(self, SenMLValueField::BooleanValue(_))
+    }
+
+    pub fn is_data(&self) -> bool {
+        matches!(self, SenMLValueField::DataValue(_))
+    }
+
+    /// Compare the values, with a tolerance for floating point values.
+    ///
+    /// Floating point values are equal if they differ by at most `epsilon`,
+    /// the other values must be strictly equal.
+    pub fn approx_eq(&self, other: &SenMLValueField, epsilon: f64) -> bool {
+        match (self, other) {
+            (SenMLValueField::FloatingPoint(a), SenMLValueField::FloatingPoint(b)) => {
+                (a - b).abs() <= epsilon
+            }
+            _ => self == other,
+        }
+    }
+
+    /// Create a data value from base64, see [`decode_senml_data`].
+    pub fn from_base64(encoded: &str) -> Result<Self, SinditSenMLError> {
+        Ok(SenMLValueField::DataValue(decode_senml_data(encoded)?))
+    }
+
+    /// Encode a data value in base64, see [`encode_senml_data`].
+    ///
+    /// Returns `None` for the other values.
+    pub fn to_base64(&self) -> Option<String> {
+        self.as_data().map(|data| encode_senml_data(data))
+    }
+
+    /// Convert the value to a floating point number.
+    ///
+    /// Booleans are 0.0 or 1.0, strings are parsed, data values are not converted.
+    pub fn coerce_to_float(&self) -> Option<f64> {
+        match self {
+            SenMLValueField::FloatingPoint(value) => Some(*value),
+            SenMLValueField::BooleanValue(value) => Some(if *value { 1.0 } else { 0.0 }),
+            SenMLValueField::StringValue(value) => value.parse().ok(),
+            SenMLValueField::DataValue(_) => None,
+        }
+    }
+
+    /// Convert a numeric or boolean value to a floating point number,
+    /// for example to plot a door sensor.
+    ///
+    /// Booleans are 0.0 or 1.0. Unlike [`Self::coerce_to_float`],
+    /// strings are not parsed, and string and data values are `None`.
+    pub fn try_coerce_to_float(&self) -> Option<f64> {
+        match self {
+            SenMLValueField::FloatingPoint(value) => Some(*value),
+            SenMLValueField::BooleanValue(value) => Some(if *value { 1.0 } else { 0.0 }),
+            SenMLValueField::StringValue(_) | SenMLValueField::DataValue(_) => None,
+        }
+    }
+
+    /// Convert the value to a boolean.
+    ///
+    /// Numbers are true if not zero, strings must be `"true"` or `"false"`,
+    /// data values are true if not empty.
+    pub fn coerce_to_bool(&self) -> Option<bool> {
+        match self {
+            SenMLValueField::FloatingPoint(value) => Some(*value != 0.0),
+            SenMLValueField::BooleanValue(value) => Some(*value),
+            SenMLValueField::StringValue(value) => value.parse().ok(),
+            SenMLValueField::DataValue(value) => Some(!value.is_empty()),
+        }
+    }
+
+    /// Convert the value to a string.
+    ///
+    /// Data values are encoded in URL safe base64 without padding,
+    /// as in SenML JSON.
+    pub fn coerce_to_string(&self) -> String {
+        match self {
+            SenMLValueField::FloatingPoint(value) => value.to_string(),
+            SenMLValueField::BooleanValue(value) => value.to_string(),
+            SenMLValueField::StringValue(value) => value.clone(),
+            SenMLValueField::DataValue(value) => Base64Variant::UrlSafeNoPad.encode(value),
+        }
+    }
+}
+
+/// Display the value for humans, for example in logs.
+///
+/// Floating point numbers use the shortest representation, `42` for `42.0`,
+/// and data values are written in hexadecimal, prefixed with `0x`.
+impl std::fmt::Display for SenMLValueField {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SenMLValueField::FloatingPoint(value) => write!(f, "{}", value),
+            SenMLValueField::BooleanValue(value) => write!(f, "{}", value),
+            SenMLValueField::StringValue(value) => f.write_str(value),
+            SenMLValueField::DataValue(value) => {
+                f.write_str("0x")?;
+                for byte in value.iter() {
+                    write!(f, "{:02x}", byte)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl From<f64> for SenMLValueField {
+    fn from(value: f64) -> Self {
+        SenMLValueField::FloatingPoint(value)
+    }
+}
+
+impl From<f32> for SenMLValueField {
+    fn from(value: f32) -> Self {
+        SenMLValueField::FloatingPoint(value as f64)
+    }
+}
+
+/// Integers with an absolute value above 2^53 lose precision.
+impl From<i64> for SenMLValueField {
+    fn from(value: i64) -> Self {
+        SenMLValueField::FloatingPoint(value as f64)
+    }
+}
+
+impl From<i32> for SenMLValueField {
+    fn from(value: i32) -> Self {
+        SenMLValueField::FloatingPoint(value as f64)
+    }
+}
+
+/// Integers above 2^53 lose precision.
+impl From<u64> for SenMLValueField {
+    fn from(value: u64) -> Self {
+        SenMLValueField::FloatingPoint(value as f64)
+    }
+}
+
+impl From<u32> for SenMLValueField {
+    fn from(value: u32) -> Self {
+        SenMLValueField::FloatingPoint(value as f64)
+    }
+}
+
+impl From<bool> for SenMLValueField {
+    fn from(value: bool) -> Self {
+        SenMLValueField::BooleanValue(value)
+    }
+}
+
+impl From<String> for SenMLValueField {
+    fn from(value: String) -> Self {
+        SenMLValueField::StringValue(value)
+    }
+}
+
+impl From<&str> for SenMLValueField {
+    fn from(value: &str) -> Self {
+        SenMLValueField::StringValue(value.to_string())
+    }
+}
+
+impl From<Vec<u8>> for SenMLValueField {
+    fn from(value: Vec<u8>) -> Self {
+        SenMLValueField::DataValue(value)
+    }
+}
+
+/// Base64 variant used to serialize data values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Base64Variant {
+    /// URL safe alphabet without padding, as required by the RFC.
+    #[default]
+    UrlSafeNoPad,
+    /// Standard alphabet with padding.
+    Standard,
+}
+
+impl Base64Variant {
+    fn encode(&self, data: &[u8]) -> String {
+        match self {
+            Base64Variant::UrlSafeNoPad => {
+                base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(data)
+            }
+            Base64Variant::Standard => base64::engine::general_purpose::STANDARD.encode(data),
+        }
+    }
+}
+
+/// Encode data in base64 as in SenML, with the URL safe alphabet
+/// without padding.
+///
+/// # Examples
+/// ```
+/// use sindit_senml::encode_senml_data;
+///
+/// assert_eq!(encode_senml_data(&[0x68, 0x69, 0x20, 0x0a]), "aGkgCg");
+/// ```
+pub fn encode_senml_data(bytes: &[u8]) -> String {
+    Base64Variant::UrlSafeNoPad.encode(bytes)
+}
+
+/// Decode data encoded in base64 as in SenML, with the URL safe alphabet
+/// without padding.
+pub fn decode_senml_data(encoded: &str) -> Result<Vec<u8>, SinditSenMLError> {
+    Ok(Base64AcceptPolicy::StrictUrlSafe.decode(encoded)?)
+}
+
+/// Base64 variants accepted when parsing data values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Base64AcceptPolicy {
+    /// Only the URL safe alphabet without padding, as required by the RFC.
+    #[default]
+    StrictUrlSafe,
+    /// Only the standard alphabet with padding.
+    StrictStandard,
+    /// Both the URL safe alphabet without padding and
+    /// the standard alphabet with padding.
+    AcceptBoth,
+}
+
+impl Base64AcceptPolicy {
+    fn decode(&self, data: &str) -> Result<Vec<u8>, base64::DecodeError> {
+        match self {
+            Base64AcceptPolicy::StrictUrlSafe => {
+                base64::engine::general_purpose::URL_SAFE_NO_PAD.decode(data)
+            }
+            Base64AcceptPolicy::StrictStandard => {
+                base64::engine::general_purpose::STANDARD.decode(data)
+            }
+            Base64AcceptPolicy::AcceptBoth => base64::engine::general_purpose::URL_SAFE_NO_PAD
+                .decode(data)
+                .or_else(|error| {
+                    base64::engine::general_purpose::STANDARD
+                        .decode(data)
+                        .map_err(|_| error)
+                }),
+        }
+    }
+}
+
+/// Case normalisation of the resolved names.
+///
+/// Only the ASCII letters are converted, as the RFC only allows ASCII
+/// characters in names.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NameCase {
+    /// Keep the names as they are.
+    #[default]
+    Preserve,
+    ToLowercase,
+    ToUppercase,
+}
+
+impl NameCase {
+    fn apply(&self, name: String) -> String {
+        match self {
+            NameCase::Preserve => name,
+            NameCase::ToLowercase => name.to_ascii_lowercase(),
+            NameCase::ToUppercase => name.to_ascii_uppercase(),
+        }
+    }
+}
+
+/// Validation of the resolved names.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NameValidation {
+    /// The rule of the RFC, see [`validate_name::validate_name`].
+    #[default]
+    Rfc8428,
+    /// The rule of the RFC, rejecting single-character names,
+    /// see [`validate_name::validate_name_strict`].
+    Strict,
+    /// The rule of the RFC, allowing Unicode letters,
+    /// see [`validate_name::validate_name_loose`].
+    Loose,
+}
+
+impl NameValidation {
+    fn validate(&self, name: &str) -> bool {
+        match self {
+            NameValidation::Rfc8428 => validate_name(name),
+            NameValidation::Strict => validate_name::validate_name_strict(name),
+            NameValidation::Loose => validate_name::validate_name_loose(name),
+        }
+    }
+}
+
+/// Options for parsing SenML.
+///
+/// The default options follow the RFC.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseOptions {
+    /// Base64 variants accepted for data values.
+    pub base64_input: Base64AcceptPolicy,
+
+    /// Reject the records with an empty string value.
+    ///
+    /// Empty string values are allowed by the RFC but often indicate a bug.
+    pub reject_empty_string_values: bool,
+
+    /// Set [`SenMLResolvedRecord::active_base_name`] to the base name
+    /// that was active when each record was resolved.
+    pub track_base_name: bool,
+
+    /// Case normalisation of the resolved names, applied before validation.
+    pub name_case: NameCase,
+
+    /// Reject the records with a resolved name that is not valid
+    /// according to [`ParseOptions::name_validation`].
+    ///
+    /// Enabled by default, as required by the RFC.
+    pub strict_names: bool,
+
+    /// Validation of the resolved names, the rule of the RFC by default.
+    pub name_validation: NameValidation,
+
+    /// Reject the records with a time before the Unix epoch.
+    ///
+    /// See also [`time::find_pre_epoch_records`].
+    pub reject_pre_epoch: bool,
+
+    /// Reject the records with a unit that is not in the SenML Units
+    /// registry, see [`validate_unit::validate_unit`].
+    pub strict_units: bool,
+
+    /// Reject the records with fields that are not defined in the RFC,
+    /// instead of keeping them in [`SenMLResolvedRecord::extra_fields`].
+    pub strict_extra_fields: bool,
+
+    /// Maximum number of records in a pack, without limit by default.
+    pub max_records: Option<usize>,
+
+    /// Time of the records without a time or a base time,
+    /// instead of the current time.
+    pub default_time: Option<DateTime<Utc>>,
+
+    /// Accept the versions other than 10, the version of RFC8428.
+    ///
+    /// Enabled by default.
+    pub allow_custom_versions: bool,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        ParseOptions {
+            base64_input: Base64AcceptPolicy::default(),
+            reject_empty_string_values: false,
+            track_base_name: false,
+            name_case: NameCase::default(),
+            strict_names: true,
+            name_validation: NameValidation::default(),
+            reject_pre_epoch: false,
+            strict_units: false,
+            strict_extra_fields: false,
+            max_records: None,
+            default_time: None,
+            allow_custom_versions: true,
+        }
+    }
+}
+
+/// Options for serializing SenML.
+///
+/// The default options follow the RFC.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SerializationOptions {
+    /// Base64 variant of the data values.
+    pub base64_output: Base64Variant,
+}
+
+fn serialize_value_entry<M>(
+    map: &mut M,
+    value: &SenMLValueField,
+    base64_output: Base64Variant,
+) -> Result<(), M::Error>
+where
+    M: SerializeMap,
+{
+    match *value {
+        SenMLValueField::BooleanValue(ref value) => map.serialize_entry("vb", value),
+        SenMLValueField::StringValue(ref value) => map.serialize_entry("vs", value),
+        SenMLValueField::FloatingPoint(ref value) => {
+            if value.fract() == 0.0 {
+                map.serialize_entry("v", &(*value as i64))
+            } else {
+                map.serialize_entry("v", value)
+            }
+        }
+        SenMLValueField::DataValue(ref value) => {
+            map.serialize_entry("vd", &base64_output.encode(value))
+        }
+    }
 }
 
 impl serde::ser::Serialize for SenMLValueField {
@@ -189,35 +822,58 @@ impl serde::ser::Serialize for SenMLValueField {
     where
         S: Serializer,
     {
-        let mut state = serializer.serialize_struct("SenMLValueField", 1)?;
-        match *self {
-            SenMLValueField::BooleanValue(ref value) => state.serialize_field("vb", value)?,
-            SenMLValueField::StringValue(ref value) => state.serialize_field("vs", value)?,
-            SenMLValueField::FloatingPoint(ref value) => {
-                if value.fract() == 0.0 {
-                    state.serialize_field("v", &(*value as i64))?
-                } else {
-                    state.serialize_field("v", value)?
-                }
-            }
-            SenMLValueField::DataValue(ref value) => state.serialize_field(
-                "vd",
-                &base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(value),
-            )?,
-        }
-        state.end()
+        let mut map = serializer.serialize_map(Some(1))?;
+        serialize_value_entry(&mut map, self, Base64Variant::default())?;
+        map.end()
     }
 }
 
-fn serialize_datetime<S>(date: &DateTime<Utc>, serializer: S) -> Result<S::Ok, S::Error>
+fn serialize_record<S>(
+    record: &SenMLResolvedRecord,
+    options: &SerializationOptions,
+    iso8601_time: bool,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
 where
     S: Serializer,
 {
-    let (timestamp, precise_timestamp) = datetime_to_timestamp(date);
-    if let Some(precise_timestamp) = precise_timestamp {
-        return serializer.serialize_f64(precise_timestamp);
+    let mut map = serializer.serialize_map(None)?;
+    map.serialize_entry("n", &record.name)?;
+    if let Some(ref unit) = record.unit {
+        map.serialize_entry("u", unit)?;
+    }
+    if let Some(ref value) = record.value {
+        serialize_value_entry(&mut map, value, options.base64_output)?;
     }
-    serializer.serialize_i64(timestamp)
+    if let Some(ref sum) = record.sum {
+        map.serialize_entry("s", sum)?;
+    }
+    if iso8601_time {
+        map.serialize_entry(
+            "t",
+            &record
+                .time
+                .to_rfc3339_opts(chrono::SecondsFormat::AutoSi, true),
+        )?;
+    } else {
+        let (timestamp, precise_timestamp) = datetime_to_timestamp(&record.time);
+        match precise_timestamp {
+            Some(precise_timestamp) => map.serialize_entry("t", &precise_timestamp)?,
+            None => map.serialize_entry("t", &timestamp)?,
+        }
+    }
+    if let Some(ref update_time) = record.update_time {
+        map.serialize_entry("ut", update_time)?;
+    }
+    if let Some(ref base_version) = record.base_version {
+        map.serialize_entry("bver", base_version)?;
+    }
+    if let Some(ref extra_fields) = record.extra_fields {
+        for (key, value) in extra_fields.iter() {
+            map.serialize_entry(key, value)?;
+        }
+    }
+    map.end()
 }
 
 /// SenML Resolved Record.
@@ -231,13 +887,12 @@ where
 /// Please note that this is not the most compact SenML representation,
 /// but it is a compatible one.
 /// <https://www.rfc-editor.org/rfc/rfc8428#section-4.6>
-#[derive(Serialize, Debug, PartialEq, Clone)]
+#[derive(Debug, Clone)]
 pub struct SenMLResolvedRecord {
     /// The name of the record.
     ///
     /// This is the concatenation of the base name and the name.
     /// The name is always present and cannot be an empty string.
-    #[serde(rename = "n")]
     pub name: String,
 
     /// The unit of the record.
@@ -247,14 +902,12 @@ pub struct SenMLResolvedRecord {
     /// defined in the SenML unit registries.
     /// <https://www.rfc-editor.org/rfc/rfc8428.html#section-12.1>
     /// <https://www.rfc-editor.org/rfc/rfc8798.html>
-    #[serde(rename = "u", skip_serializing_if = "Option::is_none")]
     pub unit: Option<String>,
 
     /// The value of the record.
     ///
     /// The value is optional as the record can also contain a `sum`.
     /// The value defaults to 0.0 if both the sum and the value are missing.
-    #[serde(flatten)]
     pub value: Option<SenMLValueField>,
 
     /// Integrated sum of the values over time.
@@ -262,14 +915,12 @@ pub struct SenMLResolvedRecord {
     /// This field should have been named "integral" according to the RFC
     /// but is named "sum" for historical reasons.
     /// Optional.
-    #[serde(rename = "s", skip_serializing_if = "Option::is_none")]
     pub sum: Option<f64>,
 
     /// Time when the value was recorded.
     ///
     /// This is a UTC DateTime that is always present.
     /// It defaults to the current time of the system.
-    #[serde(rename = "t", serialize_with = "serialize_datetime")]
     pub time: DateTime<Utc>,
 
     /// Period of time in seconds that represents the maximum time
@@ -277,64 +928,433 @@ pub struct SenMLResolvedRecord {
     ///
     /// Optional. This can be used to detect the failure of sensors or
     /// the communications path from the sensor.
-    #[serde(rename = "ut", skip_serializing_if = "Option::is_none")]
     pub update_time: Option<f64>,
 
     /// Version number of the media type format.
     ///
     /// This field is an optional positive integer and defaults to 10 if not present.
-    #[serde(rename = "bver", skip_serializing_if = "Option::is_none")]
     pub base_version: Option<u64>,
 
     /// Extra fields that are not part of the SenML specification but
     /// are allowed to be present and were in the JSON records.
-    #[serde(flatten, skip_serializing_if = "Option::is_none")]
     pub extra_fields: Option<HashMap<String, serde_json::Value>>,
+
+    /// Base name that was active when the record was resolved.
+    ///
+    /// Only set by [`parse_json_with_base_tracking`] or when
+    /// [`ParseOptions::track_base_name`] is enabled.
+    /// This field is not serialised.
+    pub active_base_name: Option<String>,
 }
 
-impl SenMLResolvedRecord {
-    pub fn get_bool_value(&self) -> Option<bool> {
-        self.value.as_ref().and_then(|v| v.as_bool().copied())
+impl serde::ser::Serialize for SenMLResolvedRecord {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serialize_record(self, &SerializationOptions::default(), false, serializer)
     }
+}
 
-    pub fn get_string_value(&self) -> Option<&String> {
-        self.value.as_ref().and_then(|v| v.as_string())
-    }
+/// SenML Resolved Record serialised with an ISO 8601 time.
+///
+/// The time, `"t"`, is serialised as an RFC 3339 string such as
+/// `"2023-01-01T12:00:00Z"` instead of a number of seconds, for the
+/// systems that expect such times. This is not standard SenML, but
+/// the records are parsed back by [`parse_json`].
+///
+/// # Examples
+/// ```
+/// use sindit_senml::{parse_json, SenMLResolvedRecordIso8601};
+///
+/// let records = parse_json(r#"[{"n": "temperature", "v": 42, "t": 1672574400}]"#, None).unwrap();
+/// let record = SenMLResolvedRecordIso8601(records[0].clone());
+/// assert_eq!(
+///     serde_json::to_string(&record).unwrap(),
+///     r#"{"n":"temperature","v":42,"t":"2023-01-01T12:00:00Z"}"#
+/// );
+/// ```
+#[derive(Debug, PartialEq, Clone)]
+pub struct SenMLResolvedRecordIso8601(pub SenMLResolvedRecord);
 
-    pub fn get_data_value(&self) -> Option<&Vec<u8>> {
-        self.value.as_ref().and_then(|v| v.as_data())
+impl From<SenMLResolvedRecord> for SenMLResolvedRecordIso8601 {
+    fn from(record: SenMLResolvedRecord) -> Self {
+        SenMLResolvedRecordIso8601(record)
     }
+}
 
-    pub fn get_float_value(&self) -> Option<f64> {
-        self.value.as_ref().and_then(|v| v.as_float().copied())
+impl serde::ser::Serialize for SenMLResolvedRecordIso8601 {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serialize_record(&self.0, &SerializationOptions::default(), true, serializer)
     }
 }
 
-fn resolve_value(
-    record: &SenMLRecord,
-    base_value: &Option<f64>,
-    index: usize,
-) -> Result<Option<SenMLValueField>, SinditSenMLError> {
-    match record.value {
-        Some(value) => {
-            if record.string_value.is_some()
-                || record.bool_value.is_some()
-                || record.data_value.is_some()
-            {
-                return Err(SinditSenMLError::OnlyOneValuePerRecord(index));
-            }
-            match base_value {
-                Some(base_value) => Ok(Some(SenMLValueField::FloatingPoint(base_value + value))),
-                None => Ok(Some(SenMLValueField::FloatingPoint(value))),
-            }
+/// Display the record on a single line, for example in logs.
+///
+/// The fields are written as `key=value` pairs, the time in RFC 3339,
+/// and the fields that are `None` are omitted:
+/// `name=temperature unit=Cel value=23.1 time=2023-01-01T12:00:00Z update_time=60`.
+impl std::fmt::Display for SenMLResolvedRecord {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "name={}", self.name)?;
+        if let Some(unit) = &self.unit {
+            write!(f, " unit={}", unit)?;
         }
-        None => match record.string_value {
-            Some(ref value) => {
-                if record.bool_value.is_some() || record.data_value.is_some() {
-                    return Err(SinditSenMLError::OnlyOneValuePerRecord(index));
-                }
-                Ok(Some(SenMLValueField::StringValue(value.to_string())))
-            }
+        if let Some(value) = &self.value {
+            write!(f, " value={}", value)?;
+        }
+        if let Some(sum) = self.sum {
+            write!(f, " sum={}", sum)?;
+        }
+        write!(
+            f,
+            " time={}",
+            self.time
+                .to_rfc3339_opts(chrono::SecondsFormat::AutoSi, true)
+        )?;
+        if let Some(update_time) = self.update_time {
+            write!(f, " update_time={}", update_time)?;
+        }
+        Ok(())
+    }
+}
+
+/// Resolve a single SenML record from a JSON object.
+///
+/// The object must have a name, `"n"`. The base fields of the object
+/// apply to the record, and the time defaults to the current time.
+///
+/// # Examples
+/// ```
+/// use sindit_senml::SenMLResolvedRecord;
+///
+/// let value = serde_json::json!({"n": "temperature", "u": "Cel", "v": 23.1});
+/// let record = SenMLResolvedRecord::try_from(value).unwrap();
+/// assert_eq!(record.name, "temperature");
+/// assert_eq!(record.get_float_value(), Some(23.1));
+/// ```
+impl TryFrom<serde_json::Value> for SenMLResolvedRecord {
+    type Error = SinditSenMLError;
+
+    fn try_from(value: serde_json::Value) -> Result<Self, Self::Error> {
+        if value.is_object() && value.get("n").is_none() {
+            return Err(SinditSenMLError::MissingName(0));
+        }
+        let record: SenMLRecord = serde_json::from_value(value)?;
+        let mut records = resolve_records(&vec![record], Utc::now())?;
+        Ok(records.remove(0))
+    }
+}
+
+/// Serialise a record to a JSON object, as in a SenML Pack.
+///
+/// # Examples
+/// ```
+/// use sindit_senml::parse_json;
+///
+/// let records = parse_json(r#"[{"n": "temperature", "v": 42.0, "t": 1320067464}]"#, None).unwrap();
+/// let value: serde_json::Value = records[0].clone().into();
+/// assert_eq!(value, serde_json::json!({"n": "temperature", "v": 42, "t": 1320067464}));
+/// ```
+impl From<SenMLResolvedRecord> for serde_json::Value {
+    fn from(record: SenMLResolvedRecord) -> Self {
+        // The serialisation only fails on map keys that are not strings,
+        // and the keys of a record are strings.
+        serde_json::to_value(&record).unwrap_or_default()
+    }
+}
+
+/// Total order of optional floating point numbers, `None` first.
+fn cmp_optional_f64(a: Option<f64>, b: Option<f64>) -> Ordering {
+    match (a, b) {
+        (Some(a), Some(b)) => a.total_cmp(&b),
+        (a, b) => a.is_some().cmp(&b.is_some()),
+    }
+}
+
+/// Total order of the values: floating point, boolean, string, then data
+/// values.
+fn cmp_value(a: &SenMLValueField, b: &SenMLValueField) -> Ordering {
+    let rank = |value: &SenMLValueField| match value {
+        SenMLValueField::FloatingPoint(_) => 0,
+        SenMLValueField::BooleanValue(_) => 1,
+        SenMLValueField::StringValue(_) => 2,
+        SenMLValueField::DataValue(_) => 3,
+    };
+    match (a, b) {
+        (SenMLValueField::FloatingPoint(a), SenMLValueField::FloatingPoint(b)) => a.total_cmp(b),
+        (SenMLValueField::BooleanValue(a), SenMLValueField::BooleanValue(b)) => a.cmp(b),
+        (SenMLValueField::StringValue(a), SenMLValueField::StringValue(b)) => a.cmp(b),
+        (SenMLValueField::DataValue(a), SenMLValueField::DataValue(b)) => a.cmp(b),
+        (a, b) => rank(a).cmp(&rank(b)),
+    }
+}
+
+/// Total order of JSON objects, compared entry by entry in key order.
+fn cmp_json_entries<'a>(
+    a: impl Iterator<Item = (&'a String, &'a serde_json::Value)>,
+    b: impl Iterator<Item = (&'a String, &'a serde_json::Value)>,
+) -> Ordering {
+    let mut a: Vec<(&String, &serde_json::Value)> = a.collect();
+    let mut b: Vec<(&String, &serde_json::Value)> = b.collect();
+    a.sort_by_key(|(key, _)| *key);
+    b.sort_by_key(|(key, _)| *key);
+    for ((key_a, value_a), (key_b, value_b)) in a.iter().zip(b.iter()) {
+        let ordering = key_a.cmp(key_b).then_with(|| cmp_json(value_a, value_b));
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+    a.len().cmp(&b.len())
+}
+
+/// Total order of JSON values, for the extra fields.
+///
+/// The numbers are ordered by value, then by representation, so `1` and
+/// `1.0` are different as in JSON.
+fn cmp_json(a: &serde_json::Value, b: &serde_json::Value) -> Ordering {
+    use serde_json::Value;
+    let rank = |value: &Value| match value {
+        Value::Null => 0,
+        Value::Bool(_) => 1,
+        Value::Number(_) => 2,
+        Value::String(_) => 3,
+        Value::Array(_) => 4,
+        Value::Object(_) => 5,
+    };
+    match (a, b) {
+        (Value::Bool(a), Value::Bool(b)) => a.cmp(b),
+        (Value::Number(a), Value::Number(b)) => {
+            cmp_optional_f64(a.as_f64(), b.as_f64()).then_with(|| a.to_string().cmp(&b.to_string()))
+        }
+        (Value::String(a), Value::String(b)) => a.cmp(b),
+        (Value::Array(a), Value::Array(b)) => {
+            for (value_a, value_b) in a.iter().zip(b.iter()) {
+                let ordering = cmp_json(value_a, value_b);
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+            }
+            a.len().cmp(&b.len())
+        }
+        (Value::Object(a), Value::Object(b)) => cmp_json_entries(a.iter(), b.iter()),
+        (a, b) => rank(a).cmp(&rank(b)),
+    }
+}
+
+/// Records are equal when all their fields are equal.
+///
+/// The floating point numbers are compared with [`f64::total_cmp`], to be
+/// consistent with [`Ord`]: a NaN value is equal to itself, and `0.0` and
+/// `-0.0` are different.
+impl PartialEq for SenMLResolvedRecord {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for SenMLResolvedRecord {}
+
+impl PartialOrd for SenMLResolvedRecord {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Order the records by time, then by name.
+///
+/// The records with the same time and name are ordered by their other
+/// fields, so this is a total order: a `BTreeSet` keeps all the different
+/// records. [`pack::SenMLSortedSet`] keeps one record per name and time.
+impl Ord for SenMLResolvedRecord {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.time
+            .cmp(&other.time)
+            .then_with(|| self.name.cmp(&other.name))
+            .then_with(|| self.unit.cmp(&other.unit))
+            .then_with(|| match (&self.value, &other.value) {
+                (Some(a), Some(b)) => cmp_value(a, b),
+                (a, b) => a.is_some().cmp(&b.is_some()),
+            })
+            .then_with(|| cmp_optional_f64(self.sum, other.sum))
+            .then_with(|| cmp_optional_f64(self.update_time, other.update_time))
+            .then_with(|| self.base_version.cmp(&other.base_version))
+            .then_with(|| match (&self.extra_fields, &other.extra_fields) {
+                (Some(a), Some(b)) => cmp_json_entries(a.iter(), b.iter()),
+                (a, b) => a.is_some().cmp(&b.is_some()),
+            })
+            .then_with(|| self.active_base_name.cmp(&other.active_base_name))
+    }
+}
+
+impl SenMLResolvedRecord {
+    pub fn get_bool_value(&self) -> Option<bool> {
+        self.value.as_ref().and_then(|v| v.as_bool().copied())
+    }
+
+    pub fn get_string_value(&self) -> Option<&String> {
+        self.value.as_ref().and_then(|v| v.as_string())
+    }
+
+    pub fn get_data_value(&self) -> Option<&Vec<u8>> {
+        self.value.as_ref().and_then(|v| v.as_data())
+    }
+
+    pub fn get_float_value(&self) -> Option<f64> {
+        self.value.as_ref().and_then(|v| v.as_float().copied())
+    }
+
+    /// Replace the value of the record by a data value.
+    pub fn set_data(&mut self, bytes: Vec<u8>) {
+        self.value = Some(SenMLValueField::DataValue(bytes));
+    }
+
+    /// The data value encoded in base64, see [`encode_senml_data`].
+    pub fn get_data_base64(&self) -> Option<String> {
+        self.value.as_ref().and_then(|value| value.to_base64())
+    }
+
+    /// Look up a value in the extra fields with a JSON Pointer.
+    ///
+    /// The first reference token is the key of the extra field,
+    /// the others look up nested values.
+    /// <https://www.rfc-editor.org/rfc/rfc6901>
+    ///
+    /// # Examples
+    /// ```
+    /// use sindit_senml::parse_json;
+    ///
+    /// let records = parse_json(r#"[{"n": "position", "meta": {"source": "gps"}}]"#, None).unwrap();
+    /// assert_eq!(records[0].extra_pointer("/meta/source"), Some(&serde_json::json!("gps")));
+    /// ```
+    pub fn extra_pointer(&self, pointer: &str) -> Option<&serde_json::Value> {
+        let pointer = pointer.strip_prefix('/')?;
+        let (key, rest) = match pointer.find('/') {
+            Some(index) => pointer.split_at(index),
+            None => (pointer, ""),
+        };
+        let key = key.replace("~1", "/").replace("~0", "~");
+        self.extra_fields.as_ref()?.get(&key)?.pointer(rest)
+    }
+
+    /// The string extra field named `key`.
+    pub fn extra_get_str(&self, key: &str) -> Option<&str> {
+        self.extra_fields.as_ref()?.get(key)?.as_str()
+    }
+
+    /// The number extra field named `key`.
+    pub fn extra_get_f64(&self, key: &str) -> Option<f64> {
+        self.extra_fields.as_ref()?.get(key)?.as_f64()
+    }
+
+    /// The boolean extra field named `key`.
+    pub fn extra_get_bool(&self, key: &str) -> Option<bool> {
+        self.extra_fields.as_ref()?.get(key)?.as_bool()
+    }
+
+    /// Check that the value and the sum are meaningful.
+    ///
+    /// The string value must not be empty and the floating point value
+    /// and the sum must be finite.
+    /// The record is not part of a pack, so the index in the errors is 0.
+    pub fn validate_value(&self) -> Result<(), SinditSenMLError> {
+        if let Some(SenMLValueField::StringValue(ref value)) = self.value {
+            if value.is_empty() {
+                return Err(SinditSenMLError::EmptyStringValue(0));
+            }
+        }
+        if let Some(SenMLValueField::FloatingPoint(value)) = self.value {
+            if !value.is_finite() {
+                return Err(SinditSenMLError::NonFiniteValue(0));
+            }
+        }
+        if let Some(sum) = self.sum {
+            if !sum.is_finite() {
+                return Err(SinditSenMLError::NonFiniteValue(0));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Check that resolved records form a valid SenML Pack.
+///
+/// The names must pass [`validate_name()`], the update times must be
+/// positive, the records must have different names or times, and all
+/// the records must have the same version.
+///
+/// # Returns
+/// * `Result<(), Vec<SinditSenMLError>>` - All the errors, in the order
+///   of the records. `DifferentBaseVersion` is reported once.
+/// # Examples
+/// ```
+/// use sindit_senml::{parse_json, validate_pack};
+///
+/// let mut records = parse_json(r#"[{"n": "temperature", "v": 42.0, "t": 1320067464}]"#, None).unwrap();
+/// assert!(validate_pack(&records).is_ok());
+/// records.push(records[0].clone());
+/// assert_eq!(validate_pack(&records).unwrap_err().len(), 1);
+/// ```
+pub fn validate_pack(records: &[SenMLResolvedRecord]) -> Result<(), Vec<SinditSenMLError>> {
+    let mut errors = Vec::new();
+    let mut keys: std::collections::HashSet<(&str, DateTime<Utc>)> =
+        std::collections::HashSet::new();
+    let base_version = records.first().and_then(|record| record.base_version);
+    let mut different_base_version = false;
+
+    for (index, record) in records.iter().enumerate() {
+        if !validate_name(&record.name) {
+            errors.push(SinditSenMLError::InvalidNameInRecord(index));
+        }
+        if let Some(update_time) = record.update_time {
+            if update_time.is_nan() || update_time <= 0.0 {
+                errors.push(SinditSenMLError::InvalidUpdateTime(index));
+            }
+        }
+        if !keys.insert((record.name.as_str(), record.time)) {
+            errors.push(SinditSenMLError::DuplicateRecord(index));
+        }
+        if record.base_version != base_version && !different_base_version {
+            different_base_version = true;
+            errors.push(SinditSenMLError::DifferentBaseVersion);
+        }
+    }
+
+    match errors.is_empty() {
+        true => Ok(()),
+        false => Err(errors),
+    }
+}
+
+fn resolve_value(
+    record: &SenMLRecord,
+    base_value: &Option<f64>,
+    index: usize,
+    options: &ParseOptions,
+) -> Result<Option<SenMLValueField>, SinditSenMLError> {
+    match record.value {
+        Some(value) => {
+            if record.string_value.is_some()
+                || record.bool_value.is_some()
+                || record.data_value.is_some()
+            {
+                return Err(SinditSenMLError::OnlyOneValuePerRecord(index));
+            }
+            match base_value {
+                Some(base_value) => Ok(Some(SenMLValueField::FloatingPoint(base_value + value))),
+                None => Ok(Some(SenMLValueField::FloatingPoint(value))),
+            }
+        }
+        None => match record.string_value {
+            Some(ref value) => {
+                if record.bool_value.is_some() || record.data_value.is_some() {
+                    return Err(SinditSenMLError::OnlyOneValuePerRecord(index));
+                }
+                Ok(Some(SenMLValueField::StringValue(value.to_string())))
+            }
             None => match record.bool_value {
                 Some(ref value) => {
                     if record.data_value.is_some() {
@@ -343,14 +1363,12 @@ fn resolve_value(
                     Ok(Some(SenMLValueField::BooleanValue(*value)))
                 }
                 None => match record.data_value {
-                    Some(ref value) => {
-                        match base64::engine::general_purpose::URL_SAFE_NO_PAD.decode(value) {
-                            Ok(value) => Ok(Some(SenMLValueField::DataValue(value))),
-                            Err(base64_error) => {
-                                Err(SinditSenMLError::InvalidBase64Value(base64_error))
-                            }
+                    Some(ref value) => match options.base64_input.decode(value) {
+                        Ok(value) => Ok(Some(SenMLValueField::DataValue(value))),
+                        Err(base64_error) => {
+                            Err(SinditSenMLError::InvalidBase64Value(base64_error))
                         }
-                    }
+                    },
                     None => match base_value {
                         Some(base_value) => Ok(Some(SenMLValueField::FloatingPoint(*base_value))),
                         None => Ok(None),
@@ -365,150 +1383,213 @@ fn resolve_records(
     input_records: &Vec<SenMLRecord>,
     now: DateTime<Utc>,
 ) -> Result<Vec<SenMLResolvedRecord>, SinditSenMLError> {
-    let mut base_name: Option<String> = None;
-    let mut base_time: Option<f64> = None;
-    let mut base_unit: Option<String> = None;
-    let mut base_value: Option<f64> = None;
-    let mut base_sum: Option<f64> = None;
-    let mut base_version: Option<u64> = None;
+    resolve_records_with_options(input_records, &|| now, &ParseOptions::default())
+}
 
-    input_records
-        .iter()
-        .enumerate()
-        .map(|(index, record)| {
-            if let Some(ref record_base_name) = record.base_name {
-                base_name = Some(record_base_name.to_string());
-            }
+/// Base fields in effect while resolving the records of a pack.
+#[derive(Debug, Clone, Default)]
+struct BaseFields {
+    name: Option<String>,
+    time: Option<f64>,
+    unit: Option<String>,
+    value: Option<f64>,
+    sum: Option<f64>,
+    version: Option<u64>,
+}
 
-            if let Some(record_base_time) = record.base_time {
-                base_time = Some(record_base_time);
+impl BaseFields {
+    /// Update the base fields with the ones of the record, then resolve it.
+    fn resolve(
+        &mut self,
+        record: &SenMLRecord,
+        index: usize,
+        now: &dyn Fn() -> DateTime<Utc>,
+        options: &ParseOptions,
+    ) -> Result<ResolvedRecordWithProvenance, SinditSenMLError> {
+        if let Some(max_records) = options.max_records {
+            if index >= max_records {
+                return Err(SinditSenMLError::TooManyRecords(max_records));
             }
+        }
 
-            if let Some(ref record_base_unit) = record.base_unit {
-                base_unit = Some(record_base_unit.to_string());
-            }
+        if let Some(ref record_base_name) = record.base_name {
+            self.name = Some(record_base_name.to_string());
+        }
 
-            if let Some(record_base_value) = record.base_value {
-                base_value = Some(record_base_value);
-            }
+        if let Some(record_base_time) = record.base_time {
+            self.time = Some(record_base_time);
+        }
 
-            if let Some(record_base_sum) = record.base_sum {
-                base_sum = Some(record_base_sum);
-            }
+        if let Some(ref record_base_unit) = record.base_unit {
+            self.unit = Some(record_base_unit.to_string());
+        }
 
-            match record.base_version {
-                Some(record_base_version) => match base_version {
-                    Some(base_version) => {
-                        if base_version != record_base_version {
-                            return Err(SinditSenMLError::DifferentBaseVersion);
-                        }
-                    }
-                    None => {
-                        if record_base_version == 0 {
-                            return Err(SinditSenMLError::InvalidVersionNumber);
-                        }
-                        base_version = Some(record_base_version);
+        if let Some(record_base_value) = record.base_value {
+            self.value = Some(record_base_value);
+        }
+
+        if let Some(record_base_sum) = record.base_sum {
+            self.sum = Some(record_base_sum);
+        }
+
+        match record.base_version {
+            Some(record_base_version) => match self.version {
+                Some(base_version) => {
+                    if base_version != record_base_version {
+                        return Err(SinditSenMLError::DifferentBaseVersion);
                     }
-                },
+                }
                 None => {
-                    // We default to 10 if no base version is present.
-                    // This is the default in the RFC.
-                    if base_version.is_none() {
-                        base_version = Some(10);
+                    if record_base_version == 0 {
+                        return Err(SinditSenMLError::InvalidVersionNumber);
                     }
+                    if record_base_version != 10 && !options.allow_custom_versions {
+                        return Err(SinditSenMLError::UnsupportedVersion(record_base_version));
+                    }
+                    self.version = Some(record_base_version);
                 }
-            };
-
-            let name = match record.name {
-                Some(ref name) => match base_name {
-                    Some(ref base_name) => base_name.to_string() + name,
-                    None => name.to_string(),
-                },
-                None => match base_name {
-                    Some(ref base_name) => base_name.to_string(),
-                    None => return Err(SinditSenMLError::MissingName(index)),
-                },
-            };
+            },
+            None => {
+                // We default to 10 if no base version is present.
+                // This is the default in the RFC.
+                if self.version.is_none() {
+                    self.version = Some(10);
+                }
+            }
+        };
 
-            if !validate_name(&name) {
-                return Err(SinditSenMLError::InvalidNameInRecord(index));
+        if options.strict_extra_fields {
+            if let Some(ref extra_fields) = record.extra_fields {
+                // The first field in alphabetical order, for reproducible errors.
+                if let Some(field) = extra_fields.keys().min() {
+                    return Err(SinditSenMLError::UnexpectedField {
+                        index,
+                        field: field.clone(),
+                    });
+                }
             }
+        }
 
-            let unit: Option<String> = match record.unit {
-                Some(ref unit) => Some(unit.to_string()),
-                None => base_unit.clone(),
-            };
+        let name = match record.name {
+            Some(ref name) => match self.name {
+                Some(ref base_name) => base_name.to_string() + name,
+                None => name.to_string(),
+            },
+            None => match self.name {
+                Some(ref base_name) => base_name.to_string(),
+                None => return Err(SinditSenMLError::MissingName(index)),
+            },
+        };
 
-            let mut value = resolve_value(record, &base_value, index)?;
+        let name = options.name_case.apply(name);
 
-            let time = match record.time {
-                Some(time) => match base_time {
-                    Some(base_time) => base_time + time,
-                    None => time,
-                },
-                None => match base_time {
-                    Some(base_time) => base_time,
-                    None => 0.0,
-                },
-            };
-            let datetime = match time::convert_senml_time(time, now) {
-                Some(datetime) => datetime,
-                None => return Err(SinditSenMLError::InvalidTimeInRecord(index)),
-            };
+        if options.strict_names && !options.name_validation.validate(&name) {
+            return Err(SinditSenMLError::InvalidNameInRecord(index));
+        }
 
-            let sum = match record.sum {
-                Some(sum) => match base_sum {
-                    Some(base_sum) => Some(base_sum + sum),
-                    None => Some(sum),
-                },
-                None => match base_sum {
-                    Some(base_sum) => Some(base_sum),
-                    None => None,
-                },
-            };
+        let unit: Option<String> = match record.unit {
+            Some(ref unit) => Some(unit.to_string()),
+            None => self.unit.clone(),
+        };
 
-            if value.is_none() && sum.is_none() {
-                // return Err(SinditSenMLError::MissingValueOrSum(index));
-                // My understanding of the RFC:
-                // A sum or a value must be present and never at the same time.
-                // Both defaults to 0, but if no base sum or sum are present,
-                // then it has to be a value because it is accepted to not have
-                // a sum value in the RFC.
-                // the default value is 0.
-                value = Some(SenMLValueField::FloatingPoint(0.0));
+        if options.strict_units {
+            if let Some(ref unit) = unit {
+                if !validate_unit::validate_unit(unit) {
+                    return Err(SinditSenMLError::UnknownUnit(index));
+                }
             }
+        }
 
-            // Version 10 is the default in SenML.
-            // However the RFC says:
-            //   The Base Version field MUST NOT be present in resolved Records if the
-            //   SenML version defined in this document is used; otherwise, it MUST be
-            //   present in all the resolved SenML Records.
-            //
-            // We interpret this as it must be skipped.
-            // let record_base_version = base_version.unwrap_or(10); //
-            let record_base_version = match base_version {
-                Some(base_version) => match base_version {
-                    10 => None,
-                    _ => Some(base_version),
-                },
-                None => None,
-            };
+        let mut value = resolve_value(record, &self.value, index, options)?;
 
-            let update_time = record.update_time;
+        if options.reject_empty_string_values {
+            if let Some(SenMLValueField::StringValue(ref value)) = value {
+                if value.is_empty() {
+                    return Err(SinditSenMLError::EmptyStringValue(index));
+                }
+            }
+        }
 
-            // skip extra_fields if the record has empty hashmap or None
-            let extra_fields = match &record.extra_fields {
-                Some(extra_fields) => {
-                    if extra_fields.is_empty() {
-                        None
-                    } else {
-                        Some(extra_fields.clone())
-                    }
+        let datetime = match (record.time, self.time, options.default_time) {
+            // RFC 3339 times are absolute, the base time does not apply.
+            (Some(SenMLTime::Absolute(datetime)), _, _) => datetime,
+            (None, None, Some(default_time)) => default_time,
+            (record_time, base_time, _) => {
+                let time = match (record_time, base_time) {
+                    (Some(SenMLTime::Seconds(time)), Some(base_time)) => base_time + time,
+                    (Some(SenMLTime::Seconds(time)), None) => time,
+                    (_, base_time) => base_time.unwrap_or(0.0),
+                };
+                match time::convert_senml_time_with(time, now) {
+                    Some(datetime) => datetime,
+                    None => return Err(SinditSenMLError::InvalidTimeInRecord(index)),
                 }
+            }
+        };
+        if options.reject_pre_epoch && datetime.timestamp() < 0 {
+            return Err(SinditSenMLError::PreEpochTimestamp(index));
+        }
+
+        let sum = match record.sum {
+            Some(sum) => match self.sum {
+                Some(base_sum) => Some(base_sum + sum),
+                None => Some(sum),
+            },
+            None => match self.sum {
+                Some(base_sum) => Some(base_sum),
                 None => None,
-            };
+            },
+        };
+
+        if value.is_none() && sum.is_none() {
+            // return Err(SinditSenMLError::MissingValueOrSum(index));
+            // My understanding of the RFC:
+            // A sum or a value must be present and never at the same time.
+            // Both defaults to 0, but if no base sum or sum are present,
+            // then it has to be a value because it is accepted to not have
+            // a sum value in the RFC.
+            // the default value is 0.
+            value = Some(SenMLValueField::FloatingPoint(0.0));
+        }
+
+        // Version 10 is the default in SenML.
+        // However the RFC says:
+        //   The Base Version field MUST NOT be present in resolved Records if the
+        //   SenML version defined in this document is used; otherwise, it MUST be
+        //   present in all the resolved SenML Records.
+        //
+        // We interpret this as it must be skipped.
+        // let record_base_version = base_version.unwrap_or(10); //
+        let record_base_version = match self.version {
+            Some(base_version) => match base_version {
+                10 => None,
+                _ => Some(base_version),
+            },
+            None => None,
+        };
+
+        let update_time = record.update_time;
 
-            Ok(SenMLResolvedRecord {
+        // skip extra_fields if the record has empty hashmap or None
+        let extra_fields = match &record.extra_fields {
+            Some(extra_fields) => {
+                if extra_fields.is_empty() {
+                    None
+                } else {
+                    Some(extra_fields.clone())
+                }
+            }
+            None => None,
+        };
+
+        // The base value is only used by floating point values.
+        let value_from_base = self.value.is_some()
+            && record.string_value.is_none()
+            && record.bool_value.is_none()
+            && record.data_value.is_none();
+
+        Ok(ResolvedRecordWithProvenance {
+            record: SenMLResolvedRecord {
                 name,
                 unit,
                 value,
@@ -517,11 +1598,92 @@ fn resolve_records(
                 update_time,
                 base_version: record_base_version,
                 extra_fields,
-            })
+                active_base_name: match options.track_base_name {
+                    true => self.name.clone(),
+                    false => None,
+                },
+            },
+            name_from_base: self.name.is_some(),
+            unit_from_base: record.unit.is_none() && self.unit.is_some(),
+            time_from_base: self.time.is_some(),
+            value_from_base,
+            sum_from_base: self.sum.is_some(),
         })
+    }
+}
+
+fn resolve_records_with_options(
+    input_records: &[SenMLRecord],
+    now: &dyn Fn() -> DateTime<Utc>,
+    options: &ParseOptions,
+) -> Result<Vec<SenMLResolvedRecord>, SinditSenMLError> {
+    let mut base_fields = BaseFields::default();
+    let mut resolved_records = Vec::with_capacity(input_records.len());
+    for (index, record) in input_records.iter().enumerate() {
+        resolved_records.push(base_fields.resolve(record, index, now, options)?.record);
+    }
+    Ok(resolved_records)
+}
+
+/// A resolved record and which of its fields were resolved using base fields.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ResolvedRecordWithProvenance {
+    /// The resolved record.
+    pub record: SenMLResolvedRecord,
+    /// The base name was prepended to the name, or used as the name.
+    pub name_from_base: bool,
+    /// The base unit was used as the unit.
+    pub unit_from_base: bool,
+    /// The base time was added to the time, or used as the time.
+    pub time_from_base: bool,
+    /// The base value was added to the value, or used as the value.
+    pub value_from_base: bool,
+    /// The base sum was added to the sum, or used as the sum.
+    pub sum_from_base: bool,
+}
+
+/// Resolve SenML records and tell which fields were resolved using base fields.
+///
+/// # Arguments
+/// * `input_records` - The SenML records, as deserialised from a SenML Pack.
+/// * `now` - The current time.
+/// # Returns
+/// * `Result<Vec<ResolvedRecordWithProvenance>, SinditSenMLError>` - The resolved records.
+/// # Examples
+/// ```
+/// use sindit_senml::{resolve_records_with_provenance, SenMLRecord};
+///
+/// let records: Vec<SenMLRecord> =
+///     serde_json::from_str(r#"[{"bn": "sensor:", "bu": "Cel", "n": "temp", "v": 20.0}]"#).unwrap();
+/// let resolved = resolve_records_with_provenance(&records, chrono::Utc::now()).unwrap();
+/// assert!(resolved[0].name_from_base);
+/// assert!(resolved[0].unit_from_base);
+/// assert!(!resolved[0].time_from_base);
+/// ```
+pub fn resolve_records_with_provenance(
+    input_records: &[SenMLRecord],
+    now: DateTime<Utc>,
+) -> Result<Vec<ResolvedRecordWithProvenance>, SinditSenMLError> {
+    let options = ParseOptions::default();
+    let mut base_fields = BaseFields::default();
+
+    input_records
+        .iter()
+        .enumerate()
+        .map(|(index, record)| base_fields.resolve(record, index, &|| now, &options))
         .collect()
 }
 
+/// Resolve SenML records, getting the current time from a function.
+///
+/// See [`parse_json_with_now_fn`].
+pub fn resolve_records_with_now_fn(
+    input_records: &[SenMLRecord],
+    now_fn: &dyn Fn() -> DateTime<Utc>,
+) -> Result<Vec<SenMLResolvedRecord>, SinditSenMLError> {
+    resolve_records_with_options(input_records, now_fn, &ParseOptions::default())
+}
+
 /// Parse SenML JSON and return SenMLResolvedRecords.
 ///
 /// # Arguments
@@ -551,12 +1713,447 @@ pub fn parse_json(
     resolve_records(&records, now.unwrap_or(Utc::now()))
 }
 
-#[cfg(test)]
-mod tests {
-
-    use crate::*;
-
-    static EMPTY_RECORD: SenMLRecord = SenMLRecord {
+/// Parse SenML JSON from a reader and return SenMLResolvedRecords.
+///
+/// Same as [`parse_json`], without reading the JSON into a string first.
+/// The reader is not buffered, wrap it in a [`std::io::BufReader`] when
+/// reading from a file or a socket.
+///
+/// # Arguments
+/// * `reader` - The reader of the SenML JSON.
+/// * `now` - The current time. Defaults to current UTC time.
+/// # Returns
+/// * `Result<Vec<SenMLResolvedRecord>, SinditSenMLError>` - The parsed SenML records.
+///   The read errors are `InvalidJSON` errors.
+/// # Examples
+/// ```
+/// use sindit_senml::parse_json_reader;
+///
+/// let json = br#"[{"n": "temperature", "v": 42.0}]"#;
+/// let records = parse_json_reader(&json[..], None).unwrap();
+/// assert_eq!(records[0].name, "temperature");
+/// ```
+pub fn parse_json_reader<R: std::io::Read>(
+    reader: R,
+    now: Option<DateTime<Utc>>,
+) -> Result<Vec<SenMLResolvedRecord>, SinditSenMLError> {
+    let records: Vec<SenMLRecord> = match serde_json::from_reader(reader) {
+        Ok(records) => records,
+        Err(error) => return Err(SinditSenMLError::InvalidJSON(error)),
+    };
+
+    resolve_records(&records, now.unwrap_or(Utc::now()))
+}
+
+/// Parse SenML JSON from an asynchronous reader and return SenMLResolvedRecords.
+///
+/// The JSON is read fully before being parsed, like [`parse_json`].
+///
+/// This is available with the `tokio` feature.
+///
+/// # Arguments
+/// * `reader` - The asynchronous reader of the SenML JSON.
+/// * `now` - The current time. Defaults to current UTC time.
+/// # Returns
+/// * `Result<Vec<SenMLResolvedRecord>, SinditSenMLError>` - The parsed SenML records,
+///   `Io` if the reading fails.
+/// # Examples
+/// ```
+/// # tokio_test();
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn tokio_test() {
+/// use sindit_senml::parse_json_async;
+///
+/// let json = br#"[{"n": "temperature", "v": 42.0}]"#;
+/// let records = parse_json_async(&json[..], None).await.unwrap();
+/// assert_eq!(records[0].name, "temperature");
+/// # }
+/// ```
+#[cfg(feature = "tokio")]
+pub async fn parse_json_async<R: tokio::io::AsyncRead + Unpin>(
+    mut reader: R,
+    now: Option<DateTime<Utc>>,
+) -> Result<Vec<SenMLResolvedRecord>, SinditSenMLError> {
+    use tokio::io::AsyncReadExt;
+
+    let mut json = Vec::new();
+    reader.read_to_end(&mut json).await?;
+    let records: Vec<SenMLRecord> = serde_json::from_slice(&json)?;
+
+    resolve_records(&records, now.unwrap_or(Utc::now()))
+}
+
+/// Parse SenML JSON in an asynchronous context and return SenMLResolvedRecords.
+///
+/// Same as [`parse_json`], which does not do any I/O.
+///
+/// This is available with the `tokio` feature.
+#[cfg(feature = "tokio")]
+pub async fn parse_json_str_async(
+    json_str: &str,
+    now: Option<DateTime<Utc>>,
+) -> Result<Vec<SenMLResolvedRecord>, SinditSenMLError> {
+    parse_json(json_str, now)
+}
+
+/// Parse SenML JSON with options and return SenMLResolvedRecords.
+///
+/// # Arguments
+/// * `json_str` - The SenML JSON string to parse.
+/// * `now` - The current time. Defaults to current UTC time.
+/// * `options` - The parsing options.
+/// # Returns
+/// * `Result<Vec<SenMLResolvedRecord>, SinditSenMLError>` - The parsed SenML records.
+/// # Examples
+/// ```
+/// use sindit_senml::{parse_json_with_options, Base64AcceptPolicy, ParseOptions};
+///
+/// let options = ParseOptions {
+///     base64_input: Base64AcceptPolicy::AcceptBoth,
+///     ..Default::default()
+/// };
+/// let json_str = r#"[{"n": "nfc-reader", "vd": "SGVsbG8gd29ybGQ="}]"#;
+/// let records = parse_json_with_options(json_str, None, &options).unwrap();
+/// assert_eq!(records[0].get_data_value().unwrap(), b"Hello world");
+/// ```
+///
+pub fn parse_json_with_options(
+    json_str: &str,
+    now: Option<DateTime<Utc>>,
+    options: &ParseOptions,
+) -> Result<Vec<SenMLResolvedRecord>, SinditSenMLError> {
+    let records: Vec<SenMLRecord> = match serde_json::from_str(json_str) {
+        Ok(records) => records,
+        Err(error) => return Err(SinditSenMLError::InvalidJSON(error)),
+    };
+
+    let now = now.unwrap_or(Utc::now());
+    resolve_records_with_options(&records, &|| now, options)
+}
+
+/// Count the records of a SenML JSON string without parsing them.
+///
+/// The elements of the top level JSON array are counted by scanning the
+/// string for commas outside of the records. The records themselves are
+/// not validated.
+///
+/// # Examples
+/// ```
+/// use sindit_senml::count_records_json;
+///
+/// let json_str = r#"[{"n": "a,b", "v": 1}, {"n": "c", "v": 2, "x": [1, 2]}]"#;
+/// assert_eq!(count_records_json(json_str).unwrap(), 2);
+/// ```
+pub fn count_records_json(json_str: &str) -> Result<usize, SinditSenMLError> {
+    fn invalid(message: &str) -> SinditSenMLError {
+        SinditSenMLError::InvalidJSON(serde::de::Error::custom(message))
+    }
+
+    let json_str = json_str.trim();
+    if !json_str.starts_with('[') {
+        return Err(invalid("expected a JSON array"));
+    }
+
+    let mut depth = 0usize;
+    let mut in_string = false;
+    let mut escaped = false;
+    // An element is expected after the opening bracket and after each comma.
+    let mut expect_element = true;
+    let mut count = 0;
+    for (index, byte) in json_str.bytes().enumerate() {
+        if in_string {
+            match byte {
+                _ if escaped => escaped = false,
+                b'\\' => escaped = true,
+                b'"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        if depth == 1
+            && expect_element
+            && !matches!(byte, b',' | b']' | b'}')
+            && !byte.is_ascii_whitespace()
+        {
+            count += 1;
+            expect_element = false;
+        }
+        match byte {
+            b'"' => in_string = true,
+            b'[' | b'{' => depth += 1,
+            b']' | b'}' => {
+                if depth == 1 && expect_element && count > 0 {
+                    return Err(invalid("trailing comma in the JSON array"));
+                }
+                depth = depth
+                    .checked_sub(1)
+                    .ok_or_else(|| invalid("unbalanced JSON array"))?;
+                if depth == 0 {
+                    if index != json_str.len() - 1 {
+                        return Err(invalid("trailing characters after the JSON array"));
+                    }
+                    return Ok(count);
+                }
+            }
+            b',' if depth == 1 => {
+                if expect_element {
+                    return Err(invalid("missing element in the JSON array"));
+                }
+                expect_element = true;
+            }
+            _ => {}
+        }
+    }
+    Err(invalid("unterminated JSON array"))
+}
+
+/// Deserialise the records of a SenML JSON pack into a vector of a given
+/// capacity.
+struct PreallocatedRecords(usize);
+
+impl<'de> serde::de::DeserializeSeed<'de> for PreallocatedRecords {
+    type Value = Vec<SenMLRecord>;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de> serde::de::Visitor<'de> for PreallocatedRecords {
+    type Value = Vec<SenMLRecord>;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("an array of SenML records")
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+    where
+        A: serde::de::SeqAccess<'de>,
+    {
+        let mut records = Vec::with_capacity(self.0);
+        while let Some(record) = seq.next_element()? {
+            records.push(record);
+        }
+        Ok(records)
+    }
+}
+
+/// Parse SenML JSON into vectors allocated once.
+///
+/// The records are counted with [`count_records_json`] before parsing,
+/// to avoid the reallocations of the vector of records while
+/// deserialising large packs.
+///
+/// # Arguments
+/// * `json_str` - The SenML JSON string to parse.
+/// * `now` - The current time. Defaults to current UTC time.
+/// # Returns
+/// * `Result<Vec<SenMLResolvedRecord>, SinditSenMLError>` - The parsed SenML records.
+pub fn parse_json_preallocated(
+    json_str: &str,
+    now: Option<DateTime<Utc>>,
+) -> Result<Vec<SenMLResolvedRecord>, SinditSenMLError> {
+    let count = count_records_json(json_str)?;
+    let mut deserializer = serde_json::Deserializer::from_str(json_str);
+    let records =
+        serde::de::DeserializeSeed::deserialize(PreallocatedRecords(count), &mut deserializer)?;
+    deserializer.end()?;
+
+    let now = now.unwrap_or(Utc::now());
+    resolve_records_with_options(&records, &|| now, &ParseOptions::default())
+}
+
+/// Parse SenML JSON and track the base name of each record.
+///
+/// A pack can change its base name between records.
+/// The base name that was active when a record was resolved is
+/// set in [`SenMLResolvedRecord::active_base_name`].
+///
+/// # Arguments
+/// * `json_str` - The SenML JSON string to parse.
+/// * `now` - The current time. Defaults to current UTC time.
+/// # Returns
+/// * `Result<Vec<SenMLResolvedRecord>, SinditSenMLError>` - The parsed SenML records.
+/// # Examples
+/// ```
+/// use sindit_senml::parse_json_with_base_tracking;
+///
+/// let json_str = r#"[{"bn": "urn:dev:ow:10e2073a01080063:", "n": "temp", "v": 23.1}, {"bn": "room1/", "n": "temp", "v": 21.0}]"#;
+/// let records = parse_json_with_base_tracking(json_str, None).unwrap();
+/// assert_eq!(records[0].active_base_name.as_deref(), Some("urn:dev:ow:10e2073a01080063:"));
+/// assert_eq!(records[1].active_base_name.as_deref(), Some("room1/"));
+/// ```
+pub fn parse_json_with_base_tracking(
+    json_str: &str,
+    now: Option<DateTime<Utc>>,
+) -> Result<Vec<SenMLResolvedRecord>, SinditSenMLError> {
+    let options = ParseOptions {
+        track_base_name: true,
+        ..Default::default()
+    };
+    parse_json_with_options(json_str, now, &options)
+}
+
+/// Parse SenML JSON, getting the current time from a function.
+///
+/// `now_fn` is called for each record with a relative time, so
+/// long-running processing does not use an outdated current time.
+/// It is not called for the records with an absolute time.
+///
+/// # Arguments
+/// * `json_str` - The SenML JSON string to parse.
+/// * `now_fn` - The function returning the current time.
+/// # Returns
+/// * `Result<Vec<SenMLResolvedRecord>, SinditSenMLError>` - The parsed SenML records.
+/// # Examples
+/// ```
+/// use sindit_senml::parse_json_with_now_fn;
+///
+/// let json_str = r#"[{"n": "temperature", "v": 42.0, "t": -10}]"#;
+/// let records = parse_json_with_now_fn(json_str, chrono::Utc::now).unwrap();
+/// assert_eq!(records.len(), 1);
+/// ```
+pub fn parse_json_with_now_fn(
+    json_str: &str,
+    now_fn: impl Fn() -> DateTime<Utc>,
+) -> Result<Vec<SenMLResolvedRecord>, SinditSenMLError> {
+    let records: Vec<SenMLRecord> = serde_json::from_str(json_str)?;
+    resolve_records_with_now_fn(&records, &now_fn)
+}
+
+/// Parse SenML JSON and return the records resolved before the first error.
+///
+/// Invalid JSON is not recovered: no records are returned in that case.
+///
+/// # Arguments
+/// * `json_str` - The SenML JSON string to parse.
+/// * `now` - The current time. Defaults to current UTC time.
+/// # Returns
+/// * `(Vec<SenMLResolvedRecord>, Option<SinditSenMLError>)` - The records
+///   resolved before the first error, and the error if any.
+/// # Examples
+/// ```
+/// use sindit_senml::parse_json_recover;
+///
+/// let json_str = r#"[{"n": "temperature", "v": 42.0},{"v": 43.0}]"#;
+/// let (records, error) = parse_json_recover(json_str, None);
+/// assert_eq!(records.len(), 1);
+/// assert!(error.is_some());
+/// ```
+///
+pub fn parse_json_recover(
+    json_str: &str,
+    now: Option<DateTime<Utc>>,
+) -> (Vec<SenMLResolvedRecord>, Option<SinditSenMLError>) {
+    let records: Vec<SenMLRecord> = match serde_json::from_str(json_str) {
+        Ok(records) => records,
+        Err(error) => return (Vec::new(), Some(SinditSenMLError::InvalidJSON(error))),
+    };
+
+    let now = now.unwrap_or(Utc::now());
+    let options = ParseOptions::default();
+    let mut base_fields = BaseFields::default();
+    let mut resolved_records = Vec::with_capacity(records.len());
+    for (index, record) in records.iter().enumerate() {
+        match base_fields.resolve(record, index, &|| now, &options) {
+            Ok(resolved) => resolved_records.push(resolved.record),
+            Err(error) => return (resolved_records, Some(error)),
+        }
+    }
+    (resolved_records, None)
+}
+
+/// Parse SenML JSON and return all the records that could be resolved.
+///
+/// The invalid records are skipped, and the base fields they contain are
+/// ignored: the next records use the base fields of the last valid record.
+/// Invalid JSON is not recovered: no records are returned in that case.
+///
+/// # Arguments
+/// * `json_str` - The SenML JSON string to parse.
+/// * `now` - The current time. Defaults to current UTC time.
+/// # Returns
+/// * `(Vec<SenMLResolvedRecord>, Vec<SinditSenMLError>)` - The valid records
+///   and the errors of the invalid ones.
+pub fn parse_json_recover_all(
+    json_str: &str,
+    now: Option<DateTime<Utc>>,
+) -> (Vec<SenMLResolvedRecord>, Vec<SinditSenMLError>) {
+    let records: Vec<SenMLRecord> = match serde_json::from_str(json_str) {
+        Ok(records) => records,
+        Err(error) => return (Vec::new(), vec![SinditSenMLError::InvalidJSON(error)]),
+    };
+
+    let now = now.unwrap_or(Utc::now());
+    let options = ParseOptions::default();
+    let mut base_fields = BaseFields::default();
+    let mut resolved_records = Vec::with_capacity(records.len());
+    let mut errors = Vec::new();
+    for (index, record) in records.iter().enumerate() {
+        let last_valid_base_fields = base_fields.clone();
+        match base_fields.resolve(record, index, &|| now, &options) {
+            Ok(resolved) => resolved_records.push(resolved.record),
+            Err(error) => {
+                errors.push(error);
+                base_fields = last_valid_base_fields;
+            }
+        }
+    }
+    (resolved_records, errors)
+}
+
+/// Serialize SenMLResolvedRecords to SenML JSON with options.
+///
+/// # Arguments
+/// * `records` - The records to serialize.
+/// * `options` - The serialization options.
+/// # Returns
+/// * `Result<String, SinditSenMLError>` - The SenML JSON string.
+/// # Examples
+/// ```
+/// use sindit_senml::{parse_json, to_json_with_options, Base64Variant, SerializationOptions};
+///
+/// let records = parse_json(r#"[{"n": "nfc-reader", "vd": "SGVsbG8gd29ybGQ", "t": 1320067464}]"#, None).unwrap();
+/// let options = SerializationOptions {
+///     base64_output: Base64Variant::Standard,
+/// };
+/// assert_eq!(
+///     to_json_with_options(&records, &options).unwrap(),
+///     r#"[{"n":"nfc-reader","vd":"SGVsbG8gd29ybGQ=","t":1320067464}]"#
+/// );
+/// ```
+///
+pub fn to_json_with_options(
+    records: &[SenMLResolvedRecord],
+    options: &SerializationOptions,
+) -> Result<String, SinditSenMLError> {
+    struct RecordWithOptions<'a>(&'a SenMLResolvedRecord, &'a SerializationOptions);
+
+    impl serde::ser::Serialize for RecordWithOptions<'_> {
+        fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            serialize_record(self.0, self.1, false, serializer)
+        }
+    }
+
+    let records: Vec<RecordWithOptions> = records
+        .iter()
+        .map(|record| RecordWithOptions(record, options))
+        .collect();
+    Ok(serde_json::to_string(&records)?)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::*;
+
+    static EMPTY_RECORD: SenMLRecord = SenMLRecord {
         base_name: None,
         base_time: None,
         base_unit: None,
@@ -579,13 +2176,19 @@ mod tests {
     fn test_resolve_value_simple() {
         // None value
 
-        assert!(resolve_value(&EMPTY_RECORD, &None, 0).unwrap().is_none());
+        assert!(
+            resolve_value(&EMPTY_RECORD, &None, 0, &ParseOptions::default())
+                .unwrap()
+                .is_none()
+        );
 
         // Floating point value
         let mut record = EMPTY_RECORD.clone();
         record.value = Some(42.0);
         assert_eq!(
-            resolve_value(&record, &None, 0,).unwrap().unwrap(),
+            resolve_value(&record, &None, 0, &ParseOptions::default())
+                .unwrap()
+                .unwrap(),
             SenMLValueField::FloatingPoint(42.0)
         );
 
@@ -593,7 +2196,9 @@ mod tests {
         let mut record = EMPTY_RECORD.clone();
         record.string_value = Some("Hello world!".to_string());
         assert_eq!(
-            resolve_value(&record, &None, 0,).unwrap().unwrap(),
+            resolve_value(&record, &None, 0, &ParseOptions::default())
+                .unwrap()
+                .unwrap(),
             SenMLValueField::StringValue("Hello world!".to_string())
         );
 
@@ -601,7 +2206,9 @@ mod tests {
         let mut record = EMPTY_RECORD.clone();
         record.bool_value = Some(true);
         assert_eq!(
-            resolve_value(&record, &None, 0,).unwrap().unwrap(),
+            resolve_value(&record, &None, 0, &ParseOptions::default())
+                .unwrap()
+                .unwrap(),
             SenMLValueField::BooleanValue(true)
         );
 
@@ -609,7 +2216,9 @@ mod tests {
         let mut record = EMPTY_RECORD.clone();
         record.bool_value = Some(false);
         assert_eq!(
-            resolve_value(&record, &None, 0,).unwrap().unwrap(),
+            resolve_value(&record, &None, 0, &ParseOptions::default())
+                .unwrap()
+                .unwrap(),
             SenMLValueField::BooleanValue(false)
         );
 
@@ -617,7 +2226,9 @@ mod tests {
         let mut record = EMPTY_RECORD.clone();
         record.data_value = Some("SGVsbG8gd29ybGQh".to_string());
         assert_eq!(
-            resolve_value(&record, &None, 0,).unwrap().unwrap(),
+            resolve_value(&record, &None, 0, &ParseOptions::default())
+                .unwrap()
+                .unwrap(),
             SenMLValueField::DataValue("Hello world!".as_bytes().to_vec())
         );
     }
@@ -627,13 +2238,16 @@ mod tests {
         // None value
         let mut record = EMPTY_RECORD.clone();
         record.base_value = Some(10.0); // Ignored
-        assert!(resolve_value(&record, &None, 0,).unwrap().is_none());
+        assert!(resolve_value(&record, &None, 0, &ParseOptions::default())
+            .unwrap()
+            .is_none());
 
         assert_eq!(
             resolve_value(
                 &record,
                 &Some(10.0), // This is the one that matters
                 0,
+                &ParseOptions::default(),
             )
             .unwrap()
             .unwrap(),
@@ -649,6 +2263,7 @@ mod tests {
                 &record,
                 &Some(32.0), // This is the one that matters
                 0,
+                &ParseOptions::default(),
             )
             .unwrap()
             .unwrap(),
@@ -664,36 +2279,36 @@ mod tests {
         let mut record = EMPTY_RECORD.clone();
         record.value = Some(42.0);
         record.string_value = Some("Hello world!".to_string());
-        assert!(resolve_value(&record, &None, 0).is_err());
+        assert!(resolve_value(&record, &None, 0, &ParseOptions::default()).is_err());
 
         // float and bool
         let mut record = EMPTY_RECORD.clone();
         record.value = Some(42.0);
         record.bool_value = Some(true);
-        assert!(resolve_value(&record, &None, 0,).is_err());
+        assert!(resolve_value(&record, &None, 0, &ParseOptions::default()).is_err());
 
         // string and bool
         let mut record = EMPTY_RECORD.clone();
         record.string_value = Some("Hello world!".to_string());
         record.bool_value = Some(true);
-        assert!(resolve_value(&record, &None, 0,).is_err());
+        assert!(resolve_value(&record, &None, 0, &ParseOptions::default()).is_err());
 
         // float and base64
         let mut record = EMPTY_RECORD.clone();
         record.value = Some(42.0);
         record.data_value = Some("SGVsbG8gd29ybGQh".to_string());
-        assert!(resolve_value(&record, &None, 0,).is_err());
+        assert!(resolve_value(&record, &None, 0, &ParseOptions::default()).is_err());
 
         // bool and base64
         let mut record = EMPTY_RECORD.clone();
         record.bool_value = Some(true);
         record.data_value = Some("SGVsbG8gd29ybGQh".to_string());
-        assert!(resolve_value(&record, &None, 0,).is_err());
+        assert!(resolve_value(&record, &None, 0, &ParseOptions::default()).is_err());
 
         // Invalid base64
         let mut record = EMPTY_RECORD.clone();
         record.data_value = Some("    ".to_string());
-        assert!(resolve_value(&record, &None, 0,).is_err());
+        assert!(resolve_value(&record, &None, 0, &ParseOptions::default()).is_err());
     }
 
     mod test_resolve_records {
@@ -813,13 +2428,13 @@ mod tests {
         #[test]
         fn test_basetime() {
             let mut first_record = EMPTY_RECORD.clone();
-            first_record.time = Some(1111111111.1);
+            first_record.time = Some(SenMLTime::Seconds(1111111111.1));
             first_record.name = Some("efgh".to_string());
             first_record.value = Some(10.0);
             let mut second_record = BASE_RECORD.clone();
             second_record.base_time = Some(2222222222.2);
             let mut third_record = EMPTY_RECORD.clone();
-            third_record.time = Some(3333333333.3);
+            third_record.time = Some(SenMLTime::Seconds(3333333333.3));
             let data = vec![first_record, second_record, third_record];
             let resolved_data = resolve_records(&data, *NOW).unwrap();
             assert_eq!(resolved_data[0].time.timestamp(), 1111111111);
@@ -832,7 +2447,7 @@ mod tests {
             let mut first_record = BASE_RECORD.clone();
             first_record.base_time = None;
             let mut second_record = EMPTY_RECORD.clone();
-            second_record.time = Some(12.0);
+            second_record.time = Some(SenMLTime::Seconds(12.0));
             let data = vec![first_record, second_record];
             let resolved_data = resolve_records(&data, *NOW).unwrap();
             let now_in_12_seconds = NOW.add(chrono::Duration::seconds(12)).timestamp();
@@ -844,7 +2459,7 @@ mod tests {
         fn test_invalid_time() {
             let mut first_record = EMPTY_RECORD.clone();
             // NaN time ?
-            first_record.time = Some(0.0 / 0.0);
+            first_record.time = Some(SenMLTime::Seconds(0.0 / 0.0));
             first_record.name = Some("efgh".to_string());
             first_record.value = Some(10.0);
             let data = vec![first_record];
@@ -872,215 +2487,1434 @@ mod tests {
         }
 
         #[test]
-        fn test_missing_value_or_sum() {
-            let mut record = EMPTY_RECORD.clone();
-            record.name = Some("efgh".to_string());
-            let data = vec![record];
-            let resolved_data = resolve_records(&data, *NOW);
+        fn test_missing_value_or_sum() {
+            let mut record = EMPTY_RECORD.clone();
+            record.name = Some("efgh".to_string());
+            let data = vec![record];
+            let resolved_data = resolve_records(&data, *NOW);
+            assert_eq!(
+                resolved_data.unwrap()[0].value,
+                Some(SenMLValueField::FloatingPoint(0.0))
+            );
+        }
+
+        #[test]
+        fn test_two_value_fields() {
+            let mut record = EMPTY_RECORD.clone();
+            record.name = Some("efgh".to_string());
+            record.value = Some(10.0);
+            record.string_value = Some("Hello world!".to_string());
+            let data = vec![record];
+            let resolved_data = resolve_records(&data, *NOW);
+            assert!(matches!(
+                resolved_data.unwrap_err(),
+                SinditSenMLError::OnlyOneValuePerRecord(0)
+            ));
+        }
+
+        #[test]
+        fn test_no_units_is_fine() {
+            let mut record = EMPTY_RECORD.clone();
+            record.name = Some("efgh".to_string());
+            record.value = Some(10.0);
+            record.unit = None;
+            let data = vec![record];
+            let resolved_data = resolve_records(&data, *NOW);
+            assert!(resolved_data.is_ok());
+        }
+
+        #[test]
+        fn test_extra_fields_are_preserved() {
+            let mut record = BASE_RECORD.clone();
+            record.extra_fields =
+                Some(serde_json::from_str(r#"{"extra_field": "extra_value"}"#).unwrap());
+            let data = vec![record];
+            let resolved_data = resolve_records(&data, *NOW).unwrap();
+            assert_eq!(
+                resolved_data[0].extra_fields,
+                Some(serde_json::from_str(r#"{"extra_field": "extra_value"}"#).unwrap())
+            );
+        }
+
+        #[test]
+        fn test_empty_extra_fields_are_skipped() {
+            let mut record = BASE_RECORD.clone();
+            record.extra_fields = Some(serde_json::from_str(r#"{}"#).unwrap());
+            let data = vec![record];
+            let resolved_data = resolve_records(&data, *NOW).unwrap();
+            assert_eq!(resolved_data[0].extra_fields, None);
+        }
+
+        #[test]
+        fn test_resolver_helpers() {
+            let mut records = resolve_records(&vec![BASE_RECORD.clone()], *NOW).unwrap();
+            let mut record = records.pop().unwrap();
+            // None, defaults to the float value
+            assert_eq!(record.get_bool_value(), None);
+            assert_eq!(record.get_string_value(), None);
+            assert_eq!(record.get_data_value(), None);
+            assert_eq!(record.get_float_value(), Some(10.0));
+            // Boolean
+            record.value = Some(SenMLValueField::BooleanValue(true));
+            assert_eq!(record.get_bool_value(), Some(true));
+            assert_eq!(record.get_string_value(), None);
+            assert_eq!(record.get_data_value(), None);
+            assert_eq!(record.get_float_value(), None);
+            // String
+            record.value = Some(SenMLValueField::StringValue("Hello world!".to_string()));
+            assert_eq!(record.get_bool_value(), None);
+            assert_eq!(record.get_string_value(), Some(&"Hello world!".to_string()));
+            assert_eq!(record.get_data_value(), None);
+            assert_eq!(record.get_float_value(), None);
+            // Data
+            record.value = Some(SenMLValueField::DataValue(Vec::from(
+                "Hello world!".as_bytes(),
+            )));
+            assert_eq!(record.get_bool_value(), None);
+            assert_eq!(record.get_string_value(), None);
+            assert_eq!(
+                record.get_data_value(),
+                Some(&Vec::from("Hello world!".as_bytes()))
+            );
+            assert_eq!(record.get_float_value(), None);
+            // Float
+            record.value = Some(SenMLValueField::FloatingPoint(10.0));
+            assert_eq!(record.get_bool_value(), None);
+            assert_eq!(record.get_string_value(), None);
+            assert_eq!(record.get_data_value(), None);
+            assert_eq!(record.get_float_value(), Some(10.0));
+        }
+
+        #[test]
+        fn test_zero_version_number() {
+            let mut record = BASE_RECORD.clone();
+            record.base_version = Some(0);
+            let data = vec![record];
+            assert!(matches!(
+                resolve_records(&data, *NOW).unwrap_err(),
+                SinditSenMLError::InvalidVersionNumber
+            ));
+        }
+    }
+
+    mod test_parse_json {
+        use crate::*;
+        use chrono::Utc;
+
+        #[test]
+        fn test_parse_json_reader() {
+            let data = r#"[{"bn": "urn:dev:ow:10e2073a01080063:", "n": "temp", "v": 23.1},
+                {"n": "label", "vs": "Machine Room"}]"#;
+            let now = Utc::now();
+            assert_eq!(
+                parse_json_reader(data.as_bytes(), Some(now)).unwrap(),
+                parse_json(data, Some(now)).unwrap()
+            );
+            let reader = std::io::BufReader::new(std::io::Cursor::new(data.to_string()));
+            assert_eq!(parse_json_reader(reader, Some(now)).unwrap().len(), 2);
+
+            assert!(matches!(
+                parse_json_reader(&b"[{"[..], None).unwrap_err(),
+                SinditSenMLError::InvalidJSON(_)
+            ));
+            assert!(matches!(
+                parse_json_reader(&br#"[{"v": 1}]"#[..], None).unwrap_err(),
+                SinditSenMLError::MissingName(0)
+            ));
+        }
+
+        #[test]
+        fn test_empty() {
+            let data = "[]";
+            let resolved_data = parse_json(data, None);
+            assert!(resolved_data.is_ok());
+            assert_eq!(resolved_data.unwrap(), Vec::new());
+        }
+
+        #[test]
+        fn test_single_record() {
+            let data = r#"[{"n": "abcd", "v": 10.0}]"#;
+            let now = Utc::now();
+            let resolved_data = parse_json(data, Some(now));
+            assert!(resolved_data.is_ok());
+            assert_eq!(
+                resolved_data.unwrap(),
+                vec![SenMLResolvedRecord {
+                    name: "abcd".to_string(),
+                    unit: None,
+                    value: Some(SenMLValueField::FloatingPoint(10.0)),
+                    sum: None,
+                    time: now,
+                    update_time: None,
+                    base_version: None,
+                    extra_fields: None,
+                    active_base_name: None,
+                }]
+            );
+        }
+
+        #[test]
+        fn test_multiple_records() {
+            let data = r#"[{"n": "abcd", "v": 10.0}, {"n": "efgh", "v": 20.0, "t": 1.5}]"#;
+            let now = Utc::now();
+            let now_in_1_5_seconds =
+                now + chrono::Duration::seconds(1) + chrono::Duration::milliseconds(500);
+            let resolved_data = parse_json(data, Some(now));
+            assert!(resolved_data.is_ok());
+            assert_eq!(
+                resolved_data.unwrap(),
+                vec![
+                    SenMLResolvedRecord {
+                        name: "abcd".to_string(),
+                        unit: None,
+                        value: Some(SenMLValueField::FloatingPoint(10.0)),
+                        sum: None,
+                        time: now,
+                        update_time: None,
+                        base_version: None,
+                        extra_fields: None,
+                        active_base_name: None,
+                    },
+                    SenMLResolvedRecord {
+                        name: "efgh".to_string(),
+                        unit: None,
+                        value: Some(SenMLValueField::FloatingPoint(20.0)),
+                        sum: None,
+                        time: now_in_1_5_seconds,
+                        update_time: None,
+                        base_version: None,
+                        extra_fields: None,
+                        active_base_name: None,
+                    }
+                ]
+            );
+        }
+
+        #[test]
+        fn test_record_with_extra_fields() {
+            let data = r#"[{"n": "abcd", "v": 10.0, "extra_field": "extra_value"}]"#;
+            let now = Utc::now();
+            let resolved_data = parse_json(data, Some(now));
+            assert!(resolved_data.is_ok());
+            assert_eq!(
+                resolved_data.unwrap(),
+                vec![SenMLResolvedRecord {
+                    name: "abcd".to_string(),
+                    unit: None,
+                    value: Some(SenMLValueField::FloatingPoint(10.0)),
+                    sum: None,
+                    time: now,
+                    update_time: None,
+                    base_version: None,
+                    extra_fields: Some(
+                        serde_json::from_str(r#"{"extra_field": "extra_value"}"#).unwrap()
+                    ),
+                    active_base_name: None,
+                }]
+            );
+        }
+
+        #[test]
+        fn test_invalid_json() {
+            let data = r#"[{"n": "abcd", "v": 10.0"#;
+            let resolved_data = parse_json(data, None);
+            assert!(matches!(
+                resolved_data.unwrap_err(),
+                SinditSenMLError::InvalidJSON(_)
+            ));
+        }
+
+        #[test]
+        fn test_name_case() {
+            let data = r#"[{"n": "Temperature", "v": 20.0}, {"n": "urn:dev:ow:abc", "v": 1.0}]"#;
+            let options = ParseOptions {
+                name_case: NameCase::ToLowercase,
+                ..Default::default()
+            };
+            let records = parse_json_with_options(data, None, &options).unwrap();
+            assert_eq!(records[0].name, "temperature");
+            assert!(validate_name(&records[0].name));
+
+            let options = ParseOptions {
+                name_case: NameCase::ToUppercase,
+                ..Default::default()
+            };
+            let records = parse_json_with_options(data, None, &options).unwrap();
+            assert_eq!(records[0].name, "TEMPERATURE");
+            assert_eq!(records[1].name, "URN:DEV:OW:ABC");
+
+            let records = parse_json_with_options(data, None, &ParseOptions::default()).unwrap();
+            assert_eq!(records[0].name, "Temperature");
+        }
+
+        #[test]
+        fn test_strict_units() {
+            let data = r#"[{"bu": "Cel", "n": "temperature", "v": 20.0}, {"n": "speed", "u": "km/h", "v": 1.0}]"#;
+            let options = ParseOptions {
+                strict_units: true,
+                ..Default::default()
+            };
+            assert!(parse_json_with_options(data, None, &options).is_ok());
+
+            let data = r#"[{"n": "temperature", "v": 20.0}, {"n": "speed", "u": "kmh", "v": 1.0}]"#;
+            assert!(matches!(
+                parse_json_with_options(data, None, &options).unwrap_err(),
+                SinditSenMLError::UnknownUnit(1)
+            ));
+            assert!(parse_json_with_options(data, None, &ParseOptions::default()).is_ok());
+
+            let data = r#"[{"bu": "celsius", "n": "temperature", "v": 20.0}]"#;
+            assert!(matches!(
+                parse_json_with_options(data, None, &options).unwrap_err(),
+                SinditSenMLError::UnknownUnit(0)
+            ));
+        }
+
+        #[test]
+        fn test_strict_extra_fields() {
+            let options = ParseOptions {
+                strict_extra_fields: true,
+                ..Default::default()
+            };
+            let data = r#"[{"bver": 5, "bn": "a", "bt": 1320067464, "bu": "Cel", "bv": 1, "bs": 1,
+                "n": "b", "u": "Cel", "v": 1, "s": 1, "t": 0, "ut": 60},
+                {"n": "c", "vs": "d"}, {"n": "e", "vb": true}, {"n": "f", "vd": "aGkgCg"}]"#;
+            assert!(parse_json_with_options(data, None, &options).is_ok());
+
+            let data = r#"[{"n": "temperature", "v": 20.0}, {"n": "speed", "v": 1.0, "zone": 2, "room": 1}]"#;
+            match parse_json_with_options(data, None, &options).unwrap_err() {
+                SinditSenMLError::UnexpectedField { index, field } => {
+                    assert_eq!(index, 1);
+                    assert_eq!(field, "room");
+                }
+                error => panic!("unexpected error {:?}", error),
+            }
+            assert!(parse_json_with_options(data, None, &ParseOptions::default()).is_ok());
+        }
+
+        #[test]
+        fn test_max_records() {
+            let data = r#"[{"n": "a", "v": 1}, {"n": "b", "v": 2}, {"n": "c", "v": 3}]"#;
+            let options = |max_records| ParseOptions {
+                max_records,
+                ..Default::default()
+            };
+            assert_eq!(
+                parse_json_with_options(data, None, &options(Some(3)))
+                    .unwrap()
+                    .len(),
+                3
+            );
+            assert!(matches!(
+                parse_json_with_options(data, None, &options(Some(2))).unwrap_err(),
+                SinditSenMLError::TooManyRecords(2)
+            ));
+            assert!(parse_json_with_options(r#"[]"#, None, &options(Some(0))).is_ok());
+        }
+
+        #[test]
+        fn test_default_time() {
+            let now = DateTime::from_timestamp(1700000000, 0).unwrap();
+            let default_time = DateTime::from_timestamp(1320067464, 0).unwrap();
+            let options = ParseOptions {
+                default_time: Some(default_time),
+                ..Default::default()
+            };
+            let data = r#"[{"n": "a", "v": 1}, {"n": "b", "v": 2, "t": -10}]"#;
+            let records = parse_json_with_options(data, Some(now), &options).unwrap();
+            assert_eq!(records[0].time, default_time);
+            assert_eq!(
+                records[1].time,
+                DateTime::from_timestamp(1699999990, 0).unwrap()
+            );
+
+            let data = r#"[{"bt": 1400000000, "n": "a", "v": 1}]"#;
+            let records = parse_json_with_options(data, Some(now), &options).unwrap();
+            assert_eq!(records[0].time.timestamp(), 1400000000);
+        }
+
+        #[test]
+        fn test_allow_custom_versions() {
+            let options = ParseOptions {
+                allow_custom_versions: false,
+                ..Default::default()
+            };
+            let data = r#"[{"bver": 5, "n": "a", "v": 1}]"#;
+            assert!(parse_json_with_options(data, None, &ParseOptions::default()).is_ok());
+            assert!(matches!(
+                parse_json_with_options(data, None, &options).unwrap_err(),
+                SinditSenMLError::UnsupportedVersion(5)
+            ));
+            let data = r#"[{"bver": 10, "n": "a", "v": 1}, {"n": "b", "v": 2}]"#;
+            assert!(parse_json_with_options(data, None, &options).is_ok());
+        }
+
+        #[test]
+        fn test_name_validation() {
+            let parse = |name: &str, name_validation: NameValidation| {
+                let data = format!(r#"[{{"n": "{}", "v": 1}}]"#, name);
+                let options = ParseOptions {
+                    name_validation,
+                    ..Default::default()
+                };
+                parse_json_with_options(&data, None, &options)
+            };
+            assert_eq!(
+                ParseOptions::default().name_validation,
+                NameValidation::Rfc8428
+            );
+
+            assert!(parse("a", NameValidation::Rfc8428).is_ok());
+            assert!(parse("a", NameValidation::Loose).is_ok());
+            assert!(matches!(
+                parse("a", NameValidation::Strict).unwrap_err(),
+                SinditSenMLError::InvalidNameInRecord(0)
+            ));
+
+            assert_eq!(parse("ä", NameValidation::Loose).unwrap()[0].name, "ä");
+            assert!(matches!(
+                parse("ä", NameValidation::Rfc8428).unwrap_err(),
+                SinditSenMLError::InvalidNameInRecord(0)
+            ));
+            assert!(matches!(
+                parse("ä", NameValidation::Strict).unwrap_err(),
+                SinditSenMLError::InvalidNameInRecord(0)
+            ));
+
+            let options = ParseOptions {
+                strict_names: false,
+                ..Default::default()
+            };
+            let records =
+                parse_json_with_options(r#"[{"n": "ä b", "v": 1}]"#, None, &options).unwrap();
+            assert_eq!(records[0].name, "ä b");
+        }
+
+        #[test]
+        fn test_count_records_json() {
+            assert_eq!(count_records_json("[]").unwrap(), 0);
+            assert_eq!(count_records_json(" [ ] ").unwrap(), 0);
+            assert_eq!(count_records_json(r#"[{"n": "a"}]"#).unwrap(), 1);
+            assert_eq!(
+                count_records_json(r#" [{"n": "a\",\\", "x": {"y": [1, 2]}}, {"n": "b"}] "#)
+                    .unwrap(),
+                2
+            );
+            assert!(count_records_json(r#"{"n": "a"}"#).is_err());
+            assert!(count_records_json(r#"[{"n": "a"}"#).is_err());
+            assert!(count_records_json(r#"[{"n": "a"}]]"#).is_err());
+            assert!(count_records_json("").is_err());
+            for json_str in ["[,]", "[1,]", "[,1]", "[1,,2]", "[ , ]"] {
+                assert!(count_records_json(json_str).is_err());
+            }
+        }
+
+        #[test]
+        fn test_parse_json_preallocated() {
+            let json_str = format!(
+                "[{}]",
+                (0..10000)
+                    .map(|index| format!(
+                        r#"{{"n":"sensor{}","v":{},"t":1320067464}}"#,
+                        index, index
+                    ))
+                    .collect::<Vec<_>>()
+                    .join(",")
+            );
+            let now = Utc::now();
+            let records = parse_json_preallocated(&json_str, Some(now)).unwrap();
+            assert_eq!(records.len(), 10000);
+            assert_eq!(records.capacity(), records.len());
+            assert_eq!(records, parse_json(&json_str, Some(now)).unwrap());
+
+            assert!(parse_json_preallocated(r#"[{"v": 1}]"#, None).is_err());
+        }
+
+        #[test]
+        fn test_reject_pre_epoch() {
+            let data = r#"[{"n": "a", "t": 1},{"n": "b", "t": -1}]"#;
+            let epoch = DateTime::<Utc>::default();
+            let options = ParseOptions {
+                reject_pre_epoch: true,
+                ..Default::default()
+            };
+            assert!(matches!(
+                parse_json_with_options(data, Some(epoch), &options).unwrap_err(),
+                SinditSenMLError::PreEpochTimestamp(1)
+            ));
+
+            let records = parse_json(data, Some(epoch)).unwrap();
+            assert_eq!(
+                records[1].time,
+                DateTime::<Utc>::from_timestamp(-1, 0).unwrap()
+            );
+            assert_eq!(time::find_pre_epoch_records(&records), vec![1]);
+        }
+
+        #[test]
+        fn test_base64_accept_policy() {
+            // Standard base64 with padding
+            let data = r#"[{"n": "abcd", "vd": "SGVsbG8gd29ybGQ="}]"#;
+
+            let options = ParseOptions {
+                base64_input: Base64AcceptPolicy::AcceptBoth,
+                ..Default::default()
+            };
+            let records = parse_json_with_options(data, None, &options).unwrap();
+            assert_eq!(records[0].get_data_value().unwrap(), b"Hello world");
+
+            let options = ParseOptions {
+                base64_input: Base64AcceptPolicy::StrictStandard,
+                ..Default::default()
+            };
+            let records = parse_json_with_options(data, None, &options).unwrap();
+            assert_eq!(records[0].get_data_value().unwrap(), b"Hello world");
+
+            let options = ParseOptions {
+                base64_input: Base64AcceptPolicy::StrictUrlSafe,
+                ..Default::default()
+            };
+            assert!(matches!(
+                parse_json_with_options(data, None, &options).unwrap_err(),
+                SinditSenMLError::InvalidBase64Value(_)
+            ));
+            // The default is strict
+            assert!(matches!(
+                parse_json(data, None).unwrap_err(),
+                SinditSenMLError::InvalidBase64Value(_)
+            ));
+
+            // URL safe base64 without padding
+            let data = r#"[{"n": "abcd", "vd": "Ly_Cuw"}]"#;
+            let options = ParseOptions {
+                base64_input: Base64AcceptPolicy::AcceptBoth,
+                ..Default::default()
+            };
+            let records = parse_json_with_options(data, None, &options).unwrap();
+            assert_eq!(records[0].get_data_value().unwrap(), b"//\xC2\xBB");
+            let options = ParseOptions {
+                base64_input: Base64AcceptPolicy::StrictStandard,
+                ..Default::default()
+            };
+            assert!(parse_json_with_options(data, None, &options).is_err());
+        }
+    }
+
+    #[cfg(feature = "tokio")]
+    mod test_parse_json_async {
+        use crate::*;
+
+        const DATA: &str = r#"[{"bn": "urn:dev:ow:10e2073a01080063:", "n": "temp", "v": 23.1},
+            {"n": "label", "vs": "Machine Room"}]"#;
+
+        #[tokio::test]
+        async fn test_parse_json_async() {
+            let now = Utc::now();
+            let expected = parse_json(DATA, Some(now)).unwrap();
+            assert_eq!(
+                parse_json_async(DATA.as_bytes(), Some(now)).await.unwrap(),
+                expected
+            );
+            assert_eq!(
+                parse_json_str_async(DATA, Some(now)).await.unwrap(),
+                expected
+            );
+
+            // A body received in many small chunks.
+            let (mut writer, reader) = tokio::io::duplex(8);
+            let writing = tokio::spawn(async move {
+                use tokio::io::AsyncWriteExt;
+                writer.write_all(DATA.as_bytes()).await.unwrap();
+            });
+            assert_eq!(parse_json_async(reader, Some(now)).await.unwrap(), expected);
+            writing.await.unwrap();
+        }
+
+        #[tokio::test]
+        async fn test_parse_json_async_errors() {
+            assert!(matches!(
+                parse_json_async(&b"[{"[..], None).await.unwrap_err(),
+                SinditSenMLError::InvalidJSON(_)
+            ));
+            assert!(matches!(
+                parse_json_str_async(r#"[{"v": 1}]"#, None)
+                    .await
+                    .unwrap_err(),
+                SinditSenMLError::MissingName(0)
+            ));
+        }
+    }
+
+    mod test_recover {
+        use crate::*;
+
+        const PACK: &str = r#"[
+            {"bn": "sensor:", "bt": 1320067464, "n": "a", "v": 1},
+            {"n": "b", "v": 2},
+            {"n": "c", "v": 3},
+            {"bn": "other:", "n": "d", "v": 4, "vs": "four"},
+            {"n": "e", "v": 5}
+        ]"#;
+
+        #[test]
+        fn test_parse_json_recover() {
+            let (records, error) = parse_json_recover(PACK, None);
+            assert_eq!(records.len(), 3);
+            assert_eq!(records[2].name, "sensor:c");
+            assert!(matches!(
+                error,
+                Some(SinditSenMLError::OnlyOneValuePerRecord(3))
+            ));
+
+            let (records, error) = parse_json_recover(r#"[{"n": "a", "v": 1}]"#, None);
+            assert_eq!(records.len(), 1);
+            assert!(error.is_none());
+
+            let (records, error) = parse_json_recover("[{", None);
+            assert!(records.is_empty());
+            assert!(matches!(error, Some(SinditSenMLError::InvalidJSON(_))));
+        }
+
+        #[test]
+        fn test_parse_json_recover_all() {
+            let (records, errors) = parse_json_recover_all(PACK, None);
+            assert_eq!(records.len(), 4);
+            // The base name of the invalid record is discarded
+            assert_eq!(records[3].name, "sensor:e");
+            assert_eq!(errors.len(), 1);
+            assert!(matches!(
+                errors[0],
+                SinditSenMLError::OnlyOneValuePerRecord(3)
+            ));
+        }
+    }
+
+    mod test_coercion {
+        use crate::*;
+
+        #[test]
+        fn test_coerce_to_float() {
+            assert_eq!(
+                SenMLValueField::BooleanValue(true).coerce_to_float(),
+                Some(1.0)
+            );
+            assert_eq!(
+                SenMLValueField::BooleanValue(false).coerce_to_float(),
+                Some(0.0)
+            );
+            assert_eq!(
+                SenMLValueField::FloatingPoint(23.1).coerce_to_float(),
+                Some(23.1)
+            );
+            assert_eq!(
+                SenMLValueField::StringValue("-1.5".to_string()).coerce_to_float(),
+                Some(-1.5)
+            );
+            assert_eq!(
+                SenMLValueField::StringValue("Machine Room".to_string()).coerce_to_float(),
+                None
+            );
+            assert_eq!(SenMLValueField::DataValue(vec![1]).coerce_to_float(), None);
+        }
+
+        #[test]
+        fn test_try_coerce_to_float() {
+            assert_eq!(
+                SenMLValueField::FloatingPoint(23.1).try_coerce_to_float(),
+                Some(23.1)
+            );
+            assert_eq!(
+                SenMLValueField::BooleanValue(false).try_coerce_to_float(),
+                Some(0.0)
+            );
+            assert_eq!(
+                SenMLValueField::BooleanValue(true).try_coerce_to_float(),
+                Some(1.0)
+            );
+            assert_eq!(
+                SenMLValueField::StringValue("-1.5".to_string()).try_coerce_to_float(),
+                None
+            );
+            assert_eq!(
+                SenMLValueField::DataValue(vec![1]).try_coerce_to_float(),
+                None
+            );
+        }
+
+        #[test]
+        fn test_coerce_to_bool() {
+            assert_eq!(
+                SenMLValueField::FloatingPoint(0.0).coerce_to_bool(),
+                Some(false)
+            );
+            assert_eq!(
+                SenMLValueField::FloatingPoint(-2.0).coerce_to_bool(),
+                Some(true)
+            );
+            assert_eq!(
+                SenMLValueField::StringValue("true".to_string()).coerce_to_bool(),
+                Some(true)
+            );
+            assert_eq!(
+                SenMLValueField::StringValue("yes".to_string()).coerce_to_bool(),
+                None
+            );
+            assert_eq!(
+                SenMLValueField::DataValue(vec![]).coerce_to_bool(),
+                Some(false)
+            );
+            assert_eq!(
+                SenMLValueField::StringValue("true".to_string()).coerce_to_bool(),
+                SenMLValueField::BooleanValue(true).coerce_to_bool()
+            );
+        }
+
+        #[test]
+        fn test_coerce_to_string() {
+            assert_eq!(
+                SenMLValueField::DataValue(vec![1, 2, 3]).coerce_to_string(),
+                "AQID"
+            );
+            assert_eq!(
+                SenMLValueField::FloatingPoint(23.1).coerce_to_string(),
+                "23.1"
+            );
+            assert_eq!(
+                SenMLValueField::BooleanValue(false).coerce_to_string(),
+                "false"
+            );
+            assert_eq!(
+                SenMLValueField::StringValue("Machine Room".to_string()).coerce_to_string(),
+                "Machine Room"
+            );
+        }
+    }
+
+    mod test_error_source {
+        use std::error::Error;
+
+        use crate::*;
+
+        #[test]
+        fn test_invalid_json_source() {
+            let error = parse_json("[{", None).unwrap_err();
+            assert!(matches!(error, SinditSenMLError::InvalidJSON(_)));
+            let source = error.source().unwrap();
+            assert!(source.downcast_ref::<serde_json::Error>().is_some());
+        }
+
+        #[test]
+        fn test_wrapped_errors_source() {
+            let error = parse_json(r#"[{"n": "nfc-reader", "vd": "!"}]"#, None).unwrap_err();
+            assert!(matches!(error, SinditSenMLError::InvalidBase64Value(_)));
+            assert!(error
+                .source()
+                .unwrap()
+                .downcast_ref::<base64::DecodeError>()
+                .is_some());
+            assert_eq!(error.to_string(), "Invalid base64 value");
+
+            let error = SinditSenMLError::from(std::io::Error::other("closed"));
+            assert!(error
+                .source()
+                .unwrap()
+                .downcast_ref::<std::io::Error>()
+                .is_some());
+
+            let error = transform::remap_names_regex(Vec::new(), "(", "").unwrap_err();
+            assert!(error
+                .source()
+                .unwrap()
+                .downcast_ref::<regex::Error>()
+                .is_some());
+
+            assert!(SinditSenMLError::InvalidName.source().is_none());
+        }
+    }
+
+    mod test_iso8601_time {
+        use crate::*;
+
+        #[test]
+        fn test_serialize_iso8601_time() {
+            let records = parse_json(
+                r#"[{"bn": "urn:dev:ow:10e2073a01080063:", "bt": 1320067464, "n": "temp", "v": 23.1},
+                    {"n": "temp", "t": 0.5, "v": 23.2}]"#,
+                None,
+            )
+            .unwrap();
+            let iso8601_records: Vec<SenMLResolvedRecordIso8601> =
+                records.iter().cloned().map(Into::into).collect();
+            let json = serde_json::to_string(&iso8601_records).unwrap();
+            assert_eq!(
+                json,
+                r#"[{"n":"urn:dev:ow:10e2073a01080063:temp","v":23.1,"t":"2011-10-31T13:24:24Z"},{"n":"urn:dev:ow:10e2073a01080063:temp","v":23.2,"t":"2011-10-31T13:24:24.500Z"}]"#
+            );
+            assert_eq!(parse_json(&json, None).unwrap(), records);
+        }
+
+        #[test]
+        fn test_parse_string_time() {
+            let records = parse_json(
+                r#"[{"n": "a", "t": "2023-01-01T12:00:00Z", "v": 1},
+                    {"n": "b", "t": "2023-01-01T13:00:00.250+01:00", "v": 2},
+                    {"n": "c", "t": 1672574400, "v": 3}]"#,
+                None,
+            )
+            .unwrap();
+            let time = DateTime::from_timestamp(1672574400, 0).unwrap();
+            assert_eq!(records[0].time, time);
+            assert_eq!(
+                records[1].time,
+                DateTime::from_timestamp(1672574400, 250_000_000).unwrap()
+            );
+            assert_eq!(records[2].time, time);
+
+            assert!(matches!(
+                parse_json(r#"[{"n": "a", "t": "yesterday", "v": 1}]"#, None).unwrap_err(),
+                SinditSenMLError::InvalidJSON(_)
+            ));
+            assert!(matches!(
+                parse_json(r#"[{"n": "a", "t": true, "v": 1}]"#, None).unwrap_err(),
+                SinditSenMLError::InvalidJSON(_)
+            ));
+            assert!(matches!(
+                parse_json(r#"[{"n": "a", "t": null, "v": 1}]"#, None).unwrap_err(),
+                SinditSenMLError::InvalidJSON(_)
+            ));
+        }
+
+        #[test]
+        fn test_string_time_is_absolute() {
+            let now = DateTime::from_timestamp(1700000000, 0).unwrap();
+            let records = parse_json(
+                r#"[{"bt": 1320067464, "n": "a", "v": 1},
+                    {"n": "b", "t": "2023-01-01T12:00:00Z", "v": 2},
+                    {"n": "c", "t": "1975-06-01T00:00:00Z", "v": 3},
+                    {"n": "d", "t": "1970-01-01T00:00:00Z", "v": 4},
+                    {"n": "e", "t": 60, "v": 5}]"#,
+                Some(now),
+            )
+            .unwrap();
+            assert_eq!(
+                records[1].time,
+                DateTime::from_timestamp(1672574400, 0).unwrap()
+            );
+            assert_eq!(
+                records[2].time,
+                DateTime::from_timestamp(170812800, 0).unwrap()
+            );
+            assert_eq!(records[3].time, DateTime::UNIX_EPOCH);
+            // The base time still applies to the numeric times.
+            assert_eq!(
+                records[4].time,
+                DateTime::from_timestamp(1320067524, 0).unwrap()
+            );
+
+            let records = parse_json(
+                r#"[{"n": "a", "t": "1975-06-01T00:00:00Z", "v": 1}]"#,
+                Some(now),
+            )
+            .unwrap();
+            let iso8601_records: Vec<SenMLResolvedRecordIso8601> =
+                records.iter().cloned().map(Into::into).collect();
+            let json = serde_json::to_string(&iso8601_records).unwrap();
+            assert_eq!(parse_json(&json, Some(now)).unwrap(), records);
+        }
+    }
+
+    mod test_validate_pack {
+        use crate::*;
+
+        #[test]
+        fn test_validate_pack() {
+            let records = parse_json(
+                r#"[{"bver": 5, "bn": "urn:dev:ow:10e2073a0108006:", "bt": 1320067464,
+                     "n": "voltage", "u": "V", "v": 120.1, "ut": 60},
+                    {"n": "current", "t": -1, "v": 1.2},
+                    {"n": "current", "v": 1.3}]"#,
+                None,
+            )
+            .unwrap();
+            assert!(validate_pack(&records).is_ok());
+            assert!(validate_pack(&[]).is_ok());
+
+            let mut invalid_records = records.clone();
+            invalid_records[0].name = String::new();
+            invalid_records[0].update_time = Some(0.0);
+            invalid_records[1].time = invalid_records[2].time;
+            invalid_records[1].base_version = None;
+            invalid_records[2].base_version = Some(4);
+            invalid_records.push(records[0].clone());
+            invalid_records[3].name = "-voltage".to_string();
+            invalid_records[3].update_time = Some(-1.0);
+
+            let errors = validate_pack(&invalid_records).unwrap_err();
+            let errors: Vec<String> = errors.iter().map(|error| error.to_string()).collect();
+            assert_eq!(
+                errors,
+                vec![
+                    SinditSenMLError::InvalidNameInRecord(0).to_string(),
+                    SinditSenMLError::InvalidUpdateTime(0).to_string(),
+                    SinditSenMLError::DifferentBaseVersion.to_string(),
+                    SinditSenMLError::DuplicateRecord(2).to_string(),
+                    SinditSenMLError::InvalidNameInRecord(3).to_string(),
+                    SinditSenMLError::InvalidUpdateTime(3).to_string(),
+                ]
+            );
+        }
+    }
+
+    mod test_ordering {
+        use crate::fixtures::MULTIPLE_DATAPOINT_AND_TIME;
+        use crate::*;
+        use std::collections::BTreeSet;
+
+        #[test]
+        fn test_btree_set_order() {
+            let records = parse_json(MULTIPLE_DATAPOINT_AND_TIME, None).unwrap();
+            let set: BTreeSet<SenMLResolvedRecord> = records.iter().cloned().collect();
+            assert_eq!(set.len(), 7);
+            let ordered: Vec<&SenMLResolvedRecord> = set.iter().collect();
+            assert!(ordered.windows(2).all(|pair| pair[0].time <= pair[1].time));
+            let values: Vec<f64> = ordered
+                .iter()
+                .filter_map(|record| record.get_float_value())
+                .collect();
+            assert_eq!(values, vec![1.2, 1.3, 1.4, 1.5, 1.6, 1.7, 120.1]);
+
+            let mut sorted_records = records.clone();
+            sorted_records.sort();
+            assert_eq!(sorted_records.iter().collect::<Vec<_>>(), ordered);
+        }
+
+        #[test]
+        fn test_compare_time_then_name() {
+            let records = parse_json(
+                r#"[{"bt": 1320067464, "n": "b", "v": 1}, {"n": "a", "v": 2},
+                    {"n": "a", "v": 3, "t": -1}, {"n": "b", "v": 4}]"#,
+                None,
+            )
+            .unwrap();
+            assert!(records[1] < records[0]);
+            assert!(records[2] < records[1]);
+            assert!(records[0] < records[3]);
+            assert_eq!(records.iter().max(), Some(&records[3]));
+        }
+
+        #[test]
+        fn test_total_order() {
+            let records = parse_json(
+                r#"[{"bt": 1320067464, "n": "a", "v": 1}, {"n": "a", "v": 1, "u": "Cel"},
+                    {"n": "a", "vs": "1"}, {"n": "a", "s": 1},
+                    {"n": "a", "v": 1, "room": 1}, {"n": "a", "v": 1, "room": 1.0},
+                    {"n": "a", "v": 1, "room": [1, {"floor": 2}]}]"#,
+                None,
+            )
+            .unwrap();
+            let set: BTreeSet<SenMLResolvedRecord> = records.iter().cloned().collect();
+            assert_eq!(set.len(), records.len());
+            for (index, a) in records.iter().enumerate() {
+                for (other_index, b) in records.iter().enumerate() {
+                    assert_eq!(a.cmp(b) == std::cmp::Ordering::Equal, index == other_index);
+                    assert_eq!(a == b, index == other_index);
+                    assert_eq!(a.cmp(b), b.cmp(a).reverse());
+                }
+            }
+
+            let mut nan_record = records[0].clone();
+            nan_record.value = Some(SenMLValueField::FloatingPoint(f64::NAN));
+            assert_eq!(nan_record, nan_record.clone());
+            let mut negative_zero_record = records[0].clone();
+            negative_zero_record.value = Some(SenMLValueField::FloatingPoint(-0.0));
+            let mut zero_record = records[0].clone();
+            zero_record.value = Some(SenMLValueField::FloatingPoint(0.0));
+            assert!(negative_zero_record < zero_record);
+        }
+    }
+
+    mod test_display {
+        use crate::*;
+
+        #[test]
+        fn test_display_value() {
+            assert_eq!(SenMLValueField::FloatingPoint(42.0).to_string(), "42");
+            assert_eq!(SenMLValueField::FloatingPoint(-23.1).to_string(), "-23.1");
+            assert_eq!(SenMLValueField::BooleanValue(true).to_string(), "true");
+            assert_eq!(
+                SenMLValueField::StringValue("hello".to_string()).to_string(),
+                "hello"
+            );
+            assert_eq!(
+                SenMLValueField::DataValue(vec![0x68, 0x69, 0x20, 0x0a]).to_string(),
+                "0x6869200a"
+            );
+            assert_eq!(SenMLValueField::DataValue(vec![]).to_string(), "0x");
+        }
+
+        #[test]
+        fn test_display_record() {
+            let records = parse_json(
+                r#"[{"n": "temperature", "u": "Cel", "v": 23.1, "t": 1672574400, "ut": 60},
+                    {"n": "open", "vb": true, "t": 1672574400.5},
+                    {"n": "energy", "u": "J", "s": 1200, "t": 1672574400}]"#,
+                None,
+            )
+            .unwrap();
+            assert_eq!(
+                records[0].to_string(),
+                "name=temperature unit=Cel value=23.1 time=2023-01-01T12:00:00Z update_time=60"
+            );
+            assert_eq!(
+                records[1].to_string(),
+                "name=open value=true time=2023-01-01T12:00:00.500Z"
+            );
+            assert_eq!(
+                records[2].to_string(),
+                "name=energy unit=J sum=1200 time=2023-01-01T12:00:00Z"
+            );
+        }
+    }
+
+    mod test_json_value {
+        use crate::*;
+        use serde_json::json;
+
+        #[test]
+        fn test_try_from_json_value() {
+            let value = json!({"bn": "urn:dev:ow:10e2073a01080063:", "n": "temp",
+                "u": "Cel", "v": 23.1, "t": 1320067464, "location": "room 1"});
+            let record = SenMLResolvedRecord::try_from(value.clone()).unwrap();
+            let expected = parse_json(&format!("[{}]", value), None).unwrap();
+            assert_eq!(record, expected[0]);
+
+            let before = Utc::now();
+            let record = SenMLResolvedRecord::try_from(json!({"n": "open", "vb": true})).unwrap();
+            assert!(record.time >= before && record.time <= Utc::now());
+            assert_eq!(record.get_bool_value(), Some(true));
+        }
+
+        #[test]
+        fn test_into_json_value() {
+            let records = parse_json(
+                r#"[{"bn": "urn:dev:ow:10e2073a01080063:", "bt": 1320067464, "bu": "Cel",
+                     "n": "temp", "v": 23.1, "location": "room 1"},
+                    {"n": "nfc-reader", "vd": "aGkgCg"}]"#,
+                None,
+            )
+            .unwrap();
+            let value: serde_json::Value = records[0].clone().into();
+            assert_eq!(
+                value,
+                json!({"n": "urn:dev:ow:10e2073a01080063:temp", "u": "Cel", "v": 23.1,
+                    "t": 1320067464, "location": "room 1"})
+            );
+            assert_eq!(
+                serde_json::Value::from(records[1].clone())["vd"],
+                json!("aGkgCg")
+            );
+
+            for record in records {
+                let value: serde_json::Value = record.clone().into();
+                assert!(value.is_object());
+                assert_eq!(SenMLResolvedRecord::try_from(value).unwrap(), record);
+            }
+        }
+
+        #[test]
+        fn test_try_from_invalid_json_value() {
+            assert!(matches!(
+                SenMLResolvedRecord::try_from(json!({"bn": "temp", "v": 1})),
+                Err(SinditSenMLError::MissingName(0))
+            ));
+            assert!(matches!(
+                SenMLResolvedRecord::try_from(json!([{"n": "temp"}])),
+                Err(SinditSenMLError::InvalidJSON(_))
+            ));
+            assert!(matches!(
+                SenMLResolvedRecord::try_from(json!({"n": "temp", "v": "1"})),
+                Err(SinditSenMLError::InvalidJSON(_))
+            ));
+            assert!(matches!(
+                SenMLResolvedRecord::try_from(json!({"n": "temp", "v": 1, "vb": true})),
+                Err(SinditSenMLError::OnlyOneValuePerRecord(0))
+            ));
+        }
+    }
+
+    mod test_value_conversions {
+        use crate::*;
+
+        #[test]
+        fn test_value_predicates() {
+            let values = [
+                SenMLValueField::FloatingPoint(23.1),
+                SenMLValueField::StringValue("Machine Room".to_string()),
+                SenMLValueField::BooleanValue(false),
+                SenMLValueField::DataValue(vec![]),
+            ];
+            let kinds: Vec<[bool; 4]> = values
+                .iter()
+                .map(|value| {
+                    [
+                        value.is_numeric(),
+                        value.is_string(),
+                        value.is_bool(),
+                        value.is_data(),
+                    ]
+                })
+                .collect();
+            assert_eq!(
+                kinds,
+                vec![
+                    [true, false, false, false],
+                    [false, true, false, false],
+                    [false, false, true, false],
+                    [false, false, false, true],
+                ]
+            );
+        }
+
+        #[test]
+        fn test_value_from_primitives() {
+            assert_eq!(
+                SenMLValueField::from(23.1),
+                SenMLValueField::FloatingPoint(23.1)
+            );
+            assert_eq!(
+                SenMLValueField::from(1.5f32),
+                SenMLValueField::FloatingPoint(1.5)
+            );
+            assert_eq!(
+                SenMLValueField::from(-3i64),
+                SenMLValueField::FloatingPoint(-3.0)
+            );
+            assert_eq!(
+                SenMLValueField::from(-3i32),
+                SenMLValueField::FloatingPoint(-3.0)
+            );
+            assert_eq!(
+                SenMLValueField::from(1320067464u64),
+                SenMLValueField::FloatingPoint(1320067464.0)
+            );
+            assert_eq!(
+                SenMLValueField::from(7u32),
+                SenMLValueField::FloatingPoint(7.0)
+            );
+            assert_eq!(
+                SenMLValueField::from(true),
+                SenMLValueField::BooleanValue(true)
+            );
+            assert_eq!(
+                SenMLValueField::from("Machine Room"),
+                SenMLValueField::StringValue("Machine Room".to_string())
+            );
+            assert_eq!(
+                SenMLValueField::from("Machine Room".to_string()),
+                SenMLValueField::from("Machine Room")
+            );
+            assert_eq!(
+                SenMLValueField::from(vec![1, 2, 3]),
+                SenMLValueField::DataValue(vec![1, 2, 3])
+            );
+
+            let mut record = SenMLResolvedRecord::builder().name("a").build().unwrap();
+            record.value = Some(23.1f64.into());
+            assert_eq!(record.get_float_value(), Some(23.1));
+        }
+    }
+
+    mod test_extra_fields {
+        use crate::*;
+        use serde_json::{json, Value};
+
+        #[test]
+        fn test_extra_pointer() {
+            let records = parse_json(
+                r#"[{"n": "position", "t": 1320067464, "meta": {"source": "gps", "accuracy": 3.0, "tags": ["a", "b"]}, "a/b": {"c~d": 1}}]"#,
+                None,
+            )
+            .unwrap();
+            let record = &records[0];
             assert_eq!(
-                resolved_data.unwrap()[0].value,
-                Some(SenMLValueField::FloatingPoint(0.0))
+                record.extra_pointer("/meta/source"),
+                Some(&Value::String("gps".to_string()))
+            );
+            assert_eq!(record.extra_pointer("/meta/accuracy"), Some(&json!(3.0)));
+            assert_eq!(record.extra_pointer("/meta/tags/1"), Some(&json!("b")));
+            assert_eq!(record.extra_pointer("/a~1b/c~0d"), Some(&json!(1)));
+            assert_eq!(
+                record.extra_pointer("/meta"),
+                record.extra_fields.as_ref().unwrap().get("meta")
             );
+            assert_eq!(record.extra_pointer("/meta/altitude"), None);
+            assert_eq!(record.extra_pointer("meta"), None);
+            assert_eq!(record.extra_pointer(""), None);
         }
 
         #[test]
-        fn test_two_value_fields() {
-            let mut record = EMPTY_RECORD.clone();
-            record.name = Some("efgh".to_string());
-            record.value = Some(10.0);
-            record.string_value = Some("Hello world!".to_string());
-            let data = vec![record];
-            let resolved_data = resolve_records(&data, *NOW);
-            assert!(matches!(
-                resolved_data.unwrap_err(),
-                SinditSenMLError::OnlyOneValuePerRecord(0)
-            ));
-        }
+        fn test_extra_get() {
+            let records = parse_json(
+                r#"[{"n": "position", "t": 1320067464, "meta": {"accuracy": 3.0}, "source": "gps", "accuracy": 3, "valid": true}]"#,
+                None,
+            )
+            .unwrap();
+            let record = &records[0];
+            assert_eq!(record.extra_get_f64("meta"), None);
+            assert_eq!(record.extra_get_f64("accuracy"), Some(3.0));
+            assert_eq!(record.extra_get_str("source"), Some("gps"));
+            assert_eq!(record.extra_get_str("valid"), None);
+            assert_eq!(record.extra_get_bool("valid"), Some(true));
+            assert_eq!(record.extra_get_bool("missing"), None);
 
-        #[test]
-        fn test_no_units_is_fine() {
-            let mut record = EMPTY_RECORD.clone();
-            record.name = Some("efgh".to_string());
-            record.value = Some(10.0);
-            record.unit = None;
-            let data = vec![record];
-            let resolved_data = resolve_records(&data, *NOW);
-            assert!(resolved_data.is_ok());
+            let records = parse_json(r#"[{"n": "a", "v": 1}]"#, None).unwrap();
+            assert_eq!(records[0].extra_get_str("source"), None);
+            assert_eq!(records[0].extra_pointer("/source"), None);
         }
+    }
+
+    mod test_base64_data {
+        use crate::*;
 
         #[test]
-        fn test_extra_fields_are_preserved() {
-            let mut record = BASE_RECORD.clone();
-            record.extra_fields =
-                Some(serde_json::from_str(r#"{"extra_field": "extra_value"}"#).unwrap());
-            let data = vec![record];
-            let resolved_data = resolve_records(&data, *NOW).unwrap();
+        fn test_encode_decode_senml_data() {
             assert_eq!(
-                resolved_data[0].extra_fields,
-                Some(serde_json::from_str(r#"{"extra_field": "extra_value"}"#).unwrap())
+                decode_senml_data(&encode_senml_data(b"hello")).unwrap(),
+                b"hello"
+            );
+            assert_eq!(
+                decode_senml_data("aGkgCg").unwrap(),
+                vec![0x68, 0x69, 0x20, 0x0a]
             );
+            assert!(matches!(
+                decode_senml_data("aGkgCg==").unwrap_err(),
+                SinditSenMLError::InvalidBase64Value(_)
+            ));
+            assert!(decode_senml_data("a+/=").is_err());
         }
 
         #[test]
-        fn test_empty_extra_fields_are_skipped() {
-            let mut record = BASE_RECORD.clone();
-            record.extra_fields = Some(serde_json::from_str(r#"{}"#).unwrap());
-            let data = vec![record];
-            let resolved_data = resolve_records(&data, *NOW).unwrap();
-            assert_eq!(resolved_data[0].extra_fields, None);
+        fn test_value_and_record_base64() {
+            let value = SenMLValueField::from_base64("aGkgCg").unwrap();
+            assert_eq!(value, SenMLValueField::DataValue(b"hi \n".to_vec()));
+            assert_eq!(value.to_base64(), Some("aGkgCg".to_string()));
+            assert_eq!(
+                SenMLValueField::StringValue("aGkgCg".to_string()).to_base64(),
+                None
+            );
+
+            let mut records = parse_json(r#"[{"n": "nfc-reader", "v": 1}]"#, None).unwrap();
+            assert_eq!(records[0].get_data_base64(), None);
+            records[0].set_data(vec![0xfb, 0xff]);
+            assert_eq!(records[0].get_data_base64(), Some("-_8".to_string()));
+            assert_eq!(records[0].get_data_value(), Some(&vec![0xfb, 0xff]));
         }
+    }
+
+    mod test_validate_value {
+        use crate::*;
 
         #[test]
-        fn test_resolver_helpers() {
-            let mut records = resolve_records(&vec![BASE_RECORD.clone()], *NOW).unwrap();
-            let mut record = records.pop().unwrap();
-            // None, defaults to the float value
-            assert_eq!(record.get_bool_value(), None);
-            assert_eq!(record.get_string_value(), None);
-            assert_eq!(record.get_data_value(), None);
-            assert_eq!(record.get_float_value(), Some(10.0));
-            // Boolean
-            record.value = Some(SenMLValueField::BooleanValue(true));
-            assert_eq!(record.get_bool_value(), Some(true));
-            assert_eq!(record.get_string_value(), None);
-            assert_eq!(record.get_data_value(), None);
-            assert_eq!(record.get_float_value(), None);
-            // String
-            record.value = Some(SenMLValueField::StringValue("Hello world!".to_string()));
-            assert_eq!(record.get_bool_value(), None);
-            assert_eq!(record.get_string_value(), Some(&"Hello world!".to_string()));
-            assert_eq!(record.get_data_value(), None);
-            assert_eq!(record.get_float_value(), None);
-            // Data
-            record.value = Some(SenMLValueField::DataValue(Vec::from(
-                "Hello world!".as_bytes(),
-            )));
-            assert_eq!(record.get_bool_value(), None);
-            assert_eq!(record.get_string_value(), None);
-            assert_eq!(
-                record.get_data_value(),
-                Some(&Vec::from("Hello world!".as_bytes()))
-            );
-            assert_eq!(record.get_float_value(), None);
-            // Float
-            record.value = Some(SenMLValueField::FloatingPoint(10.0));
-            assert_eq!(record.get_bool_value(), None);
-            assert_eq!(record.get_string_value(), None);
-            assert_eq!(record.get_data_value(), None);
-            assert_eq!(record.get_float_value(), Some(10.0));
+        fn test_reject_empty_string_values() {
+            let data = r#"[{"n":"s","vs":""}]"#;
+            let options = ParseOptions {
+                reject_empty_string_values: true,
+                ..Default::default()
+            };
+            assert!(matches!(
+                parse_json_with_options(data, None, &options).unwrap_err(),
+                SinditSenMLError::EmptyStringValue(0)
+            ));
+
+            // Allowed by default
+            let records = parse_json(data, None).unwrap();
+            assert_eq!(records[0].get_string_value(), Some(&String::new()));
+            let records = parse_json_with_options(data, None, &ParseOptions::default()).unwrap();
+            assert_eq!(records[0].get_string_value(), Some(&String::new()));
+
+            assert!(matches!(
+                records[0].validate_value().unwrap_err(),
+                SinditSenMLError::EmptyStringValue(0)
+            ));
         }
 
         #[test]
-        fn test_zero_version_number() {
-            let mut record = BASE_RECORD.clone();
-            record.base_version = Some(0);
-            let data = vec![record];
+        fn test_validate_value() {
+            let mut record = SenMLResolvedRecord {
+                name: "abcd".to_string(),
+                unit: None,
+                value: Some(SenMLValueField::StringValue("Hello world!".to_string())),
+                sum: None,
+                time: DateTime::<Utc>::from_timestamp(1234567890, 0).unwrap(),
+                update_time: None,
+                base_version: None,
+                extra_fields: None,
+                active_base_name: None,
+            };
+            assert!(record.validate_value().is_ok());
+
+            record.value = Some(SenMLValueField::FloatingPoint(f64::NAN));
             assert!(matches!(
-                resolve_records(&data, *NOW).unwrap_err(),
-                SinditSenMLError::InvalidVersionNumber
+                record.validate_value().unwrap_err(),
+                SinditSenMLError::NonFiniteValue(0)
+            ));
+
+            record.value = None;
+            record.sum = Some(f64::INFINITY);
+            assert!(matches!(
+                record.validate_value().unwrap_err(),
+                SinditSenMLError::NonFiniteValue(0)
             ));
         }
     }
 
-    mod test_parse_json {
+    mod test_now_fn {
         use crate::*;
-        use chrono::Utc;
+        use std::cell::Cell;
 
         #[test]
-        fn test_empty() {
-            let data = "[]";
-            let resolved_data = parse_json(data, None);
-            assert!(resolved_data.is_ok());
-            assert_eq!(resolved_data.unwrap(), Vec::new());
+        fn test_parse_json_with_now_fn() {
+            let calls = Cell::new(0);
+            let now = DateTime::<Utc>::from_timestamp(1320067464, 0).unwrap();
+            let now_fn = || {
+                calls.set(calls.get() + 1);
+                now + chrono::Duration::seconds(calls.get())
+            };
+            let records = parse_json_with_now_fn(
+                r#"[
+                    {"n": "a", "v": 1, "t": 1320067400},
+                    {"n": "b", "v": 2, "t": -5},
+                    {"n": "c", "v": 3},
+                    {"bt": 1320067000, "n": "d", "v": 4, "t": 10}
+                ]"#,
+                now_fn,
+            )
+            .unwrap();
+            assert_eq!(calls.get(), 2);
+            assert_eq!(records[0].time.timestamp(), 1320067400);
+            assert_eq!(records[1].time.timestamp(), 1320067464 + 1 - 5);
+            assert_eq!(records[2].time.timestamp(), 1320067464 + 2);
+            assert_eq!(records[3].time.timestamp(), 1320067010);
         }
 
         #[test]
-        fn test_single_record() {
-            let data = r#"[{"n": "abcd", "v": 10.0}]"#;
-            let now = Utc::now();
-            let resolved_data = parse_json(data, Some(now));
-            assert!(resolved_data.is_ok());
-            assert_eq!(
-                resolved_data.unwrap(),
-                vec![SenMLResolvedRecord {
-                    name: "abcd".to_string(),
-                    unit: None,
-                    value: Some(SenMLValueField::FloatingPoint(10.0)),
-                    sum: None,
-                    time: now,
-                    update_time: None,
-                    base_version: None,
-                    extra_fields: None,
-                }]
-            );
+        fn test_resolve_records_with_now_fn_absolute() {
+            let calls = Cell::new(0);
+            let records: Vec<SenMLRecord> = serde_json::from_str(
+                r#"[{"bt": 1320067464, "n": "a", "v": 1},{"n": "b", "v": 2, "t": -5}]"#,
+            )
+            .unwrap();
+            let resolved = resolve_records_with_now_fn(&records, &|| {
+                calls.set(calls.get() + 1);
+                Utc::now()
+            })
+            .unwrap();
+            assert_eq!(calls.get(), 0);
+            assert_eq!(resolved[1].time.timestamp(), 1320067459);
         }
+    }
+
+    mod test_base_tracking {
+        use crate::fixtures::COLLECTION_OF_RESOURCES;
+        use crate::*;
 
         #[test]
-        fn test_multiple_records() {
-            let data = r#"[{"n": "abcd", "v": 10.0}, {"n": "efgh", "v": 20.0, "t": 1.5}]"#;
-            let now = Utc::now();
-            let now_in_1_5_seconds =
-                now + chrono::Duration::seconds(1) + chrono::Duration::milliseconds(500);
-            let resolved_data = parse_json(data, Some(now));
-            assert!(resolved_data.is_ok());
-            assert_eq!(
-                resolved_data.unwrap(),
-                vec![
-                    SenMLResolvedRecord {
-                        name: "abcd".to_string(),
-                        unit: None,
-                        value: Some(SenMLValueField::FloatingPoint(10.0)),
-                        sum: None,
-                        time: now,
-                        update_time: None,
-                        base_version: None,
-                        extra_fields: None,
-                    },
-                    SenMLResolvedRecord {
-                        name: "efgh".to_string(),
-                        unit: None,
-                        value: Some(SenMLValueField::FloatingPoint(20.0)),
-                        sum: None,
-                        time: now_in_1_5_seconds,
-                        update_time: None,
-                        base_version: None,
-                        extra_fields: None,
-                    }
-                ]
-            );
+        fn test_collection_of_resources() {
+            let records = parse_json_with_base_tracking(COLLECTION_OF_RESOURCES, None).unwrap();
+            assert_eq!(records.len(), 4);
+            assert_eq!(records[0].active_base_name.as_deref(), Some("2001:db8::2/"));
+            assert_eq!(records[1].active_base_name.as_deref(), Some("2001:db8::2/"));
+            assert_eq!(records[2].active_base_name.as_deref(), Some("2001:db8::1/"));
+            assert_eq!(records[3].active_base_name.as_deref(), Some("2001:db8::1/"));
+            assert_eq!(records[3].name, "2001:db8::1/humidity");
         }
 
         #[test]
-        fn test_record_with_extra_fields() {
-            let data = r#"[{"n": "abcd", "v": 10.0, "extra_field": "extra_value"}]"#;
-            let now = Utc::now();
-            let resolved_data = parse_json(data, Some(now));
-            assert!(resolved_data.is_ok());
+        fn test_not_tracked_by_default() {
+            let records = parse_json(COLLECTION_OF_RESOURCES, None).unwrap();
+            assert!(records
+                .iter()
+                .all(|record| record.active_base_name.is_none()));
+
+            let json = serde_json::to_string(
+                &parse_json_with_base_tracking(COLLECTION_OF_RESOURCES, None).unwrap(),
+            )
+            .unwrap();
+            assert_eq!(json, serde_json::to_string(&records).unwrap());
+        }
+
+        #[test]
+        fn test_no_base_name() {
+            let records =
+                parse_json_with_base_tracking(r#"[{"n": "temperature", "v": 42.0}]"#, None)
+                    .unwrap();
+            assert_eq!(records[0].active_base_name, None);
+        }
+    }
+
+    mod test_provenance {
+        use crate::*;
+
+        #[test]
+        fn test_multiple_datapoints_and_time() {
+            let records: Vec<SenMLRecord> = serde_json::from_str(
+                r#"[
+                    {"bn":"urn:dev:ow:10e2073a0108006:","bt":1.276020076001e+09,
+                    "bu":"A","bver":5,
+                    "n":"voltage","u":"V","v":120.1},
+                    {"n":"current","t":-5,"v":1.2},
+                    {"n":"current","t":-4,"v":1.3},
+                    {"n":"current","t":-3,"v":1.4},
+                    {"n":"current","t":-2,"v":1.5},
+                    {"n":"current","t":-1,"v":1.6},
+                    {"n":"current","v":1.7}
+                ]"#,
+            )
+            .unwrap();
+            let resolved = resolve_records_with_provenance(&records, Utc::now()).unwrap();
+            assert_eq!(resolved.len(), 7);
             assert_eq!(
-                resolved_data.unwrap(),
-                vec![SenMLResolvedRecord {
-                    name: "abcd".to_string(),
-                    unit: None,
-                    value: Some(SenMLValueField::FloatingPoint(10.0)),
-                    sum: None,
-                    time: now,
-                    update_time: None,
-                    base_version: None,
-                    extra_fields: Some(
-                        serde_json::from_str(r#"{"extra_field": "extra_value"}"#).unwrap()
-                    ),
-                }]
+                resolved
+                    .iter()
+                    .map(|resolved| resolved.record.clone())
+                    .collect::<Vec<_>>(),
+                resolve_records(&records, Utc::now()).unwrap()
             );
+
+            assert!(resolved[0].name_from_base);
+            assert!(!resolved[0].unit_from_base);
+            assert!(resolved[0].time_from_base);
+            for resolved in resolved[1..].iter() {
+                assert!(resolved.name_from_base);
+                assert!(resolved.unit_from_base);
+                assert!(resolved.time_from_base);
+            }
+            // Only the base time
+            assert_eq!(resolved[6].record.time.timestamp(), 1276020076);
+            for resolved in resolved.iter() {
+                assert!(!resolved.value_from_base);
+                assert!(!resolved.sum_from_base);
+            }
         }
 
         #[test]
-        fn test_invalid_json() {
-            let data = r#"[{"n": "abcd", "v": 10.0"#;
-            let resolved_data = parse_json(data, None);
-            assert!(matches!(
-                resolved_data.unwrap_err(),
-                SinditSenMLError::InvalidJSON(_)
-            ));
+        fn test_base_value_and_sum() {
+            let records: Vec<SenMLRecord> = serde_json::from_str(
+                r#"[
+                    {"n":"meter","bv":10,"v":1},
+                    {"n":"meter","vs":"reset"},
+                    {"n":"meter","bs":100,"s":5}
+                ]"#,
+            )
+            .unwrap();
+            let resolved = resolve_records_with_provenance(&records, Utc::now()).unwrap();
+            assert!(resolved[0].value_from_base);
+            assert!(!resolved[0].name_from_base);
+            assert!(!resolved[0].time_from_base);
+            assert!(!resolved[1].value_from_base);
+            assert!(resolved[2].value_from_base);
+            assert!(resolved[2].sum_from_base);
+            assert_eq!(resolved[2].record.sum, Some(105.0));
         }
     }
 
@@ -1107,6 +3941,7 @@ mod tests {
                 update_time: None,
                 base_version: Some(12),
                 extra_fields: None,
+                active_base_name: None,
             }];
             let serialised_data = serde_json::to_string(&data).unwrap();
             assert_eq!(
@@ -1130,6 +3965,7 @@ mod tests {
                     extra_fields: Some(
                         serde_json::from_str(r#"{"extra_field": "extra_value"}"#).unwrap(),
                     ),
+                    active_base_name: None,
                 },
                 SenMLResolvedRecord {
                     name: "efgh".to_string(),
@@ -1142,6 +3978,7 @@ mod tests {
                     update_time: None,
                     base_version: None,
                     extra_fields: Some(serde_json::from_str(r#"{"no":false}"#).unwrap()),
+                    active_base_name: None,
                 },
                 SenMLResolvedRecord {
                     name: "ijkl".to_string(),
@@ -1152,6 +3989,7 @@ mod tests {
                     update_time: None,
                     base_version: None,
                     extra_fields: None,
+                    active_base_name: None,
                 },
                 SenMLResolvedRecord {
                     name: "mnop".to_string(),
@@ -1162,6 +4000,7 @@ mod tests {
                     update_time: None,
                     base_version: None,
                     extra_fields: None,
+                    active_base_name: None,
                 },
             ];
             let serialised_data = serde_json::to_string(&data).unwrap();
@@ -1183,6 +4022,7 @@ mod tests {
                 update_time: None,
                 base_version: Some(11),
                 extra_fields: None,
+                active_base_name: None,
             }];
             let serialised_data = serde_json::to_string(&data).unwrap();
             let parsed_data: serde_json::Value = serde_json::from_str(&serialised_data).unwrap();
@@ -1202,6 +4042,7 @@ mod tests {
                 update_time: None,
                 base_version: Some(11),
                 extra_fields: None,
+                active_base_name: None,
             }];
             let serialised_data = serde_json::to_string(&data).unwrap();
             let parsed_data: serde_json::Value = serde_json::from_str(&serialised_data).unwrap();
@@ -1212,6 +4053,39 @@ mod tests {
             let parsed_records = parse_json(&serialised_data, None).unwrap();
             assert_eq!(parsed_records[0].get_data_value().unwrap(), b"//\xC2\xBB");
         }
+
+        #[test]
+        fn test_base64_output_variant() {
+            let data = vec![SenMLResolvedRecord {
+                name: "abcd".to_string(),
+                unit: None,
+                value: Some(SenMLValueField::DataValue(b"//\xC2\xBB Hello".to_vec())),
+                sum: None,
+                time: DateTime::<Utc>::from_timestamp(1234567890, 0).unwrap(),
+                update_time: None,
+                base_version: None,
+                extra_fields: None,
+                active_base_name: None,
+            }];
+
+            let options = SerializationOptions {
+                base64_output: Base64Variant::Standard,
+            };
+            assert_eq!(
+                to_json_with_options(&data, &options).unwrap(),
+                r#"[{"n":"abcd","vd":"Ly/CuyBIZWxsbw==","t":1234567890}]"#
+            );
+
+            // The default is the same as the serde serialisation
+            assert_eq!(
+                to_json_with_options(&data, &SerializationOptions::default()).unwrap(),
+                serde_json::to_string(&data).unwrap()
+            );
+            assert_eq!(
+                serde_json::to_string(&data).unwrap(),
+                r#"[{"n":"abcd","vd":"Ly_CuyBIZWxsbw","t":1234567890}]"#
+            );
+        }
     }
 
     mod test_crate_documentation_examples {
@@ -1238,6 +4112,7 @@ mod tests {
                 update_time: None,
                 base_version: None,
                 extra_fields: None,
+                active_base_name: None,
             };
             let json = serde_json::to_string(&vec![record]).unwrap();
             assert_eq!(
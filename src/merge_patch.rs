@@ -0,0 +1,321 @@
+//! # JSON Merge Patch of SenML records
+//!
+//! Devices can send only the fields that changed, as a JSON Merge Patch
+//! of the serialised record.
+//! <https://www.rfc-editor.org/rfc/rfc7396>
+//!
+//! The patch uses the SenML JSON labels, `n`, `u`, `v`, `vs`, `vb`, `vd`,
+//! `s`, `t`, `ut`, and `bver`. The other members are extra fields.
+
+use chrono::Utc;
+use serde_json::{Map, Value};
+
+use crate::time::convert_senml_time;
+use crate::validate_name::validate_name;
+use crate::{Base64AcceptPolicy, SenMLResolvedRecord, SenMLValueField, SinditSenMLError};
+
+const VALUE_LABELS: [&str; 4] = ["v", "vs", "vb", "vd"];
+
+fn value_label(value: &SenMLValueField) -> &'static str {
+    match value {
+        SenMLValueField::FloatingPoint(_) => "v",
+        SenMLValueField::StringValue(_) => "vs",
+        SenMLValueField::BooleanValue(_) => "vb",
+        SenMLValueField::DataValue(_) => "vd",
+    }
+}
+
+fn patch_value(label: &str, patch: &Value) -> Result<SenMLValueField, SinditSenMLError> {
+    match (label, patch) {
+        ("v", Value::Number(number)) => number
+            .as_f64()
+            .filter(|value| value.is_finite())
+            .map(SenMLValueField::FloatingPoint)
+            .ok_or(SinditSenMLError::WrongValueType),
+        ("vs", Value::String(value)) => Ok(SenMLValueField::StringValue(value.clone())),
+        ("vb", Value::Bool(value)) => Ok(SenMLValueField::BooleanValue(*value)),
+        ("vd", Value::String(value)) => Ok(SenMLValueField::DataValue(
+            Base64AcceptPolicy::default().decode(value)?,
+        )),
+        _ => Err(SinditSenMLError::WrongValueType),
+    }
+}
+
+fn patch_number(patch: &Value) -> Result<Option<f64>, SinditSenMLError> {
+    match patch {
+        Value::Null => Ok(None),
+        Value::Number(number) => number
+            .as_f64()
+            .filter(|value| value.is_finite())
+            .map(Some)
+            .ok_or(SinditSenMLError::WrongValueType),
+        _ => Err(SinditSenMLError::WrongValueType),
+    }
+}
+
+/// Apply a JSON Merge Patch to a value, as in the RFC.
+fn merge_value(target: &mut Value, patch: &Value) {
+    let Value::Object(patch) = patch else {
+        *target = patch.clone();
+        return;
+    };
+    if !target.is_object() {
+        *target = Value::Object(Map::new());
+    }
+    if let Value::Object(target) = target {
+        for (key, value) in patch {
+            if value.is_null() {
+                target.remove(key);
+            } else {
+                merge_value(target.entry(key.clone()).or_insert(Value::Null), value);
+            }
+        }
+    }
+}
+
+/// Apply a JSON Merge Patch to a record.
+///
+/// A `null` removes the field. The name and the time cannot be removed.
+/// A new value replaces the value of the record, whatever its type.
+/// The record is not modified if the patch is invalid.
+///
+/// # Arguments
+/// * `record` - The record to update.
+/// * `patch` - The patch, a JSON object.
+/// # Returns
+/// * `Result<(), SinditSenMLError>` - `NonNullableField` if the patch removes
+///   the name or the time, `WrongValueType` if a SenML field has the wrong type.
+/// # Examples
+/// ```
+/// use sindit_senml::{parse_json, merge_patch::apply_merge_patch, SenMLValueField};
+///
+/// let mut records = parse_json(r#"[{"n": "temperature", "u": "Cel", "v": 23.1, "t": 1320067464}]"#, None).unwrap();
+/// apply_merge_patch(&mut records[0], &serde_json::json!({"v": 23.4, "u": null})).unwrap();
+/// assert_eq!(records[0].value, Some(SenMLValueField::FloatingPoint(23.4)));
+/// assert_eq!(records[0].unit, None);
+/// ```
+pub fn apply_merge_patch(
+    record: &mut SenMLResolvedRecord,
+    patch: &Value,
+) -> Result<(), SinditSenMLError> {
+    let Value::Object(patch) = patch else {
+        return Err(SinditSenMLError::InvalidJSON(serde::de::Error::custom(
+            "a merge patch of a record must be an object",
+        )));
+    };
+
+    let mut patched = record.clone();
+    let new_values: Vec<(&str, &Value)> = VALUE_LABELS
+        .iter()
+        .filter_map(|label| patch.get(*label).map(|value| (*label, value)))
+        .filter(|(_, value)| !value.is_null())
+        .collect();
+    if new_values.len() > 1 {
+        return Err(SinditSenMLError::OnlyOneValuePerRecord(0));
+    }
+
+    for (key, value) in patch {
+        match key.as_str() {
+            "n" => match value {
+                Value::Null => return Err(SinditSenMLError::NonNullableField(key.clone())),
+                Value::String(name) => {
+                    if !validate_name(name) {
+                        return Err(SinditSenMLError::InvalidName);
+                    }
+                    patched.name = name.clone();
+                }
+                _ => return Err(SinditSenMLError::WrongValueType),
+            },
+            "u" => match value {
+                Value::Null => patched.unit = None,
+                Value::String(unit) => patched.unit = Some(unit.clone()),
+                _ => return Err(SinditSenMLError::WrongValueType),
+            },
+            "v" | "vs" | "vb" | "vd" => {
+                if value.is_null() {
+                    // Only the label of the current value is a member of the record.
+                    if patched.value.as_ref().map(value_label) == Some(key.as_str()) {
+                        patched.value = None;
+                    }
+                }
+            }
+            "s" => patched.sum = patch_number(value)?,
+            "t" => {
+                // Relative times are relative to now, as in `parse_json`.
+                let seconds = patch_number(value)?
+                    .ok_or_else(|| SinditSenMLError::NonNullableField(key.clone()))?;
+                patched.time =
+                    convert_senml_time(seconds, Utc::now()).ok_or(SinditSenMLError::InvalidTime)?;
+            }
+            "ut" => patched.update_time = patch_number(value)?,
+            "bver" => match value {
+                Value::Null => patched.base_version = None,
+                Value::Number(number) => {
+                    patched.base_version = Some(
+                        number
+                            .as_u64()
+                            .ok_or(SinditSenMLError::InvalidVersionNumber)?,
+                    )
+                }
+                _ => return Err(SinditSenMLError::WrongValueType),
+            },
+            _ => {
+                let extra_fields = patched.extra_fields.get_or_insert_with(Default::default);
+                if value.is_null() {
+                    extra_fields.remove(key);
+                } else {
+                    merge_value(
+                        extra_fields.entry(key.clone()).or_insert(Value::Null),
+                        value,
+                    );
+                }
+                if extra_fields.is_empty() {
+                    patched.extra_fields = None;
+                }
+            }
+        }
+    }
+    if let Some((label, value)) = new_values.first() {
+        patched.value = Some(patch_value(label, value)?);
+    }
+
+    *record = patched;
+    Ok(())
+}
+
+/// Build the JSON Merge Patch of two JSON values, as in the RFC.
+fn diff_values(old: &Value, new: &Value) -> Option<Value> {
+    match (old, new) {
+        (Value::Object(old), Value::Object(new)) => {
+            let mut patch = Map::new();
+            for key in old.keys() {
+                if !new.contains_key(key) {
+                    patch.insert(key.clone(), Value::Null);
+                }
+            }
+            for (key, new_value) in new {
+                match old.get(key) {
+                    Some(old_value) => {
+                        if let Some(value_patch) = diff_values(old_value, new_value) {
+                            patch.insert(key.clone(), value_patch);
+                        }
+                    }
+                    None => {
+                        patch.insert(key.clone(), new_value.clone());
+                    }
+                }
+            }
+            if patch.is_empty() {
+                None
+            } else {
+                Some(Value::Object(patch))
+            }
+        }
+        _ if old == new => None,
+        // A null cannot be set by a merge patch, it is removed instead.
+        _ => Some(new.clone()),
+    }
+}
+
+/// Create the minimal JSON Merge Patch that transforms `old` into `new`,
+/// see [`apply_merge_patch`].
+///
+/// # Returns
+/// * `serde_json::Value` - The patch, an empty object if the records are equal.
+pub fn create_merge_patch(old: &SenMLResolvedRecord, new: &SenMLResolvedRecord) -> Value {
+    let old = serde_json::to_value(old).unwrap_or(Value::Null);
+    let new = serde_json::to_value(new).unwrap_or(Value::Null);
+    diff_values(&old, &new).unwrap_or_else(|| Value::Object(Map::new()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_json;
+    use serde_json::json;
+
+    #[test]
+    fn test_create_and_apply_merge_patch() {
+        let records = parse_json(
+            r#"[
+                {"n": "temperature", "u": "Cel", "v": 23.1, "t": 1320067464, "location": {"room": "1", "floor": 2}},
+                {"n": "temperature", "u": "K", "v": 296.25, "t": 1320067464, "location": {"room": "1"}}
+            ]"#,
+            None,
+        )
+        .unwrap();
+        let patch = create_merge_patch(&records[0], &records[1]);
+        assert_eq!(
+            patch,
+            json!({"u": "K", "v": 296.25, "location": {"floor": null}})
+        );
+
+        let mut record = records[0].clone();
+        apply_merge_patch(&mut record, &patch).unwrap();
+        assert_eq!(record, records[1]);
+        assert_eq!(create_merge_patch(&record, &records[1]), json!({}));
+    }
+
+    #[test]
+    fn test_merge_patch_value_type() {
+        let records = parse_json(
+            r#"[
+                {"n": "status", "v": 1, "t": 1320067464, "ut": 10},
+                {"n": "status", "vs": "on", "t": 1320067465}
+            ]"#,
+            None,
+        )
+        .unwrap();
+        let patch = create_merge_patch(&records[0], &records[1]);
+        assert_eq!(
+            patch,
+            json!({"v": null, "vs": "on", "t": 1320067465, "ut": null})
+        );
+
+        let mut record = records[0].clone();
+        apply_merge_patch(&mut record, &patch).unwrap();
+        assert_eq!(record, records[1]);
+
+        apply_merge_patch(&mut record, &json!({"v": null})).unwrap();
+        assert_eq!(record, records[1]);
+        apply_merge_patch(&mut record, &json!({"vs": null, "location": "room1"})).unwrap();
+        assert_eq!(record.value, None);
+        assert_eq!(record.extra_fields.unwrap()["location"], json!("room1"));
+    }
+
+    #[test]
+    fn test_invalid_merge_patch() {
+        let mut records = parse_json(
+            r#"[{"n": "temperature", "v": 23.1, "t": 1320067464}]"#,
+            None,
+        )
+        .unwrap();
+        let original = records[0].clone();
+
+        assert!(matches!(
+            apply_merge_patch(&mut records[0], &json!({"n": null})).unwrap_err(),
+            SinditSenMLError::NonNullableField(field) if field == "n"
+        ));
+        assert!(matches!(
+            apply_merge_patch(&mut records[0], &json!({"u": "K", "t": null})).unwrap_err(),
+            SinditSenMLError::NonNullableField(field) if field == "t"
+        ));
+        assert!(matches!(
+            apply_merge_patch(&mut records[0], &json!({"v": "23.4"})).unwrap_err(),
+            SinditSenMLError::WrongValueType
+        ));
+        assert!(matches!(
+            apply_merge_patch(&mut records[0], &json!({"n": "bad name"})).unwrap_err(),
+            SinditSenMLError::InvalidName
+        ));
+        assert!(matches!(
+            apply_merge_patch(&mut records[0], &json!({"v": 1, "vb": true})).unwrap_err(),
+            SinditSenMLError::OnlyOneValuePerRecord(0)
+        ));
+        assert!(matches!(
+            apply_merge_patch(&mut records[0], &json!([])).unwrap_err(),
+            SinditSenMLError::InvalidJSON(_)
+        ));
+        assert_eq!(records[0], original);
+    }
+}
@@ -0,0 +1,130 @@
+//! # SenML MessagePack
+//!
+//! MessagePack representation of SenML, with the same structure and the
+//! same text labels as SenML JSON. It is more compact than JSON, and
+//! decoders without knowledge of SenML get the same maps as from the JSON.
+//! <https://github.com/msgpack/msgpack/blob/master/spec.md>
+//!
+//! The data values are base64 strings, as in JSON.
+//!
+//! This is available with the `msgpack` feature.
+
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+
+use crate::{resolve_records, SenMLRecord, SenMLResolvedRecord, SinditSenMLError};
+
+/// Parse a SenML MessagePack pack and return SenMLResolvedRecords.
+///
+/// The pack is resolved like a SenML JSON pack, see [`crate::parse_json`].
+///
+/// # Arguments
+/// * `bytes` - The SenML MessagePack pack, an array of maps.
+/// * `now` - The current time. Defaults to current UTC time.
+/// # Returns
+/// * `Result<Vec<SenMLResolvedRecord>, SinditSenMLError>` - The parsed SenML records,
+///   `InvalidMsgPack` if the bytes are not MessagePack, or `InvalidJSON` if
+///   the maps are not SenML records.
+/// # Examples
+/// ```
+/// use sindit_senml::msgpack::parse_msgpack;
+///
+/// // [{"n": "temp", "v": 1}]
+/// let bytes = [0x91, 0x82, 0xa1, 0x6e, 0xa4, 0x74, 0x65, 0x6d, 0x70, 0xa1, 0x76, 0x01];
+/// let records = parse_msgpack(&bytes, None).unwrap();
+/// assert_eq!(records[0].name, "temp");
+/// assert_eq!(records[0].get_float_value(), Some(1.0));
+/// ```
+pub fn parse_msgpack(
+    bytes: &[u8],
+    now: Option<DateTime<Utc>>,
+) -> Result<Vec<SenMLResolvedRecord>, SinditSenMLError> {
+    let mut reader = bytes;
+    let json: Value =
+        rmp_serde::from_read(&mut reader).map_err(|_| SinditSenMLError::InvalidMsgPack)?;
+    if !reader.is_empty() || !json.is_array() {
+        return Err(SinditSenMLError::InvalidMsgPack);
+    }
+    let records: Vec<SenMLRecord> = serde_json::from_value(json)?;
+    resolve_records(&records, now.unwrap_or(Utc::now()))
+}
+
+/// Encode resolved records to a SenML MessagePack pack.
+///
+/// The records have the same fields as in their JSON serialisation.
+///
+/// # Examples
+/// ```
+/// use sindit_senml::{msgpack::{parse_msgpack, to_msgpack_bytes}, parse_json};
+///
+/// let records = parse_json(r#"[{"n": "temp", "v": 23.1, "t": 1320067464}]"#, None).unwrap();
+/// assert_eq!(parse_msgpack(&to_msgpack_bytes(&records), None).unwrap(), records);
+/// ```
+pub fn to_msgpack_bytes(records: &[SenMLResolvedRecord]) -> Vec<u8> {
+    // Serialising records does not fail as all the keys are strings.
+    rmp_serde::to_vec_named(records).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::MULTIPLE_DATATYPES;
+    use crate::parse_json;
+
+    #[test]
+    fn test_msgpack_roundtrip() {
+        let now = DateTime::from_timestamp(1700000000, 0).unwrap();
+        let records = parse_json(MULTIPLE_DATATYPES, Some(now)).unwrap();
+        let bytes = to_msgpack_bytes(&records);
+        assert_eq!(parse_msgpack(&bytes, None).unwrap(), records);
+        assert!(bytes.len() < serde_json::to_string(&records).unwrap().len());
+
+        let records = parse_json(
+            r#"[{"bver": 5, "n": "energy", "u": "J", "s": -70000, "t": 1320067464.5,
+                 "ut": 60, "location": {"room": 1, "floor": -2}}]"#,
+            None,
+        )
+        .unwrap();
+        assert_eq!(
+            parse_msgpack(&to_msgpack_bytes(&records), None).unwrap(),
+            records
+        );
+        assert_eq!(to_msgpack_bytes(&[]), vec![0x90]);
+        assert!(parse_msgpack(&[0x90], None).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_same_maps_as_json() {
+        let records = parse_json(MULTIPLE_DATATYPES, None).unwrap();
+        let value: Value = rmp_serde::from_slice(&to_msgpack_bytes(&records)).unwrap();
+        assert_eq!(value, serde_json::to_value(&records).unwrap());
+    }
+
+    #[test]
+    fn test_invalid_senml_msgpack() {
+        // Truncated, trailing bytes, not an array, integer keys, and
+        // never used marker.
+        for bytes in [
+            &[0x91, 0x82, 0xa1][..],
+            &[0x90, 0x90][..],
+            &[0x80][..],
+            &[0x91, 0x81, 0x00, 0xa1, 0x61][..],
+            &[0xc1][..],
+        ] {
+            assert!(matches!(
+                parse_msgpack(bytes, None),
+                Err(SinditSenMLError::InvalidMsgPack)
+            ));
+        }
+        // Array nested too deeply.
+        assert!(matches!(
+            parse_msgpack(&[0x91; 100], None),
+            Err(SinditSenMLError::InvalidMsgPack)
+        ));
+        // MessagePack, but not SenML.
+        assert!(matches!(
+            parse_msgpack(&[0x91, 0x81, 0xa1, 0x76, 0xa1, 0x61], None),
+            Err(SinditSenMLError::InvalidJSON(_))
+        ));
+    }
+}
@@ -0,0 +1,615 @@
+//! # SenML Pack containers
+//!
+//! Types wrapping a list of resolved SenML records (a SenML Pack)
+//! to make them easier to share and query.
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+use std::sync::Arc;
+
+use chrono::{DateTime, Duration, Utc};
+
+use crate::{parse_json, SenMLResolvedRecord, SenMLValueField, SinditSenMLError};
+
+/// Owned SenML Pack, the records of a pack in order.
+///
+/// This derefs to the `Vec` of records, and is displayed as a SenML JSON array.
+///
+/// # Examples
+/// ```
+/// use sindit_senml::pack::SenMLPack;
+///
+/// let mut pack = SenMLPack::parse_json(r#"[{"n": "temperature", "v": 42.0, "t": 1320067464}]"#, None).unwrap();
+/// pack.extend(SenMLPack::parse_json(r#"[{"n": "humidity", "v": 40.0, "t": 1320067464}]"#, None).unwrap());
+/// assert_eq!(pack.len(), 2);
+/// assert_eq!(
+///     pack.to_string(),
+///     r#"[{"n":"temperature","v":42,"t":1320067464},{"n":"humidity","v":40,"t":1320067464}]"#
+/// );
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SenMLPack(Vec<SenMLResolvedRecord>);
+
+impl SenMLPack {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parse SenML JSON into a new pack, see [`parse_json`].
+    ///
+    /// # Arguments
+    /// * `json_str` - The SenML JSON string to parse.
+    /// * `now` - The current time. Defaults to current UTC time.
+    pub fn parse_json(
+        json_str: &str,
+        now: Option<DateTime<Utc>>,
+    ) -> Result<Self, SinditSenMLError> {
+        Ok(SenMLPack(parse_json(json_str, now)?))
+    }
+
+    /// Serialise the records to a SenML JSON array.
+    pub fn to_json(&self) -> String {
+        // The serialisation only fails on map keys that are not strings,
+        // and the keys of a record are strings.
+        serde_json::to_string(&self.0).unwrap_or_default()
+    }
+
+    pub fn into_records(self) -> Vec<SenMLResolvedRecord> {
+        self.0
+    }
+}
+
+impl Deref for SenMLPack {
+    type Target = Vec<SenMLResolvedRecord>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for SenMLPack {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl From<Vec<SenMLResolvedRecord>> for SenMLPack {
+    fn from(records: Vec<SenMLResolvedRecord>) -> Self {
+        SenMLPack(records)
+    }
+}
+
+impl FromIterator<SenMLResolvedRecord> for SenMLPack {
+    fn from_iter<I: IntoIterator<Item = SenMLResolvedRecord>>(iter: I) -> Self {
+        SenMLPack(iter.into_iter().collect())
+    }
+}
+
+impl Extend<SenMLResolvedRecord> for SenMLPack {
+    fn extend<I: IntoIterator<Item = SenMLResolvedRecord>>(&mut self, iter: I) {
+        self.0.extend(iter);
+    }
+}
+
+impl IntoIterator for SenMLPack {
+    type Item = SenMLResolvedRecord;
+    type IntoIter = std::vec::IntoIter<SenMLResolvedRecord>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a SenMLPack {
+    type Item = &'a SenMLResolvedRecord;
+    type IntoIter = std::slice::Iter<'a, SenMLResolvedRecord>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a mut SenMLPack {
+    type Item = &'a mut SenMLResolvedRecord;
+    type IntoIter = std::slice::IterMut<'a, SenMLResolvedRecord>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter_mut()
+    }
+}
+
+impl fmt::Display for SenMLPack {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_json())
+    }
+}
+
+/// Immutable view over a SenML Pack with shared ownership.
+///
+/// The records are stored behind an `Arc`, so cloning a view is cheap
+/// and the same pack can be read from multiple threads.
+///
+/// # Examples
+/// ```
+/// use sindit_senml::pack::SenMLPackView;
+///
+/// let view = SenMLPackView::from_parse(r#"[{"n": "temperature", "v": 42.0}]"#, None).unwrap();
+/// let other_view = view.clone();
+/// assert_eq!(other_view.len(), 1);
+/// assert_eq!(other_view[0].name, "temperature");
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct SenMLPackView(Arc<Vec<SenMLResolvedRecord>>);
+
+impl SenMLPackView {
+    /// Parse SenML JSON into a new view.
+    ///
+    /// # Arguments
+    /// * `json_str` - The SenML JSON string to parse.
+    /// * `now` - The current time. Defaults to current UTC time.
+    pub fn from_parse(
+        json_str: &str,
+        now: Option<DateTime<Utc>>,
+    ) -> Result<Self, SinditSenMLError> {
+        Ok(SenMLPackView(Arc::new(parse_json(json_str, now)?)))
+    }
+
+    /// Create a view from a copy of the given records.
+    pub fn snapshot(records: &[SenMLResolvedRecord]) -> Self {
+        SenMLPackView(Arc::new(records.to_vec()))
+    }
+
+    /// Group the records by name, preserving the order of the records
+    /// within each group.
+    pub fn group_by_name(&self) -> HashMap<&str, Vec<&SenMLResolvedRecord>> {
+        crate::pack_ops::group_by_name_ref(self)
+    }
+
+    /// Records with a time in the range `[start, end[`.
+    pub fn filter_by_time_range(
+        &self,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Vec<&SenMLResolvedRecord> {
+        crate::pack_ops::filter_by_time_range(self, start, end)
+    }
+
+    /// Most recent value of each name.
+    ///
+    /// This is [`crate::pack_ops::latest_by_name`] without the names whose
+    /// most recent record has no value.
+    pub fn latest_values(&self) -> HashMap<&str, &SenMLValueField> {
+        crate::pack_ops::latest_by_name(self)
+            .into_iter()
+            .filter_map(|(name, record)| record.value.as_ref().map(|value| (name, value)))
+            .collect()
+    }
+}
+
+impl Deref for SenMLPackView {
+    type Target = [SenMLResolvedRecord];
+
+    fn deref(&self) -> &Self::Target {
+        self.0.as_slice()
+    }
+}
+
+impl From<Vec<SenMLResolvedRecord>> for SenMLPackView {
+    fn from(records: Vec<SenMLResolvedRecord>) -> Self {
+        SenMLPackView(Arc::new(records))
+    }
+}
+
+/// Bounded buffer of records, evicting the oldest ones.
+///
+/// The buffer keeps at most `max_records` records, and the records older
+/// than `max_age` are evicted. Without limits, the buffer grows without bounds.
+///
+/// # Examples
+/// ```
+/// use sindit_senml::{parse_json, pack::SenMLRingBuffer};
+///
+/// let mut buffer = SenMLRingBuffer::new(Some(2), None);
+/// for record in parse_json(r#"[{"n": "a"},{"n": "b"},{"n": "c"}]"#, None).unwrap() {
+///     buffer.push(record);
+/// }
+/// assert_eq!(buffer.len(), 2);
+/// assert_eq!(buffer.as_slice()[0].name, "b");
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct SenMLRingBuffer {
+    records: VecDeque<SenMLResolvedRecord>,
+    max_records: Option<usize>,
+    max_age: Option<Duration>,
+}
+
+impl SenMLRingBuffer {
+    /// Create a new empty buffer.
+    ///
+    /// # Arguments
+    /// * `max_records` - The maximum number of records, unbounded if `None`.
+    /// * `max_age` - The maximum age of the records, unbounded if `None`.
+    pub fn new(max_records: Option<usize>, max_age: Option<Duration>) -> Self {
+        SenMLRingBuffer {
+            records: VecDeque::new(),
+            max_records,
+            max_age,
+        }
+    }
+
+    /// Add a record at the end of the buffer.
+    ///
+    /// The oldest records are evicted if the buffer is full.
+    /// The records older than `max_age` before the time of the new record
+    /// are evicted, see [`SenMLRingBuffer::evict_stale`].
+    pub fn push(&mut self, record: SenMLResolvedRecord) {
+        let time = record.time;
+        self.records.push_back(record);
+        if let Some(max_records) = self.max_records {
+            while self.records.len() > max_records {
+                self.records.pop_front();
+            }
+        }
+        self.evict_stale(time);
+        if !self.records.as_slices().1.is_empty() {
+            // The records wrapped around the end of the deque. They are moved
+            // back together for as_slice, with room for as many records, so
+            // this happens at most once every `len` pushes.
+            self.records.reserve(self.records.len());
+            self.records.make_contiguous();
+        }
+    }
+
+    /// Remove the oldest record of the buffer.
+    pub fn pop_front(&mut self) -> Option<SenMLResolvedRecord> {
+        self.records.pop_front()
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    /// The records of the buffer, from the oldest to the newest pushed.
+    pub fn as_slice(&self) -> &[SenMLResolvedRecord] {
+        // Only push_back can wrap the records, and push makes them
+        // contiguous again.
+        self.records.as_slices().0
+    }
+
+    /// Evict the records older than `max_age` before `now`.
+    ///
+    /// The records are expected to be pushed in time order: the eviction
+    /// stops at the first record that is not older than `max_age`.
+    ///
+    /// # Returns
+    /// * `usize` - The number of evicted records.
+    pub fn evict_stale(&mut self, now: DateTime<Utc>) -> usize {
+        let length = self.records.len();
+        if let Some(max_age) = self.max_age {
+            while matches!(self.records.front(), Some(record) if now - record.time > max_age) {
+                self.records.pop_front();
+            }
+        }
+        length - self.records.len()
+    }
+}
+
+fn sorted_set_key(name: &str, time: DateTime<Utc>) -> (String, i128) {
+    (
+        name.to_string(),
+        time.timestamp() as i128 * 1_000_000_000 + time.timestamp_subsec_nanos() as i128,
+    )
+}
+
+/// Set of records sorted by name and time, with at most one record
+/// for each name and time.
+///
+/// # Examples
+/// ```
+/// use sindit_senml::{parse_json, pack::SenMLSortedSet};
+///
+/// let records = parse_json(r#"[{"n": "b", "t": 1320067464},{"n": "a", "t": 1320067464},{"n": "b", "t": 1320067464}]"#, None).unwrap();
+/// let set = SenMLSortedSet::from_records(records);
+/// assert_eq!(set.len(), 2);
+/// assert_eq!(set.iter().next().unwrap().name, "a");
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SenMLSortedSet(BTreeMap<(String, i128), SenMLResolvedRecord>);
+
+impl SenMLSortedSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a set from records, the last record of each name and time is kept.
+    pub fn from_records(records: Vec<SenMLResolvedRecord>) -> Self {
+        let mut set = Self::new();
+        for record in records {
+            set.insert(record);
+        }
+        set
+    }
+
+    /// Insert a record, replacing the record with the same name and time.
+    ///
+    /// # Returns
+    /// * `bool` - `true` if there was no record with the same name and time.
+    pub fn insert(&mut self, record: SenMLResolvedRecord) -> bool {
+        self.0
+            .insert(sorted_set_key(&record.name, record.time), record)
+            .is_none()
+    }
+
+    /// Remove the record with the given name and time.
+    pub fn remove(&mut self, name: &str, time: DateTime<Utc>) -> Option<SenMLResolvedRecord> {
+        self.0.remove(&sorted_set_key(name, time))
+    }
+
+    pub fn contains(&self, name: &str, time: DateTime<Utc>) -> bool {
+        self.0.contains_key(&sorted_set_key(name, time))
+    }
+
+    /// The records, sorted by name and time.
+    pub fn iter(&self) -> impl Iterator<Item = &SenMLResolvedRecord> {
+        self.0.values()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// The records, sorted by name and time.
+    pub fn into_records(self) -> Vec<SenMLResolvedRecord> {
+        self.0.into_values().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use static_assertions::assert_impl_all;
+    use std::thread;
+
+    assert_impl_all!(SenMLPackView: Send, Sync, Clone);
+
+    const PACK: &str = r#"[
+        {"bn": "urn:dev:ow:10e2073a01080063:", "bt": 1320067464, "n": "temp", "v": 20.0},
+        {"n": "temp", "t": 60, "v": 21.0},
+        {"n": "open", "vb": false},
+        {"n": "open", "t": 120, "vb": true}
+    ]"#;
+
+    #[test]
+    fn test_pack() {
+        let now = DateTime::from_timestamp(1700000000, 0).unwrap();
+        let records = parse_json(PACK, Some(now)).unwrap();
+        let mut pack = SenMLPack::parse_json(PACK, Some(now)).unwrap();
+        assert_eq!(pack.as_slice(), records.as_slice());
+        assert_eq!(pack.to_string(), serde_json::to_string(&records).unwrap());
+        assert_eq!(SenMLPack::parse_json(&pack.to_json(), None).unwrap(), pack);
+
+        for record in &mut pack {
+            record.unit = Some("Cel".to_string());
+        }
+        pack.retain(|record| record.get_float_value().is_some());
+        assert_eq!(pack.len(), 2);
+        assert!((&pack).into_iter().all(|record| record.unit.is_some()));
+
+        let mut collected: SenMLPack = records.clone().into_iter().rev().collect();
+        collected.extend(pack);
+        assert_eq!(collected.len(), 6);
+        assert_eq!(collected[0], records[3]);
+        assert_eq!(collected.into_iter().count(), 6);
+
+        assert_eq!(SenMLPack::new().to_string(), "[]");
+        assert_eq!(SenMLPack::from(records.clone()).into_records(), records);
+    }
+
+    #[test]
+    fn test_clone_shares_records() {
+        let view = SenMLPackView::from_parse(PACK, None).unwrap();
+        let other_view = view.clone();
+        assert!(Arc::ptr_eq(&view.0, &other_view.0));
+        assert_eq!(view, other_view);
+    }
+
+    #[test]
+    fn test_snapshot() {
+        let records = parse_json(PACK, None).unwrap();
+        let view = SenMLPackView::snapshot(&records);
+        assert_eq!(&view[..], records.as_slice());
+    }
+
+    #[test]
+    fn test_read_from_threads() {
+        let view = SenMLPackView::from_parse(PACK, None).unwrap();
+        let handles: Vec<_> = [view.clone(), view.clone()]
+            .into_iter()
+            .map(|view| {
+                thread::spawn(move || {
+                    let groups = view.group_by_name();
+                    (
+                        view.len(),
+                        groups.len(),
+                        groups["urn:dev:ow:10e2073a01080063:temp"].len(),
+                    )
+                })
+            })
+            .collect();
+        for handle in handles {
+            assert_eq!(handle.join().unwrap(), (4, 2, 2));
+        }
+    }
+
+    #[test]
+    fn test_filter_by_time_range() {
+        let view = SenMLPackView::from_parse(PACK, None).unwrap();
+        let start = DateTime::<Utc>::from_timestamp(1320067464, 0).unwrap();
+        let end = DateTime::<Utc>::from_timestamp(1320067524, 0).unwrap();
+        let records = view.filter_by_time_range(start, end);
+        assert_eq!(records.len(), 2);
+        assert!(records.iter().all(|record| record.time == start));
+    }
+
+    #[test]
+    fn test_latest_values() {
+        let view = SenMLPackView::from_parse(PACK, None).unwrap();
+        let latest = view.latest_values();
+        assert_eq!(latest.len(), 2);
+        assert_eq!(
+            latest["urn:dev:ow:10e2073a01080063:temp"],
+            &SenMLValueField::FloatingPoint(21.0)
+        );
+        assert_eq!(
+            latest["urn:dev:ow:10e2073a01080063:open"],
+            &SenMLValueField::BooleanValue(true)
+        );
+
+        let view = SenMLPackView::from_parse(
+            r#"[{"n": "energy", "v": 1, "t": 1320067464}, {"n": "energy", "s": 2, "t": 1320067524}]"#,
+            None,
+        )
+        .unwrap();
+        assert!(view.latest_values().is_empty());
+    }
+
+    #[test]
+    fn test_ring_buffer_max_records() {
+        let records = parse_json(
+            &format!(
+                "[{}]",
+                (0..15)
+                    .map(|index| format!(
+                        r#"{{"n": "sensor", "t": {}, "v": {}}}"#,
+                        1320067464 + index,
+                        index
+                    ))
+                    .collect::<Vec<_>>()
+                    .join(",")
+            ),
+            None,
+        )
+        .unwrap();
+        let mut buffer = SenMLRingBuffer::new(Some(10), None);
+        for record in records.iter() {
+            buffer.push(record.clone());
+        }
+        assert_eq!(buffer.len(), 10);
+        assert_eq!(buffer.as_slice(), &records[5..]);
+        assert_eq!(buffer.pop_front(), Some(records[5].clone()));
+        assert_eq!(buffer.len(), 9);
+    }
+
+    #[test]
+    fn test_ring_buffer_wraps() {
+        let records = parse_json(
+            &format!(
+                "[{}]",
+                (0..1000)
+                    .map(|index| format!(r#"{{"n": "sensor", "v": {}}}"#, index))
+                    .collect::<Vec<_>>()
+                    .join(",")
+            ),
+            None,
+        )
+        .unwrap();
+        let mut buffer = SenMLRingBuffer::new(Some(10), None);
+        let mut expected: Vec<SenMLResolvedRecord> = Vec::new();
+        for (index, record) in records.iter().enumerate() {
+            buffer.push(record.clone());
+            expected.push(record.clone());
+            if expected.len() > 10 {
+                expected.remove(0);
+            }
+            if index % 7 == 0 {
+                assert_eq!(buffer.pop_front().as_ref(), expected.first());
+                expected.remove(0);
+            }
+            assert_eq!(buffer.as_slice(), &expected[..]);
+        }
+    }
+
+    #[test]
+    fn test_ring_buffer_max_age() {
+        let records = parse_json(
+            r#"[
+                {"bn": "sensor", "bt": 1320067464, "v": 1},
+                {"t": 30, "v": 2},
+                {"t": 90, "v": 3},
+                {"t": 100, "v": 4}
+            ]"#,
+            None,
+        )
+        .unwrap();
+        let mut buffer = SenMLRingBuffer::new(None, Some(Duration::seconds(60)));
+        buffer.push(records[0].clone());
+        buffer.push(records[1].clone());
+        assert_eq!(buffer.len(), 2);
+        // The first record is 90 seconds older than the third one
+        buffer.push(records[2].clone());
+        assert_eq!(buffer.as_slice(), &records[1..3]);
+        buffer.push(records[3].clone());
+        assert_eq!(buffer.as_slice(), &records[2..4]);
+
+        let now = records[3].time + Duration::seconds(55);
+        assert_eq!(buffer.evict_stale(now), 1);
+        assert_eq!(buffer.as_slice(), &records[3..]);
+        assert_eq!(buffer.evict_stale(now + Duration::seconds(10)), 1);
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn test_sorted_set() {
+        let records = parse_json(
+            r#"[
+                {"bt": 1320067464, "n": "temperature", "v": 23.1, "t": 1},
+                {"n": "humidity", "v": 40},
+                {"n": "temperature", "v": 23.0},
+                {"n": "temperature", "v": 23.2, "t": 1},
+                {"n": "humidity", "v": 41}
+            ]"#,
+            None,
+        )
+        .unwrap();
+        let mut set = SenMLSortedSet::new();
+        let inserted: Vec<bool> = records
+            .iter()
+            .map(|record| set.insert(record.clone()))
+            .collect();
+        assert_eq!(inserted, vec![true, true, true, false, false]);
+        assert_eq!(set.len(), 3);
+        assert_eq!(set, SenMLSortedSet::from_records(records.clone()));
+
+        let time = records[0].time;
+        assert!(set.contains("temperature", time));
+        assert_eq!(set.remove("temperature", time), Some(records[3].clone()));
+        assert!(!set.contains("temperature", time));
+        assert_eq!(set.remove("temperature", time), None);
+        assert_eq!(set.len(), 2);
+
+        let keys: Vec<(&str, DateTime<Utc>)> = set
+            .iter()
+            .map(|record| (record.name.as_str(), record.time))
+            .collect();
+        assert_eq!(
+            keys,
+            vec![
+                ("humidity", records[1].time),
+                ("temperature", records[2].time)
+            ]
+        );
+        assert_eq!(
+            set.into_records(),
+            vec![records[4].clone(), records[2].clone()]
+        );
+    }
+}
@@ -0,0 +1,1361 @@
+//! # Operations on SenML Packs
+//!
+//! Functions to organise the resolved records of a pack.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use chrono::{DateTime, Utc};
+
+use crate::{parse_json, SenMLResolvedRecord, SenMLValueField, SinditSenMLError};
+
+/// Key of a record in a sorted map: the name and the time in nanoseconds
+/// since the Unix epoch.
+pub type SortedMapKey = (String, i128);
+
+fn time_nanos(record: &SenMLResolvedRecord) -> i128 {
+    record.time.timestamp_nanos_opt().unwrap_or_default() as i128
+}
+
+/// Sort the records by name and time in a map.
+///
+/// Records having the same name and time are deduplicated,
+/// the last one is kept.
+/// Times out of the range of nanosecond timestamps, before 1677 or
+/// after 2262, use 0 as the time.
+///
+/// # Examples
+/// ```
+/// use sindit_senml::{parse_json, pack_ops::to_sorted_map};
+///
+/// let records = parse_json(
+///     r#"[{"bt": 1320067464, "n": "b"},{"n": "a", "t": 1},{"n": "a"}]"#,
+///     None,
+/// ).unwrap();
+/// let map = to_sorted_map(records);
+/// let keys: Vec<(&str, i128)> = map.keys().map(|(name, time)| (name.as_str(), *time)).collect();
+/// assert_eq!(
+///     keys,
+///     vec![("a", 1320067464_000_000_000), ("a", 1320067465_000_000_000), ("b", 1320067464_000_000_000)]
+/// );
+/// ```
+pub fn to_sorted_map(
+    records: Vec<SenMLResolvedRecord>,
+) -> BTreeMap<SortedMapKey, SenMLResolvedRecord> {
+    records
+        .into_iter()
+        .map(|record| ((record.name.clone(), time_nanos(&record)), record))
+        .collect()
+}
+
+/// Records of a sorted map, sorted by name and time.
+pub fn from_sorted_map(
+    map: BTreeMap<SortedMapKey, SenMLResolvedRecord>,
+) -> Vec<SenMLResolvedRecord> {
+    map.into_values().collect()
+}
+
+/// Group the records by name in a sorted map.
+///
+/// The records of each name are kept in the pack order.
+pub fn into_name_grouped_btree(
+    records: Vec<SenMLResolvedRecord>,
+) -> BTreeMap<String, Vec<SenMLResolvedRecord>> {
+    let mut groups: BTreeMap<String, Vec<SenMLResolvedRecord>> = BTreeMap::new();
+    for record in records {
+        groups.entry(record.name.clone()).or_default().push(record);
+    }
+    groups
+}
+
+/// Group the records by name.
+///
+/// The records of each name are kept in the pack order.
+///
+/// # Examples
+/// ```
+/// use sindit_senml::{parse_json, pack_ops::group_by_name};
+///
+/// let records = parse_json(r#"[{"n": "a", "v": 1},{"n": "b", "v": 2},{"n": "a", "v": 3}]"#, None).unwrap();
+/// let groups = group_by_name(records);
+/// assert_eq!(groups["a"].len(), 2);
+/// assert_eq!(groups["a"][1].get_float_value(), Some(3.0));
+/// ```
+pub fn group_by_name(
+    records: Vec<SenMLResolvedRecord>,
+) -> HashMap<String, Vec<SenMLResolvedRecord>> {
+    let mut groups: HashMap<String, Vec<SenMLResolvedRecord>> = HashMap::new();
+    for record in records {
+        groups.entry(record.name.clone()).or_default().push(record);
+    }
+    groups
+}
+
+/// Group references to the records by name.
+///
+/// The records of each name are kept in the pack order.
+pub fn group_by_name_ref(
+    records: &[SenMLResolvedRecord],
+) -> HashMap<&str, Vec<&SenMLResolvedRecord>> {
+    let mut groups: HashMap<&str, Vec<&SenMLResolvedRecord>> = HashMap::new();
+    for record in records {
+        groups.entry(record.name.as_str()).or_default().push(record);
+    }
+    groups
+}
+
+/// The last record of each name, in the order of the pack.
+///
+/// # Examples
+/// ```
+/// use sindit_senml::{parse_json, pack_ops::into_name_map};
+///
+/// let records = parse_json(r#"[{"n": "a", "v": 1},{"n": "b", "v": 2},{"n": "a", "v": 3}]"#, None).unwrap();
+/// let map = into_name_map(records);
+/// assert_eq!(map["a"].get_float_value(), Some(3.0));
+/// ```
+pub fn into_name_map(records: Vec<SenMLResolvedRecord>) -> HashMap<String, SenMLResolvedRecord> {
+    records
+        .into_iter()
+        .map(|record| (record.name.clone(), record))
+        .collect()
+}
+
+/// The records of each name, see [`group_by_name`].
+pub fn into_name_multimap(
+    records: Vec<SenMLResolvedRecord>,
+) -> HashMap<String, Vec<SenMLResolvedRecord>> {
+    group_by_name(records)
+}
+
+/// The last record of each name, in the order of the pack.
+pub fn name_map_ref(records: &[SenMLResolvedRecord]) -> HashMap<&str, &SenMLResolvedRecord> {
+    records
+        .iter()
+        .map(|record| (record.name.as_str(), record))
+        .collect()
+}
+
+/// The most recent record of each name, in a single pass.
+///
+/// When several records of a name have the most recent time, the last
+/// one in the pack is returned. The records do not need to be sorted.
+///
+/// # Examples
+/// ```
+/// use sindit_senml::{parse_json, pack_ops::latest_by_name};
+///
+/// let records = parse_json(
+///     r#"[{"bt": 1320067464, "n": "a", "v": 1, "t": 60},{"n": "a", "v": 2},{"n": "b", "v": 3}]"#,
+///     None,
+/// ).unwrap();
+/// let latest = latest_by_name(&records);
+/// assert_eq!(latest["a"].get_float_value(), Some(1.0));
+/// assert_eq!(latest["b"].get_float_value(), Some(3.0));
+/// ```
+pub fn latest_by_name(records: &[SenMLResolvedRecord]) -> HashMap<&str, &SenMLResolvedRecord> {
+    let mut latest: HashMap<&str, &SenMLResolvedRecord> = HashMap::new();
+    for record in records.iter() {
+        match latest.get(record.name.as_str()) {
+            Some(previous) if previous.time > record.time => {}
+            _ => {
+                latest.insert(record.name.as_str(), record);
+            }
+        }
+    }
+    latest
+}
+
+/// References to the records of each name, see [`group_by_name_ref`].
+pub fn name_multimap_ref(
+    records: &[SenMLResolvedRecord],
+) -> HashMap<&str, Vec<&SenMLResolvedRecord>> {
+    group_by_name_ref(records)
+}
+
+/// Unique names of the records, in the order of their first occurrence.
+///
+/// # Examples
+/// ```
+/// use sindit_senml::{parse_json, pack_ops::unique_names};
+///
+/// let records = parse_json(r#"[{"n": "b"},{"n": "a"},{"n": "b"}]"#, None).unwrap();
+/// assert_eq!(unique_names(&records).collect::<Vec<_>>(), vec!["b", "a"]);
+/// ```
+pub fn unique_names(records: &[SenMLResolvedRecord]) -> impl Iterator<Item = &str> {
+    let mut seen: HashSet<&str> = HashSet::new();
+    records
+        .iter()
+        .map(|record| record.name.as_str())
+        .filter(move |name| seen.insert(name))
+}
+
+/// Unique names of the records, sorted.
+pub fn unique_names_sorted(records: &[SenMLResolvedRecord]) -> Vec<&str> {
+    let mut names: Vec<&str> = unique_names(records).collect();
+    names.sort_unstable();
+    names
+}
+
+/// Number of sensors, i.e. unique names, in the records.
+pub fn sensor_count(records: &[SenMLResolvedRecord]) -> usize {
+    unique_names(records).count()
+}
+
+fn name_time_keys(records: &[SenMLResolvedRecord]) -> HashSet<(&str, i128)> {
+    records
+        .iter()
+        .map(|record| (record.name.as_str(), time_nanos(record)))
+        .collect()
+}
+
+/// Records of `a` having the same name and time as a record of `b`.
+///
+/// # Examples
+/// ```
+/// use sindit_senml::{parse_json, pack_ops::intersect_packs};
+///
+/// let a = parse_json(r#"[{"n": "a", "t": 1320067464},{"n": "b", "t": 1320067464}]"#, None).unwrap();
+/// let b = parse_json(r#"[{"n": "b", "v": 2, "t": 1320067464},{"n": "a", "t": 1320067524}]"#, None).unwrap();
+/// let intersection = intersect_packs(&a, &b);
+/// assert_eq!(intersection.len(), 1);
+/// assert_eq!(intersection[0].name, "b");
+/// ```
+pub fn intersect_packs<'a>(
+    a: &'a [SenMLResolvedRecord],
+    b: &'a [SenMLResolvedRecord],
+) -> Vec<&'a SenMLResolvedRecord> {
+    let keys = name_time_keys(b);
+    a.iter()
+        .filter(|record| keys.contains(&(record.name.as_str(), time_nanos(record))))
+        .collect()
+}
+
+/// Records of `a` having the same name as a record of `b`.
+pub fn intersect_by_name_only<'a>(
+    a: &'a [SenMLResolvedRecord],
+    b: &'a [SenMLResolvedRecord],
+) -> Vec<&'a SenMLResolvedRecord> {
+    let names: HashSet<&str> = b.iter().map(|record| record.name.as_str()).collect();
+    a.iter()
+        .filter(|record| names.contains(record.name.as_str()))
+        .collect()
+}
+
+/// Records of `a` without a record of `b` having the same name and time.
+///
+/// This is the complement of [`intersect_packs`] in `a`.
+pub fn subtract_packs<'a>(
+    a: &'a [SenMLResolvedRecord],
+    b: &'a [SenMLResolvedRecord],
+) -> Vec<&'a SenMLResolvedRecord> {
+    let keys = name_time_keys(b);
+    a.iter()
+        .filter(|record| !keys.contains(&(record.name.as_str(), time_nanos(record))))
+        .collect()
+}
+
+/// Sort the records by time, keeping the order of the records with the
+/// same time.
+pub fn stable_sort_by_time(records: &mut [SenMLResolvedRecord]) {
+    records.sort_by_key(|record| record.time);
+}
+
+/// Sort the records by time, then by name, for a deterministic order.
+pub fn sort_by_time_then_name(records: &mut [SenMLResolvedRecord]) {
+    records.sort_by(|a, b| a.time.cmp(&b.time).then_with(|| a.name.cmp(&b.name)));
+}
+
+/// Sort the records by ascending time.
+///
+/// The sort is stable, this is the same as [`stable_sort_by_time`].
+pub fn sort_by_time(records: &mut [SenMLResolvedRecord]) {
+    stable_sort_by_time(records);
+}
+
+/// Sort the records by descending time, the most recent first.
+///
+/// Records with the same time keep their order in the pack.
+pub fn sort_by_time_desc(records: &mut [SenMLResolvedRecord]) {
+    records.sort_by_key(|record| std::cmp::Reverse(record.time));
+}
+
+/// Sort the records by name, in lexicographic order.
+///
+/// Records with the same name keep their order in the pack.
+pub fn sort_by_name(records: &mut [SenMLResolvedRecord]) {
+    records.sort_by(|a, b| a.name.cmp(&b.name));
+}
+
+/// Sort the records by name, then by time.
+///
+/// This is the canonical order, see [`sort_pack_canonical`].
+pub fn sort_by_name_then_time(records: &mut [SenMLResolvedRecord]) {
+    sort_pack_canonical(records);
+}
+
+/// Whether the records are sorted by ascending time, records with the
+/// same time being in any order.
+pub fn is_stable_time_sorted(records: &[SenMLResolvedRecord]) -> bool {
+    records.windows(2).all(|pair| pair[0].time <= pair[1].time)
+}
+
+fn canonical_key(record: &SenMLResolvedRecord) -> (&str, DateTime<Utc>) {
+    (record.name.as_str(), record.time)
+}
+
+/// Sort the records in the canonical order, by name then by time,
+/// for a reproducible processing of packs.
+///
+/// Records with the same name and time keep their order in the pack.
+pub fn sort_pack_canonical(records: &mut [SenMLResolvedRecord]) {
+    records.sort_by(|a, b| canonical_key(a).cmp(&canonical_key(b)));
+}
+
+/// The records sorted in the canonical order, see [`sort_pack_canonical`].
+pub fn canonical_pack(mut records: Vec<SenMLResolvedRecord>) -> Vec<SenMLResolvedRecord> {
+    sort_pack_canonical(&mut records);
+    records
+}
+
+/// Whether the records are sorted in the canonical order,
+/// see [`sort_pack_canonical`].
+pub fn is_canonical_order(records: &[SenMLResolvedRecord]) -> bool {
+    records
+        .windows(2)
+        .all(|pair| canonical_key(&pair[0]) <= canonical_key(&pair[1]))
+}
+
+/// Records of the sensor named `name`, in the order of the pack.
+///
+/// This is [`select_sensors`] with a single name.
+pub fn records_for_sensor<'a>(
+    records: &'a [SenMLResolvedRecord],
+    name: &str,
+) -> Vec<&'a SenMLResolvedRecord> {
+    select_sensors(records, &[name])
+}
+
+/// Records of the sensor named `name`, sorted by time.
+///
+/// Records with the same time stay in the order of the pack.
+pub fn records_for_sensor_sorted<'a>(
+    records: &'a [SenMLResolvedRecord],
+    name: &str,
+) -> Vec<&'a SenMLResolvedRecord> {
+    let mut sensor_records = records_for_sensor(records, name);
+    sensor_records.sort_by_key(|record| record.time);
+    sensor_records
+}
+
+/// Whether the pack has a record of the sensor named `name`.
+pub fn has_sensor(records: &[SenMLResolvedRecord], name: &str) -> bool {
+    records.iter().any(|record| record.name == name)
+}
+
+/// Records with a name matching `pred`, in the order of the pack.
+///
+/// # Examples
+/// ```
+/// use sindit_senml::{parse_json, pack_ops::filter_by_name};
+///
+/// let records = parse_json(r#"[{"n": "temp1"},{"n": "hum"},{"n": "temp2"}]"#, None).unwrap();
+/// let temperatures = filter_by_name(&records, |name| name.starts_with("temp"));
+/// assert_eq!(temperatures.len(), 2);
+/// ```
+pub fn filter_by_name<F: Fn(&str) -> bool>(
+    records: &[SenMLResolvedRecord],
+    pred: F,
+) -> Vec<&SenMLResolvedRecord> {
+    records.iter().filter(|record| pred(&record.name)).collect()
+}
+
+/// Records with a name starting with `prefix`, such as the records
+/// sharing a base name.
+pub fn filter_by_name_prefix<'a>(
+    records: &'a [SenMLResolvedRecord],
+    prefix: &str,
+) -> Vec<&'a SenMLResolvedRecord> {
+    filter_by_name(records, |name| name.starts_with(prefix))
+}
+
+/// Records named `name`.
+///
+/// This is the same as [`records_for_sensor`].
+pub fn filter_by_name_exact<'a>(
+    records: &'a [SenMLResolvedRecord],
+    name: &str,
+) -> Vec<&'a SenMLResolvedRecord> {
+    records_for_sensor(records, name)
+}
+
+/// Records with a time in the range `[start, end[`, in the order of the pack.
+pub fn filter_by_time_range(
+    records: &[SenMLResolvedRecord],
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> Vec<&SenMLResolvedRecord> {
+    records
+        .iter()
+        .filter(|record| record.time >= start && record.time < end)
+        .collect()
+}
+
+/// Iterator over the records with a time in the window
+/// `[start, start + duration[`, in the order of the pack.
+///
+/// Nothing is allocated, see [`filter_by_time_range`] to collect the records.
+///
+/// # Examples
+/// ```
+/// use chrono::Duration;
+/// use sindit_senml::{parse_json, pack_ops::records_in_window};
+///
+/// let records = parse_json(
+///     r#"[{"bt": 1320067464, "n": "a", "v": 1},{"n": "a", "v": 2, "t": 30},{"n": "a", "v": 3, "t": 60}]"#,
+///     None,
+/// ).unwrap();
+/// let sum: f64 = records_in_window(&records, records[0].time, Duration::seconds(60))
+///     .filter_map(|record| record.get_float_value())
+///     .sum();
+/// assert_eq!(sum, 3.0);
+/// ```
+pub fn records_in_window(
+    records: &[SenMLResolvedRecord],
+    start: DateTime<Utc>,
+    duration: chrono::Duration,
+) -> impl Iterator<Item = &SenMLResolvedRecord> + '_ {
+    let end = start.checked_add_signed(duration);
+    records.iter().filter(move |record| {
+        record.time >= start
+            && match end {
+                Some(end) => record.time < end,
+                None => duration > chrono::Duration::zero(),
+            }
+    })
+}
+
+/// Index of the records of each sensor, to look up the records of
+/// many sensors without scanning the pack each time.
+///
+/// # Examples
+/// ```
+/// use sindit_senml::{parse_json, pack_ops::SenMLSensorIndex};
+///
+/// let records = parse_json(r#"[{"n": "a"},{"n": "b"},{"n": "a"}]"#, None).unwrap();
+/// let index = SenMLSensorIndex::from_records(&records);
+/// assert_eq!(index.get("a"), &[0, 2]);
+/// assert!(index.get("c").is_empty());
+/// ```
+#[derive(Debug, Clone)]
+pub struct SenMLSensorIndex<'a> {
+    indices: HashMap<&'a str, Vec<usize>>,
+}
+
+impl<'a> SenMLSensorIndex<'a> {
+    pub fn from_records(records: &'a [SenMLResolvedRecord]) -> Self {
+        let mut indices: HashMap<&'a str, Vec<usize>> = HashMap::new();
+        for (index, record) in records.iter().enumerate() {
+            indices.entry(record.name.as_str()).or_default().push(index);
+        }
+        SenMLSensorIndex { indices }
+    }
+
+    /// Indices of the records of the sensor named `name`, in ascending order.
+    pub fn get(&self, name: &str) -> &[usize] {
+        self.indices
+            .get(name)
+            .map_or(&[], |indices| indices.as_slice())
+    }
+
+    /// Names of the indexed sensors, in arbitrary order.
+    pub fn names(&self) -> impl Iterator<Item = &'a str> + '_ {
+        self.indices.keys().copied()
+    }
+}
+
+/// Filter emitting only the records whose value changed since the last
+/// record emitted for the same sensor.
+///
+/// The first record of each sensor is always emitted.
+///
+/// # Examples
+/// ```
+/// use sindit_senml::{parse_json, pack_ops::ChangedValueFilter};
+///
+/// let records = parse_json(r#"[{"n": "open", "vb": true},{"n": "open", "vb": true},{"n": "open", "vb": false}]"#, None).unwrap();
+/// let mut filter = ChangedValueFilter::new();
+/// assert_eq!(filter.filter(&records), vec![&records[0], &records[2]]);
+/// assert!(filter.filter(&records[2..]).is_empty());
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ChangedValueFilter {
+    last_values: HashMap<String, Option<SenMLValueField>>,
+}
+
+impl ChangedValueFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn filter_with<'a, F>(
+        &mut self,
+        records: &'a [SenMLResolvedRecord],
+        changed: F,
+    ) -> Vec<&'a SenMLResolvedRecord>
+    where
+        F: Fn(&SenMLValueField, &SenMLValueField) -> bool,
+    {
+        records
+            .iter()
+            .filter(|record| {
+                let is_changed = match (self.last_values.get(&record.name), &record.value) {
+                    (None, _) => true,
+                    (Some(Some(last)), Some(value)) => changed(last, value),
+                    (Some(last), value) => last != value,
+                };
+                if is_changed {
+                    self.last_values
+                        .insert(record.name.clone(), record.value.clone());
+                }
+                is_changed
+            })
+            .collect()
+    }
+
+    /// Records whose value differs from the last emitted value of their sensor.
+    pub fn filter<'a>(
+        &mut self,
+        records: &'a [SenMLResolvedRecord],
+    ) -> Vec<&'a SenMLResolvedRecord> {
+        self.filter_with(records, |last, value| last != value)
+    }
+
+    /// Records whose float value differs by more than `threshold` from the
+    /// last emitted value of their sensor.
+    ///
+    /// The other values are compared exactly, as in [`ChangedValueFilter::filter`].
+    pub fn threshold_filter<'a>(
+        &mut self,
+        records: &'a [SenMLResolvedRecord],
+        threshold: f64,
+    ) -> Vec<&'a SenMLResolvedRecord> {
+        self.filter_with(records, |last, value| match (last, value) {
+            (SenMLValueField::FloatingPoint(last), SenMLValueField::FloatingPoint(value)) => {
+                (value - last).abs() > threshold
+            }
+            _ => last != value,
+        })
+    }
+}
+
+/// Records of the sensors named in `sensor_names`.
+///
+/// # Examples
+/// ```
+/// use sindit_senml::{parse_json, pack_ops::select_sensors};
+///
+/// let records = parse_json(r#"[{"n": "a"},{"n": "b"},{"n": "c"},{"n": "a"}]"#, None).unwrap();
+/// assert_eq!(select_sensors(&records, &["a", "c"]).len(), 3);
+/// ```
+pub fn select_sensors<'a>(
+    records: &'a [SenMLResolvedRecord],
+    sensor_names: &[&str],
+) -> Vec<&'a SenMLResolvedRecord> {
+    let names: HashSet<&str> = sensor_names.iter().copied().collect();
+    records
+        .iter()
+        .filter(|record| names.contains(record.name.as_str()))
+        .collect()
+}
+
+/// Records of the sensors with a name matching one of the glob `patterns`.
+///
+/// The patterns use the [`globset`] syntax, `*` also matches the `/`
+/// separators of the names.
+///
+/// Returns `InvalidGlobPattern` if a pattern is invalid.
+///
+/// # Examples
+/// ```
+/// use sindit_senml::{parse_json, pack_ops::select_sensors_glob};
+///
+/// let records = parse_json(r#"[{"n": "room1/temperature"},{"n": "room1/humidity"},{"n": "room2/temperature"}]"#, None).unwrap();
+/// assert_eq!(select_sensors_glob(&records, &["*/temperature"]).unwrap().len(), 2);
+/// ```
+pub fn select_sensors_glob<'a>(
+    records: &'a [SenMLResolvedRecord],
+    patterns: &[&str],
+) -> Result<Vec<&'a SenMLResolvedRecord>, SinditSenMLError> {
+    let mut builder = globset::GlobSetBuilder::new();
+    for pattern in patterns.iter() {
+        builder.add(globset::Glob::new(pattern)?);
+    }
+    let glob_set = builder.build()?;
+    Ok(records
+        .iter()
+        .filter(|record| glob_set.is_match(&record.name))
+        .collect())
+}
+
+/// Records of the sensors not named in `sensor_names`.
+///
+/// This is the complement of [`select_sensors`].
+pub fn exclude_sensors<'a>(
+    records: &'a [SenMLResolvedRecord],
+    sensor_names: &[&str],
+) -> Vec<&'a SenMLResolvedRecord> {
+    let names: HashSet<&str> = sensor_names.iter().copied().collect();
+    records
+        .iter()
+        .filter(|record| !names.contains(record.name.as_str()))
+        .collect()
+}
+
+/// Parse two SenML Packs and concatenate their records.
+///
+/// The packs are resolved independently: the base fields of the first
+/// pack do not apply to the records of the second pack.
+///
+/// # Arguments
+/// * `first` - The first SenML JSON string.
+/// * `second` - The second SenML JSON string.
+/// * `now` - The current time. Defaults to current UTC time.
+pub fn concat_packs_json(
+    first: &str,
+    second: &str,
+    now: Option<DateTime<Utc>>,
+) -> Result<Vec<SenMLResolvedRecord>, SinditSenMLError> {
+    let now = now.unwrap_or(Utc::now());
+    let mut records = parse_json(first, Some(now))?;
+    records.extend(parse_json(second, Some(now))?);
+    Ok(records)
+}
+
+/// Concatenate packs into a single pack sorted by time.
+///
+/// The records with the same time keep the order of the packs.
+///
+/// # Examples
+/// ```
+/// use sindit_senml::{parse_json, pack_ops::merge_packs};
+///
+/// let first = parse_json(r#"[{"n": "a", "v": 1, "t": 1320067464}, {"n": "a", "v": 2, "t": 1320067524}]"#, None).unwrap();
+/// let second = parse_json(r#"[{"n": "b", "v": 3, "t": 1320067494}]"#, None).unwrap();
+/// let merged = merge_packs(&[first, second]);
+/// let names: Vec<&str> = merged.iter().map(|record| record.name.as_str()).collect();
+/// assert_eq!(names, vec!["a", "b", "a"]);
+/// ```
+pub fn merge_packs(packs: &[Vec<SenMLResolvedRecord>]) -> Vec<SenMLResolvedRecord> {
+    let mut records = packs.concat();
+    stable_sort_by_time(&mut records);
+    records
+}
+
+/// Same as [`merge_packs`] but only the last of the records having the
+/// same name and the same time, to the millisecond, is kept.
+pub fn merge_packs_dedup(packs: &[Vec<SenMLResolvedRecord>]) -> Vec<SenMLResolvedRecord> {
+    let mut records = packs.concat();
+    dedup_by_name_and_time_keep_last(&mut records);
+    stable_sort_by_time(&mut records);
+    records
+}
+
+/// Whether each record is the first with its name and time, at the
+/// millisecond, in the iteration order.
+fn first_occurrences<'a>(records: impl Iterator<Item = &'a SenMLResolvedRecord>) -> Vec<bool> {
+    let mut seen: HashSet<(&str, i64)> = HashSet::new();
+    records
+        .map(|record| seen.insert((record.name.as_str(), record.time.timestamp_millis())))
+        .collect()
+}
+
+/// Remove the records having the same name and time, at the millisecond,
+/// as a previous record.
+///
+/// The first occurrence is kept and the remaining records keep their order.
+///
+/// # Examples
+/// ```
+/// use sindit_senml::{parse_json, pack_ops::dedup_by_name_and_time};
+///
+/// let mut records = parse_json(
+///     r#"[{"bt": 1320067464, "n": "a", "v": 1},{"n": "b", "v": 2},{"n": "a", "v": 3}]"#,
+///     None,
+/// ).unwrap();
+/// dedup_by_name_and_time(&mut records);
+/// assert_eq!(records.len(), 2);
+/// assert_eq!(records[0].get_float_value(), Some(1.0));
+/// ```
+pub fn dedup_by_name_and_time(records: &mut Vec<SenMLResolvedRecord>) {
+    let mut keep = first_occurrences(records.iter()).into_iter();
+    records.retain(|_| keep.next().unwrap_or(true));
+}
+
+/// Remove the records having the same name and time, at the millisecond,
+/// as a following record.
+///
+/// The last occurrence is kept and the remaining records keep their order.
+pub fn dedup_by_name_and_time_keep_last(records: &mut Vec<SenMLResolvedRecord>) {
+    let mut keep = first_occurrences(records.iter().rev());
+    keep.reverse();
+    let mut keep = keep.into_iter();
+    records.retain(|_| keep.next().unwrap_or(true));
+}
+
+/// Concatenate two SenML JSON strings into a single pack.
+///
+/// Please note that the base fields of the first pack apply to the records
+/// of the second pack, unless they are overridden. The resulting pack may
+/// not be valid SenML, for example when the packs use different versions.
+/// Use [`concat_packs_json`] to resolve the packs independently.
+///
+/// # Examples
+/// ```
+/// use sindit_senml::pack_ops::concat_pack_strings;
+///
+/// let json = concat_pack_strings(r#"[{"n": "a", "v": 1}]"#, r#" [{"n": "b", "v": 2}] "#).unwrap();
+/// assert_eq!(json, r#"[{"n": "a", "v": 1},{"n": "b", "v": 2}]"#);
+/// ```
+pub fn concat_pack_strings(first: &str, second: &str) -> Result<String, SinditSenMLError> {
+    // Check that both strings are JSON arrays.
+    let first_len = serde_json::from_str::<Vec<serde::de::IgnoredAny>>(first)?.len();
+    let second_len = serde_json::from_str::<Vec<serde::de::IgnoredAny>>(second)?.len();
+    let first = first.trim();
+    let second = second.trim();
+    // Both strings are JSON arrays, they start with '[' and end with ']'.
+    let first_items = &first[..first.len() - 1];
+    let second_items = &second[1..];
+
+    let separator = if first_len > 0 && second_len > 0 {
+        ","
+    } else {
+        ""
+    };
+    let first_items = if first_len > 0 { first_items } else { "[" };
+    let second_items = if second_len > 0 { second_items } else { "]" };
+    Ok(format!("{}{}{}", first_items, separator, second_items))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{MULTIPLE_DATAPOINT_AND_TIME, MULTIPLE_DATATYPES, SINGLE_DATAPOINT};
+    use crate::parse_json;
+    use chrono::Utc;
+
+    #[test]
+    fn test_stable_sort_by_time() {
+        let now = Utc::now();
+        let records = parse_json(MULTIPLE_DATATYPES, Some(now)).unwrap();
+        let mut sorted_records = records.clone();
+        stable_sort_by_time(&mut sorted_records);
+        assert_eq!(sorted_records, records);
+        assert!(is_stable_time_sorted(&sorted_records));
+
+        sort_by_time_then_name(&mut sorted_records);
+        assert_eq!(
+            sorted_records
+                .iter()
+                .map(|record| record.name.as_str())
+                .collect::<Vec<&str>>(),
+            unique_names_sorted(&records)
+        );
+
+        let records = parse_json(
+            r#"[
+                {"bt":1320067464,"n":"d","v":1},
+                {"n":"c","t":-10,"v":2},
+                {"n":"b","v":3},
+                {"n":"a","t":-10,"v":4}
+            ]"#,
+            None,
+        )
+        .unwrap();
+        assert!(!is_stable_time_sorted(&records));
+        let mut sorted_records = records.clone();
+        stable_sort_by_time(&mut sorted_records);
+        assert!(is_stable_time_sorted(&sorted_records));
+        let names: Vec<&str> = sorted_records
+            .iter()
+            .map(|record| record.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["c", "a", "d", "b"]);
+
+        sort_by_time_then_name(&mut sorted_records);
+        let names: Vec<&str> = sorted_records
+            .iter()
+            .map(|record| record.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["a", "c", "b", "d"]);
+    }
+
+    #[test]
+    fn test_sort_stability() {
+        let records = parse_json(
+            r#"[{"bt": 1320067464, "n": "b", "v": 1, "t": 1},
+                {"n": "a", "v": 2},
+                {"n": "b", "v": 3},
+                {"n": "a", "v": 4, "t": 1},
+                {"n": "b", "v": 5}]"#,
+            None,
+        )
+        .unwrap();
+        let values = |records: &[SenMLResolvedRecord]| -> Vec<f64> {
+            records
+                .iter()
+                .map(|record| record.get_float_value().unwrap())
+                .collect()
+        };
+
+        let mut sorted_records = records.clone();
+        sort_by_time(&mut sorted_records);
+        assert_eq!(values(&sorted_records), vec![2.0, 3.0, 5.0, 1.0, 4.0]);
+
+        let mut sorted_records = records.clone();
+        sort_by_time_desc(&mut sorted_records);
+        assert_eq!(values(&sorted_records), vec![1.0, 4.0, 2.0, 3.0, 5.0]);
+
+        let mut sorted_records = records.clone();
+        sort_by_name(&mut sorted_records);
+        assert_eq!(values(&sorted_records), vec![2.0, 4.0, 1.0, 3.0, 5.0]);
+
+        let mut sorted_records = records.clone();
+        sort_by_name_then_time(&mut sorted_records);
+        assert_eq!(values(&sorted_records), vec![2.0, 4.0, 3.0, 5.0, 1.0]);
+    }
+
+    #[test]
+    fn test_records_for_sensor() {
+        let records = parse_json(MULTIPLE_DATAPOINT_AND_TIME, None).unwrap();
+        let current = "urn:dev:ow:10e2073a0108006:current";
+        let voltage = "urn:dev:ow:10e2073a0108006:voltage";
+
+        let index = SenMLSensorIndex::from_records(&records);
+        assert_eq!(index.get(current), &[1, 2, 3, 4, 5, 6]);
+        assert_eq!(index.get(voltage), &[0]);
+        assert!(index.get("current").is_empty());
+        assert_eq!(index.names().count(), 2);
+
+        assert_eq!(records_for_sensor(&records, current).len(), 6);
+        assert_eq!(records_for_sensor(&records, voltage), vec![&records[0]]);
+        assert!(has_sensor(&records, voltage));
+        assert!(!has_sensor(&records, "voltage"));
+
+        let mut reversed = records.clone();
+        reversed.reverse();
+        let sorted = records_for_sensor_sorted(&reversed, current);
+        assert_eq!(sorted, records_for_sensor(&records, current));
+    }
+
+    #[test]
+    fn test_filter_by_name() {
+        let records = parse_json(MULTIPLE_DATAPOINT_AND_TIME, None).unwrap();
+        assert_eq!(
+            filter_by_name(&records, |name| name.ends_with("voltage")),
+            vec![&records[0]]
+        );
+        assert_eq!(
+            filter_by_name_prefix(&records, "urn:dev:ow:10e2073a0108006:").len(),
+            7
+        );
+        assert_eq!(
+            filter_by_name_prefix(&records, "urn:dev:ow:10e2073a0108006:cur").len(),
+            6
+        );
+        assert_eq!(
+            filter_by_name_exact(&records, "urn:dev:ow:10e2073a0108006:current"),
+            records_for_sensor(&records, "urn:dev:ow:10e2073a0108006:current")
+        );
+        assert!(filter_by_name_exact(&records, "current").is_empty());
+    }
+
+    #[test]
+    fn test_filter_by_time_range() {
+        let records = parse_json(MULTIPLE_DATAPOINT_AND_TIME, None).unwrap();
+        let start = records[2].time;
+        let end = records[4].time;
+        assert_eq!(
+            filter_by_time_range(&records, start, end),
+            vec![&records[2], &records[3]]
+        );
+        assert!(filter_by_time_range(&records, start, start).is_empty());
+        assert!(filter_by_time_range(&records, end, start).is_empty());
+        assert_eq!(
+            filter_by_time_range(&records, records[1].time, records[0].time).len(),
+            5
+        );
+    }
+
+    #[test]
+    fn test_records_in_window() {
+        let records = parse_json(MULTIPLE_DATAPOINT_AND_TIME, None).unwrap();
+        let start = records[2].time;
+        let window: Vec<&SenMLResolvedRecord> =
+            records_in_window(&records, start, chrono::Duration::seconds(2)).collect();
+        assert_eq!(window, vec![&records[2], &records[3]]);
+        assert_eq!(
+            window,
+            filter_by_time_range(&records, start, records[4].time)
+        );
+        assert_eq!(
+            records_in_window(&records, start, chrono::Duration::zero()).count(),
+            0
+        );
+        assert_eq!(
+            records_in_window(&records, start, chrono::Duration::seconds(-2)).count(),
+            0
+        );
+        assert_eq!(
+            records_in_window(&records, start, chrono::Duration::max_value()).count(),
+            6
+        );
+    }
+
+    #[test]
+    fn test_changed_value_filter() {
+        let json = (0..10)
+            .map(|index| {
+                format!(
+                    r#"{{"n": "temperature", "v": {}, "t": {}}}"#,
+                    if index % 2 == 0 { 23.0 } else { 23.1 },
+                    1320067464 + index
+                )
+            })
+            .collect::<Vec<String>>()
+            .join(",");
+        let records = parse_json(&format!("[{}]", json), None).unwrap();
+
+        let mut filter = ChangedValueFilter::new();
+        assert_eq!(filter.threshold_filter(&records, 0.5), vec![&records[0]]);
+        assert!(filter.threshold_filter(&records, 0.5).is_empty());
+        assert_eq!(filter.threshold_filter(&records, 0.05).len(), 9);
+
+        let mut filter = ChangedValueFilter::new();
+        assert_eq!(filter.filter(&records).len(), 10);
+        assert!(filter.filter(&records[9..]).is_empty());
+
+        let records = parse_json(
+            r#"[
+                {"n": "a", "v": 1, "t": 1320067464},
+                {"n": "b", "vs": "on", "t": 1320067464},
+                {"n": "a", "vs": "1", "t": 1320067465},
+                {"n": "b", "vs": "on", "t": 1320067465},
+                {"n": "a", "s": 2, "t": 1320067466},
+                {"n": "a", "s": 3, "t": 1320067467}
+            ]"#,
+            None,
+        )
+        .unwrap();
+        let mut filter = ChangedValueFilter::new();
+        assert_eq!(
+            filter.threshold_filter(&records, 10.0),
+            vec![&records[0], &records[1], &records[2], &records[4]]
+        );
+    }
+
+    #[test]
+    fn test_canonical_pack() {
+        let records = parse_json(
+            r#"[
+                {"bn":"sensor:","bt":1320067464,"n":"b","v":1},
+                {"n":"a","t":10,"v":2},
+                {"n":"a","v":3},
+                {"n":"b","t":-10,"v":4},
+                {"n":"a","v":5}
+            ]"#,
+            None,
+        )
+        .unwrap();
+        assert!(!is_canonical_order(&records));
+
+        let canonical = canonical_pack(records.clone());
+        assert!(is_canonical_order(&canonical));
+        assert_eq!(canonical_pack(canonical.clone()), canonical);
+        let values: Vec<f64> = canonical
+            .iter()
+            .filter_map(SenMLResolvedRecord::get_float_value)
+            .collect();
+        assert_eq!(values, vec![3.0, 5.0, 2.0, 4.0, 1.0]);
+
+        let mut sorted_records = records;
+        sort_pack_canonical(&mut sorted_records);
+        assert_eq!(sorted_records, canonical);
+
+        assert!(is_canonical_order(&[]));
+        assert!(is_canonical_order(&canonical[..1]));
+    }
+
+    #[test]
+    fn test_sorted_map_order() {
+        let records = parse_json(MULTIPLE_DATATYPES, Some(Utc::now())).unwrap();
+        let map = to_sorted_map(records.clone());
+        let names: Vec<&str> = map.keys().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec![
+                "urn:dev:ow:10e2073a01080063:label",
+                "urn:dev:ow:10e2073a01080063:nfc-reader",
+                "urn:dev:ow:10e2073a01080063:open",
+                "urn:dev:ow:10e2073a01080063:temp",
+            ]
+        );
+
+        let mut sorted_records = records;
+        sorted_records.sort_by(|a, b| a.name.cmp(&b.name).then(a.time.cmp(&b.time)));
+        assert_eq!(from_sorted_map(map), sorted_records);
+    }
+
+    #[test]
+    fn test_sorted_map_roundtrip_by_time() {
+        let records = parse_json(
+            r#"[
+                {"bn":"sensor:","bt":1320067464,"n":"b","v":1},
+                {"n":"a","t":10,"v":2},
+                {"n":"a","v":3},
+                {"n":"b","t":-10,"v":4}
+            ]"#,
+            None,
+        )
+        .unwrap();
+        let sorted = from_sorted_map(to_sorted_map(records.clone()));
+        let values: Vec<f64> = sorted
+            .iter()
+            .map(|record| record.get_float_value().unwrap())
+            .collect();
+        assert_eq!(values, vec![3.0, 2.0, 4.0, 1.0]);
+        assert_eq!(sorted.len(), records.len());
+        assert!(records.iter().all(|record| sorted.contains(record)));
+    }
+
+    #[test]
+    fn test_into_name_grouped_btree() {
+        let records =
+            parse_json(r#"[{"n":"b","v":1},{"n":"a","v":2},{"n":"b","v":3}]"#, None).unwrap();
+        let groups = into_name_grouped_btree(records);
+        assert_eq!(groups.keys().collect::<Vec<_>>(), vec!["a", "b"]);
+        assert_eq!(groups["b"].len(), 2);
+        assert_eq!(groups["b"][0].get_float_value(), Some(1.0));
+        assert_eq!(groups["b"][1].get_float_value(), Some(3.0));
+    }
+
+    #[test]
+    fn test_group_by_name() {
+        let records = parse_json(MULTIPLE_DATAPOINT_AND_TIME, None).unwrap();
+
+        let groups = group_by_name_ref(&records);
+        assert_eq!(groups.len(), 2);
+        assert_eq!(
+            groups["urn:dev:ow:10e2073a0108006:voltage"],
+            vec![&records[0]]
+        );
+        let currents: Vec<&SenMLResolvedRecord> = records[1..].iter().collect();
+        assert_eq!(groups["urn:dev:ow:10e2073a0108006:current"], currents);
+
+        let groups = group_by_name(records.clone());
+        assert_eq!(groups.len(), 2);
+        assert_eq!(
+            groups["urn:dev:ow:10e2073a0108006:current"].as_slice(),
+            &records[1..]
+        );
+
+        // The records of each name are contiguous in this pack, so
+        // flattening the groups in the order of the names gives the pack.
+        let mut flattened = Vec::new();
+        let mut groups = groups;
+        for name in unique_names(&records) {
+            flattened.extend(groups.remove(name).unwrap());
+        }
+        assert!(groups.is_empty());
+        assert_eq!(flattened, records);
+
+        assert!(group_by_name(Vec::new()).is_empty());
+    }
+
+    #[test]
+    fn test_name_maps() {
+        let records = parse_json(MULTIPLE_DATAPOINT_AND_TIME, None).unwrap();
+
+        let map = name_map_ref(&records);
+        assert_eq!(map.len(), 2);
+        assert_eq!(map["urn:dev:ow:10e2073a0108006:voltage"], &records[0]);
+        assert_eq!(map["urn:dev:ow:10e2073a0108006:current"], &records[6]);
+        assert_eq!(
+            name_multimap_ref(&records)["urn:dev:ow:10e2073a0108006:current"].len(),
+            6
+        );
+
+        let map = into_name_map(records.clone());
+        assert_eq!(map.len(), 2);
+        assert_eq!(
+            map["urn:dev:ow:10e2073a0108006:current"].get_float_value(),
+            Some(1.7)
+        );
+        let multimap = into_name_multimap(records.clone());
+        assert_eq!(
+            multimap["urn:dev:ow:10e2073a0108006:current"].as_slice(),
+            &records[1..]
+        );
+        assert!(into_name_map(Vec::new()).is_empty());
+    }
+
+    #[test]
+    fn test_latest_by_name() {
+        let records = parse_json(
+            r#"[{"bt": 1320067464, "n": "a", "v": 1, "t": 60},
+                {"n": "b", "v": 2},
+                {"n": "a", "v": 3, "t": 30},
+                {"n": "b", "v": 4},
+                {"n": "a", "v": 5, "t": 60},
+                {"n": "b", "v": 6, "t": -1}]"#,
+            None,
+        )
+        .unwrap();
+        let latest = latest_by_name(&records);
+        assert_eq!(latest.len(), 2);
+        assert_eq!(latest["a"], &records[4]);
+        assert_eq!(latest["b"], &records[3]);
+        assert!(latest_by_name(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_unique_names() {
+        let records = parse_json(MULTIPLE_DATATYPES, None).unwrap();
+        assert_eq!(
+            unique_names(&records).collect::<Vec<_>>(),
+            vec![
+                "urn:dev:ow:10e2073a01080063:temp",
+                "urn:dev:ow:10e2073a01080063:label",
+                "urn:dev:ow:10e2073a01080063:open",
+                "urn:dev:ow:10e2073a01080063:nfc-reader",
+            ]
+        );
+        assert_eq!(sensor_count(&records), 4);
+
+        let records = parse_json(MULTIPLE_DATAPOINT_AND_TIME, None).unwrap();
+        assert_eq!(
+            unique_names(&records).collect::<Vec<_>>(),
+            vec![
+                "urn:dev:ow:10e2073a0108006:voltage",
+                "urn:dev:ow:10e2073a0108006:current",
+            ]
+        );
+        assert_eq!(
+            unique_names_sorted(&records),
+            vec![
+                "urn:dev:ow:10e2073a0108006:current",
+                "urn:dev:ow:10e2073a0108006:voltage",
+            ]
+        );
+        assert_eq!(sensor_count(&records), 2);
+        assert_eq!(sensor_count(&[]), 0);
+    }
+
+    #[test]
+    fn test_concat_packs_json() {
+        let records =
+            concat_packs_json(SINGLE_DATAPOINT, MULTIPLE_DATAPOINT_AND_TIME, None).unwrap();
+        assert_eq!(records.len(), 8);
+        assert_eq!(records[0].name, "urn:dev:ow:10e2073a01080063");
+        assert_eq!(records[1].name, "urn:dev:ow:10e2073a0108006:voltage");
+
+        // The base fields of the first pack do not leak
+        let records =
+            concat_packs_json(MULTIPLE_DATAPOINT_AND_TIME, SINGLE_DATAPOINT, None).unwrap();
+        assert_eq!(records.len(), 8);
+        assert_eq!(records[7].name, "urn:dev:ow:10e2073a01080063");
+        assert_eq!(records[7].base_version, None);
+        assert_eq!(records[6].base_version, Some(5));
+
+        assert!(matches!(
+            concat_packs_json(SINGLE_DATAPOINT, "[{", None).unwrap_err(),
+            SinditSenMLError::InvalidJSON(_)
+        ));
+    }
+
+    #[test]
+    fn test_merge_packs() {
+        let first = parse_json(
+            r#"[
+                {"bn":"sensor:","bt":1320067464,"n":"a","v":1,"location":"room1"},
+                {"n":"a","t":60,"v":2}
+            ]"#,
+            None,
+        )
+        .unwrap();
+        let second = parse_json(
+            r#"[
+                {"bn":"sensor:","bt":1320067464,"n":"b","t":30,"v":3},
+                {"n":"a","t":0.0001,"v":4},
+                {"n":"b","t":60,"v":5}
+            ]"#,
+            None,
+        )
+        .unwrap();
+        let packs = vec![first, second];
+
+        let merged = merge_packs(&packs);
+        let values: Vec<f64> = merged
+            .iter()
+            .filter_map(SenMLResolvedRecord::get_float_value)
+            .collect();
+        assert_eq!(values, vec![1.0, 4.0, 3.0, 2.0, 5.0]);
+        assert!(merged[0].extra_fields.is_some());
+
+        // The records 1 and 4 have the same name and millisecond.
+        let merged = merge_packs_dedup(&packs);
+        let values: Vec<f64> = merged
+            .iter()
+            .filter_map(SenMLResolvedRecord::get_float_value)
+            .collect();
+        assert_eq!(values, vec![4.0, 3.0, 2.0, 5.0]);
+
+        assert!(merge_packs(&[]).is_empty());
+        assert!(merge_packs_dedup(&[Vec::new(), Vec::new()]).is_empty());
+    }
+
+    #[test]
+    fn test_dedup_by_name_and_time() {
+        let records = parse_json(
+            r#"[{"bt": 1320067464, "n": "a", "v": 1},
+                {"n": "b", "v": 2},
+                {"n": "a", "v": 3, "t": 0.0004},
+                {"n": "a", "v": 4, "t": 0.002},
+                {"n": "b", "v": 5},
+                {"n": "c", "v": 6}]"#,
+            None,
+        )
+        .unwrap();
+        let values = |records: &[SenMLResolvedRecord]| -> Vec<f64> {
+            records
+                .iter()
+                .filter_map(SenMLResolvedRecord::get_float_value)
+                .collect()
+        };
+
+        let mut deduplicated = records.clone();
+        dedup_by_name_and_time(&mut deduplicated);
+        assert_eq!(values(&deduplicated), vec![1.0, 2.0, 4.0, 6.0]);
+
+        let mut deduplicated = records.clone();
+        dedup_by_name_and_time_keep_last(&mut deduplicated);
+        assert_eq!(values(&deduplicated), vec![3.0, 4.0, 5.0, 6.0]);
+
+        let mut empty = Vec::new();
+        dedup_by_name_and_time(&mut empty);
+        assert!(empty.is_empty());
+    }
+
+    #[test]
+    fn test_concat_pack_strings() {
+        let json = concat_pack_strings(SINGLE_DATAPOINT, MULTIPLE_DATAPOINT_AND_TIME).unwrap();
+        let values: Vec<serde_json::Value> = serde_json::from_str(&json).unwrap();
+        assert_eq!(values.len(), 8);
+        // The version of the first pack applies to the second pack
+        assert!(matches!(
+            parse_json(&json, None).unwrap_err(),
+            SinditSenMLError::DifferentBaseVersion
+        ));
+
+        // The base fields of the first pack apply to the second pack
+        let json = concat_pack_strings(MULTIPLE_DATAPOINT_AND_TIME, SINGLE_DATAPOINT).unwrap();
+        let records = parse_json(&json, None).unwrap();
+        assert_eq!(records.len(), 8);
+        assert_eq!(
+            records[7].name,
+            "urn:dev:ow:10e2073a0108006:urn:dev:ow:10e2073a01080063"
+        );
+
+        assert_eq!(concat_pack_strings("[]", " [] ").unwrap(), "[]");
+        assert_eq!(
+            concat_pack_strings("[]", r#"[{"n":"a"}]"#).unwrap(),
+            r#"[{"n":"a"}]"#
+        );
+        assert_eq!(
+            concat_pack_strings(r#"[{"n":"a"}]"#, "[ ]").unwrap(),
+            r#"[{"n":"a"}]"#
+        );
+        assert!(matches!(
+            concat_pack_strings("{}", "[]").unwrap_err(),
+            SinditSenMLError::InvalidJSON(_)
+        ));
+    }
+
+    #[test]
+    fn test_intersect_and_subtract() {
+        let a = parse_json(
+            r#"[
+                {"bn":"room1/","bt":1320067464,"n":"temperature","v":20.0},
+                {"n":"temperature","t":60,"v":20.5},
+                {"n":"humidity","v":40.0},
+                {"n":"humidity","t":60,"v":41.0},
+                {"n":"pressure","v":1013.0}
+            ]"#,
+            None,
+        )
+        .unwrap();
+        let b = parse_json(
+            r#"[
+                {"bn":"room1/","bt":1320067464,"n":"temperature","v":21.0},
+                {"n":"humidity","t":60,"v":41.0},
+                {"n":"humidity","t":120,"v":42.0},
+                {"n":"light","v":300.0}
+            ]"#,
+            None,
+        )
+        .unwrap();
+
+        let intersection = intersect_packs(&a, &b);
+        let difference = subtract_packs(&a, &b);
+        assert_eq!(intersection, vec![&a[0], &a[3]]);
+        assert_eq!(difference, vec![&a[1], &a[2], &a[4]]);
+        assert_eq!(intersection.len() + difference.len(), a.len());
+        for record in a.iter() {
+            assert_ne!(intersection.contains(&record), difference.contains(&record));
+        }
+
+        assert_eq!(
+            intersect_by_name_only(&a, &b),
+            vec![&a[0], &a[1], &a[2], &a[3]]
+        );
+        assert_eq!(subtract_packs(&a, &a), Vec::<&SenMLResolvedRecord>::new());
+        assert_eq!(intersect_packs(&a, &[]), Vec::<&SenMLResolvedRecord>::new());
+    }
+
+    #[test]
+    fn test_select_sensors() {
+        let records = parse_json(MULTIPLE_DATAPOINT_AND_TIME, None).unwrap();
+        let names = [
+            "urn:dev:ow:10e2073a0108006:voltage",
+            "urn:dev:ow:10e2073a0108006:current",
+        ];
+        assert_eq!(select_sensors(&records, &names).len(), 7);
+        assert!(exclude_sensors(&records, &names).is_empty());
+
+        let voltages = select_sensors(&records, &names[..1]);
+        assert_eq!(voltages, vec![&records[0]]);
+        assert_eq!(exclude_sensors(&records, &names[..1]).len(), 6);
+        assert!(select_sensors(&records, &[]).is_empty());
+    }
+
+    #[test]
+    fn test_select_sensors_glob() {
+        let records = parse_json(MULTIPLE_DATAPOINT_AND_TIME, None).unwrap();
+        assert_eq!(
+            select_sensors_glob(&records, &["*:voltage", "*:current"])
+                .unwrap()
+                .len(),
+            7
+        );
+        assert_eq!(
+            select_sensors_glob(&records, &["*:volt?ge"]).unwrap(),
+            vec![&records[0]]
+        );
+        assert!(select_sensors_glob(&records, &["voltage"])
+            .unwrap()
+            .is_empty());
+        assert!(matches!(
+            select_sensors_glob(&records, &["[voltage"]).unwrap_err(),
+            SinditSenMLError::InvalidGlobPattern(_)
+        ));
+    }
+}
@@ -0,0 +1,497 @@
+//! # Apache Parquet export of SenML Packs
+//!
+//! Write and read the resolved records of a pack as a Parquet file,
+//! for data lake ingestion, using [`arrow2`].
+//! <https://parquet.apache.org/docs/file-format/>
+//!
+//! The file has one row per record and the columns:
+//!
+//! | Column       | Type                     | Nullable |
+//! |--------------|--------------------------|----------|
+//! | `name`       | `BYTE_ARRAY` UTF8        | no       |
+//! | `unit`       | `BYTE_ARRAY` UTF8        | yes      |
+//! | `value_f64`  | `DOUBLE`                 | yes      |
+//! | `value_bool` | `BOOLEAN`                | yes      |
+//! | `value_str`  | `BYTE_ARRAY` UTF8        | yes      |
+//! | `value_data` | `BYTE_ARRAY`             | yes      |
+//! | `sum`        | `DOUBLE`                 | yes      |
+//! | `time_ns`    | `INT64` timestamp (ns)   | no       |
+//!
+//! The update time, the version, and the extra fields are not exported.
+//!
+//! The pages are written with the Snappy compression. The reader supports
+//! the compressions and encodings of the files written by the usual tools,
+//! such as Spark, pandas, or DuckDB.
+
+use std::io;
+
+use arrow2::array::{Array, BinaryArray, BooleanArray, PrimitiveArray, Utf8Array};
+use arrow2::chunk::Chunk;
+use arrow2::datatypes::{DataType, Field, Schema, TimeUnit};
+use arrow2::io::parquet::{read, write};
+use chrono::{DateTime, Utc};
+
+use crate::validate_name::validate_name;
+use crate::{SenMLResolvedRecord, SenMLValueField, SinditSenMLError};
+
+/// Names of the columns of the file written for the records.
+const COLUMNS: [&str; 8] = [
+    "name",
+    "unit",
+    "value_f64",
+    "value_bool",
+    "value_str",
+    "value_data",
+    "sum",
+    "time_ns",
+];
+
+/// Number of rows read at once.
+const CHUNK_SIZE: usize = 8192;
+
+fn invalid<E>(_: E) -> SinditSenMLError {
+    SinditSenMLError::InvalidParquet
+}
+
+fn schema() -> Schema {
+    Schema::from(vec![
+        Field::new(COLUMNS[0], DataType::Utf8, false),
+        Field::new(COLUMNS[1], DataType::Utf8, true),
+        Field::new(COLUMNS[2], DataType::Float64, true),
+        Field::new(COLUMNS[3], DataType::Boolean, true),
+        Field::new(COLUMNS[4], DataType::Utf8, true),
+        Field::new(COLUMNS[5], DataType::Binary, true),
+        Field::new(COLUMNS[6], DataType::Float64, true),
+        Field::new(
+            COLUMNS[7],
+            DataType::Timestamp(TimeUnit::Nanosecond, Some("UTC".to_string())),
+            false,
+        ),
+    ])
+}
+
+/// Write the records as a Parquet file.
+///
+/// # Arguments
+/// * `records` - The records to write.
+/// * `writer` - The writer of the file.
+/// # Returns
+/// * `Result<(), SinditSenMLError>` - `InvalidTimeInRecord` for a time out
+///   of the range of nanosecond timestamps, before 1677 or after 2262.
+/// # Examples
+/// ```
+/// use sindit_senml::{parse_json, parquet::{read_parquet, write_parquet}};
+///
+/// let records = parse_json(r#"[{"n": "temperature", "u": "Cel", "v": 23.1, "t": 1320067464}]"#, None).unwrap();
+/// let mut bytes = Vec::new();
+/// write_parquet(&records, &mut bytes).unwrap();
+/// assert_eq!(read_parquet(&mut bytes.as_slice(), None).unwrap(), records);
+/// ```
+pub fn write_parquet(
+    records: &[SenMLResolvedRecord],
+    writer: &mut dyn io::Write,
+) -> Result<(), SinditSenMLError> {
+    let times = records
+        .iter()
+        .enumerate()
+        .map(|(index, record)| {
+            record
+                .time
+                .timestamp_nanos_opt()
+                .ok_or(SinditSenMLError::InvalidTimeInRecord(index))
+        })
+        .collect::<Result<Vec<i64>, SinditSenMLError>>()?;
+    let schema = schema();
+    let arrays: Vec<Box<dyn Array>> = vec![
+        Utf8Array::<i32>::from_iter_values(records.iter().map(|record| &record.name)).boxed(),
+        records
+            .iter()
+            .map(|record| record.unit.as_ref())
+            .collect::<Utf8Array<i32>>()
+            .boxed(),
+        records
+            .iter()
+            .map(|record| {
+                record
+                    .value
+                    .as_ref()
+                    .and_then(SenMLValueField::as_float)
+                    .copied()
+            })
+            .collect::<PrimitiveArray<f64>>()
+            .boxed(),
+        records
+            .iter()
+            .map(|record| {
+                record
+                    .value
+                    .as_ref()
+                    .and_then(SenMLValueField::as_bool)
+                    .copied()
+            })
+            .collect::<BooleanArray>()
+            .boxed(),
+        records
+            .iter()
+            .map(|record| record.value.as_ref().and_then(SenMLValueField::as_string))
+            .collect::<Utf8Array<i32>>()
+            .boxed(),
+        records
+            .iter()
+            .map(|record| record.value.as_ref().and_then(SenMLValueField::as_data))
+            .collect::<BinaryArray<i32>>()
+            .boxed(),
+        records
+            .iter()
+            .map(|record| record.sum)
+            .collect::<PrimitiveArray<f64>>()
+            .boxed(),
+        PrimitiveArray::<i64>::from_vec(times)
+            .to(schema.fields[7].data_type.clone())
+            .boxed(),
+    ];
+
+    let options = write::WriteOptions {
+        write_statistics: true,
+        compression: write::CompressionOptions::Snappy,
+        version: write::Version::V2,
+        data_pagesize_limit: None,
+    };
+    let encodings = schema
+        .fields
+        .iter()
+        .map(|_| vec![write::Encoding::Plain])
+        .collect();
+    // An empty pack has no row group.
+    let chunks = if records.is_empty() {
+        Vec::new()
+    } else {
+        vec![Chunk::try_new(arrays)]
+    };
+    let row_groups =
+        write::RowGroupIterator::try_new(chunks.into_iter(), &schema, options, encodings)
+            .map_err(invalid)?;
+    let mut file_writer = write::FileWriter::try_new(writer, schema, options).map_err(invalid)?;
+    for row_group in row_groups {
+        file_writer
+            .write(row_group.map_err(invalid)?)
+            .map_err(invalid)?;
+    }
+    file_writer.end(None).map_err(invalid)?;
+    Ok(())
+}
+
+/// Values of a column of strings.
+fn strings(array: &dyn Array) -> Option<Vec<Option<String>>> {
+    let as_any = array.as_any();
+    if let Some(array) = as_any.downcast_ref::<Utf8Array<i32>>() {
+        return Some(
+            array
+                .iter()
+                .map(|value| value.map(str::to_string))
+                .collect(),
+        );
+    }
+    as_any.downcast_ref::<Utf8Array<i64>>().map(|array| {
+        array
+            .iter()
+            .map(|value| value.map(str::to_string))
+            .collect()
+    })
+}
+
+/// Values of a column of bytes.
+fn bytes(array: &dyn Array) -> Option<Vec<Option<Vec<u8>>>> {
+    let as_any = array.as_any();
+    if let Some(array) = as_any.downcast_ref::<BinaryArray<i32>>() {
+        return Some(
+            array
+                .iter()
+                .map(|value| value.map(<[u8]>::to_vec))
+                .collect(),
+        );
+    }
+    as_any.downcast_ref::<BinaryArray<i64>>().map(|array| {
+        array
+            .iter()
+            .map(|value| value.map(<[u8]>::to_vec))
+            .collect()
+    })
+}
+
+/// Values of a column of floating point numbers, single or double precision.
+fn floats(array: &dyn Array) -> Option<Vec<Option<f64>>> {
+    let as_any = array.as_any();
+    if let Some(array) = as_any.downcast_ref::<PrimitiveArray<f64>>() {
+        return Some(array.iter().map(|value| value.copied()).collect());
+    }
+    as_any
+        .downcast_ref::<PrimitiveArray<f32>>()
+        .map(|array| array.iter().map(|value| value.map(|v| *v as f64)).collect())
+}
+
+fn bools(array: &dyn Array) -> Option<Vec<Option<bool>>> {
+    array
+        .as_any()
+        .downcast_ref::<BooleanArray>()
+        .map(|array| array.iter().collect())
+}
+
+/// Values of a column of timestamps, in nanoseconds.
+///
+/// Integers without a time unit are nanoseconds, as the name of the column.
+fn timestamps(array: &dyn Array) -> Option<Vec<Option<i64>>> {
+    let nanoseconds_per_unit = match array.data_type() {
+        DataType::Timestamp(TimeUnit::Second, _) => 1_000_000_000,
+        DataType::Timestamp(TimeUnit::Millisecond, _) => 1_000_000,
+        DataType::Timestamp(TimeUnit::Microsecond, _) => 1_000,
+        DataType::Timestamp(TimeUnit::Nanosecond, _) | DataType::Int64 => 1,
+        _ => return None,
+    };
+    let array = array.as_any().downcast_ref::<PrimitiveArray<i64>>()?;
+    array
+        .iter()
+        .map(|value| match value {
+            Some(value) => value.checked_mul(nanoseconds_per_unit).map(Some),
+            None => Some(None),
+        })
+        .collect()
+}
+
+/// Values of the column `name` of the chunk, all null if the column
+/// is missing.
+fn column<T: Clone>(
+    schema: &Schema,
+    chunk: &Chunk<Box<dyn Array>>,
+    name: &str,
+    values: fn(&dyn Array) -> Option<Vec<Option<T>>>,
+) -> Result<Vec<Option<T>>, SinditSenMLError> {
+    match schema.fields.iter().position(|field| field.name == name) {
+        Some(index) => {
+            values(chunk.arrays()[index].as_ref()).ok_or(SinditSenMLError::InvalidParquet)
+        }
+        None => Ok(vec![None; chunk.len()]),
+    }
+}
+
+/// Read records from a Parquet file written by [`write_parquet`], or with
+/// the same columns.
+///
+/// Only the `name` column is required. The other columns are null if
+/// missing, and the records without a time have the time `now`.
+/// The other columns of the file are ignored.
+///
+/// # Arguments
+/// * `reader` - The reader of the file.
+/// * `now` - The current time. Defaults to current UTC time.
+/// # Returns
+/// * `Result<Vec<SenMLResolvedRecord>, SinditSenMLError>` - The records,
+///   `InvalidParquet` if the file is invalid or not supported.
+pub fn read_parquet(
+    reader: &mut dyn io::Read,
+    now: Option<DateTime<Utc>>,
+) -> Result<Vec<SenMLResolvedRecord>, SinditSenMLError> {
+    let now = now.unwrap_or(Utc::now());
+    let mut file = Vec::new();
+    reader.read_to_end(&mut file)?;
+    let mut file = io::Cursor::new(file);
+
+    let metadata = read::read_metadata(&mut file).map_err(invalid)?;
+    let column_count = metadata.schema().columns().len();
+    if metadata
+        .row_groups
+        .iter()
+        .any(|row_group| row_group.columns().len() != column_count)
+    {
+        return Err(SinditSenMLError::InvalidParquet);
+    }
+    let schema = read::infer_schema(&metadata).map_err(invalid)?;
+    let schema = Schema::from(
+        schema
+            .fields
+            .into_iter()
+            .filter(|field| COLUMNS.contains(&field.name.as_str()))
+            .collect::<Vec<Field>>(),
+    );
+    if !schema.fields.iter().any(|field| field.name == COLUMNS[0]) {
+        return Err(SinditSenMLError::InvalidParquet);
+    }
+
+    let mut records = Vec::new();
+    let chunks = read::FileReader::new(
+        file,
+        metadata.row_groups,
+        schema.clone(),
+        Some(CHUNK_SIZE),
+        None,
+        None,
+    );
+    for chunk in chunks {
+        let chunk = chunk.map_err(invalid)?;
+        let names = column(&schema, &chunk, COLUMNS[0], strings)?;
+        let units = column(&schema, &chunk, COLUMNS[1], strings)?;
+        let value_floats = column(&schema, &chunk, COLUMNS[2], floats)?;
+        let value_bools = column(&schema, &chunk, COLUMNS[3], bools)?;
+        let value_strings = column(&schema, &chunk, COLUMNS[4], strings)?;
+        let value_data = column(&schema, &chunk, COLUMNS[5], bytes)?;
+        let sums = column(&schema, &chunk, COLUMNS[6], floats)?;
+        let times = column(&schema, &chunk, COLUMNS[7], timestamps)?;
+
+        for (((((((name, unit), float), boolean), string), data), sum), time) in names
+            .into_iter()
+            .zip(units)
+            .zip(value_floats)
+            .zip(value_bools)
+            .zip(value_strings)
+            .zip(value_data)
+            .zip(sums)
+            .zip(times)
+        {
+            let index = records.len();
+            let name = name.ok_or(SinditSenMLError::MissingName(index))?;
+            if !validate_name(&name) {
+                return Err(SinditSenMLError::InvalidNameInRecord(index));
+            }
+            let mut values: Vec<SenMLValueField> = [
+                float.map(SenMLValueField::FloatingPoint),
+                boolean.map(SenMLValueField::BooleanValue),
+                string.map(SenMLValueField::StringValue),
+                data.map(SenMLValueField::DataValue),
+            ]
+            .into_iter()
+            .flatten()
+            .collect();
+            if values.len() > 1 {
+                return Err(SinditSenMLError::OnlyOneValuePerRecord(index));
+            }
+            let time = match time {
+                Some(nanoseconds) => DateTime::<Utc>::from_timestamp(
+                    nanoseconds.div_euclid(1_000_000_000),
+                    nanoseconds.rem_euclid(1_000_000_000) as u32,
+                )
+                .ok_or(SinditSenMLError::InvalidTimeInRecord(index))?,
+                None => now,
+            };
+            records.push(SenMLResolvedRecord {
+                name,
+                unit,
+                value: values.pop(),
+                sum,
+                time,
+                update_time: None,
+                base_version: None,
+                extra_fields: None,
+                active_base_name: None,
+            });
+        }
+    }
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::MULTIPLE_DATATYPES;
+    use crate::parse_json;
+
+    #[test]
+    fn test_parquet_roundtrip() {
+        let records = parse_json(MULTIPLE_DATATYPES, None).unwrap();
+        let mut bytes = Vec::new();
+        write_parquet(&records, &mut bytes).unwrap();
+        assert!(bytes.starts_with(b"PAR1"));
+        assert!(bytes.ends_with(b"PAR1"));
+        assert_eq!(read_parquet(&mut bytes.as_slice(), None).unwrap(), records);
+
+        let records = parse_json(
+            r#"[
+                {"n": "energy", "u": "kWh", "s": 10.5, "t": 1320067464.5},
+                {"n": "energy", "u": "kWh", "s": 11, "t": 1320067524},
+                {"n": "power", "v": 1, "t": 1320067524}
+            ]"#,
+            None,
+        )
+        .unwrap();
+        let mut bytes = Vec::new();
+        write_parquet(&records, &mut bytes).unwrap();
+        assert_eq!(read_parquet(&mut bytes.as_slice(), None).unwrap(), records);
+
+        let mut bytes = Vec::new();
+        write_parquet(&[], &mut bytes).unwrap();
+        assert!(read_parquet(&mut bytes.as_slice(), None)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_parquet_many_records() {
+        let json = (0..20000)
+            .map(|index| match index % 3 {
+                0 => format!(
+                    r#"{{"n": "a", "v": {}, "t": {}}}"#,
+                    index,
+                    1320067464 + index
+                ),
+                1 => format!(r#"{{"n": "b", "vb": true, "t": {}}}"#, 1320067464 + index),
+                _ => format!(
+                    r#"{{"n": "c", "u": "Cel", "vs": "x{}", "t": {}}}"#,
+                    index,
+                    1320067464 + index
+                ),
+            })
+            .collect::<Vec<String>>()
+            .join(",");
+        let records = parse_json(&format!("[{}]", json), None).unwrap();
+        let mut bytes = Vec::new();
+        write_parquet(&records, &mut bytes).unwrap();
+        assert_eq!(read_parquet(&mut bytes.as_slice(), None).unwrap(), records);
+    }
+
+    #[test]
+    fn test_invalid_parquet() {
+        let records = parse_json(MULTIPLE_DATATYPES, None).unwrap();
+        let mut bytes = Vec::new();
+        write_parquet(&records, &mut bytes).unwrap();
+
+        // A footer with a row group of 2^62 rows and no column chunks.
+        let mut huge_row_group = b"PAR1".to_vec();
+        let mut metadata = vec![
+            0x15, 0x02, // version: 1
+            0x19, 0x1c, // schema: a single element
+            0x48, 0x06, b's', b'c', b'h', b'e', b'm', b'a', // name: "schema"
+            0x15, 0x00, // num_children: 0
+            0x00, // end of the element
+            0x16, // num_rows
+        ];
+        let row_count = [0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x01];
+        metadata.extend_from_slice(&row_count);
+        metadata.extend_from_slice(&[
+            0x19, 0x1c, // row_groups: a single row group
+            0x19, 0x0c, // columns: none
+            0x16, 0x00, // total_byte_size: 0
+            0x16, // num_rows
+        ]);
+        metadata.extend_from_slice(&row_count);
+        metadata.extend_from_slice(&[0x00, 0x00]);
+        huge_row_group.extend_from_slice(&metadata);
+        huge_row_group.extend_from_slice(&(metadata.len() as u32).to_le_bytes());
+        huge_row_group.extend_from_slice(b"PAR1");
+
+        for invalid_bytes in [
+            &b""[..],
+            &b"PAR1PAR1"[..],
+            &bytes[..bytes.len() - 1],
+            &bytes[4..],
+            &huge_row_group[..],
+        ] {
+            assert!(matches!(
+                read_parquet(&mut &invalid_bytes[..], None).unwrap_err(),
+                SinditSenMLError::InvalidParquet
+            ));
+        }
+
+        let mut truncated = bytes[..20].to_vec();
+        truncated.extend_from_slice(&bytes[bytes.len() - 8..]);
+        assert!(read_parquet(&mut truncated.as_slice(), None).is_err());
+    }
+}
@@ -0,0 +1,179 @@
+//! # Replay SenML Packs
+//!
+//! Feed pre-recorded records to a callback at a pace following the
+//! records time, optionally accelerated or slowed down.
+//!
+//! With a speed factor of 2.0, a 10-second recording is replayed in 5 seconds.
+//! The records are replayed in the order of the pack, records going back in
+//! time are replayed without waiting.
+
+use std::time::Duration;
+
+use crate::{SenMLResolvedRecord, SinditSenMLError};
+
+fn check_speed_factor(speed_factor: f64) -> Result<(), SinditSenMLError> {
+    if speed_factor.is_finite() && speed_factor > 0.0 {
+        Ok(())
+    } else {
+        Err(SinditSenMLError::InvalidSpeedFactor)
+    }
+}
+
+/// Time to wait between two records.
+fn replay_delay(
+    previous: &SenMLResolvedRecord,
+    record: &SenMLResolvedRecord,
+    speed_factor: f64,
+) -> Duration {
+    let milliseconds = (record.time - previous.time).num_milliseconds() as f64 / speed_factor;
+    if milliseconds > 0.0 {
+        Duration::from_secs_f64(milliseconds / 1000.0)
+    } else {
+        Duration::ZERO
+    }
+}
+
+/// Replay records to a callback, blocking the current thread while waiting.
+///
+/// # Arguments
+/// * `records` - The records to replay.
+/// * `speed_factor` - Strictly positive replay speed, 1.0 is real time.
+/// * `callback` - Called with each record.
+/// # Examples
+/// ```
+/// use sindit_senml::{parse_json, replay::replay_blocking};
+///
+/// let records = parse_json(r#"[{"n": "temperature", "v": 42.0}]"#, None).unwrap();
+/// replay_blocking(records, 10.0, |record| println!("{:?}", record)).unwrap();
+/// ```
+pub fn replay_blocking(
+    records: Vec<SenMLResolvedRecord>,
+    speed_factor: f64,
+    callback: impl Fn(&SenMLResolvedRecord),
+) -> Result<(), SinditSenMLError> {
+    check_speed_factor(speed_factor)?;
+    let mut previous: Option<&SenMLResolvedRecord> = None;
+    for record in records.iter() {
+        if let Some(previous) = previous {
+            std::thread::sleep(replay_delay(previous, record, speed_factor));
+        }
+        callback(record);
+        previous = Some(record);
+    }
+    Ok(())
+}
+
+/// Asynchronous replayer of records.
+///
+/// Waiting is done using tokio timers.
+///
+/// This is available with the `async` feature.
+#[cfg(feature = "async")]
+pub struct PackReplayer<F: Fn(SenMLResolvedRecord)> {
+    records: Vec<SenMLResolvedRecord>,
+    speed_factor: f64,
+    callback: F,
+}
+
+#[cfg(feature = "async")]
+impl<F: Fn(SenMLResolvedRecord)> PackReplayer<F> {
+    /// Create a new replayer.
+    ///
+    /// # Arguments
+    /// * `records` - The records to replay.
+    /// * `speed_factor` - Strictly positive replay speed, 1.0 is real time.
+    /// * `callback` - Called with each record.
+    pub fn new(records: Vec<SenMLResolvedRecord>, speed_factor: f64, callback: F) -> Self {
+        PackReplayer {
+            records,
+            speed_factor,
+            callback,
+        }
+    }
+
+    /// Replay all the records.
+    ///
+    /// Running the replayer again replays the records from the start.
+    pub async fn run(&mut self) -> Result<(), SinditSenMLError> {
+        check_speed_factor(self.speed_factor)?;
+        let mut previous: Option<&SenMLResolvedRecord> = None;
+        for record in self.records.iter() {
+            if let Some(previous) = previous {
+                tokio::time::sleep(replay_delay(previous, record, self.speed_factor)).await;
+            }
+            (self.callback)(record.clone());
+            previous = Some(record);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_json;
+    use std::sync::Mutex;
+    use std::time::Instant;
+
+    // 5 records over 4 seconds
+    const PACK: &str = r#"[
+        {"bn": "urn:dev:ow:10e2073a01080063", "bt": 1320067464, "v": 20.0},
+        {"t": 1, "v": 20.1},
+        {"t": 2, "v": 20.2},
+        {"t": 3, "v": 20.3},
+        {"t": 4, "v": 20.4}
+    ]"#;
+
+    #[test]
+    fn test_replay_blocking() {
+        let records = parse_json(PACK, None).unwrap();
+        let replayed = Mutex::new(Vec::new());
+        let start = Instant::now();
+        replay_blocking(records.clone(), 100.0, |record| {
+            replayed.lock().unwrap().push(record.clone())
+        })
+        .unwrap();
+        let elapsed = start.elapsed();
+        assert!(elapsed >= Duration::from_millis(40));
+        assert!(elapsed < Duration::from_millis(400));
+        assert_eq!(replayed.into_inner().unwrap(), records);
+    }
+
+    #[test]
+    fn test_invalid_speed_factor() {
+        let records = parse_json(PACK, None).unwrap();
+        for speed_factor in [0.0, -1.0, f64::NAN, f64::INFINITY] {
+            assert!(matches!(
+                replay_blocking(records.clone(), speed_factor, |_| {}).unwrap_err(),
+                SinditSenMLError::InvalidSpeedFactor
+            ));
+        }
+    }
+
+    #[test]
+    fn test_replay_delay() {
+        let records = parse_json(PACK, None).unwrap();
+        assert_eq!(
+            replay_delay(&records[0], &records[1], 2.0),
+            Duration::from_millis(500)
+        );
+        // Going back in time
+        assert_eq!(replay_delay(&records[1], &records[0], 2.0), Duration::ZERO);
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn test_pack_replayer() {
+        let records = parse_json(PACK, None).unwrap();
+        let replayed = Mutex::new(Vec::new());
+        let mut replayer = PackReplayer::new(records.clone(), 100.0, |record| {
+            replayed.lock().unwrap().push(record)
+        });
+        let start = Instant::now();
+        replayer.run().await.unwrap();
+        let elapsed = start.elapsed();
+        assert!(elapsed >= Duration::from_millis(40));
+        assert!(elapsed < Duration::from_millis(400));
+        assert_eq!(replayed.into_inner().unwrap(), records);
+    }
+}
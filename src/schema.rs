@@ -0,0 +1,229 @@
+//! # Schema validation of SenML Packs
+//!
+//! Check that a pack has the expected sensors, units, and value ranges,
+//! for example to validate the packs of a device against its configuration.
+
+use std::collections::{HashMap, HashSet};
+
+use serde::Deserialize;
+
+use crate::{SenMLResolvedRecord, SinditSenMLError};
+
+/// Inclusive range of the float values of a sensor.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct ValueConstraint {
+    #[serde(default)]
+    pub min: Option<f64>,
+    #[serde(default)]
+    pub max: Option<f64>,
+}
+
+impl ValueConstraint {
+    fn contains(&self, value: f64) -> bool {
+        let above_min = match self.min {
+            Some(min) => value >= min,
+            None => true,
+        };
+        let below_max = match self.max {
+            Some(max) => value <= max,
+            None => true,
+        };
+        above_min && below_max
+    }
+}
+
+/// Constraints on the records of a pack.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct PackSchema {
+    /// Sensors that must have at least one record.
+    #[serde(default)]
+    pub required_sensors: Vec<String>,
+    /// Sensors that must not have any record.
+    #[serde(default)]
+    pub forbidden_sensors: Vec<String>,
+    /// Unit of the records of a sensor.
+    #[serde(default)]
+    pub required_unit_for: HashMap<String, String>,
+    /// Range of the float values of a sensor.
+    #[serde(default)]
+    pub value_constraints: HashMap<String, ValueConstraint>,
+}
+
+impl PackSchema {
+    /// Load a schema from JSON.
+    ///
+    /// All the members are optional.
+    ///
+    /// # Examples
+    /// ```
+    /// use sindit_senml::schema::PackSchema;
+    ///
+    /// let schema = PackSchema::from_json(r#"{
+    ///     "required_sensors": ["temperature"],
+    ///     "required_unit_for": {"temperature": "Cel"},
+    ///     "value_constraints": {"temperature": {"min": -40, "max": 85}}
+    /// }"#).unwrap();
+    /// assert_eq!(schema.value_constraints["temperature"].max, Some(85.0));
+    /// ```
+    pub fn from_json(json_str: &str) -> Result<PackSchema, SinditSenMLError> {
+        Ok(serde_json::from_str(json_str)?)
+    }
+}
+
+/// A record or a pack not matching a schema.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SchemaViolation {
+    /// A required sensor has no record.
+    MissingSensor(String),
+    /// A forbidden sensor has records, first found at `index`.
+    ForbiddenSensor { name: String, index: usize },
+    /// The record at `index` does not have the required unit.
+    WrongUnit {
+        index: usize,
+        expected: String,
+        found: Option<String>,
+    },
+    /// The float value of the record at `index` is out of range.
+    ValueOutOfRange { index: usize, value: f64 },
+}
+
+/// Validate the records of a pack against a schema.
+///
+/// The missing sensors are listed first, in the order of the schema,
+/// then the violations of the records, in the order of the records.
+/// Only the float values are checked against the value constraints.
+///
+/// # Returns
+/// * `Vec<SchemaViolation>` - The violations, empty if the pack is valid.
+pub fn validate_pack_schema(
+    records: &[SenMLResolvedRecord],
+    schema: &PackSchema,
+) -> Vec<SchemaViolation> {
+    let names: HashSet<&str> = records.iter().map(|record| record.name.as_str()).collect();
+    let mut violations: Vec<SchemaViolation> = schema
+        .required_sensors
+        .iter()
+        .filter(|name| !names.contains(name.as_str()))
+        .map(|name| SchemaViolation::MissingSensor(name.clone()))
+        .collect();
+
+    let forbidden: HashSet<&str> = schema
+        .forbidden_sensors
+        .iter()
+        .map(String::as_str)
+        .collect();
+    let mut reported_forbidden: HashSet<&str> = HashSet::new();
+
+    for (index, record) in records.iter().enumerate() {
+        let name = record.name.as_str();
+        if forbidden.contains(name) && reported_forbidden.insert(name) {
+            violations.push(SchemaViolation::ForbiddenSensor {
+                name: record.name.clone(),
+                index,
+            });
+        }
+        if let Some(expected) = schema.required_unit_for.get(name) {
+            if record.unit.as_ref() != Some(expected) {
+                violations.push(SchemaViolation::WrongUnit {
+                    index,
+                    expected: expected.clone(),
+                    found: record.unit.clone(),
+                });
+            }
+        }
+        if let (Some(constraint), Some(value)) =
+            (schema.value_constraints.get(name), record.get_float_value())
+        {
+            if !constraint.contains(value) {
+                violations.push(SchemaViolation::ValueOutOfRange { index, value });
+            }
+        }
+    }
+
+    violations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_json;
+
+    // Adapted from the RFC8428 example with multiple data types
+    const PACK: &str = r#"[
+        {"n":"temperature","u":"K","v":296.25},
+        {"n":"label","vs":"Machine Room"},
+        {"n":"open","vb":false}
+    ]"#;
+
+    #[test]
+    fn test_validate_pack_schema() {
+        let records = parse_json(PACK, None).unwrap();
+        let schema = PackSchema::from_json(
+            r#"{
+                "required_sensors": ["temperature", "humidity"],
+                "required_unit_for": {"temperature": "Cel", "humidity": "%RH"}
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            validate_pack_schema(&records, &schema),
+            vec![
+                SchemaViolation::MissingSensor("humidity".to_string()),
+                SchemaViolation::WrongUnit {
+                    index: 0,
+                    expected: "Cel".to_string(),
+                    found: Some("K".to_string()),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_forbidden_sensors_and_value_constraints() {
+        let records = parse_json(
+            r#"[
+                {"n":"temperature","u":"Cel","v":23.1},
+                {"n":"debug","vs":"on"},
+                {"n":"temperature","u":"Cel","v":90},
+                {"n":"debug","vs":"off"},
+                {"n":"temperature","u":"Cel","v":-40}
+            ]"#,
+            None,
+        )
+        .unwrap();
+        let schema = PackSchema {
+            forbidden_sensors: vec!["debug".to_string()],
+            value_constraints: HashMap::from([(
+                "temperature".to_string(),
+                ValueConstraint {
+                    min: Some(-40.0),
+                    max: Some(85.0),
+                },
+            )]),
+            ..Default::default()
+        };
+        assert_eq!(
+            validate_pack_schema(&records, &schema),
+            vec![
+                SchemaViolation::ForbiddenSensor {
+                    name: "debug".to_string(),
+                    index: 1,
+                },
+                SchemaViolation::ValueOutOfRange {
+                    index: 2,
+                    value: 90.0,
+                },
+            ]
+        );
+        assert!(validate_pack_schema(&records, &PackSchema::default()).is_empty());
+    }
+
+    #[test]
+    fn test_invalid_schema_json() {
+        assert!(matches!(
+            PackSchema::from_json(r#"{"required_sensors": "temperature"}"#).unwrap_err(),
+            SinditSenMLError::InvalidJSON(_)
+        ));
+        assert_eq!(PackSchema::from_json("{}").unwrap(), PackSchema::default());
+    }
+}
@@ -0,0 +1,158 @@
+//! # Signature of SenML Packs
+//!
+//! Detect tampered packs using a HMAC-SHA256 signature.
+//!
+//! The signature is stored as the `_sig` extra field of the first record,
+//! encoded in URL safe base64 without padding. It is computed over the
+//! canonical JSON of the pack without the signature: the records are
+//! serialised without whitespace and with sorted keys.
+
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use subtle::ConstantTimeEq;
+
+use crate::{parse_json, SenMLResolvedRecord, SinditSenMLError};
+
+/// Name of the extra field holding the signature.
+pub const SIGNATURE_FIELD: &str = "_sig";
+
+type JsonRecord = serde_json::Map<String, serde_json::Value>;
+
+fn compute_signature(records: &[JsonRecord], key: &[u8]) -> Result<Vec<u8>, SinditSenMLError> {
+    // serde_json maps are sorted by key, the serialisation is canonical.
+    let canonical = serde_json::to_vec(records)?;
+    // HMAC accepts keys of any length.
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(key).map_err(|_| SinditSenMLError::InvalidSignature)?;
+    mac.update(&canonical);
+    Ok(mac.finalize().into_bytes().to_vec())
+}
+
+/// Sign a SenML JSON pack.
+///
+/// An existing signature is replaced.
+///
+/// # Arguments
+/// * `json_str` - The SenML JSON string to sign.
+/// * `key` - The secret key.
+/// # Returns
+/// * `Result<String, SinditSenMLError>` - The signed pack in canonical JSON,
+///   `EmptyPack` if the pack has no records.
+/// # Examples
+/// ```
+/// use sindit_senml::signing::{sign_pack_json, verify_pack_json};
+///
+/// let signed = sign_pack_json(r#"[{"n": "temperature", "v": 42.0}]"#, b"secret").unwrap();
+/// let records = verify_pack_json(&signed, b"secret").unwrap();
+/// assert_eq!(records[0].name, "temperature");
+/// assert!(verify_pack_json(&signed, b"wrong").is_err());
+/// ```
+pub fn sign_pack_json(json_str: &str, key: &[u8]) -> Result<String, SinditSenMLError> {
+    let mut records: Vec<JsonRecord> = serde_json::from_str(json_str)?;
+    let first = records.first_mut().ok_or(SinditSenMLError::EmptyPack)?;
+    first.remove(SIGNATURE_FIELD);
+
+    let signature = compute_signature(&records, key)?;
+    records[0].insert(
+        SIGNATURE_FIELD.to_string(),
+        serde_json::Value::from(base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(signature)),
+    );
+    Ok(serde_json::to_string(&records)?)
+}
+
+/// Verify the signature of a SenML JSON pack and parse it.
+///
+/// The signature is removed from the returned records.
+///
+/// # Arguments
+/// * `json_str` - The signed SenML JSON string.
+/// * `key` - The secret key.
+/// # Returns
+/// * `Result<Vec<SenMLResolvedRecord>, SinditSenMLError>` - The parsed records,
+///   `InvalidSignature` if the pack is not valid JSON, is not signed, or if the
+///   signature does not match.
+pub fn verify_pack_json(
+    json_str: &str,
+    key: &[u8],
+) -> Result<Vec<SenMLResolvedRecord>, SinditSenMLError> {
+    let mut records: Vec<JsonRecord> =
+        serde_json::from_str(json_str).map_err(|_| SinditSenMLError::InvalidSignature)?;
+    let signature = records
+        .first_mut()
+        .and_then(|first| first.remove(SIGNATURE_FIELD))
+        .ok_or(SinditSenMLError::InvalidSignature)?;
+    let signature = signature
+        .as_str()
+        .and_then(|signature| {
+            base64::engine::general_purpose::URL_SAFE_NO_PAD
+                .decode(signature)
+                .ok()
+        })
+        .ok_or(SinditSenMLError::InvalidSignature)?;
+
+    let expected = compute_signature(&records, key)?;
+    if !bool::from(expected.as_slice().ct_eq(signature.as_slice())) {
+        return Err(SinditSenMLError::InvalidSignature);
+    }
+
+    parse_json(&serde_json::to_string(&records)?, None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PACK: &str = r#"[
+        {"bn": "urn:dev:ow:10e2073a01080063:", "bt": 1320067464, "n": "temp", "u": "Cel", "v": 23.1},
+        {"n": "label", "vs": "Machine Room", "location": "room1"},
+        {"n": "open", "vb": false, "t": -1.5}
+    ]"#;
+
+    #[test]
+    fn test_sign_and_verify() {
+        let signed = sign_pack_json(PACK, b"secret").unwrap();
+        assert!(signed.contains(SIGNATURE_FIELD));
+        let records = verify_pack_json(&signed, b"secret").unwrap();
+        assert_eq!(records, parse_json(PACK, None).unwrap());
+
+        // Signing again gives the same pack
+        assert_eq!(sign_pack_json(&signed, b"secret").unwrap(), signed);
+    }
+
+    #[test]
+    fn test_modified_byte() {
+        let signed = sign_pack_json(PACK, b"secret").unwrap();
+        for index in 0..signed.len() {
+            let mut modified = signed.clone().into_bytes();
+            modified[index] ^= 0x01;
+            let modified = String::from_utf8(modified).unwrap();
+            assert!(
+                matches!(
+                    verify_pack_json(&modified, b"secret").unwrap_err(),
+                    SinditSenMLError::InvalidSignature
+                ),
+                "byte {} modified: {}",
+                index,
+                modified
+            );
+        }
+    }
+
+    #[test]
+    fn test_wrong_key_and_unsigned_pack() {
+        let signed = sign_pack_json(PACK, b"secret").unwrap();
+        assert!(matches!(
+            verify_pack_json(&signed, b"Secret").unwrap_err(),
+            SinditSenMLError::InvalidSignature
+        ));
+        assert!(matches!(
+            verify_pack_json(PACK, b"secret").unwrap_err(),
+            SinditSenMLError::InvalidSignature
+        ));
+        assert!(matches!(
+            sign_pack_json("[]", b"secret").unwrap_err(),
+            SinditSenMLError::EmptyPack
+        ));
+    }
+}
@@ -0,0 +1,841 @@
+//! # Statistics of SenML Packs
+//!
+//! Summaries computed over the resolved records of a pack.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::io;
+use std::mem::size_of;
+
+use chrono::{DateTime, Duration, Utc};
+
+use crate::{SenMLResolvedRecord, SenMLValueField};
+
+/// Summary of a SenML Pack.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PackSummary {
+    pub record_count: usize,
+    /// Number of unique names.
+    pub sensor_count: usize,
+    /// Time between the oldest and the most recent records.
+    /// `None` for an empty pack.
+    pub time_span: Option<Duration>,
+    pub float_record_count: usize,
+    pub bool_record_count: usize,
+    pub string_record_count: usize,
+    pub data_record_count: usize,
+    pub sum_record_count: usize,
+    pub has_extra_fields: bool,
+    /// Version of the first record.
+    /// `None` for the default version or an empty pack.
+    pub base_version: Option<u64>,
+}
+
+/// Summarize the records of a pack in a single pass.
+///
+/// # Examples
+/// ```
+/// use sindit_senml::{parse_json, stats::summarize};
+///
+/// let records = parse_json(r#"[{"n": "temperature", "v": 42.0},{"n": "open", "vb": true}]"#, None).unwrap();
+/// let summary = summarize(&records);
+/// assert_eq!(summary.record_count, 2);
+/// assert_eq!(summary.bool_record_count, 1);
+/// ```
+pub fn summarize(records: &[SenMLResolvedRecord]) -> PackSummary {
+    let mut names: HashSet<&str> = HashSet::new();
+    let mut summary = PackSummary {
+        record_count: records.len(),
+        sensor_count: 0,
+        time_span: None,
+        float_record_count: 0,
+        bool_record_count: 0,
+        string_record_count: 0,
+        data_record_count: 0,
+        sum_record_count: 0,
+        has_extra_fields: false,
+        base_version: records.first().and_then(|record| record.base_version),
+    };
+    let mut time_range = None;
+
+    for record in records.iter() {
+        names.insert(record.name.as_str());
+        time_range = match time_range {
+            None => Some((record.time, record.time)),
+            Some((min, max)) => Some((record.time.min(min), record.time.max(max))),
+        };
+        match record.value {
+            Some(SenMLValueField::FloatingPoint(_)) => summary.float_record_count += 1,
+            Some(SenMLValueField::BooleanValue(_)) => summary.bool_record_count += 1,
+            Some(SenMLValueField::StringValue(_)) => summary.string_record_count += 1,
+            Some(SenMLValueField::DataValue(_)) => summary.data_record_count += 1,
+            None => {}
+        }
+        if record.sum.is_some() {
+            summary.sum_record_count += 1;
+        }
+        if record.extra_fields.is_some() {
+            summary.has_extra_fields = true;
+        }
+    }
+
+    summary.sensor_count = names.len();
+    summary.time_span = time_range.map(|(min, max)| max - min);
+    summary
+}
+
+impl fmt::Display for PackSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} records from {} sensors",
+            self.record_count, self.sensor_count
+        )?;
+        match self.time_span {
+            Some(time_span) => writeln!(
+                f,
+                "Time span: {} s",
+                time_span.num_milliseconds() as f64 / 1000.0
+            )?,
+            None => writeln!(f, "Time span: none")?,
+        }
+        writeln!(
+            f,
+            "Values: {} float, {} boolean, {} string, {} data",
+            self.float_record_count,
+            self.bool_record_count,
+            self.string_record_count,
+            self.data_record_count
+        )?;
+        writeln!(f, "Sums: {}", self.sum_record_count)?;
+        writeln!(
+            f,
+            "Extra fields: {}",
+            if self.has_extra_fields { "yes" } else { "no" }
+        )?;
+        write!(f, "Version: {}", self.base_version.unwrap_or(10))
+    }
+}
+
+fn json_value_heap_bytes(value: &serde_json::Value) -> usize {
+    match value {
+        serde_json::Value::String(string) => string.capacity(),
+        serde_json::Value::Array(values) => {
+            values.capacity() * size_of::<serde_json::Value>()
+                + values.iter().map(json_value_heap_bytes).sum::<usize>()
+        }
+        serde_json::Value::Object(map) => map
+            .iter()
+            .map(|(key, value)| {
+                size_of::<String>()
+                    + size_of::<serde_json::Value>()
+                    + key.capacity()
+                    + json_value_heap_bytes(value)
+            })
+            .sum(),
+        _ => 0,
+    }
+}
+
+/// Size of a record without its heap allocations, in bytes.
+pub const fn record_size_fixed() -> usize {
+    size_of::<SenMLResolvedRecord>()
+}
+
+fn value_heap_bytes(value: Option<&SenMLValueField>) -> usize {
+    match value {
+        Some(SenMLValueField::StringValue(value)) => value.capacity(),
+        Some(SenMLValueField::DataValue(value)) => value.capacity(),
+        _ => 0,
+    }
+}
+
+fn extra_fields_heap_bytes(extra_fields: Option<&HashMap<String, serde_json::Value>>) -> usize {
+    extra_fields.map_or(0, |extra_fields| {
+        extra_fields.capacity() * (size_of::<String>() + size_of::<serde_json::Value>())
+            + extra_fields
+                .iter()
+                .map(|(key, value)| key.capacity() + json_value_heap_bytes(value))
+                .sum::<usize>()
+    })
+}
+
+/// Heap allocations of a record, in bytes.
+///
+/// This is the capacity of the name, the unit, the string or data value,
+/// the extra fields, and the active base name.
+/// The overhead of the allocator and of the hash maps' control bytes
+/// is not counted.
+pub fn record_heap_bytes(record: &SenMLResolvedRecord) -> usize {
+    record.name.capacity()
+        + record.unit.as_ref().map_or(0, |unit| unit.capacity())
+        + value_heap_bytes(record.value.as_ref())
+        + extra_fields_heap_bytes(record.extra_fields.as_ref())
+        + record
+            .active_base_name
+            .as_ref()
+            .map_or(0, |base_name| base_name.capacity())
+}
+
+/// Heap allocations of the records, in bytes, see [`record_heap_bytes`].
+pub fn pack_total_bytes(records: &[SenMLResolvedRecord]) -> usize {
+    records.iter().map(record_heap_bytes).sum()
+}
+
+/// Estimate the memory used by the records, in bytes.
+///
+/// This is the size of the records plus their heap allocations,
+/// see [`record_heap_bytes`].
+pub fn estimate_memory_bytes(records: &[SenMLResolvedRecord]) -> usize {
+    records.len() * record_size_fixed() + pack_total_bytes(records)
+}
+
+/// Writer counting the bytes instead of storing them.
+struct ByteCounter(usize);
+
+impl io::Write for ByteCounter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0 += buf.len();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Length in bytes of the JSON serialisation of the records.
+///
+/// The JSON is not allocated.
+///
+/// # Examples
+/// ```
+/// use sindit_senml::{parse_json, stats::estimate_serialized_json_bytes};
+///
+/// let records = parse_json(r#"[{"n": "temperature", "v": 42.0, "t": 1320067464}]"#, None).unwrap();
+/// assert_eq!(
+///     estimate_serialized_json_bytes(&records),
+///     serde_json::to_string(&records).unwrap().len()
+/// );
+/// ```
+pub fn estimate_serialized_json_bytes(records: &[SenMLResolvedRecord]) -> usize {
+    let mut counter = ByteCounter(0);
+    // Serialising records to JSON does not fail as all the keys are strings.
+    match serde_json::to_writer(&mut counter, records) {
+        Ok(()) => counter.0,
+        Err(_) => 0,
+    }
+}
+
+/// Float values of the records with the given name, in order.
+fn float_values(records: &[SenMLResolvedRecord], name: &str) -> Vec<f64> {
+    records
+        .iter()
+        .filter(|record| record.name == name)
+        .filter_map(|record| record.value.as_ref()?.as_float().copied())
+        .filter(|value| value.is_finite())
+        .collect()
+}
+
+/// Histogram of the float values of a sensor.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Histogram {
+    /// `(lower_inclusive, upper_exclusive, count)` for each bucket, in order.
+    pub buckets: Vec<(f64, f64, usize)>,
+}
+
+/// Maximum width of the bars of [`Histogram::display`].
+const HISTOGRAM_BAR_WIDTH: usize = 40;
+
+impl Histogram {
+    /// Render the histogram as an ASCII bar chart, one line per bucket.
+    ///
+    /// The longest bar is 40 characters.
+    pub fn display(&self) -> String {
+        let max_count = self
+            .buckets
+            .iter()
+            .map(|(_, _, count)| *count)
+            .max()
+            .unwrap_or(0);
+        self.buckets
+            .iter()
+            .map(|(lower, upper, count)| {
+                let bar_length = (count * HISTOGRAM_BAR_WIDTH)
+                    .checked_div(max_count)
+                    .unwrap_or(0);
+                format!(
+                    "[{}, {}) {} {}",
+                    lower,
+                    upper,
+                    "#".repeat(bar_length),
+                    count
+                )
+            })
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+}
+
+/// Histogram of the float values of a sensor, with equal-width buckets
+/// from the minimum to the maximum value.
+///
+/// The maximum value is counted in the last bucket.
+/// If all the values are equal, the range is extended to one.
+///
+/// # Arguments
+/// * `records` - The records.
+/// * `name` - The name of the sensor.
+/// * `bucket_count` - The number of buckets.
+/// # Returns
+/// * `Option<Histogram>` - `None` if the sensor has no float values
+///   or `bucket_count` is zero.
+/// # Examples
+/// ```
+/// use sindit_senml::{parse_json, stats::compute_histogram};
+///
+/// let records = parse_json(r#"[{"n": "t", "v": 0},{"n": "t", "v": 1},{"n": "t", "v": 4}]"#, None).unwrap();
+/// let histogram = compute_histogram(&records, "t", 2).unwrap();
+/// assert_eq!(histogram.buckets, vec![(0.0, 2.0, 2), (2.0, 4.0, 1)]);
+/// ```
+pub fn compute_histogram(
+    records: &[SenMLResolvedRecord],
+    name: &str,
+    bucket_count: usize,
+) -> Option<Histogram> {
+    let values = float_values(records, name);
+    if values.is_empty() || bucket_count == 0 {
+        return None;
+    }
+    let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+    let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    let width = if max > min {
+        (max - min) / bucket_count as f64
+    } else {
+        1.0 / bucket_count as f64
+    };
+
+    let mut buckets: Vec<(f64, f64, usize)> = (0..bucket_count)
+        .map(|index| {
+            (
+                min + width * index as f64,
+                min + width * (index + 1) as f64,
+                0,
+            )
+        })
+        .collect();
+    for value in values {
+        let index = (((value - min) / width) as usize).min(bucket_count - 1);
+        buckets[index].2 += 1;
+    }
+    Some(Histogram { buckets })
+}
+
+/// Histogram of the float values of a sensor, with the given bucket
+/// boundaries.
+///
+/// The values outside of the boundaries are not counted.
+///
+/// # Arguments
+/// * `records` - The records.
+/// * `name` - The name of the sensor.
+/// * `boundaries` - The finite and strictly increasing boundaries of the buckets.
+/// # Returns
+/// * `Option<Histogram>` - `None` if the sensor has no float values,
+///   or if the boundaries are invalid.
+pub fn compute_histogram_custom_buckets(
+    records: &[SenMLResolvedRecord],
+    name: &str,
+    boundaries: &[f64],
+) -> Option<Histogram> {
+    if boundaries.len() < 2
+        || boundaries.iter().any(|boundary| !boundary.is_finite())
+        || boundaries.windows(2).any(|pair| pair[0] >= pair[1])
+    {
+        return None;
+    }
+    let values = float_values(records, name);
+    if values.is_empty() {
+        return None;
+    }
+
+    let mut buckets: Vec<(f64, f64, usize)> = boundaries
+        .windows(2)
+        .map(|pair| (pair[0], pair[1], 0))
+        .collect();
+    for value in values {
+        // The boundaries are sorted, so the first larger boundary ends the bucket.
+        let upper = boundaries.partition_point(|boundary| *boundary <= value);
+        if upper > 0 && upper < boundaries.len() {
+            buckets[upper - 1].2 += 1;
+        }
+    }
+    Some(Histogram { buckets })
+}
+
+/// Quantile of sorted values, with linear interpolation between
+/// the closest ranks.
+fn sorted_quantile(sorted_values: &[f64], quantile: f64) -> Option<f64> {
+    if sorted_values.is_empty() || !(0.0..=1.0).contains(&quantile) {
+        return None;
+    }
+    let position = quantile * (sorted_values.len() - 1) as f64;
+    let lower = position.floor() as usize;
+    let upper = position.ceil() as usize;
+    let fraction = position - lower as f64;
+    Some(sorted_values[lower] + (sorted_values[upper] - sorted_values[lower]) * fraction)
+}
+
+/// Quantile of the float values of a sensor, with linear interpolation.
+///
+/// # Arguments
+/// * `records` - The records.
+/// * `name` - The name of the sensor.
+/// * `quantile` - The quantile, from 0.0 to 1.0.
+/// # Returns
+/// * `Option<f64>` - `None` if the sensor has no float values
+///   or the quantile is not between 0.0 and 1.0.
+/// # Examples
+/// ```
+/// use sindit_senml::{parse_json, stats::compute_quantile};
+///
+/// let records = parse_json(r#"[{"n": "t", "v": 1},{"n": "t", "v": 2},{"n": "t", "v": 4}]"#, None).unwrap();
+/// assert_eq!(compute_quantile(&records, "t", 0.5), Some(2.0));
+/// assert_eq!(compute_quantile(&records, "t", 0.75), Some(3.0));
+/// ```
+pub fn compute_quantile(records: &[SenMLResolvedRecord], name: &str, quantile: f64) -> Option<f64> {
+    compute_quantiles(records, name, &[quantile])[0]
+}
+
+/// Quantiles of the float values of a sensor, sorting the values once.
+///
+/// See [`compute_quantile`].
+pub fn compute_quantiles(
+    records: &[SenMLResolvedRecord],
+    name: &str,
+    quantiles: &[f64],
+) -> Vec<Option<f64>> {
+    let mut values = float_values(records, name);
+    values.sort_by(f64::total_cmp);
+    quantiles
+        .iter()
+        .map(|quantile| sorted_quantile(&values, *quantile))
+        .collect()
+}
+
+/// Common percentiles of the float values of a sensor.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SensorPercentiles {
+    pub p50: f64,
+    pub p90: f64,
+    pub p95: f64,
+    pub p99: f64,
+}
+
+/// Percentiles of the float values of a sensor.
+///
+/// # Returns
+/// * `Option<SensorPercentiles>` - `None` if the sensor has no float values.
+pub fn sensor_percentiles(
+    records: &[SenMLResolvedRecord],
+    name: &str,
+) -> Option<SensorPercentiles> {
+    match compute_quantiles(records, name, &[0.5, 0.9, 0.95, 0.99])[..] {
+        [Some(p50), Some(p90), Some(p95), Some(p99)] => {
+            Some(SensorPercentiles { p50, p90, p95, p99 })
+        }
+        _ => None,
+    }
+}
+
+const NANOSECONDS_PER_SECOND: i128 = 1_000_000_000;
+
+/// Number of records in each time bucket.
+///
+/// A record is in the bucket starting at
+/// `floor(time / bucket_duration) * bucket_duration`, from the Unix epoch.
+/// Only the buckets with records are returned, so gaps in the activity
+/// are missing bucket start times.
+///
+/// # Arguments
+/// * `records` - The records.
+/// * `bucket_duration` - The duration of the buckets.
+/// # Returns
+/// * `Vec<(DateTime<Utc>, usize)>` - The start of the buckets and their
+///   number of records, sorted by time. Empty if the duration is not positive.
+pub fn record_count_per_bucket(
+    records: &[SenMLResolvedRecord],
+    bucket_duration: Duration,
+) -> Vec<(DateTime<Utc>, usize)> {
+    let bucket_nanos = match bucket_duration.num_nanoseconds() {
+        Some(nanoseconds) if nanoseconds > 0 => nanoseconds as i128,
+        _ => return Vec::new(),
+    };
+    let mut counts: BTreeMap<i128, usize> = BTreeMap::new();
+    for record in records.iter() {
+        let nanoseconds = record.time.timestamp() as i128 * NANOSECONDS_PER_SECOND
+            + record.time.timestamp_subsec_nanos() as i128;
+        *counts
+            .entry(nanoseconds.div_euclid(bucket_nanos) * bucket_nanos)
+            .or_insert(0) += 1;
+    }
+    counts
+        .into_iter()
+        .filter_map(|(start, count)| {
+            let start = DateTime::<Utc>::from_timestamp(
+                start.div_euclid(NANOSECONDS_PER_SECOND) as i64,
+                start.rem_euclid(NANOSECONDS_PER_SECOND) as u32,
+            )?;
+            Some((start, count))
+        })
+        .collect()
+}
+
+/// Number of records per second, for each second with records.
+///
+/// See [`record_count_per_bucket`].
+pub fn records_per_second_series(records: &[SenMLResolvedRecord]) -> Vec<(DateTime<Utc>, f64)> {
+    record_count_per_bucket(records, Duration::seconds(1))
+        .into_iter()
+        .map(|(start, count)| (start, count as f64))
+        .collect()
+}
+
+/// Statistics of the float values of a sensor.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SenMLStats {
+    pub count: usize,
+    pub min: f64,
+    pub max: f64,
+    pub mean: f64,
+    pub sum: f64,
+    /// Time of the oldest float value.
+    pub first_time: DateTime<Utc>,
+    /// Time of the most recent float value.
+    pub last_time: DateTime<Utc>,
+}
+
+impl SenMLStats {
+    fn new(value: f64, time: DateTime<Utc>) -> Self {
+        SenMLStats {
+            count: 1,
+            min: value,
+            max: value,
+            mean: value,
+            sum: value,
+            first_time: time,
+            last_time: time,
+        }
+    }
+
+    fn add(&mut self, value: f64, time: DateTime<Utc>) {
+        self.count += 1;
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+        self.sum += value;
+        self.mean = self.sum / self.count as f64;
+        self.first_time = self.first_time.min(time);
+        self.last_time = self.last_time.max(time);
+    }
+}
+
+/// Statistics of the float values of each sensor, in a single pass.
+///
+/// The records without a float value are skipped, so the sensors
+/// without float values are missing.
+///
+/// # Examples
+/// ```
+/// use sindit_senml::{parse_json, stats::compute_stats};
+///
+/// let records = parse_json(r#"[{"n": "t", "v": 1},{"n": "t", "v": 3},{"n": "open", "vb": true}]"#, None).unwrap();
+/// let stats = compute_stats(&records);
+/// assert_eq!(stats.len(), 1);
+/// assert_eq!(stats["t"].mean, 2.0);
+/// ```
+pub fn compute_stats(records: &[SenMLResolvedRecord]) -> HashMap<String, SenMLStats> {
+    let mut stats: HashMap<String, SenMLStats> = HashMap::new();
+    for record in records.iter() {
+        let value = match record.value {
+            Some(SenMLValueField::FloatingPoint(value)) => value,
+            _ => continue,
+        };
+        match stats.get_mut(record.name.as_str()) {
+            Some(sensor_stats) => sensor_stats.add(value, record.time),
+            None => {
+                stats.insert(record.name.clone(), SenMLStats::new(value, record.time));
+            }
+        }
+    }
+    stats
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::MULTIPLE_DATATYPES;
+    use crate::parse_json;
+
+    #[test]
+    fn test_summarize_multiple_datatypes() {
+        let records = parse_json(MULTIPLE_DATATYPES, None).unwrap();
+        let summary = summarize(&records);
+        assert_eq!(summary.record_count, 4);
+        assert_eq!(summary.sensor_count, 4);
+        assert_eq!(summary.float_record_count, 1);
+        assert_eq!(summary.bool_record_count, 1);
+        assert_eq!(summary.string_record_count, 1);
+        assert_eq!(summary.data_record_count, 1);
+        assert_eq!(summary.sum_record_count, 0);
+        assert!(!summary.has_extra_fields);
+        assert_eq!(summary.base_version, None);
+        assert_eq!(summary.time_span, Some(Duration::zero()));
+    }
+
+    #[test]
+    fn test_summarize_time_span() {
+        let records = parse_json(
+            r#"[
+                {"bn":"meter:","bt":1320067464,"bver":11,"n":"energy","s":10},
+                {"n":"energy","t":-60,"s":5,"location":"room1"},
+                {"n":"power","t":120,"v":1}
+            ]"#,
+            None,
+        )
+        .unwrap();
+        let summary = summarize(&records);
+        assert_eq!(summary.sensor_count, 2);
+        assert_eq!(summary.time_span, Some(Duration::seconds(180)));
+        assert_eq!(summary.sum_record_count, 2);
+        assert_eq!(summary.float_record_count, 1);
+        assert!(summary.has_extra_fields);
+        assert_eq!(summary.base_version, Some(11));
+        assert_eq!(
+            summary.to_string(),
+            "3 records from 2 sensors\nTime span: 180 s\nValues: 1 float, 0 boolean, 0 string, 0 data\nSums: 2\nExtra fields: yes\nVersion: 11"
+        );
+    }
+
+    #[test]
+    fn test_summarize_empty() {
+        let summary = summarize(&[]);
+        assert_eq!(summary.record_count, 0);
+        assert_eq!(summary.sensor_count, 0);
+        assert_eq!(summary.time_span, None);
+        assert_eq!(summary.base_version, None);
+    }
+
+    #[test]
+    fn test_estimate_serialized_json_bytes() {
+        let records = parse_json(MULTIPLE_DATATYPES, None).unwrap();
+        assert_eq!(
+            estimate_serialized_json_bytes(&records),
+            serde_json::to_string(&records).unwrap().len()
+        );
+        assert_eq!(estimate_serialized_json_bytes(&[]), 2);
+    }
+
+    #[test]
+    fn test_record_heap_bytes() {
+        let name = "urn:dev:temperature1";
+        assert_eq!(name.len(), 20);
+        let records = parse_json(
+            &format!(r#"[{{"n": "{}", "v": 23.1, "t": 1320067464}}]"#, name),
+            None,
+        )
+        .unwrap();
+        let heap_bytes = record_heap_bytes(&records[0]);
+        assert!((20..=32).contains(&heap_bytes));
+        assert_eq!(pack_total_bytes(&records), heap_bytes);
+        assert_eq!(
+            estimate_memory_bytes(&records),
+            record_size_fixed() + heap_bytes
+        );
+
+        let records = parse_json(MULTIPLE_DATATYPES, None).unwrap();
+        assert!(record_heap_bytes(&records[1]) >= records[1].name.len() + "Machine Room".len());
+        assert_eq!(pack_total_bytes(&[]), 0);
+    }
+
+    #[test]
+    fn test_estimate_memory_bytes() {
+        let records = parse_json(MULTIPLE_DATATYPES, None).unwrap();
+        let repeated: Vec<SenMLResolvedRecord> = (0..10).map(|_| records[0].clone()).collect();
+        let record_bytes = estimate_memory_bytes(&repeated[..1]);
+        assert!(record_bytes > std::mem::size_of::<SenMLResolvedRecord>());
+
+        assert_eq!(estimate_memory_bytes(&repeated), 10 * record_bytes);
+        assert_eq!(estimate_memory_bytes(&repeated[..5]), 5 * record_bytes);
+        assert_eq!(estimate_memory_bytes(&[]), 0);
+    }
+
+    #[test]
+    fn test_compute_histogram_uniform() {
+        // Deterministic xorshift, as uniform as needed here.
+        let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+        let json = (0..100)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                format!(
+                    r#"{{"n": "noise", "v": {}}}"#,
+                    (state >> 11) as f64 / (1u64 << 53) as f64
+                )
+            })
+            .collect::<Vec<String>>()
+            .join(",");
+        let records = parse_json(&format!("[{}]", json), None).unwrap();
+
+        let histogram = compute_histogram(&records, "noise", 10).unwrap();
+        assert_eq!(histogram.buckets.len(), 10);
+        assert_eq!(
+            histogram
+                .buckets
+                .iter()
+                .map(|(_, _, count)| count)
+                .sum::<usize>(),
+            100
+        );
+        for (_, _, count) in histogram.buckets.iter() {
+            assert!((5..=15).contains(count), "{}", histogram.display());
+        }
+        assert_eq!(histogram.display().lines().count(), 10);
+
+        assert_eq!(compute_histogram(&records, "noise", 0), None);
+        assert_eq!(compute_histogram(&records, "temperature", 10), None);
+    }
+
+    #[test]
+    fn test_compute_histogram_custom_buckets() {
+        let records = parse_json(
+            r#"[
+                {"n": "t", "v": -1},{"n": "t", "v": 0},{"n": "t", "v": 5},
+                {"n": "t", "v": 10},{"n": "t", "v": 20},{"n": "t", "vs": "7"},
+                {"n": "h", "v": 5}
+            ]"#,
+            None,
+        )
+        .unwrap();
+        let histogram =
+            compute_histogram_custom_buckets(&records, "t", &[0.0, 10.0, 20.0]).unwrap();
+        assert_eq!(histogram.buckets, vec![(0.0, 10.0, 2), (10.0, 20.0, 1)]);
+        assert_eq!(
+            histogram.display(),
+            format!(
+                "[0, 10) {} 2\n[10, 20) {} 1",
+                "#".repeat(40),
+                "#".repeat(20)
+            )
+        );
+        assert_eq!(
+            compute_histogram_custom_buckets(&records, "t", &[0.0]),
+            None
+        );
+        assert_eq!(
+            compute_histogram_custom_buckets(&records, "t", &[0.0, 0.0, 1.0]),
+            None
+        );
+
+        let histogram = compute_histogram(&records, "h", 2).unwrap();
+        assert_eq!(histogram.buckets, vec![(5.0, 5.5, 1), (5.5, 6.0, 0)]);
+    }
+
+    #[test]
+    fn test_compute_quantiles() {
+        let records = parse_json(
+            r#"[
+                {"n": "latency", "v": 4},{"n": "latency", "v": 2},{"n": "latency", "v": 5},
+                {"n": "latency", "v": 1},{"n": "latency", "v": 3},{"n": "status", "vb": true}
+            ]"#,
+            None,
+        )
+        .unwrap();
+        assert_eq!(compute_quantile(&records, "latency", 0.5), Some(3.0));
+        assert_eq!(compute_quantile(&records, "latency", 1.0), Some(5.0));
+        assert_eq!(compute_quantile(&records, "latency", 0.0), Some(1.0));
+        assert_eq!(compute_quantile(&records, "latency", 0.1), Some(1.4));
+        assert_eq!(compute_quantile(&records, "latency", 1.5), None);
+        assert_eq!(compute_quantile(&records, "latency", f64::NAN), None);
+        assert_eq!(compute_quantile(&records, "status", 0.5), None);
+        assert_eq!(
+            compute_quantiles(&records, "latency", &[0.25, -0.1, 0.75]),
+            vec![Some(2.0), None, Some(4.0)]
+        );
+
+        let percentiles = sensor_percentiles(&records, "latency").unwrap();
+        assert_eq!(percentiles.p50, 3.0);
+        assert!((percentiles.p90 - 4.6).abs() < 1e-12);
+        assert!((percentiles.p95 - 4.8).abs() < 1e-12);
+        assert!((percentiles.p99 - 4.96).abs() < 1e-12);
+        assert_eq!(sensor_percentiles(&records, "status"), None);
+    }
+
+    #[test]
+    fn test_record_count_per_bucket() {
+        let json = (0..120)
+            .map(|index| format!(r#"{{"n": "t", "v": 1, "t": {}}}"#, 1320067440 + index))
+            .collect::<Vec<String>>()
+            .join(",");
+        let records = parse_json(&format!("[{}]", json), None).unwrap();
+
+        let buckets = record_count_per_bucket(&records, Duration::seconds(60));
+        assert_eq!(buckets.len(), 2);
+        assert_eq!(buckets[0].0.timestamp(), 1320067440);
+        assert_eq!(buckets[1].0 - buckets[0].0, Duration::seconds(60));
+        assert_eq!(buckets[0].1, 60);
+        assert_eq!(buckets[1].1, 60);
+
+        let series = records_per_second_series(&records);
+        assert_eq!(series.len(), 120);
+        assert!(series.iter().all(|(_, rate)| *rate == 1.0));
+
+        assert!(record_count_per_bucket(&records, Duration::zero()).is_empty());
+        assert!(record_count_per_bucket(&[], Duration::seconds(60)).is_empty());
+    }
+
+    #[test]
+    fn test_record_count_per_bucket_pre_epoch() {
+        let mut records = parse_json(r#"[{"n": "t", "v": 1, "t": 1320067464}]"#, None).unwrap();
+        records[0].time = DateTime::<Utc>::from_timestamp(-90, 500).unwrap();
+        assert_eq!(
+            record_count_per_bucket(&records, Duration::seconds(60)),
+            vec![(DateTime::<Utc>::from_timestamp(-120, 0).unwrap(), 1)]
+        );
+    }
+
+    #[test]
+    fn test_compute_stats() {
+        let records = parse_json(
+            r#"[{"bn": "room1/", "bt": 1320067464, "n": "temp", "v": 21.0, "t": 60},
+                {"n": "temp", "v": 19.0},
+                {"n": "temp", "v": 23.0, "t": 30},
+                {"n": "humidity", "v": 40.0},
+                {"n": "open", "vb": true},
+                {"n": "label", "vs": "Machine Room"}]"#,
+            None,
+        )
+        .unwrap();
+        let stats = compute_stats(&records);
+        assert_eq!(stats.len(), 2);
+        assert_eq!(
+            stats["room1/temp"],
+            SenMLStats {
+                count: 3,
+                min: 19.0,
+                max: 23.0,
+                mean: 21.0,
+                sum: 63.0,
+                first_time: DateTime::<Utc>::from_timestamp(1320067464, 0).unwrap(),
+                last_time: DateTime::<Utc>::from_timestamp(1320067524, 0).unwrap(),
+            }
+        );
+        assert_eq!(stats["room1/humidity"].count, 1);
+        assert_eq!(stats["room1/humidity"].mean, 40.0);
+        assert_eq!(
+            stats["room1/humidity"].first_time,
+            stats["room1/humidity"].last_time
+        );
+        assert!(compute_stats(&[]).is_empty());
+    }
+}
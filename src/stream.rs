@@ -1,28 +1,382 @@
-use crate::SenMLRecord;
+//! # Streaming SenML
+//!
+//! Resolve the records of a SenML JSON Pack one at a time, as they are
+//! received from an asynchronous reader.
+//!
+//! A SenSML stream is a pack sent incrementally, for example over a
+//! long-lived HTTP connection, and may never be closed.
+//! <https://www.rfc-editor.org/rfc/rfc8428#section-4.8>
+//!
+//! This is available with the `stream` feature.
 
-// See
-// https://github.com/Marcono1234/struson/discussions/19
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use chrono::{DateTime, Utc};
+use futures_core::Stream;
+use tokio::io::{AsyncRead, ReadBuf};
+
+use crate::{BaseFields, ParseOptions, SenMLRecord, SenMLResolvedRecord, SinditSenMLError};
+
+/// Size of the buffer of the reads.
+const READ_BUFFER_SIZE: usize = 4096;
+
+fn invalid_stream(message: &str) -> SinditSenMLError {
+    SinditSenMLError::InvalidJSON(serde::de::Error::custom(message))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum SplitterState {
+    #[default]
+    BeforeArray,
+    BeforeFirstElement,
+    BeforeElement,
+    InElement,
+    AfterElement,
+    Done,
+}
+
+/// Incremental splitter of a JSON array in the JSON of its elements.
+///
+/// The elements are not validated, only the array structure is.
+#[derive(Debug, Clone, Default)]
+struct ArraySplitter {
+    state: SplitterState,
+    element: Vec<u8>,
+    depth: usize,
+    in_string: bool,
+    escaped: bool,
+}
+
+impl ArraySplitter {
+    /// Push the next byte, returning the JSON of an element if the byte
+    /// completes it.
+    fn push(&mut self, byte: u8) -> Result<Option<Vec<u8>>, SinditSenMLError> {
+        let is_whitespace = matches!(byte, b' ' | b'\t' | b'\n' | b'\r');
+        match self.state {
+            SplitterState::BeforeArray => match byte {
+                b'[' => self.state = SplitterState::BeforeFirstElement,
+                _ if is_whitespace => {}
+                _ => return Err(invalid_stream("expected the start of an array")),
+            },
+            SplitterState::BeforeFirstElement | SplitterState::BeforeElement => match byte {
+                b']' if self.state == SplitterState::BeforeFirstElement => {
+                    self.state = SplitterState::Done
+                }
+                b']' | b',' => return Err(invalid_stream("expected an array element")),
+                _ if is_whitespace => {}
+                _ => {
+                    self.state = SplitterState::InElement;
+                    return self.push_element_byte(byte);
+                }
+            },
+            SplitterState::InElement => return self.push_element_byte(byte),
+            SplitterState::AfterElement => match byte {
+                b',' => self.state = SplitterState::BeforeElement,
+                b']' => self.state = SplitterState::Done,
+                _ if is_whitespace => {}
+                _ => return Err(invalid_stream("expected `,` or `]`")),
+            },
+            SplitterState::Done => {
+                if !is_whitespace {
+                    return Err(invalid_stream("trailing characters after the array"));
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    fn push_element_byte(&mut self, byte: u8) -> Result<Option<Vec<u8>>, SinditSenMLError> {
+        if self.in_string {
+            self.element.push(byte);
+            if self.escaped {
+                self.escaped = false;
+            } else if byte == b'\\' {
+                self.escaped = true;
+            } else if byte == b'"' {
+                self.in_string = false;
+            }
+            return Ok(None);
+        }
+        match byte {
+            b'"' => self.in_string = true,
+            b'{' | b'[' => self.depth += 1,
+            b'}' | b']' if self.depth > 0 => {
+                self.depth -= 1;
+                if self.depth == 0 {
+                    self.element.push(byte);
+                    return Ok(Some(self.take_element()));
+                }
+            }
+            // End of a scalar element.
+            b',' | b']' | b' ' | b'\t' | b'\n' | b'\r' if self.depth == 0 => {
+                let element = self.take_element();
+                self.push(byte)?;
+                return Ok(Some(element));
+            }
+            _ => {}
+        }
+        self.element.push(byte);
+        Ok(None)
+    }
+
+    fn take_element(&mut self) -> Vec<u8> {
+        self.state = SplitterState::AfterElement;
+        std::mem::take(&mut self.element)
+    }
+
+    /// Finish at the end of the input, returning the last element if it
+    /// is complete.
+    ///
+    /// The array does not need to be closed, as a stream may end when the
+    /// connection is closed.
+    fn finish(&mut self) -> Result<Option<Vec<u8>>, SinditSenMLError> {
+        match self.state {
+            SplitterState::BeforeArray => Err(invalid_stream("expected the start of an array")),
+            SplitterState::InElement if self.depth == 0 && !self.in_string => {
+                Ok(Some(self.take_element()))
+            }
+            SplitterState::InElement => {
+                // The error of the truncated element.
+                serde_json::from_slice::<serde_json::Value>(&self.element)?;
+                Err(invalid_stream("truncated array element"))
+            }
+            _ => Ok(None),
+        }
+    }
+}
+
+/// Stream of the resolved records of a SenML JSON Pack read from an
+/// asynchronous reader.
+///
+/// The records are resolved as soon as they are received, keeping the
+/// base fields of the previous records.
+/// The stream ends when the array is closed or the reader reaches its end,
+/// or after the first error.
+///
+/// # Examples
+/// ```
+/// # tokio_test();
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn tokio_test() {
+/// use sindit_senml::stream::SenMLStreamReader;
+/// use tokio_stream::StreamExt;
+///
+/// let json = br#"[{"bn": "sensor:", "n": "temp", "v": 20.0}, {"n": "temp", "v": 21.0}"#;
+/// let mut stream = SenMLStreamReader::new(&json[..], None);
+/// while let Some(record) = stream.next().await {
+///     assert_eq!(record.unwrap().name, "sensor:temp");
+/// }
+/// # }
+/// ```
+pub struct SenMLStreamReader<R: AsyncRead> {
+    reader: R,
+    buffer: Box<[u8]>,
+    position: usize,
+    filled: usize,
+    splitter: ArraySplitter,
+    base_fields: BaseFields,
+    index: usize,
+    now: Option<DateTime<Utc>>,
+    options: ParseOptions,
+    done: bool,
+}
+
+impl<R: AsyncRead> SenMLStreamReader<R> {
+    /// Stream the records read from `reader`.
+    ///
+    /// # Arguments
+    /// * `reader` - The asynchronous reader of the SenML JSON.
+    /// * `now` - The current time. Defaults to the current UTC time when
+    ///   each record is resolved.
+    pub fn new(reader: R, now: Option<DateTime<Utc>>) -> Self {
+        Self::with_options(reader, now, ParseOptions::default())
+    }
+
+    /// Stream the records read from `reader`, with parsing options.
+    pub fn with_options(reader: R, now: Option<DateTime<Utc>>, options: ParseOptions) -> Self {
+        SenMLStreamReader {
+            reader,
+            buffer: vec![0; READ_BUFFER_SIZE].into_boxed_slice(),
+            position: 0,
+            filled: 0,
+            splitter: ArraySplitter::default(),
+            base_fields: BaseFields::default(),
+            index: 0,
+            now,
+            options,
+            done: false,
+        }
+    }
+
+    /// Resolve the JSON of a record.
+    fn resolve(&mut self, element: &[u8]) -> Result<SenMLResolvedRecord, SinditSenMLError> {
+        let index = self.index;
+        self.index += 1;
+        let result = serde_json::from_slice::<SenMLRecord>(element)
+            .map_err(SinditSenMLError::from)
+            .and_then(|record| {
+                let now = self.now;
+                self.base_fields
+                    .resolve(&record, index, &|| now.unwrap_or(Utc::now()), &self.options)
+                    .map(|resolved| resolved.record)
+            });
+        if result.is_err() {
+            self.done = true;
+        }
+        result
+    }
+
+    fn fail(
+        &mut self,
+        error: SinditSenMLError,
+    ) -> Poll<Option<Result<SenMLResolvedRecord, SinditSenMLError>>> {
+        self.done = true;
+        Poll::Ready(Some(Err(error)))
+    }
+}
+
+impl<R: AsyncRead + Unpin> Stream for SenMLStreamReader<R> {
+    type Item = Result<SenMLResolvedRecord, SinditSenMLError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            if this.done {
+                return Poll::Ready(None);
+            }
+            while this.position < this.filled {
+                let byte = this.buffer[this.position];
+                this.position += 1;
+                match this.splitter.push(byte) {
+                    Ok(Some(element)) => return Poll::Ready(Some(this.resolve(&element))),
+                    Ok(None) => {}
+                    Err(error) => return this.fail(error),
+                }
+            }
+            if this.splitter.state == SplitterState::Done {
+                // Trailing whitespace after the array is not read.
+                this.done = true;
+                return Poll::Ready(None);
+            }
+
+            let mut read_buffer = ReadBuf::new(&mut this.buffer);
+            match Pin::new(&mut this.reader).poll_read(cx, &mut read_buffer) {
+                Poll::Pending => return Poll::Pending,
+                Poll::Ready(Err(error)) => return this.fail(error.into()),
+                Poll::Ready(Ok(())) => {
+                    let filled = read_buffer.filled().len();
+                    if filled == 0 {
+                        this.done = true;
+                        return match this.splitter.finish() {
+                            Ok(Some(element)) => Poll::Ready(Some(this.resolve(&element))),
+                            Ok(None) => Poll::Ready(None),
+                            Err(error) => Poll::Ready(Some(Err(error))),
+                        };
+                    }
+                    this.position = 0;
+                    this.filled = filled;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
 mod tests {
-    use crate::SenMLRecord;
-    use struson::reader::*;
+    use super::*;
+    use crate::parse_json;
+    use tokio::io::AsyncWriteExt;
+    use tokio_stream::StreamExt;
 
-    #[test]
-    fn test_stream() {
-        let json = r#"{"a": [1, true]}"#;
-        let reader = std::io::BufReader::new(json.as_bytes());
-        let mut json_reader = struson::reader::JsonStreamReader::new(reader);
+    fn split(json: &str) -> Result<Vec<String>, SinditSenMLError> {
+        let mut splitter = ArraySplitter::default();
+        let mut elements = Vec::new();
+        for byte in json.bytes() {
+            if let Some(element) = splitter.push(byte)? {
+                elements.push(String::from_utf8(element).unwrap());
+            }
+        }
+        if let Some(element) = splitter.finish()? {
+            elements.push(String::from_utf8(element).unwrap());
+        }
+        Ok(elements)
+    }
 
-        json_reader.begin_array().expect("Begin array error");
+    #[test]
+    fn test_array_splitter() {
+        assert_eq!(
+            split(r#" [ {"n": "a", "x": [1, {"y": "]}"}]} , {"n": "b\"}"},1,true ] "#).unwrap(),
+            vec![
+                r#"{"n": "a", "x": [1, {"y": "]}"}]}"#,
+                r#"{"n": "b\"}"}"#,
+                "1",
+                "true"
+            ]
+        );
+        assert_eq!(split("[]").unwrap(), Vec::<String>::new());
+        assert_eq!(split(r#"[{"n": "a"},"#).unwrap(), vec![r#"{"n": "a"}"#]);
+        assert_eq!(split("[1").unwrap(), vec!["1"]);
 
-        while json_reader.has_next()? {
-            // let user: User = json_reader.deserialize_next()?;
-            let record: SenMLRecord = json_reader.deserialize_next()?;
-            // ... use deserialized value in some way
-            println!("deserialized: {record:?}")
+        for json in ["", "{}", "[,]", "[1,]", "[1 2]", "[1] 2", r#"[{"n": "a""#] {
+            assert!(matches!(
+                split(json).unwrap_err(),
+                SinditSenMLError::InvalidJSON(_)
+            ));
         }
+    }
+
+    #[tokio::test]
+    async fn test_stream_reader() {
+        let json = r#"[
+            {"bn": "urn:dev:ow:10e2073a01080063:", "bt": 1320067464, "bu": "Cel", "n": "temp", "v": 23.1},
+            {"n": "temp", "t": 10, "v": 23.2},
+            {"n": "label", "vs": "Machine Room"}
+        ]"#;
+        let records: Vec<SenMLResolvedRecord> = SenMLStreamReader::new(json.as_bytes(), None)
+            .collect::<Result<Vec<SenMLResolvedRecord>, SinditSenMLError>>()
+            .await
+            .unwrap();
+        assert_eq!(records, parse_json(json, None).unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_stream_reader_incremental() {
+        let (mut writer, reader) = tokio::io::duplex(64);
+        let mut stream = SenMLStreamReader::new(reader, None);
+
+        writer
+            .write_all(br#"[{"bn": "sensor:", "bt": 1320067464, "n": "a", "v": 1},"#)
+            .await
+            .unwrap();
+        let record = stream.next().await.unwrap().unwrap();
+        assert_eq!(record.name, "sensor:a");
+
+        writer.write_all(br#" {"n": "b", "t": 1"#).await.unwrap();
+        writer.write_all(br#"0, "v": 2}"#).await.unwrap();
+        let record = stream.next().await.unwrap().unwrap();
+        assert_eq!(record.name, "sensor:b");
+        assert_eq!(record.time.timestamp(), 1320067474);
+
+        // The connection is closed without closing the array.
+        drop(writer);
+        assert!(stream.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_stream_reader_errors() {
+        let json = br#"[{"n": "a", "v": 1}, {"v": 2}, {"n": "c", "v": 3}]"#;
+        let results: Vec<Result<SenMLResolvedRecord, SinditSenMLError>> =
+            SenMLStreamReader::new(&json[..], None).collect().await;
+        assert_eq!(results.len(), 2);
+        assert!(results[0].is_ok());
+        assert!(matches!(results[1], Err(SinditSenMLError::MissingName(1))));
 
-        // Optionally consume the remainder of the JSON document
-        json_reader.end_array()?;
-        json_reader.consume_trailing_whitespace()?;
+        let mut stream = SenMLStreamReader::new(&br#"[{"n": "a", "v": "#[..], None);
+        assert!(matches!(
+            stream.next().await,
+            Some(Err(SinditSenMLError::InvalidJSON(_)))
+        ));
+        assert!(stream.next().await.is_none());
     }
 }
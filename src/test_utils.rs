@@ -0,0 +1,123 @@
+//! # Test utilities
+//!
+//! Helpers to test code processing SenML records through callbacks.
+//!
+//! This is available with the `test_utils` feature.
+
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use chrono::{DateTime, Utc};
+
+use crate::{parse_json, SenMLResolvedRecord, SinditSenMLError};
+
+/// Capture the values passed to a callback.
+///
+/// The callbacks share the captured values with the capture,
+/// so they can be moved to other threads.
+///
+/// # Examples
+/// ```
+/// use sindit_senml::test_utils::CallbackCapture;
+///
+/// let capture = CallbackCapture::new();
+/// let callback = capture.callback();
+/// callback(1);
+/// callback(2);
+/// assert_eq!(capture.captured(), vec![1, 2]);
+/// ```
+#[derive(Debug, Clone)]
+pub struct CallbackCapture<T: Clone> {
+    captured: Arc<Mutex<Vec<T>>>,
+}
+
+impl<T: Clone> Default for CallbackCapture<T> {
+    fn default() -> Self {
+        CallbackCapture {
+            captured: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+}
+
+impl<T: Clone> CallbackCapture<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Vec<T>> {
+        // A callback panicking while pushing does not corrupt the values.
+        self.captured
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// A callback capturing the values it is called with.
+    pub fn callback(&self) -> impl Fn(T) + Clone {
+        let capture = self.clone();
+        move |value| capture.lock().push(value)
+    }
+
+    /// The captured values, in the order of the calls.
+    pub fn captured(&self) -> Vec<T> {
+        self.lock().clone()
+    }
+
+    /// The number of calls.
+    pub fn count(&self) -> usize {
+        self.lock().len()
+    }
+}
+
+impl CallbackCapture<SenMLResolvedRecord> {
+    /// Parse SenML JSON and call a callback of a new capture with each record.
+    ///
+    /// No records are captured if the parsing fails.
+    pub fn from_parse(
+        json_str: &str,
+        now: Option<DateTime<Utc>>,
+    ) -> (Self, Result<(), SinditSenMLError>) {
+        let capture = CallbackCapture::new();
+        let result = parse_json(json_str, now).map(|records| {
+            let callback = capture.callback();
+            records.into_iter().for_each(callback);
+        });
+        (capture, result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::MULTIPLE_DATAPOINT_AND_TIME;
+
+    #[test]
+    fn test_capture_from_parse() {
+        let (capture, result) = CallbackCapture::from_parse(MULTIPLE_DATAPOINT_AND_TIME, None);
+        assert!(result.is_ok());
+        assert_eq!(capture.count(), 7);
+        assert_eq!(
+            capture.captured(),
+            parse_json(MULTIPLE_DATAPOINT_AND_TIME, None).unwrap()
+        );
+
+        let (capture, result) = CallbackCapture::from_parse(r#"[{"v": 1}]"#, None);
+        assert!(matches!(result, Err(SinditSenMLError::MissingName(0))));
+        assert_eq!(capture.count(), 0);
+    }
+
+    #[test]
+    fn test_capture_across_threads() {
+        let capture = CallbackCapture::new();
+        let handles: Vec<_> = (0..4)
+            .map(|index| {
+                let callback = capture.callback();
+                std::thread::spawn(move || callback(index))
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        let mut captured = capture.captured();
+        captured.sort();
+        assert_eq!(captured, vec![0, 1, 2, 3]);
+    }
+}
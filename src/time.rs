@@ -11,6 +11,8 @@
 
 use chrono::{DateTime, Duration, Utc};
 
+use crate::{SenMLResolvedRecord, SinditSenMLError};
+
 // 2**28
 const TIME_THRESHOLD: f64 = 268_435_456.0;
 
@@ -32,6 +34,15 @@ const TIME_THRESHOLD: f64 = 268_435_456.0;
 /// assert!(result.is_some());
 /// ```
 pub fn convert_senml_time(seconds: f64, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    convert_senml_time_with(seconds, || now)
+}
+
+/// Convert a SenML time value to a `DateTime<Utc>`,
+/// calling `now` only if the time value is relative.
+pub(crate) fn convert_senml_time_with<F>(seconds: f64, now: F) -> Option<DateTime<Utc>>
+where
+    F: FnOnce() -> DateTime<Utc>,
+{
     // Check if seconds is a valid time value (not NaN or infinity)
     if !seconds.is_finite() {
         return None;
@@ -56,7 +67,7 @@ pub fn convert_senml_time(seconds: f64, now: DateTime<Utc>) -> Option<DateTime<U
     }
 
     // Relative time to now
-    return Some(now + Duration::seconds(whole_seconds) + Duration::nanoseconds(nanoseconds));
+    return Some(now() + Duration::seconds(whole_seconds) + Duration::nanoseconds(nanoseconds));
 }
 
 /// Convert a `DateTime<Utc>` to a Unix timestamp.
@@ -89,9 +100,85 @@ pub fn datetime_to_timestamp(datetime: &DateTime<Utc>) -> (i64, Option<f64>) {
     }
 }
 
+/// Convert a duration to seconds, with subsecond precision.
+pub(crate) fn duration_seconds(duration: Duration) -> f64 {
+    let seconds = duration.num_seconds();
+    let nanoseconds = (duration - Duration::seconds(seconds))
+        .num_nanoseconds()
+        .unwrap_or_default();
+    seconds as f64 + nanoseconds as f64 / 1_000_000_000f64
+}
+
+/// Find the records with a time before the Unix epoch.
+///
+/// Such times are almost certainly bugs, for example a sensor clock
+/// that was not set or a wrong relative time.
+///
+/// # Returns
+/// * `Vec<usize>` - The indices of the records.
+pub fn find_pre_epoch_records(records: &[SenMLResolvedRecord]) -> Vec<usize> {
+    records
+        .iter()
+        .enumerate()
+        .filter(|(_, record)| record.time.timestamp() < 0)
+        .map(|(index, _)| index)
+        .collect()
+}
+
+/// Find the records with a time more than 100 years after `now`.
+///
+/// Such times are suspicious but valid SenML.
+///
+/// # Returns
+/// * `Vec<usize>` - The indices of the records.
+pub fn find_far_future_records(records: &[SenMLResolvedRecord], now: DateTime<Utc>) -> Vec<usize> {
+    // 100 years, including the leap days.
+    let limit = now + Duration::days(36525);
+    records
+        .iter()
+        .enumerate()
+        .filter(|(_, record)| record.time > limit)
+        .map(|(index, _)| index)
+        .collect()
+}
+
+fn is_before_time_threshold(record: &SenMLResolvedRecord) -> bool {
+    (record.time.timestamp() as f64) < TIME_THRESHOLD
+}
+
+/// Check whether all the times are absolute SenML times.
+///
+/// A time before 2**28 seconds after the Unix epoch would have been
+/// read as a relative time, and is likely a relative time that was
+/// never resolved, for example in a pack built manually.
+///
+/// # Returns
+/// * `bool` - `true` if all the times are at or after 2**28 seconds.
+pub fn normalize_times_to_absolute(records: &[SenMLResolvedRecord]) -> bool {
+    !records.iter().any(is_before_time_threshold)
+}
+
+/// Fail on the first record with a time that looks relative,
+/// see [`normalize_times_to_absolute`].
+///
+/// # Returns
+/// * `Result<(), SinditSenMLError>` - `RelativeTimestampInResolvedPack` with the index of the record.
+pub fn assert_all_absolute_timestamps(
+    records: &[SenMLResolvedRecord],
+) -> Result<(), SinditSenMLError> {
+    match records.iter().position(is_before_time_threshold) {
+        Some(index) => Err(SinditSenMLError::RelativeTimestampInResolvedPack(index)),
+        None => Ok(()),
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::convert_senml_time;
+    use super::{
+        assert_all_absolute_timestamps, convert_senml_time, find_far_future_records,
+        find_pre_epoch_records, normalize_times_to_absolute,
+    };
+    use crate::SinditSenMLError;
     use chrono::{DateTime, Utc};
 
     #[test]
@@ -143,6 +230,22 @@ mod tests {
         assert_eq!(result.unwrap(), expected);
     }
 
+    #[test]
+    fn test_negative_relative_half_second_time() {
+        // The fraction of a negative time is negative, and is subtracted
+        // like the whole seconds.
+        let now = DateTime::<Utc>::from_timestamp(10_0000, 0).unwrap();
+        let result = convert_senml_time(-10.5, now);
+        assert_eq!(
+            result.unwrap(),
+            DateTime::<Utc>::from_timestamp(99989, 500_000_000).unwrap()
+        );
+        assert_eq!(
+            convert_senml_time(-0.5, now).unwrap(),
+            DateTime::<Utc>::from_timestamp(99999, 500_000_000).unwrap()
+        );
+    }
+
     #[test]
     fn test_datetime_to_timestamp() {
         let datetime = DateTime::<Utc>::from_timestamp(1234567890, 123456789).unwrap();
@@ -158,4 +261,37 @@ mod tests {
         let unprecise_datetime = DateTime::<Utc>::from_timestamp(1234567890, 123456716).unwrap();
         assert_eq!(result.unwrap(), unprecise_datetime);
     }
+
+    #[test]
+    fn test_find_pre_epoch_and_far_future_records() {
+        let now = DateTime::<Utc>::from_timestamp(1320067464, 0).unwrap();
+        let mut records = crate::parse_json(
+            r#"[{"n": "a", "t": 1320067464},{"n": "b", "t": 1320067464},{"n": "c", "t": 9999999999}]"#,
+            Some(now),
+        )
+        .unwrap();
+        records[1].time = DateTime::<Utc>::from_timestamp(-1, 0).unwrap();
+        assert_eq!(find_pre_epoch_records(&records), vec![1]);
+        assert_eq!(find_far_future_records(&records, now), vec![2]);
+        assert!(find_pre_epoch_records(&records[..1]).is_empty());
+    }
+
+    #[test]
+    fn test_assert_all_absolute_timestamps() {
+        let mut records = crate::parse_json(
+            r#"[{"n": "a", "t": 1320067464},{"n": "b", "t": 268435456}]"#,
+            None,
+        )
+        .unwrap();
+        assert!(normalize_times_to_absolute(&records));
+        assert!(assert_all_absolute_timestamps(&records).is_ok());
+        assert!(normalize_times_to_absolute(&[]));
+
+        records[1].time = DateTime::<Utc>::from_timestamp(100, 0).unwrap();
+        assert!(!normalize_times_to_absolute(&records));
+        assert!(matches!(
+            assert_all_absolute_timestamps(&records).unwrap_err(),
+            SinditSenMLError::RelativeTimestampInResolvedPack(1)
+        ));
+    }
 }
@@ -0,0 +1,1225 @@
+//! # Transform SenML records
+//!
+//! Functions modifying resolved records, for example before forwarding them
+//! to another system.
+
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+
+use crate::time::duration_seconds;
+use crate::validate_name::validate_name;
+use crate::{SenMLResolvedRecord, SenMLValueField, SinditSenMLError};
+
+/// Name given to records when the name is dropped by a projection.
+///
+/// Please note that this name is not a valid SenML name.
+pub const DROPPED_NAME: &str = "_";
+
+/// Select which fields to keep in the records.
+///
+/// All the fields are kept by default.
+/// Dropping a field replaces it with:
+/// * `name`: [`DROPPED_NAME`], which does not pass
+///   [`validate_name`](crate::validate_name::validate_name).
+/// * `time`: the Unix epoch.
+/// * the other fields: `None`.
+///
+/// The base version is always kept.
+///
+/// # Examples
+/// ```
+/// use sindit_senml::{parse_json, transform::ProjectionBuilder};
+///
+/// let records = parse_json(r#"[{"n": "temperature", "u": "Cel", "v": 42.0, "x": 1}]"#, None).unwrap();
+/// let records = ProjectionBuilder::new()
+///     .drop_unit()
+///     .drop_extra_fields()
+///     .project(records);
+/// assert_eq!(records[0].name, "temperature");
+/// assert_eq!(records[0].unit, None);
+/// assert_eq!(records[0].extra_fields, None);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProjectionBuilder {
+    keep_name: bool,
+    keep_unit: bool,
+    keep_value: bool,
+    keep_sum: bool,
+    keep_time: bool,
+    keep_update_time: bool,
+    keep_extra_fields: bool,
+}
+
+impl Default for ProjectionBuilder {
+    fn default() -> Self {
+        ProjectionBuilder {
+            keep_name: true,
+            keep_unit: true,
+            keep_value: true,
+            keep_sum: true,
+            keep_time: true,
+            keep_update_time: true,
+            keep_extra_fields: true,
+        }
+    }
+}
+
+impl ProjectionBuilder {
+    /// A projection keeping all the fields.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn drop_name(mut self) -> Self {
+        self.keep_name = false;
+        self
+    }
+
+    pub fn drop_unit(mut self) -> Self {
+        self.keep_unit = false;
+        self
+    }
+
+    pub fn drop_value(mut self) -> Self {
+        self.keep_value = false;
+        self
+    }
+
+    pub fn drop_sum(mut self) -> Self {
+        self.keep_sum = false;
+        self
+    }
+
+    pub fn drop_time(mut self) -> Self {
+        self.keep_time = false;
+        self
+    }
+
+    pub fn drop_update_time(mut self) -> Self {
+        self.keep_update_time = false;
+        self
+    }
+
+    pub fn drop_extra_fields(mut self) -> Self {
+        self.keep_extra_fields = false;
+        self
+    }
+
+    /// Apply the projection to the records.
+    pub fn project(&self, records: Vec<SenMLResolvedRecord>) -> Vec<SenMLResolvedRecord> {
+        records
+            .into_iter()
+            .map(|mut record| {
+                if !self.keep_name {
+                    record.name = DROPPED_NAME.to_string();
+                }
+                if !self.keep_unit {
+                    record.unit = None;
+                }
+                if !self.keep_value {
+                    record.value = None;
+                }
+                if !self.keep_sum {
+                    record.sum = None;
+                }
+                if !self.keep_time {
+                    // The default DateTime is the Unix epoch.
+                    record.time = DateTime::<Utc>::default();
+                }
+                if !self.keep_update_time {
+                    record.update_time = None;
+                }
+                if !self.keep_extra_fields {
+                    record.extra_fields = None;
+                }
+                record
+            })
+            .collect()
+    }
+}
+
+/// Keep only the selected fields of the records.
+///
+/// See [`ProjectionBuilder`] for how the dropped fields are replaced.
+#[allow(clippy::too_many_arguments)]
+pub fn project_records(
+    records: Vec<SenMLResolvedRecord>,
+    keep_name: bool,
+    keep_unit: bool,
+    keep_value: bool,
+    keep_sum: bool,
+    keep_time: bool,
+    keep_update_time: bool,
+    keep_extra_fields: bool,
+) -> Vec<SenMLResolvedRecord> {
+    ProjectionBuilder {
+        keep_name,
+        keep_unit,
+        keep_value,
+        keep_sum,
+        keep_time,
+        keep_update_time,
+        keep_extra_fields,
+    }
+    .project(records)
+}
+
+impl SenMLResolvedRecord {
+    fn float_value_mut(&mut self) -> Result<&mut f64, SinditSenMLError> {
+        match self.value {
+            Some(SenMLValueField::FloatingPoint(ref mut value)) => Ok(value),
+            _ => Err(SinditSenMLError::WrongValueType),
+        }
+    }
+
+    /// Add `delta` to the floating point value of the record.
+    ///
+    /// Returns `WrongValueType` if the record has no floating point value.
+    pub fn add_to_float(&mut self, delta: f64) -> Result<(), SinditSenMLError> {
+        *self.float_value_mut()? += delta;
+        Ok(())
+    }
+
+    /// Multiply the floating point value of the record by `factor`.
+    ///
+    /// Returns `WrongValueType` if the record has no floating point value.
+    pub fn scale_float(&mut self, factor: f64) -> Result<(), SinditSenMLError> {
+        *self.float_value_mut()? *= factor;
+        Ok(())
+    }
+
+    /// Clamp the floating point value of the record to `[min, max]`.
+    ///
+    /// # Returns
+    /// * `bool` - `true` if the value was changed, `false` if the record
+    ///   has no floating point value or the value is in range.
+    pub fn clamp_float(&mut self, min: f64, max: f64) -> bool {
+        let Ok(value) = self.float_value_mut() else {
+            return false;
+        };
+        let clamped = value.max(min).min(max);
+        if clamped == *value {
+            return false;
+        }
+        *value = clamped;
+        true
+    }
+
+    /// Clone the record and add `offset` to its time.
+    ///
+    /// Returns `InvalidTime` if the new time is out of range.
+    pub fn with_time_offset(&self, offset: chrono::Duration) -> Result<Self, SinditSenMLError> {
+        let time = self
+            .time
+            .checked_add_signed(offset)
+            .ok_or(SinditSenMLError::InvalidTime)?;
+        Ok(SenMLResolvedRecord {
+            time,
+            ..self.clone()
+        })
+    }
+
+    /// Clone the record and replace its value.
+    pub fn with_value(&self, value: SenMLValueField) -> Self {
+        SenMLResolvedRecord {
+            value: Some(value),
+            ..self.clone()
+        }
+    }
+
+    /// Clone the record and replace its name.
+    ///
+    /// Returns `InvalidName` if the name is not a valid SenML name.
+    pub fn with_name(&self, name: &str) -> Result<Self, SinditSenMLError> {
+        if !validate_name(name) {
+            return Err(SinditSenMLError::InvalidName);
+        }
+        Ok(SenMLResolvedRecord {
+            name: name.to_string(),
+            ..self.clone()
+        })
+    }
+
+    /// Generate records from this record, one per time and floating point value.
+    ///
+    /// The generated records are clones of this record, with the same
+    /// name and unit.
+    ///
+    /// Returns `SeriesLengthMismatch` if there are not as many times as values.
+    ///
+    /// # Examples
+    /// ```
+    /// use chrono::{DateTime, Utc};
+    /// use sindit_senml::parse_json;
+    ///
+    /// let records = parse_json(r#"[{"n": "temperature", "u": "Cel", "v": 20.0}]"#, None).unwrap();
+    /// let times = [
+    ///     DateTime::<Utc>::from_timestamp(1320067464, 0).unwrap(),
+    ///     DateTime::<Utc>::from_timestamp(1320067524, 0).unwrap(),
+    /// ];
+    /// let series = records[0].derive_series(&times, &[20.5, 21.0]).unwrap();
+    /// assert_eq!(series[1].time, times[1]);
+    /// assert_eq!(series[1].get_float_value(), Some(21.0));
+    /// ```
+    pub fn derive_series(
+        &self,
+        times: &[DateTime<Utc>],
+        values: &[f64],
+    ) -> Result<Vec<Self>, SinditSenMLError> {
+        if times.len() != values.len() {
+            return Err(SinditSenMLError::SeriesLengthMismatch {
+                times: times.len(),
+                values: values.len(),
+            });
+        }
+        Ok(times
+            .iter()
+            .zip(values.iter())
+            .map(|(time, value)| SenMLResolvedRecord {
+                time: *time,
+                value: Some(SenMLValueField::FloatingPoint(*value)),
+                ..self.clone()
+            })
+            .collect())
+    }
+}
+
+/// Add `delta` to the floating point values of the records named `name`.
+///
+/// Records without a floating point value are left unchanged.
+///
+/// # Returns
+/// * `usize` - The number of modified records.
+/// # Examples
+/// ```
+/// use sindit_senml::{parse_json, transform::add_float_to_records};
+///
+/// let mut records = parse_json(r#"[{"n": "setpoint", "v": 20.0}]"#, None).unwrap();
+/// assert_eq!(add_float_to_records(&mut records, "setpoint", 1.5), 1);
+/// assert_eq!(records[0].get_float_value(), Some(21.5));
+/// ```
+pub fn add_float_to_records(records: &mut [SenMLResolvedRecord], name: &str, delta: f64) -> usize {
+    records
+        .iter_mut()
+        .filter(|record| record.name == name)
+        .filter_map(|record| record.add_to_float(delta).ok())
+        .count()
+}
+
+/// Clamp the floating point values of the records named `name`
+/// to `[min, max]`.
+///
+/// # Returns
+/// * `usize` - The number of clamped records.
+/// # Examples
+/// ```
+/// use sindit_senml::{parse_json, transform::clamp_pack};
+///
+/// let mut records = parse_json(r#"[{"n": "setpoint", "v": 120.0},{"n": "setpoint", "v": 20.0}]"#, None).unwrap();
+/// assert_eq!(clamp_pack(&mut records, "setpoint", 0.0, 100.0), 1);
+/// assert_eq!(records[0].get_float_value(), Some(100.0));
+/// ```
+pub fn clamp_pack(records: &mut [SenMLResolvedRecord], name: &str, min: f64, max: f64) -> usize {
+    records
+        .iter_mut()
+        .filter(|record| record.name == name)
+        .map(|record| record.clamp_float(min, max))
+        .filter(|clamped| *clamped)
+        .count()
+}
+
+/// Clamp the floating point values of the records to the `(min, max)`
+/// range of their name in `constraints`.
+///
+/// # Returns
+/// * `usize` - The number of clamped records.
+pub fn clamp_pack_all(
+    records: &mut [SenMLResolvedRecord],
+    constraints: &HashMap<String, (f64, f64)>,
+) -> usize {
+    records
+        .iter_mut()
+        .map(|record| match constraints.get(&record.name) {
+            Some((min, max)) => record.clamp_float(*min, *max),
+            None => false,
+        })
+        .filter(|clamped| *clamped)
+        .count()
+}
+
+/// Convert the ASCII letters of the names of the records to lowercase.
+///
+/// See also [`NameCase`](crate::NameCase) to normalise the names when parsing.
+pub fn normalize_names_lowercase(records: &mut [SenMLResolvedRecord]) {
+    for record in records.iter_mut() {
+        record.name.make_ascii_lowercase();
+    }
+}
+
+/// Convert the ASCII letters of the names of the records to uppercase.
+///
+/// See also [`NameCase`](crate::NameCase) to normalise the names when parsing.
+pub fn normalize_names_uppercase(records: &mut [SenMLResolvedRecord]) {
+    for record in records.iter_mut() {
+        record.name.make_ascii_uppercase();
+    }
+}
+
+fn rename_records<F>(
+    records: Vec<SenMLResolvedRecord>,
+    rename: F,
+) -> Result<Vec<SenMLResolvedRecord>, SinditSenMLError>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    records
+        .into_iter()
+        .enumerate()
+        .map(|(index, mut record)| {
+            if let Some(name) = rename(&record.name) {
+                if !validate_name(&name) {
+                    return Err(SinditSenMLError::InvalidNameInRecord(index));
+                }
+                record.name = name;
+            }
+            Ok(record)
+        })
+        .collect()
+}
+
+/// Replace the names of the records using `mapping`.
+///
+/// The names missing from the mapping are left unchanged.
+///
+/// Returns `InvalidNameInRecord` if a new name is not a valid SenML name.
+///
+/// # Examples
+/// ```
+/// use std::collections::HashMap;
+/// use sindit_senml::{parse_json, transform::remap_names};
+///
+/// let records = parse_json(r#"[{"n": "0x01", "v": 230.0},{"n": "current", "v": 1.2}]"#, None).unwrap();
+/// let mapping = HashMap::from([("0x01".to_string(), "voltage".to_string())]);
+/// let records = remap_names(records, &mapping).unwrap();
+/// assert_eq!(records[0].name, "voltage");
+/// assert_eq!(records[1].name, "current");
+/// ```
+pub fn remap_names(
+    records: Vec<SenMLResolvedRecord>,
+    mapping: &HashMap<String, String>,
+) -> Result<Vec<SenMLResolvedRecord>, SinditSenMLError> {
+    rename_records(records, |name| mapping.get(name).cloned())
+}
+
+/// Replace the first match of the regular expression `pattern` in the
+/// names of the records by `replacement`.
+///
+/// The replacement can refer to the capture groups, see
+/// [`regex::Regex::replace`].
+///
+/// Returns `InvalidRegex` if the pattern is invalid, and
+/// `InvalidNameInRecord` if a new name is not a valid SenML name.
+pub fn remap_names_regex(
+    records: Vec<SenMLResolvedRecord>,
+    pattern: &str,
+    replacement: &str,
+) -> Result<Vec<SenMLResolvedRecord>, SinditSenMLError> {
+    let regex = regex::Regex::new(pattern)?;
+    rename_records(records, |name| {
+        Some(regex.replace(name, replacement).into_owned())
+    })
+}
+
+/// Prepend `prefix` to the names of the records.
+///
+/// Returns `InvalidNameInRecord` if a new name is not a valid SenML name.
+pub fn prefix_all_names(
+    records: Vec<SenMLResolvedRecord>,
+    prefix: &str,
+) -> Result<Vec<SenMLResolvedRecord>, SinditSenMLError> {
+    rename_records(records, |name| Some(format!("{}{}", prefix, name)))
+}
+
+/// How to merge new extra fields with the existing ones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ExtraFieldsMergeStrategy {
+    /// The new fields replace the existing fields having the same key.
+    #[default]
+    Overwrite,
+    /// Only the new fields with a new key are added.
+    KeepExisting,
+    /// The objects are merged recursively, the other values are replaced.
+    DeepMerge,
+}
+
+fn deep_merge(existing: &mut serde_json::Value, new: serde_json::Value) {
+    match (existing, new) {
+        (serde_json::Value::Object(existing), serde_json::Value::Object(new)) => {
+            for (key, value) in new {
+                match existing.get_mut(&key) {
+                    Some(existing_value) => deep_merge(existing_value, value),
+                    None => {
+                        existing.insert(key, value);
+                    }
+                }
+            }
+        }
+        (existing, new) => *existing = new,
+    }
+}
+
+/// Merge `new_fields` into the extra fields of the record.
+///
+/// # Examples
+/// ```
+/// use std::collections::HashMap;
+/// use serde_json::json;
+/// use sindit_senml::{parse_json, transform::{merge_extra_fields, ExtraFieldsMergeStrategy}};
+///
+/// let mut records = parse_json(r#"[{"n": "temperature", "v": 21.5, "meta": {"location": "room1"}}]"#, None).unwrap();
+/// let new_fields = HashMap::from([("meta".to_string(), json!({"source": "sensorA"}))]);
+/// merge_extra_fields(&mut records[0], new_fields, ExtraFieldsMergeStrategy::DeepMerge);
+/// assert_eq!(
+///     records[0].extra_fields.as_ref().unwrap()["meta"],
+///     json!({"location": "room1", "source": "sensorA"})
+/// );
+/// ```
+pub fn merge_extra_fields(
+    record: &mut SenMLResolvedRecord,
+    new_fields: HashMap<String, serde_json::Value>,
+    strategy: ExtraFieldsMergeStrategy,
+) {
+    if new_fields.is_empty() {
+        return;
+    }
+    let extra_fields = record.extra_fields.get_or_insert_with(HashMap::new);
+    for (key, value) in new_fields {
+        match strategy {
+            ExtraFieldsMergeStrategy::Overwrite => {
+                extra_fields.insert(key, value);
+            }
+            ExtraFieldsMergeStrategy::KeepExisting => {
+                extra_fields.entry(key).or_insert(value);
+            }
+            ExtraFieldsMergeStrategy::DeepMerge => match extra_fields.get_mut(&key) {
+                Some(existing) => deep_merge(existing, value),
+                None => {
+                    extra_fields.insert(key, value);
+                }
+            },
+        }
+    }
+}
+
+/// Merge `new_fields` into the extra fields of all the records.
+///
+/// See [`merge_extra_fields`].
+pub fn merge_extra_fields_all(
+    records: &mut [SenMLResolvedRecord],
+    new_fields: &HashMap<String, serde_json::Value>,
+    strategy: ExtraFieldsMergeStrategy,
+) {
+    for record in records.iter_mut() {
+        merge_extra_fields(record, new_fields.clone(), strategy);
+    }
+}
+
+/// Merge the fields computed by `enricher` into the extra fields of the records.
+///
+/// The enricher only reads the records. The fields it returns replace the
+/// extra fields having the same key. They should not use the names of the
+/// SenML fields, such as `n` or `v`.
+///
+/// # Returns
+/// * `usize` - The number of records that received at least one new field.
+/// # Examples
+/// ```
+/// use sindit_senml::{parse_json, transform::{add_value_type_tag, enrich_records}};
+///
+/// let mut records = parse_json(r#"[{"n": "open", "vb": true, "t": 1320067464}]"#, None).unwrap();
+/// assert_eq!(enrich_records(&mut records, add_value_type_tag()), 1);
+/// assert_eq!(
+///     serde_json::to_string(&records[0]).unwrap(),
+///     r#"{"n":"open","vb":true,"t":1320067464,"value_type":"bool"}"#
+/// );
+/// ```
+pub fn enrich_records<F>(records: &mut [SenMLResolvedRecord], enricher: F) -> usize
+where
+    F: Fn(&SenMLResolvedRecord) -> HashMap<String, serde_json::Value>,
+{
+    let mut enriched_count = 0;
+    for record in records.iter_mut() {
+        let fields = enricher(record);
+        if fields.is_empty() {
+            continue;
+        }
+        let extra_fields = record.extra_fields.get_or_insert_with(HashMap::new);
+        let mut new_field = false;
+        for (key, value) in fields {
+            new_field |= extra_fields.insert(key, value).is_none();
+        }
+        if new_field {
+            enriched_count += 1;
+        }
+    }
+    enriched_count
+}
+
+/// Enricher adding the `value_type` extra field to the records with a value.
+///
+/// The value type is `float`, `bool`, `string`, or `data`.
+/// See [`enrich_records`].
+pub fn add_value_type_tag() -> impl Fn(&SenMLResolvedRecord) -> HashMap<String, serde_json::Value> {
+    |record| {
+        let value_type = match record.value {
+            Some(SenMLValueField::FloatingPoint(_)) => "float",
+            Some(SenMLValueField::BooleanValue(_)) => "bool",
+            Some(SenMLValueField::StringValue(_)) => "string",
+            Some(SenMLValueField::DataValue(_)) => "data",
+            None => return HashMap::new(),
+        };
+        HashMap::from([("value_type".to_string(), serde_json::json!(value_type))])
+    }
+}
+
+/// Insert a statistics record after every `window_size` float records
+/// of `sensor_name`.
+///
+/// The statistics record is named `sensor_name` followed by `/stats`,
+/// has the time and version of the last record of the window, and a string
+/// value with the JSON object `{"max": ..., "mean": ..., "min": ...}`.
+/// An incomplete window at the end is not annotated.
+///
+/// # Examples
+/// ```
+/// use sindit_senml::{parse_json, transform::annotate_with_stats};
+///
+/// let mut records = parse_json(r#"[{"n": "t", "v": 1},{"n": "t", "v": 3}]"#, None).unwrap();
+/// annotate_with_stats(&mut records, "t", 2);
+/// assert_eq!(records[2].name, "t/stats");
+/// assert_eq!(
+///     records[2].get_string_value().map(String::as_str),
+///     Some(r#"{"max":3.0,"mean":2.0,"min":1.0}"#)
+/// );
+/// ```
+pub fn annotate_with_stats(
+    records: &mut Vec<SenMLResolvedRecord>,
+    sensor_name: &str,
+    window_size: usize,
+) {
+    if window_size == 0 {
+        return;
+    }
+    let mut annotated = Vec::with_capacity(records.len() + records.len() / window_size);
+    let mut window: Vec<f64> = Vec::with_capacity(window_size);
+    for record in records.drain(..) {
+        let value = match record.value {
+            Some(SenMLValueField::FloatingPoint(value)) if record.name == sensor_name => {
+                Some(value)
+            }
+            _ => None,
+        };
+        let time = record.time;
+        let base_version = record.base_version;
+        annotated.push(record);
+
+        let Some(value) = value else {
+            continue;
+        };
+        window.push(value);
+        if window.len() == window_size {
+            let stats = serde_json::json!({
+                "mean": window.iter().sum::<f64>() / window_size as f64,
+                "min": window.iter().copied().fold(f64::INFINITY, f64::min),
+                "max": window.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+            });
+            annotated.push(SenMLResolvedRecord {
+                name: format!("{}/stats", sensor_name),
+                unit: None,
+                value: Some(SenMLValueField::StringValue(stats.to_string())),
+                sum: None,
+                time,
+                update_time: None,
+                base_version,
+                extra_fields: None,
+                active_base_name: None,
+            });
+            window.clear();
+        }
+    }
+    *records = annotated;
+}
+
+/// Rate of change of the float values of each sensor.
+///
+/// For each pair of consecutive float values of a sensor, in the order
+/// of the pack, a record with `(value - previous value) / elapsed seconds`
+/// is created at the middle of the two times. The unit is the unit of
+/// the sensor followed by `/s`, for example `J/s` for a `J` sensor.
+/// The pairs with the same time are skipped.
+///
+/// # Examples
+/// ```
+/// use sindit_senml::{parse_json, transform::compute_rate_of_change};
+///
+/// let records = parse_json(
+///     r#"[{"bt": 1320067464, "n": "energy", "u": "J", "v": 0},{"n": "energy", "u": "J", "v": 600, "t": 60}]"#,
+///     None,
+/// ).unwrap();
+/// let rates = compute_rate_of_change(&records);
+/// assert_eq!(rates[0].get_float_value(), Some(10.0));
+/// assert_eq!(rates[0].unit.as_deref(), Some("J/s"));
+/// assert_eq!(rates[0].time.timestamp(), 1320067494);
+/// ```
+pub fn compute_rate_of_change(records: &[SenMLResolvedRecord]) -> Vec<SenMLResolvedRecord> {
+    let mut previous_records: HashMap<&str, (&SenMLResolvedRecord, f64)> = HashMap::new();
+    let mut rates = Vec::new();
+    for record in records.iter() {
+        let Some(SenMLValueField::FloatingPoint(value)) = record.value else {
+            continue;
+        };
+        if let Some((previous, previous_value)) =
+            previous_records.insert(record.name.as_str(), (record, value))
+        {
+            let elapsed = record.time - previous.time;
+            let seconds = duration_seconds(elapsed);
+            if seconds == 0.0 {
+                continue;
+            }
+            rates.push(SenMLResolvedRecord {
+                name: record.name.clone(),
+                unit: record.unit.as_ref().map(|unit| format!("{}/s", unit)),
+                value: Some(SenMLValueField::FloatingPoint(
+                    (value - previous_value) / seconds,
+                )),
+                sum: None,
+                time: previous.time + elapsed / 2,
+                update_time: None,
+                base_version: record.base_version,
+                extra_fields: None,
+                active_base_name: None,
+            });
+        }
+    }
+    rates
+}
+
+/// Linear interpolation of the float values of two records of a sensor.
+///
+/// # Arguments
+/// * `a` - The record before `t`.
+/// * `b` - The record after `t`.
+/// * `t` - The time of the interpolated record.
+/// # Returns
+/// * `Option<SenMLResolvedRecord>` - The record at `t`, with the name and
+///   the unit of the records. `None` if the names differ, if a value is not
+///   a float, or if `t` is not between the times of `a` and `b`.
+/// # Examples
+/// ```
+/// use sindit_senml::{parse_json, transform::interpolate_at};
+///
+/// let records = parse_json(
+///     r#"[{"bt": 1320067464, "n": "temp", "u": "Cel", "v": 20},{"n": "temp", "u": "Cel", "v": 23, "t": 60}]"#,
+///     None,
+/// ).unwrap();
+/// let time = records[0].time + chrono::Duration::seconds(20);
+/// let record = interpolate_at(&records[0], &records[1], time).unwrap();
+/// assert_eq!(record.get_float_value(), Some(21.0));
+/// assert_eq!(record.time, time);
+/// ```
+pub fn interpolate_at(
+    a: &SenMLResolvedRecord,
+    b: &SenMLResolvedRecord,
+    t: DateTime<Utc>,
+) -> Option<SenMLResolvedRecord> {
+    if a.name != b.name || t < a.time || t > b.time {
+        return None;
+    }
+    let (
+        Some(SenMLValueField::FloatingPoint(a_value)),
+        Some(SenMLValueField::FloatingPoint(b_value)),
+    ) = (&a.value, &b.value)
+    else {
+        return None;
+    };
+    let span = duration_seconds(b.time - a.time);
+    let value = if span == 0.0 {
+        *a_value
+    } else {
+        a_value + (b_value - a_value) * duration_seconds(t - a.time) / span
+    };
+    Some(SenMLResolvedRecord {
+        name: a.name.clone(),
+        unit: a.unit.clone().or_else(|| b.unit.clone()),
+        value: Some(SenMLValueField::FloatingPoint(value)),
+        sum: None,
+        time: t,
+        update_time: None,
+        base_version: a.base_version,
+        extra_fields: None,
+        active_base_name: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::MULTIPLE_DATATYPES;
+    use crate::parse_json;
+
+    const PACK: &str = r#"[
+        {"n": "temperature", "u": "Cel", "v": 23.1, "t": 1320067464, "ut": 60, "location": "room1"},
+        {"n": "humidity", "u": "%RH", "v": 40.0, "t": 1320067464, "owner": "alice"}
+    ]"#;
+
+    #[test]
+    fn test_drop_extra_fields() {
+        let records = parse_json(PACK, None).unwrap();
+        assert!(records.iter().all(|record| record.extra_fields.is_some()));
+        let projected = ProjectionBuilder::new()
+            .drop_extra_fields()
+            .project(records.clone());
+        assert_eq!(projected.len(), 2);
+        for (record, projected_record) in records.iter().zip(projected.iter()) {
+            assert_eq!(projected_record.extra_fields, None);
+            assert_eq!(projected_record.name, record.name);
+            assert_eq!(projected_record.unit, record.unit);
+            assert_eq!(projected_record.value, record.value);
+            assert_eq!(projected_record.time, record.time);
+        }
+        let json = serde_json::to_string(&projected).unwrap();
+        assert!(!json.contains("location"));
+        assert!(!json.contains("owner"));
+    }
+
+    #[test]
+    fn test_keep_everything() {
+        let records = parse_json(PACK, None).unwrap();
+        assert_eq!(ProjectionBuilder::new().project(records.clone()), records);
+        assert_eq!(
+            project_records(records.clone(), true, true, true, true, true, true, true),
+            records
+        );
+    }
+
+    #[test]
+    fn test_drop_everything() {
+        let records = parse_json(PACK, None).unwrap();
+        let projected = project_records(records, false, false, false, false, false, false, false);
+        for record in projected.iter() {
+            assert_eq!(record.name, DROPPED_NAME);
+            assert!(!validate_name(&record.name));
+            assert_eq!(record.unit, None);
+            assert_eq!(record.value, None);
+            assert_eq!(record.sum, None);
+            assert_eq!(record.time.timestamp(), 0);
+            assert_eq!(record.update_time, None);
+            assert_eq!(record.extra_fields, None);
+        }
+    }
+
+    #[test]
+    fn test_add_float_to_records() {
+        let records = parse_json(MULTIPLE_DATATYPES, None).unwrap();
+        let mut modified = records.clone();
+        assert_eq!(
+            add_float_to_records(&mut modified, "urn:dev:ow:10e2073a01080063:temp", 5.0),
+            1
+        );
+        assert_eq!(modified[0].get_float_value(), Some(28.1));
+        assert_eq!(modified[1..], records[1..]);
+
+        // Not a floating point value
+        assert_eq!(
+            add_float_to_records(&mut modified, "urn:dev:ow:10e2073a01080063:open", 5.0),
+            0
+        );
+        assert_eq!(modified[2], records[2]);
+    }
+
+    #[test]
+    fn test_float_arithmetic_wrong_type() {
+        let mut records = parse_json(MULTIPLE_DATATYPES, None).unwrap();
+        assert!(matches!(
+            records[2].add_to_float(5.0).unwrap_err(),
+            SinditSenMLError::WrongValueType
+        ));
+        assert!(matches!(
+            records[1].scale_float(2.0).unwrap_err(),
+            SinditSenMLError::WrongValueType
+        ));
+        assert_eq!(records[2].get_bool_value(), Some(false));
+
+        records[0].scale_float(2.0).unwrap();
+        assert_eq!(records[0].get_float_value(), Some(46.2));
+    }
+
+    #[test]
+    fn test_derive_series() {
+        let records = parse_json(PACK, None).unwrap();
+        let start = records[0].time;
+        let times: Vec<DateTime<Utc>> = (0..10)
+            .map(|index| start + chrono::Duration::minutes(index))
+            .collect();
+        let values: Vec<f64> = (0..10).map(|index| 20.0 + index as f64 / 10.0).collect();
+        let series = records[0].derive_series(&times, &values).unwrap();
+        assert_eq!(series.len(), 10);
+        for (index, record) in series.iter().enumerate() {
+            assert_eq!(record.name, "temperature");
+            assert_eq!(record.unit.as_deref(), Some("Cel"));
+            assert_eq!(record.time, times[index]);
+            assert_eq!(record.get_float_value(), Some(values[index]));
+        }
+
+        assert!(matches!(
+            records[0].derive_series(&times, &values[1..]).unwrap_err(),
+            SinditSenMLError::SeriesLengthMismatch {
+                times: 10,
+                values: 9
+            }
+        ));
+    }
+
+    #[test]
+    fn test_with_fields() {
+        let records = parse_json(PACK, None).unwrap();
+        let record = records[0]
+            .with_time_offset(chrono::Duration::seconds(60))
+            .unwrap();
+        assert_eq!(record.time - records[0].time, chrono::Duration::seconds(60));
+        assert_eq!(record.value, records[0].value);
+        assert!(matches!(
+            records[0]
+                .with_time_offset(chrono::Duration::max_value())
+                .unwrap_err(),
+            SinditSenMLError::InvalidTime
+        ));
+
+        let record = records[0].with_value(SenMLValueField::BooleanValue(true));
+        assert_eq!(record.get_bool_value(), Some(true));
+        assert_eq!(record.name, records[0].name);
+
+        let record = records[0]
+            .with_name("urn:dev:ow:10e2073a01080063:temp")
+            .unwrap();
+        assert_eq!(record.name, "urn:dev:ow:10e2073a01080063:temp");
+        assert_eq!(record.unit, records[0].unit);
+        assert!(matches!(
+            records[0].with_name("-temperature").unwrap_err(),
+            SinditSenMLError::InvalidName
+        ));
+    }
+
+    #[test]
+    fn test_normalize_names() {
+        let mut records =
+            parse_json(r#"[{"n": "Temperature"},{"n": "urn:dev:ow:abc"}]"#, None).unwrap();
+        normalize_names_uppercase(&mut records);
+        assert_eq!(records[0].name, "TEMPERATURE");
+        assert_eq!(records[1].name, "URN:DEV:OW:ABC");
+        normalize_names_lowercase(&mut records);
+        assert_eq!(records[0].name, "temperature");
+        assert_eq!(records[1].name, "urn:dev:ow:abc");
+        assert!(records.iter().all(|record| validate_name(&record.name)));
+    }
+
+    #[test]
+    fn test_add_value_type_tag() {
+        let mut records = parse_json(MULTIPLE_DATATYPES, None).unwrap();
+        assert_eq!(enrich_records(&mut records, add_value_type_tag()), 4);
+        let value_types: Vec<&serde_json::Value> = records
+            .iter()
+            .map(|record| &record.extra_fields.as_ref().unwrap()["value_type"])
+            .collect();
+        assert_eq!(value_types, vec!["float", "string", "bool", "data"]);
+
+        // The fields are already present
+        assert_eq!(enrich_records(&mut records, add_value_type_tag()), 0);
+    }
+
+    #[test]
+    fn test_enrich_with_empty_map() {
+        let records = parse_json(MULTIPLE_DATATYPES, None).unwrap();
+        let mut enriched = records.clone();
+        assert_eq!(enrich_records(&mut enriched, |_| HashMap::new()), 0);
+        assert_eq!(enriched, records);
+    }
+
+    const ELECTRICAL_PACK: &str = r#"[
+        {"bt": 1320067464, "n": "voltage", "u": "V", "v": 120.1},
+        {"n": "current", "u": "A", "v": 1.2},
+        {"n": "voltage", "u": "V", "t": 60, "v": 119.8}
+    ]"#;
+
+    #[test]
+    fn test_remap_names() {
+        let records = parse_json(ELECTRICAL_PACK, None).unwrap();
+        let mapping = HashMap::from([("voltage".to_string(), "line-voltage".to_string())]);
+        let remapped = remap_names(records.clone(), &mapping).unwrap();
+        assert_eq!(remapped[0].name, "line-voltage");
+        assert_eq!(remapped[1].name, "current");
+        assert_eq!(remapped[2].name, "line-voltage");
+        assert_eq!(remapped[2].value, records[2].value);
+
+        let mapping = HashMap::from([("current".to_string(), "line current".to_string())]);
+        assert!(matches!(
+            remap_names(records, &mapping).unwrap_err(),
+            SinditSenMLError::InvalidNameInRecord(1)
+        ));
+    }
+
+    #[test]
+    fn test_remap_names_regex() {
+        let records = parse_json(ELECTRICAL_PACK, None).unwrap();
+        let remapped = remap_names_regex(records.clone(), "^(volt|curr)", "line-$1").unwrap();
+        assert_eq!(remapped[0].name, "line-voltage");
+        assert_eq!(remapped[1].name, "line-current");
+
+        assert!(matches!(
+            remap_names_regex(records.clone(), "^v", "-").unwrap_err(),
+            SinditSenMLError::InvalidNameInRecord(0)
+        ));
+        assert!(matches!(
+            remap_names_regex(records, "(", "").unwrap_err(),
+            SinditSenMLError::InvalidRegex(_)
+        ));
+    }
+
+    #[test]
+    fn test_prefix_all_names() {
+        let records = parse_json(ELECTRICAL_PACK, None).unwrap();
+        let prefixed = prefix_all_names(records.clone(), "sensor/").unwrap();
+        assert_eq!(
+            prefixed
+                .iter()
+                .map(|record| record.name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["sensor/voltage", "sensor/current", "sensor/voltage"]
+        );
+        assert!(matches!(
+            prefix_all_names(records, "/").unwrap_err(),
+            SinditSenMLError::InvalidNameInRecord(0)
+        ));
+    }
+
+    #[test]
+    fn test_merge_extra_fields_strategies() {
+        let records = parse_json(
+            r#"[{"n": "temperature", "v": 21.5, "meta": {"location": "room1", "floor": 1}, "owner": "alice"}]"#,
+            None,
+        )
+        .unwrap();
+        let new_fields = HashMap::from([
+            (
+                "meta".to_string(),
+                serde_json::json!({"source": "sensorA", "floor": 2}),
+            ),
+            ("quality".to_string(), serde_json::json!("good")),
+        ]);
+
+        let mut record = records[0].clone();
+        merge_extra_fields(
+            &mut record,
+            new_fields.clone(),
+            ExtraFieldsMergeStrategy::DeepMerge,
+        );
+        let extra_fields = record.extra_fields.unwrap();
+        assert_eq!(
+            extra_fields["meta"],
+            serde_json::json!({"location": "room1", "source": "sensorA", "floor": 2})
+        );
+        assert_eq!(extra_fields["quality"], "good");
+        assert_eq!(extra_fields["owner"], "alice");
+
+        let mut record = records[0].clone();
+        merge_extra_fields(
+            &mut record,
+            new_fields.clone(),
+            ExtraFieldsMergeStrategy::Overwrite,
+        );
+        let extra_fields = record.extra_fields.unwrap();
+        assert_eq!(
+            extra_fields["meta"],
+            serde_json::json!({"source": "sensorA", "floor": 2})
+        );
+        assert_eq!(extra_fields["quality"], "good");
+
+        let mut record = records[0].clone();
+        merge_extra_fields(
+            &mut record,
+            new_fields,
+            ExtraFieldsMergeStrategy::KeepExisting,
+        );
+        let extra_fields = record.extra_fields.unwrap();
+        assert_eq!(
+            extra_fields["meta"],
+            serde_json::json!({"location": "room1", "floor": 1})
+        );
+        assert_eq!(extra_fields["quality"], "good");
+    }
+
+    #[test]
+    fn test_merge_extra_fields_all() {
+        let mut records = parse_json(PACK, None).unwrap();
+        let new_fields = HashMap::from([("gateway".to_string(), serde_json::json!("gw1"))]);
+        merge_extra_fields_all(
+            &mut records,
+            &new_fields,
+            ExtraFieldsMergeStrategy::default(),
+        );
+        assert!(records
+            .iter()
+            .all(|record| record.extra_fields.as_ref().unwrap()["gateway"] == "gw1"));
+
+        let mut records = parse_json(r#"[{"n": "temperature", "v": 21.5}]"#, None).unwrap();
+        merge_extra_fields_all(
+            &mut records,
+            &HashMap::new(),
+            ExtraFieldsMergeStrategy::Overwrite,
+        );
+        assert_eq!(records[0].extra_fields, None);
+    }
+
+    #[test]
+    fn test_annotate_with_stats() {
+        let json = (0..9)
+            .map(|index| {
+                format!(
+                    r#"{{"n": "t", "v": {}, "t": {}}}"#,
+                    index,
+                    1320067464 + index
+                )
+            })
+            .collect::<Vec<String>>()
+            .join(",");
+        let mut records = parse_json(&format!("[{}]", json), None).unwrap();
+        annotate_with_stats(&mut records, "t", 3);
+        assert_eq!(records.len(), 12);
+
+        let annotation_indices: Vec<usize> = records
+            .iter()
+            .enumerate()
+            .filter(|(_, record)| record.name == "t/stats")
+            .map(|(index, _)| index)
+            .collect();
+        assert_eq!(annotation_indices, vec![3, 7, 11]);
+        assert_eq!(records[7].time, records[6].time);
+        let stats: serde_json::Value =
+            serde_json::from_str(records[7].get_string_value().unwrap()).unwrap();
+        assert_eq!(
+            stats,
+            serde_json::json!({"mean": 4.0, "min": 3.0, "max": 5.0})
+        );
+
+        let mut records = parse_json(PACK, None).unwrap();
+        annotate_with_stats(&mut records, "temperature", 2);
+        annotate_with_stats(&mut records, "temperature", 0);
+        assert_eq!(records, parse_json(PACK, None).unwrap());
+    }
+
+    #[test]
+    fn test_compute_rate_of_change() {
+        let records = parse_json(
+            r#"[{"bn": "meter/", "bt": 1320067464, "n": "energy", "u": "J", "v": 1000},
+                {"n": "voltage", "u": "V", "v": 230},
+                {"n": "energy", "u": "J", "v": 1600, "t": 60},
+                {"n": "label", "vs": "Machine Room", "t": 90},
+                {"n": "energy", "u": "J", "v": 1600, "t": 60},
+                {"n": "energy", "u": "J", "v": 1500, "t": 60.5},
+                {"n": "count", "v": 2, "t": 10},
+                {"n": "count", "v": 5, "t": 12}]"#,
+            None,
+        )
+        .unwrap();
+        let rates = compute_rate_of_change(&records);
+        assert_eq!(rates.len(), 3);
+
+        assert_eq!(rates[0].name, "meter/energy");
+        assert_eq!(rates[0].unit.as_deref(), Some("J/s"));
+        assert_eq!(rates[0].get_float_value(), Some(10.0));
+        assert_eq!(rates[0].time.timestamp(), 1320067494);
+
+        assert_eq!(rates[1].get_float_value(), Some(-200.0));
+        assert_eq!(
+            rates[1].time,
+            DateTime::<Utc>::from_timestamp(1320067524, 250_000_000).unwrap()
+        );
+
+        assert_eq!(rates[2].name, "meter/count");
+        assert_eq!(rates[2].unit, None);
+        assert_eq!(rates[2].get_float_value(), Some(1.5));
+        assert_eq!(rates[2].time.timestamp(), 1320067475);
+
+        assert!(compute_rate_of_change(&records[..2]).is_empty());
+    }
+
+    #[test]
+    fn test_interpolate_at() {
+        let records = parse_json(
+            r#"[{"bt": 1320067464, "n": "temp", "u": "Cel", "v": 20},
+                {"n": "temp", "v": 24, "t": 4},
+                {"n": "humidity", "u": "%RH", "v": 40, "t": 2},
+                {"n": "temp", "vs": "overheat", "t": 8}]"#,
+            None,
+        )
+        .unwrap();
+        let (a, b) = (&records[0], &records[1]);
+        let at = |milliseconds| a.time + chrono::Duration::milliseconds(milliseconds);
+
+        let record = interpolate_at(a, b, at(1000)).unwrap();
+        assert_eq!(record.name, "temp");
+        assert_eq!(record.unit.as_deref(), Some("Cel"));
+        assert_eq!(record.get_float_value(), Some(21.0));
+        assert_eq!(record.time, at(1000));
+        assert_eq!(
+            interpolate_at(a, b, at(2500)).unwrap().get_float_value(),
+            Some(22.5)
+        );
+        assert_eq!(
+            interpolate_at(a, b, a.time).unwrap().get_float_value(),
+            Some(20.0)
+        );
+        assert_eq!(
+            interpolate_at(a, b, b.time).unwrap().get_float_value(),
+            Some(24.0)
+        );
+        assert_eq!(
+            interpolate_at(a, a, a.time).unwrap().get_float_value(),
+            Some(20.0)
+        );
+
+        assert_eq!(interpolate_at(a, b, at(-1)), None);
+        assert_eq!(interpolate_at(a, b, at(4001)), None);
+        assert_eq!(interpolate_at(b, a, at(1000)), None);
+        assert_eq!(interpolate_at(a, &records[2], at(1000)), None);
+        assert_eq!(interpolate_at(b, &records[3], at(5000)), None);
+    }
+
+    #[test]
+    fn test_clamp_pack() {
+        let json = [
+            20.0, 45.5, 101.0, 99.9, 100.0, 150.0, 0.0, -10.0, 80.0, 60.0,
+        ]
+        .iter()
+        .map(|value| format!(r#"{{"n": "temperature", "u": "Cel", "v": {}}}"#, value))
+        .chain([r#"{"n": "temperature", "vs": "overheat"}"#.to_string()])
+        .collect::<Vec<String>>()
+        .join(",");
+        let original = parse_json(&format!("[{}]", json), None).unwrap();
+
+        let mut records = original.clone();
+        assert_eq!(clamp_pack(&mut records, "temperature", -20.0, 100.0), 2);
+        assert_eq!(records[2].get_float_value(), Some(100.0));
+        assert_eq!(records[5].get_float_value(), Some(100.0));
+        assert_eq!(records[10], original[10]);
+        assert_eq!(clamp_pack(&mut records, "temperature", -20.0, 100.0), 0);
+        assert_eq!(clamp_pack(&mut records, "humidity", 0.0, 1.0), 0);
+
+        let mut records = original.clone();
+        let constraints = HashMap::from([("temperature".to_string(), (0.0, 100.0))]);
+        assert_eq!(clamp_pack_all(&mut records, &constraints), 3);
+        assert_eq!(records[7].get_float_value(), Some(0.0));
+        assert_eq!(clamp_pack_all(&mut records, &HashMap::new()), 0);
+    }
+}
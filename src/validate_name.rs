@@ -7,6 +7,11 @@
 //! to "Z", "a" to "z", and "0" to "9", as well as "-", ":", ".", "/",
 //! and "_"; furthermore, it MUST start with a character out of the set
 //! "A" to "Z", "a" to "z", or "0" to "9".
+//!
+//! [`validate_name`] implements this rule and is used by default when
+//! parsing. [`validate_name_strict`] and [`validate_name_loose`] are
+//! stricter and looser variants, selected with
+//! [`crate::ParseOptions::name_validation`].
 use once_cell::sync::OnceCell;
 use regex::Regex;
 
@@ -36,6 +41,46 @@ pub fn validate_name(name: &str) -> bool {
         .is_match(name)
 }
 
+/// Validate a name according to the SenML specifications,
+/// also rejecting single-character names.
+///
+/// The RFC allows names of one character, but the names should be
+/// meaningful to differentiate the sensors.
+///
+/// # Example
+/// ```
+/// use sindit_senml::validate_name::validate_name_strict;
+/// assert!(validate_name_strict("t1"));
+/// assert!(!validate_name_strict("t"));
+/// ```
+pub fn validate_name_strict(name: &str) -> bool {
+    name.len() >= 2 && validate_name(name)
+}
+
+/// Validate a name like [`validate_name`], but also allowing Unicode
+/// letters in addition to the ASCII letters.
+///
+/// This does not follow the RFC, but accepts names in other scripts.
+///
+/// # Example
+/// ```
+/// use sindit_senml::validate_name::validate_name_loose;
+/// assert!(validate_name_loose("température"));
+/// assert!(validate_name_loose("センサー"));
+/// assert!(!validate_name_loose("-sensor"));
+/// ```
+pub fn validate_name_loose(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(first) if first.is_ascii_alphanumeric() || first.is_alphabetic() => chars.all(|c| {
+            c.is_ascii_alphanumeric()
+                || c.is_alphabetic()
+                || matches!(c, '-' | ':' | '.' | '/' | '_')
+        }),
+        _ => false,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -58,4 +103,26 @@ mod tests {
         assert!(!validate_name("sensor@name")); // Contains an invalid character
         assert!(!validate_name("センサー")); // Contains non-Latin characters
     }
+
+    #[test]
+    fn test_strict_names() {
+        assert!(validate_name_strict("Sensor1"));
+        assert!(validate_name_strict("t1"));
+        assert!(!validate_name_strict("a"));
+        assert!(!validate_name_strict("ä"));
+        assert!(!validate_name_strict("-sensor"));
+    }
+
+    #[test]
+    fn test_loose_names() {
+        assert!(validate_name_loose("a"));
+        assert!(validate_name_loose("ä"));
+        assert!(validate_name_loose("température/1"));
+        assert!(validate_name_loose("センサー"));
+        assert!(!validate_name_loose(""));
+        assert!(!validate_name_loose("-sensor"));
+        assert!(!validate_name_loose("sensor name"));
+        assert!(!validate_name_loose("sensor@name"));
+        assert!(!validate_name_loose("1²")); // Not a letter
+    }
 }
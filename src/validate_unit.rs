@@ -0,0 +1,83 @@
+//! # Validate SenML Unit
+//!
+//! The units should be registered in the SenML Units registry, which
+//! contains the units of RFC8428 and the units added by RFC8798.
+//! <https://www.rfc-editor.org/rfc/rfc8428.html#section-12.1>
+//! <https://www.rfc-editor.org/rfc/rfc8798.html>
+//!
+//! The secondary units of RFC8798, such as `kWh` or `ms`, are also
+//! accepted.
+use std::collections::HashSet;
+
+use once_cell::sync::Lazy;
+
+/// Units of RFC8428 section 12.1.
+const RFC8428_UNITS: &[&str] = &[
+    "m", "kg", "g", "s", "A", "K", "cd", "mol", "Hz", "rad", "sr", "N", "Pa", "J", "W", "C", "V",
+    "F", "Ohm", "S", "Wb", "T", "H", "Cel", "lm", "lx", "Bq", "Gy", "Sv", "kat", "m2", "m3", "l",
+    "m/s", "m/s2", "m3/s", "l/s", "W/m2", "cd/m2", "bit", "bit/s", "lat", "lon", "pH", "dB", "dBW",
+    "Bspl", "count", "/", "%", "%RH", "%EL", "EL", "1/s", "1/min", "beat/min", "beats", "S/m",
+];
+
+/// Units of RFC8798 section 2.
+const RFC8798_UNITS: &[&str] = &["B", "VA", "VAs", "var", "vars", "J/m", "kg/m3", "deg"];
+
+/// Secondary units of RFC8798 section 3.
+const RFC8798_SECONDARY_UNITS: &[&str] = &[
+    "ms", "min", "h", "MHz", "kW", "kVA", "kvar", "Ah", "Wh", "kWh", "varh", "kvarh", "kVAh",
+    "Wh/km", "KiB", "GB", "Mbit/s", "B/s", "MB/s", "mV", "mA", "dBm", "ug/m3", "mm/h", "m/h",
+    "ppm", "/100", "/1000", "hPa", "mm", "cm", "km", "km/h",
+];
+
+/// Units of the SenML Units registry.
+static UNITS: Lazy<HashSet<&'static str>> = Lazy::new(|| {
+    RFC8428_UNITS
+        .iter()
+        .chain(RFC8798_UNITS)
+        .chain(RFC8798_SECONDARY_UNITS)
+        .copied()
+        .collect()
+});
+
+/// Validate a unit against the SenML Units registry.
+///
+/// The units are case sensitive.
+///
+/// # Arguments
+/// * `unit` - The unit to validate
+/// # Output
+/// * `bool` - True if the unit is registered, false otherwise
+/// # Example
+/// ```
+/// use sindit_senml::validate_unit::validate_unit;
+/// assert!(validate_unit("Cel"));
+/// assert!(validate_unit("kWh"));
+/// assert!(!validate_unit("celsius"));
+/// ```
+pub fn validate_unit(unit: &str) -> bool {
+    UNITS.contains(unit)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_registered_units() {
+        assert!(validate_unit("Cel"));
+        assert!(validate_unit("%RH"));
+        assert!(validate_unit("m/s2"));
+        assert!(validate_unit("/"));
+        assert!(validate_unit("VA")); // RFC8798
+        assert!(validate_unit("hPa")); // RFC8798 secondary unit
+    }
+
+    #[test]
+    fn test_unknown_units() {
+        assert!(!validate_unit(""));
+        assert!(!validate_unit("cel")); // Units are case sensitive
+        assert!(!validate_unit("°C"));
+        assert!(!validate_unit("m/s^2"));
+        assert!(!validate_unit(" Cel"));
+    }
+}
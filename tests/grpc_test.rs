@@ -0,0 +1,42 @@
+#![cfg(feature = "grpc")]
+
+mod tests {
+    use sindit_senml::grpc::{SenMLServiceClient, SenMLServiceImpl};
+    use sindit_senml::parse_json;
+    use tokio::net::TcpListener;
+    use tokio::sync::mpsc;
+    use tokio_stream::wrappers::TcpListenerStream;
+
+    #[tokio::test]
+    async fn test_stream_records() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let (sender, mut receiver) = mpsc::channel(16);
+        tokio::spawn(
+            tonic::transport::Server::builder()
+                .add_service(SenMLServiceImpl::new(sender).into_server())
+                .serve_with_incoming(TcpListenerStream::new(listener)),
+        );
+
+        let json_str = format!(
+            r#"[{{"bn":"urn:dev:ow:10e2073a01080063:","bt":1320067464,"bu":"Cel"}},{}]"#,
+            (0..100)
+                .map(|i| format!(r#"{{"n":"temp","t":{},"v":{}.5}}"#, i, i))
+                .collect::<Vec<_>>()
+                .join(",")
+        );
+        let records = parse_json(&json_str, None).unwrap()[1..].to_vec();
+        assert_eq!(records.len(), 100);
+
+        let mut client = SenMLServiceClient::connect(format!("http://{}", address))
+            .await
+            .unwrap();
+        client.send_records(&records).await.unwrap();
+
+        let mut received = Vec::new();
+        while received.len() < records.len() {
+            received.extend(receiver.recv().await.unwrap());
+        }
+        assert_eq!(received, records);
+    }
+}
@@ -0,0 +1,69 @@
+#![cfg(feature = "macros")]
+
+mod tests {
+    use sindit_senml::{senml_pack, SenMLValueField};
+
+    #[test]
+    fn test_senml_pack() {
+        let records = senml_pack! {
+            [name: "temperature", unit: "Cel", value: 23.1, time: 1234567890],
+            [name: "count", value: 2],
+            [name: "open", value_bool: true],
+            [name: "label", value_str: "Machine Room"],
+            [name: "nfc-reader", value_data: [0x68, 0x69, 0x20, 0x0a]],
+            [name: "empty"],
+        };
+        assert_eq!(records.len(), 6);
+
+        assert_eq!(records[0].name, "temperature");
+        assert_eq!(records[0].unit, Some("Cel".to_string()));
+        assert_eq!(records[0].get_float_value(), Some(23.1));
+        assert_eq!(records[0].time.timestamp(), 1234567890);
+
+        assert_eq!(records[1].get_float_value(), Some(2.0));
+        assert_eq!(records[1].unit, None);
+        assert_eq!(records[1].time.timestamp(), 0);
+        assert_eq!(records[2].get_bool_value(), Some(true));
+        assert_eq!(
+            records[3].get_string_value(),
+            Some(&"Machine Room".to_string())
+        );
+        assert_eq!(
+            records[4].value,
+            Some(SenMLValueField::DataValue(vec![0x68, 0x69, 0x20, 0x0a]))
+        );
+        assert_eq!(records[5].value, None);
+    }
+
+    #[test]
+    fn test_senml_pack_empty() {
+        let records: Vec<sindit_senml::SenMLResolvedRecord> = senml_pack! {};
+        assert!(records.is_empty());
+    }
+
+    #[test]
+    fn test_senml_pack_times() {
+        let records = senml_pack! {
+            [name: "a", time: -60],
+            [name: "b", time: 1234567890.25],
+            [name: "c", time: -0.5],
+        };
+        assert_eq!(records[0].time.timestamp(), -60);
+        assert_eq!(records[1].time.timestamp(), 1234567890);
+        assert_eq!(records[1].time.timestamp_subsec_millis(), 250);
+        assert_eq!(records[2].time.timestamp(), -1);
+        assert_eq!(records[2].time.timestamp_subsec_millis(), 500);
+    }
+
+    #[test]
+    fn test_senml_pack_invalid_name() {
+        let test_cases = trybuild::TestCases::new();
+        test_cases.compile_fail("tests/ui/senml_pack_invalid_name.rs");
+    }
+
+    #[test]
+    fn test_senml_pack_time_out_of_range() {
+        let test_cases = trybuild::TestCases::new();
+        test_cases.compile_fail("tests/ui/senml_pack_time_out_of_range.rs");
+    }
+}
@@ -0,0 +1,134 @@
+//! Invalid SenML inputs and the errors they produce.
+//! https://www.rfc-editor.org/rfc/rfc8428
+
+use sindit_senml::{parse_json, SinditSenMLError};
+
+fn parse_error(json_str: &str) -> SinditSenMLError {
+    parse_json(json_str, None).expect_err(json_str)
+}
+
+#[test]
+fn test_top_level_object() {
+    assert!(matches!(
+        parse_error(r#"{"n":"urn:dev:ow:10e2073a01080063","v":23.1}"#),
+        SinditSenMLError::InvalidJSON(_)
+    ));
+}
+
+#[test]
+fn test_record_without_fields() {
+    assert!(matches!(
+        parse_error(r#"[{}]"#),
+        SinditSenMLError::MissingName(0)
+    ));
+}
+
+#[test]
+fn test_base_name_not_a_string() {
+    assert!(matches!(
+        parse_error(r#"[{"bn":42,"n":"temperature","v":23.1}]"#),
+        SinditSenMLError::InvalidJSON(_)
+    ));
+}
+
+#[test]
+fn test_base_time_string() {
+    assert!(matches!(
+        parse_error(r#"[{"bt":"1320067464","n":"temperature","v":23.1}]"#),
+        SinditSenMLError::InvalidJSON(_)
+    ));
+}
+
+#[test]
+fn test_null_value() {
+    assert!(matches!(
+        parse_error(r#"[{"n":"temperature","v":null}]"#),
+        SinditSenMLError::InvalidJSON(_)
+    ));
+}
+
+#[test]
+fn test_null_time() {
+    assert!(matches!(
+        parse_error(r#"[{"n":"temperature","v":23.1,"t":null}]"#),
+        SinditSenMLError::InvalidJSON(_)
+    ));
+}
+
+#[test]
+fn test_negative_base_version() {
+    assert!(matches!(
+        parse_error(r#"[{"bver":-1,"n":"temperature","v":23.1}]"#),
+        SinditSenMLError::InvalidJSON(_)
+    ));
+}
+
+#[test]
+fn test_float_base_version() {
+    assert!(matches!(
+        parse_error(r#"[{"bver":10.5,"n":"temperature","v":23.1}]"#),
+        SinditSenMLError::InvalidJSON(_)
+    ));
+}
+
+#[test]
+fn test_name_with_newline() {
+    assert!(matches!(
+        parse_error(r#"[{"n":"temper\nature","v":23.1}]"#),
+        SinditSenMLError::InvalidNameInRecord(0)
+    ));
+}
+
+#[test]
+fn test_name_starting_with_dash() {
+    assert!(matches!(
+        parse_error(r#"[{"n":"-temperature","v":23.1}]"#),
+        SinditSenMLError::InvalidNameInRecord(0)
+    ));
+}
+
+#[test]
+fn test_data_value_not_base64() {
+    assert!(matches!(
+        parse_error(r#"[{"n":"nfc-reader","vd":"aGk*Cg"}]"#),
+        SinditSenMLError::InvalidBase64Value(_)
+    ));
+}
+
+#[test]
+fn test_data_value_padding_with_url_safe_alphabet() {
+    assert!(matches!(
+        parse_error(r#"[{"n":"nfc-reader","vd":"aGk-Cg=="}]"#),
+        SinditSenMLError::InvalidBase64Value(_)
+    ));
+}
+
+#[test]
+fn test_infinity_time() {
+    assert!(matches!(
+        parse_error(r#"[{"n":"temperature","v":23.1,"t":"Infinity"}]"#),
+        SinditSenMLError::InvalidJSON(_)
+    ));
+    assert!(matches!(
+        parse_error(r#"[{"n":"temperature","v":23.1,"t":Infinity}]"#),
+        SinditSenMLError::InvalidJSON(_)
+    ));
+}
+
+#[test]
+fn test_base_version_change() {
+    assert!(matches!(
+        parse_error(
+            r#"[{"bver":5,"n":"temperature","v":23.1},{"bver":10,"n":"temperature","v":23.2}]"#
+        ),
+        SinditSenMLError::DifferentBaseVersion
+    ));
+}
+
+#[test]
+fn test_base_version_zero() {
+    assert!(matches!(
+        parse_error(r#"[{"bver":0,"n":"temperature","v":23.1}]"#),
+        SinditSenMLError::InvalidVersionNumber
+    ));
+}
@@ -524,4 +524,60 @@ mod tests {
             basetime + Duration::milliseconds(100)
         ));
     }
+
+    #[test]
+    fn test_optimize_pack_roundtrip() {
+        use sindit_senml::compact::optimize_pack;
+
+        let now = Utc::now();
+        for example in [
+            SenMLSpecificationExamples::SINGLE_DATAPOINT,
+            SenMLSpecificationExamples::MULTIPLE_DATAPOINT,
+            SenMLSpecificationExamples::MULTIPLE_DATAPOINT_AND_TIME,
+            SenMLSpecificationExamples::MULTIPLE_MEASUREMENTS,
+            SenMLSpecificationExamples::RESOLVED_DATA,
+            SenMLSpecificationExamples::MULTIPLE_DATATYPES,
+            SenMLSpecificationExamples::COLLECTION_OF_RESOURCES,
+            SenMLSpecificationExamples::SETTING_ACTUATOR,
+            SenMLSpecificationExamples::LIGHTS_ON,
+            SenMLSpecificationExamples::SYNCHRONIZED_LIGHTS_OFF,
+        ] {
+            let records = parse_json(example, Some(now)).unwrap();
+            let optimized = optimize_pack(&records);
+            let result = parse_json(&optimized, Some(now)).unwrap();
+            assert_eq!(result.len(), records.len());
+            for (result, record) in result.iter().zip(records.iter()) {
+                assert_eq!(result.name, record.name);
+                assert_eq!(result.unit, record.unit);
+                assert_eq!(result.value, record.value);
+                assert_eq!(result.sum, record.sum);
+                assert_eq!(result.update_time, record.update_time);
+                assert_eq!(result.base_version, record.base_version);
+                assert_eq!(result.extra_fields, record.extra_fields);
+                assert!(dates_similar(result.time, record.time));
+            }
+        }
+    }
+
+    #[cfg(feature = "stream")]
+    #[tokio::test]
+    async fn test_stream() {
+        use sindit_senml::stream::SenMLStreamReader;
+        use tokio_stream::StreamExt;
+
+        let basetime = DateTime::<Utc>::from_timestamp(1320067464, 0).unwrap();
+        let result: Vec<_> =
+            SenMLStreamReader::new(SenMLSpecificationExamples::STREAM.as_bytes(), None)
+                .collect::<Result<Vec<_>, _>>()
+                .await
+                .unwrap();
+        assert_eq!(result.len(), 9);
+        let values = [21.2, 21.3, 21.4, 21.4, 21.5, 21.5, 21.5, 21.6, 21.7];
+        for (index, (record, value)) in result.iter().zip(values).enumerate() {
+            assert_eq!(record.name, "urn:dev:ow:10e2073a01080063");
+            assert_eq!(record.unit, Some(String::from("%RH")));
+            assert_eq!(record.get_float_value(), Some(value));
+            assert_eq!(record.time, basetime + Duration::seconds(10 * index as i64));
+        }
+    }
 }
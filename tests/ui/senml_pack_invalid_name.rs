@@ -0,0 +1,5 @@
+use sindit_senml::senml_pack;
+
+fn main() {
+    let _records = senml_pack! { [name: "-temperature", value: 23.1] };
+}